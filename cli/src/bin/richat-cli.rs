@@ -1,8 +1,8 @@
 use {
     clap::{Parser, Subcommand},
     richat_cli::{
-        pubsub::ArgsAppPubSub, stream_grpc::ArgsAppStreamGrpc, stream_richat::ArgsAppStreamRichat,
-        track::ArgsAppTrack,
+        pubsub::ArgsAppPubSub, record::ArgsAppRecord, stream_grpc::ArgsAppStreamGrpc,
+        stream_richat::ArgsAppStreamRichat, track::ArgsAppTrack,
     },
     std::sync::atomic::{AtomicU64, Ordering},
 };
@@ -12,7 +12,11 @@ use {
 static GLOBAL: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc;
 
 #[derive(Debug, Parser)]
-#[clap(author, version, about = "Richat Cli Tool: pubsub, stream, track")]
+#[clap(
+    author,
+    version,
+    about = "Richat Cli Tool: pubsub, stream, record, track"
+)]
 struct Args {
     #[command(subcommand)]
     action: ArgsAppSelect,
@@ -29,6 +33,9 @@ enum ArgsAppSelect {
     /// Stream data directly from the richat-plugin
     StreamRichat(ArgsAppStreamRichat),
 
+    /// Record stream from the richat-plugin to the file
+    Record(ArgsAppRecord),
+
     /// Events tracker
     Track(ArgsAppTrack),
 }
@@ -48,6 +55,7 @@ async fn main2() -> anyhow::Result<()> {
         ArgsAppSelect::Pubsub(action) => action.run().await,
         ArgsAppSelect::StreamGrpc(action) => action.run().await,
         ArgsAppSelect::StreamRichat(action) => action.run().await,
+        ArgsAppSelect::Record(action) => action.run().await,
         ArgsAppSelect::Track(action) => action.run().await,
     }
 }
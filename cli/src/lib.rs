@@ -1,4 +1,5 @@
 pub mod pubsub;
+pub mod record;
 pub mod stream;
 pub mod stream_grpc;
 pub mod stream_richat;
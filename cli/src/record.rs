@@ -0,0 +1,61 @@
+use {
+    crate::stream_richat::ArgsAppStreamRichat,
+    anyhow::Context,
+    clap::Args,
+    futures::stream::StreamExt,
+    richat_client::file::FileRecordWriter,
+    std::{path::PathBuf, time::SystemTime},
+    tracing::info,
+};
+
+#[derive(Debug, Args)]
+pub struct ArgsAppRecord {
+    /// Path of the file to write the stream, replayed by richat `file` source
+    #[clap(long)]
+    output: PathBuf,
+
+    /// Stop once this number of messages is recorded
+    #[clap(long)]
+    max_messages: Option<u64>,
+
+    #[command(flatten)]
+    stream: ArgsAppStreamRichat,
+}
+
+impl ArgsAppRecord {
+    pub async fn run(self) -> anyhow::Result<()> {
+        let mut writer = FileRecordWriter::create(&self.output)
+            .await
+            .with_context(|| format!("failed to create {}", self.output.display()))?;
+        let mut stream = self.stream.subscribe_raw().await?;
+
+        let ctrl_c = tokio::signal::ctrl_c();
+        tokio::pin!(ctrl_c);
+
+        let mut messages = 0;
+        let result = loop {
+            if self.max_messages.is_some_and(|max| messages >= max) {
+                break Ok(());
+            }
+
+            let message = tokio::select! {
+                message = stream.next() => message,
+                _ = &mut ctrl_c => break Ok(()),
+            };
+            match message {
+                Some(Ok(data)) => {
+                    if let Err(error) = writer.write(SystemTime::now(), &data).await {
+                        break Err(error).context("failed to write message");
+                    }
+                    messages += 1;
+                }
+                Some(Err(error)) => break Err(error).context("failed to receive message"),
+                None => break Ok(()),
+            }
+        };
+
+        writer.flush().await.context("failed to flush file")?;
+        info!("recorded {messages} messages to {}", self.output.display());
+        result
+    }
+}
@@ -22,7 +22,9 @@ use {
             subscribe_request_filter_accounts_filter_lamports::Cmp as AccountsFilterLamports,
             subscribe_request_filter_accounts_filter_memcmp::Data as AccountsFilterMemcmpOneof,
         },
-        richat::SubscribeAccountsRequest,
+        richat::{
+            SubscribeAccountsRequest, SubscribeUpdateAccountsSnapshot, SubscribeUpdateRollback,
+        },
     },
     solana_pubkey::Pubkey,
     std::{collections::HashMap, fs::File, path::PathBuf, str::FromStr, sync::Arc, time::Duration},
@@ -38,6 +40,15 @@ type EntryFilterMap = HashMap<String, SubscribeRequestFilterEntry>;
 type BlocksFilterMap = HashMap<String, SubscribeRequestFilterBlocks>;
 type BlocksMetaFilterMap = HashMap<String, SubscribeRequestFilterBlocksMeta>;
 
+/// Richat extensions of `SubscribeUpdate.update_oneof`
+#[derive(Clone, PartialEq, prost::Message)]
+struct SubscribeUpdateRichat {
+    #[prost(message, optional, tag = "100")]
+    accounts_snapshot: Option<SubscribeUpdateAccountsSnapshot>,
+    #[prost(message, optional, tag = "101")]
+    rollback: Option<SubscribeUpdateRollback>,
+}
+
 #[derive(Debug, Args)]
 pub struct ArgsAppStreamGrpc {
     #[clap(short, long, default_value_t = String::from("http://127.0.0.1:10000"))]
@@ -184,7 +195,7 @@ impl ArgsAppStreamGrpc {
                 info!("version: {}", version.version);
 
                 let (request, stats, verify_encoding) = action_subscribe
-                    .get_subscribe_request()
+                    .get_subscribe_request(commitment)
                     .await?
                     .expect("subscribe action");
 
@@ -630,6 +641,10 @@ struct ActionSubscribeAccounts {
     #[clap(long)]
     account_path: Option<String>,
 
+    /// Receive current state of accounts before updates, requires accounts cache on the server
+    #[clap(long, default_value_t = false)]
+    snapshot: bool,
+
     /// Show total stat instead of messages
     #[clap(long, default_value_t = false)]
     stats: bool,
@@ -642,6 +657,7 @@ struct ActionSubscribeAccounts {
 impl ActionSubscribeAccounts {
     async fn get_subscribe_request(
         self,
+        commitment: Option<CommitmentLevel>,
     ) -> anyhow::Result<Option<(SubscribeAccountsRequest, bool, bool)>> {
         let mut accounts = self.account;
         if let Some(path) = self.account_path {
@@ -661,6 +677,8 @@ impl ActionSubscribeAccounts {
                     .map(|s| Pubkey::from_str(&s).map(|pk| pk.to_bytes().to_vec()))
                     .collect::<Result<Vec<_>, _>>()?,
                 remove: vec![],
+                snapshot: self.snapshot,
+                commitment: commitment.map(|x| x as i32),
             },
             self.stats,
             self.verify_encoding,
@@ -678,10 +696,29 @@ async fn geyser_subscribe(
     let pb_multi_stream = Arc::clone(&pb_multi);
     let stream = get_stream()
         .await?
-        .and_then(move |vec| {
+        .try_filter_map(move |vec| {
             let pb_multi_stream = Arc::clone(&pb_multi_stream);
             async move {
                 let msg = SubscribeUpdate::decode(vec.as_slice())?;
+                if msg.update_oneof.is_none() {
+                    let msg = SubscribeUpdateRichat::decode(vec.as_slice())?;
+                    if let Some(msg) = msg.accounts_snapshot {
+                        info!(
+                            "accounts snapshot: slot {}, accounts {}",
+                            msg.slot, msg.accounts
+                        );
+                        return Ok(None);
+                    }
+                    if let Some(msg) = msg.rollback {
+                        info!(
+                            "rollback: slots {:?}, cause {:?} at slot {}",
+                            msg.slots,
+                            msg.cause(),
+                            msg.cause_slot
+                        );
+                        return Ok(None);
+                    }
+                }
                 if verify_encoding && vec != msg.encode_to_vec() {
                     pb_multi_stream
                         .println(format!(
@@ -690,7 +727,7 @@ async fn geyser_subscribe(
                         ))
                         .unwrap();
                 }
-                Ok(msg)
+                Ok(Some(msg))
             }
         })
         .boxed();
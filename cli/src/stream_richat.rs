@@ -26,13 +26,21 @@ use {
     richat_shared::transports::{grpc::ConfigGrpcServer, quic::ConfigQuicServer},
     solana_clock::Slot,
     solana_message::{LegacyMessage, Message, SanitizedMessage},
+    solana_pubkey::Pubkey,
     solana_transaction::sanitized::SanitizedTransaction,
     std::{collections::HashSet, net::SocketAddr, path::PathBuf, sync::Arc, time::Duration},
     tonic::service::Interceptor,
     tracing::info,
 };
 
-type SubscribeStreamInput = BoxStream<'static, Result<Vec<u8>, ReceiveError>>;
+pub(crate) type SubscribeStreamInput = BoxStream<'static, Result<Vec<u8>, ReceiveError>>;
+
+fn pubkeys_to_bytes(pubkeys: &[Pubkey]) -> Vec<Vec<u8>> {
+    pubkeys
+        .iter()
+        .map(|pubkey| pubkey.to_bytes().to_vec())
+        .collect()
+}
 
 #[derive(Debug, Args)]
 pub struct ArgsAppStreamRichat {
@@ -51,10 +59,34 @@ pub struct ArgsAppStreamRichat {
     #[clap(long)]
     disable_entries: bool,
 
+    /// Stream only these accounts
+    #[clap(long)]
+    accounts_pubkey: Vec<Pubkey>,
+
+    /// Stream only accounts owned by these programs
+    #[clap(long)]
+    accounts_owner: Vec<Pubkey>,
+
+    /// Stream only transactions with any of these accounts
+    #[clap(long)]
+    transactions_account_include: Vec<Pubkey>,
+
+    /// Do not stream vote transactions
+    #[clap(long)]
+    transactions_exclude_vote: bool,
+
+    /// Do not stream failed transactions
+    #[clap(long)]
+    transactions_exclude_failed: bool,
+
     /// Subscribe on stream from slot
     #[clap(long)]
     replay_from_slot: Option<Slot>,
 
+    /// Finish stream once this slot is finalized
+    #[clap(long)]
+    replay_to_slot: Option<Slot>,
+
     /// Access token
     #[clap(long)]
     x_token: Option<String>,
@@ -77,18 +109,32 @@ impl ArgsAppStreamRichat {
             disable_accounts: self.disable_accounts,
             disable_transactions: self.disable_transactions,
             disable_entries: self.disable_entries,
+            accounts_pubkey: pubkeys_to_bytes(&self.accounts_pubkey),
+            accounts_owner: pubkeys_to_bytes(&self.accounts_owner),
+            transactions_account_include: pubkeys_to_bytes(&self.transactions_account_include),
+            transactions_exclude_vote: self.transactions_exclude_vote,
+            transactions_exclude_failed: self.transactions_exclude_failed,
         };
+        let replay_to_slot = self.replay_to_slot;
         let x_token = self.x_token.map(|xt| xt.into_bytes());
         match self.action {
             ArgsAppStreamSelect::Quic(args) => {
-                args.subscribe(replay_from_slot, filter, x_token).await
+                args.subscribe(replay_from_slot, replay_to_slot, filter, x_token)
+                    .await
             }
             ArgsAppStreamSelect::Grpc(args) => {
-                args.subscribe(replay_from_slot, filter, x_token).await
+                args.subscribe(replay_from_slot, replay_to_slot, filter, x_token)
+                    .await
             }
         }
     }
 
+    /// Encoded `SubscribeUpdate` messages as received from the server
+    pub(crate) async fn subscribe_raw(self) -> anyhow::Result<SubscribeStreamInput> {
+        let replay_from_slot = self.replay_from_slot;
+        self.subscribe(replay_from_slot).await
+    }
+
     pub async fn run(self) -> anyhow::Result<()> {
         let pb_multi = Arc::new(MultiProgress::new());
         let replay_from_slot = self.replay_from_slot;
@@ -168,6 +214,7 @@ impl ArgsAppStreamQuic {
     async fn subscribe(
         self,
         replay_from_slot: Option<Slot>,
+        replay_to_slot: Option<Slot>,
         filter: RichatFilter,
         x_token: Option<Vec<u8>>,
     ) -> anyhow::Result<SubscribeStreamInput> {
@@ -193,7 +240,7 @@ impl ArgsAppStreamQuic {
         info!("connected to {} over Quic", self.endpoint);
 
         let stream = client
-            .subscribe(replay_from_slot, Some(filter))
+            .subscribe(replay_from_slot, replay_to_slot, Some(filter))
             .await
             .context("failed to subscribe")?;
         info!("subscribed");
@@ -313,6 +360,7 @@ impl ArgsAppStreamGrpc {
     async fn subscribe(
         self,
         replay_from_slot: Option<Slot>,
+        replay_to_slot: Option<Slot>,
         filter: RichatFilter,
         x_token: Option<Vec<u8>>,
     ) -> anyhow::Result<SubscribeStreamInput> {
@@ -326,6 +374,7 @@ impl ArgsAppStreamGrpc {
         let stream = client
             .subscribe_richat(GrpcSubscribeRequest {
                 replay_from_slot,
+                replay_to_slot,
                 filter: Some(filter),
             })
             .await
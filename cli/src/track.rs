@@ -165,16 +165,22 @@ impl ConfigSourceRichatPluginAgave {
             disable_accounts: !accounts_enabled,
             disable_transactions: !transactions_enabled,
             disable_entries: false,
+            ..Default::default()
         });
 
         let stream = match self {
             Self::Quic(config) => {
-                let stream = config.connect().await?.subscribe(None, filter).await?;
+                let stream = config
+                    .connect()
+                    .await?
+                    .subscribe(None, None, filter)
+                    .await?;
                 stream.into_parsed()
             }
             Self::Grpc(config) => {
                 let request = GrpcSubscribeRequest {
                     replay_from_slot: None,
+                    replay_to_slot: None,
                     filter,
                 };
 
@@ -35,6 +35,16 @@ pub enum SubscribeError {
     XTokenRequired,
     #[error("x-token invalid")]
     XTokenInvalid,
+    #[error("replay to slot should be greater than or equal to replay from slot")]
+    InvalidReplayRange,
+    #[error("replay queue is full")]
+    ReplayQueueFull,
+    #[error("x-token is not allowed to use this app")]
+    XTokenAppNotAllowed,
+    #[error("x-token reached max number of subscriptions")]
+    XTokenSubscriptionsLimit,
+    #[error("invalid filter")]
+    InvalidFilter,
 }
 
 impl SubscribeError {
@@ -61,6 +71,17 @@ impl SubscribeError {
                 }
                 Ok(QuicSubscribeResponseError::XTokenRequired) => SubscribeError::XTokenRequired,
                 Ok(QuicSubscribeResponseError::XTokenInvalid) => SubscribeError::XTokenInvalid,
+                Ok(QuicSubscribeResponseError::InvalidReplayRange) => {
+                    SubscribeError::InvalidReplayRange
+                }
+                Ok(QuicSubscribeResponseError::ReplayQueueFull) => SubscribeError::ReplayQueueFull,
+                Ok(QuicSubscribeResponseError::XTokenAppNotAllowed) => {
+                    SubscribeError::XTokenAppNotAllowed
+                }
+                Ok(QuicSubscribeResponseError::XTokenSubscriptionsLimit) => {
+                    SubscribeError::XTokenSubscriptionsLimit
+                }
+                Ok(QuicSubscribeResponseError::InvalidFilter) => SubscribeError::InvalidFilter,
                 Err(_error) => SubscribeError::Unknown(error),
             })
         } else {
@@ -85,6 +106,8 @@ pub enum ReceiveError {
     Lagged,
     #[error("internal geyser stream is closed")]
     Closed,
+    #[error("replay finished after {0} messages")]
+    ReplayFinished(u64),
 }
 
 impl From<QuicSubscribeClose> for ReceiveError {
@@ -92,6 +115,7 @@ impl From<QuicSubscribeClose> for ReceiveError {
         match QuicSubscribeCloseError::try_from(close.error) {
             Ok(QuicSubscribeCloseError::Lagged) => Self::Lagged,
             Ok(QuicSubscribeCloseError::Closed) => Self::Closed,
+            Ok(QuicSubscribeCloseError::Finished) => Self::ReplayFinished(close.messages()),
             Err(_error) => Self::Unknown(close.error),
         }
     }
@@ -0,0 +1,310 @@
+use {
+    crate::error::ReceiveError,
+    futures::stream::{BoxStream, StreamExt, try_unfold},
+    serde::{
+        Deserialize,
+        de::{self, Deserializer},
+    },
+    std::{
+        io,
+        path::{Path, PathBuf},
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    },
+    tokio::{
+        fs::File,
+        io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader, BufWriter},
+        time::{Instant, sleep_until},
+    },
+    tracing::warn,
+};
+
+/// Header of the file with recorded stream.
+///
+/// Every record is receive time (`u64` microseconds since UNIX epoch), size of
+/// the message (`u32`) and encoded `SubscribeUpdate`, numbers are little-endian.
+pub const FILE_RECORD_MAGIC: [u8; 8] = *b"RICHATR1";
+
+/// Max size of the recorded message, bigger sizes are treated as corrupted file.
+pub const FILE_RECORD_SIZE_MAX: u32 = 256 * 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileRecord {
+    pub received_at: SystemTime,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub struct FileRecordWriter<W> {
+    writer: W,
+}
+
+impl FileRecordWriter<BufWriter<File>> {
+    pub async fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::create(path).await?;
+        Self::new(BufWriter::new(file)).await
+    }
+}
+
+impl<W: AsyncWrite + Unpin> FileRecordWriter<W> {
+    pub async fn new(mut writer: W) -> io::Result<Self> {
+        writer.write_all(&FILE_RECORD_MAGIC).await?;
+        Ok(Self { writer })
+    }
+
+    pub async fn write(&mut self, received_at: SystemTime, data: &[u8]) -> io::Result<()> {
+        let received_at = received_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_micros() as u64;
+        let size = u32::try_from(data.len())
+            .ok()
+            .filter(|size| *size <= FILE_RECORD_SIZE_MAX)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "message is too large"))?;
+
+        self.writer.write_u64_le(received_at).await?;
+        self.writer.write_u32_le(size).await?;
+        self.writer.write_all(data).await
+    }
+
+    pub async fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush().await
+    }
+}
+
+#[derive(Debug)]
+pub struct FileRecordReader<R> {
+    reader: R,
+}
+
+impl FileRecordReader<BufReader<File>> {
+    pub async fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::open(path).await?;
+        Self::new(BufReader::new(file)).await
+    }
+}
+
+impl<R: AsyncRead + Unpin> FileRecordReader<R> {
+    pub async fn new(mut reader: R) -> io::Result<Self> {
+        let mut magic = [0; FILE_RECORD_MAGIC.len()];
+        reader.read_exact(&mut magic).await?;
+        if magic != FILE_RECORD_MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "invalid file header, not a recorded stream",
+            ));
+        }
+        Ok(Self { reader })
+    }
+
+    /// Returns `None` at the end of the file, incomplete record at the end
+    /// (recorder was killed in the middle of the write) is skipped with a warning.
+    pub async fn read(&mut self) -> io::Result<Option<FileRecord>> {
+        let received_at = match self.reader.read_u64_le().await {
+            Ok(value) => UNIX_EPOCH + Duration::from_micros(value),
+            Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(error) => return Err(error),
+        };
+        let size = match self.reader.read_u32_le().await {
+            Ok(size) => size,
+            Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => {
+                warn!("incomplete record header at the end of the file");
+                return Ok(None);
+            }
+            Err(error) => return Err(error),
+        };
+        if size > FILE_RECORD_SIZE_MAX {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("record size {size} exceeds limit {FILE_RECORD_SIZE_MAX}"),
+            ));
+        }
+
+        // buffer is extended by read data, not by size from the file
+        let mut data = Vec::new();
+        let read = (&mut self.reader)
+            .take(size as u64)
+            .read_to_end(&mut data)
+            .await?;
+        if read < size as usize {
+            warn!(size, read, "incomplete record at the end of the file");
+            return Ok(None);
+        }
+        Ok(Some(FileRecord { received_at, data }))
+    }
+}
+
+impl<R: AsyncRead + Unpin + Send + 'static> FileRecordReader<R> {
+    /// Stream of recorded messages, delayed according to receive time and speed.
+    pub fn replay(
+        self,
+        speed: FileReplaySpeed,
+    ) -> BoxStream<'static, Result<Vec<u8>, ReceiveError>> {
+        try_unfold(
+            (self, None),
+            move |(mut reader, mut started): (Self, Option<(SystemTime, Instant)>)| async move {
+                let Some(record) = reader.read().await? else {
+                    return Ok(None);
+                };
+
+                if let FileReplaySpeed::Multiplier(multiplier) = speed {
+                    let (first_received_at, started_at) =
+                        *started.get_or_insert_with(|| (record.received_at, Instant::now()));
+                    let elapsed = record
+                        .received_at
+                        .duration_since(first_received_at)
+                        .unwrap_or_default();
+                    sleep_until(started_at + elapsed.div_f64(multiplier)).await;
+                }
+
+                Ok(Some((record.data, (reader, started))))
+            },
+        )
+        .boxed()
+    }
+}
+
+/// Pace of the replay relative to receive time of recorded messages.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FileReplaySpeed {
+    /// `1.0` is original pace, `2.0` is twice faster
+    Multiplier(f64),
+    /// As fast as possible
+    Max,
+}
+
+impl Default for FileReplaySpeed {
+    fn default() -> Self {
+        Self::Multiplier(1.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for FileReplaySpeed {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Value {
+            Multiplier(f64),
+            Str(String),
+        }
+
+        match Value::deserialize(deserializer)? {
+            Value::Multiplier(multiplier) if multiplier > 0.0 => Ok(Self::Multiplier(multiplier)),
+            Value::Multiplier(multiplier) => Err(de::Error::custom(format!(
+                "speed should be greater than zero, got {multiplier}"
+            ))),
+            Value::Str(value) if value == "max" => Ok(Self::Max),
+            Value::Str(value) => Err(de::Error::custom(format!(
+                "expected speed multiplier or `max`, got `{value}`"
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ConfigFileClient {
+    pub path: PathBuf,
+    #[serde(default)]
+    pub speed: FileReplaySpeed,
+}
+
+impl ConfigFileClient {
+    pub async fn open(self) -> io::Result<BoxStream<'static, Result<Vec<u8>, ReceiveError>>> {
+        FileRecordReader::open(&self.path)
+            .await
+            .map(|reader| reader.replay(self.speed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{
+            FILE_RECORD_MAGIC, FILE_RECORD_SIZE_MAX, FileRecord, FileRecordReader,
+            FileRecordWriter, FileReplaySpeed,
+        },
+        std::{
+            io,
+            time::{Duration, SystemTime, UNIX_EPOCH},
+        },
+    };
+
+    async fn write_records(records: &[FileRecord]) -> Vec<u8> {
+        let mut buffer = vec![];
+        let mut writer = FileRecordWriter::new(&mut buffer).await.unwrap();
+        for record in records {
+            writer
+                .write(record.received_at, &record.data)
+                .await
+                .unwrap();
+        }
+        writer.flush().await.unwrap();
+        buffer
+    }
+
+    async fn read_records(buffer: &[u8]) -> io::Result<Vec<FileRecord>> {
+        let mut reader = FileRecordReader::new(buffer).await?;
+        let mut records = vec![];
+        while let Some(record) = reader.read().await? {
+            records.push(record);
+        }
+        Ok(records)
+    }
+
+    fn records() -> Vec<FileRecord> {
+        (0..3u64)
+            .map(|index| FileRecord {
+                received_at: UNIX_EPOCH + Duration::from_micros(1_700_000_000_000_000 + index),
+                data: vec![index as u8; index as usize * 10],
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_round_trip() {
+        let records = records();
+        let buffer = write_records(&records).await;
+        assert_eq!(read_records(&buffer).await.unwrap(), records);
+    }
+
+    #[tokio::test]
+    async fn test_truncated_tail() {
+        let records = records();
+        let buffer = write_records(&records).await;
+        let last_size = 8 + 4 + records[2].data.len();
+
+        // cut in the middle of data, size and receive time of the last record
+        for cut in [1, last_size - 10, last_size - 2] {
+            let truncated = &buffer[..buffer.len() - cut];
+            assert_eq!(read_records(truncated).await.unwrap(), records[..2]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_size_limit() {
+        let mut buffer = FILE_RECORD_MAGIC.to_vec();
+        buffer.extend_from_slice(&0u64.to_le_bytes());
+        buffer.extend_from_slice(&(FILE_RECORD_SIZE_MAX + 1).to_le_bytes());
+        let error = read_records(&buffer).await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        let mut buffer = vec![];
+        let mut writer = FileRecordWriter::new(&mut buffer).await.unwrap();
+        let data = vec![0; FILE_RECORD_SIZE_MAX as usize + 1];
+        let error = writer.write(SystemTime::now(), &data).await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_replay_speed_deserialize() {
+        let parse = serde_json::from_str::<FileReplaySpeed>;
+        assert_eq!(parse("2.0").unwrap(), FileReplaySpeed::Multiplier(2.0));
+        assert_eq!(parse("1").unwrap(), FileReplaySpeed::Multiplier(1.0));
+        assert_eq!(parse(r#""max""#).unwrap(), FileReplaySpeed::Max);
+        assert!(parse("0").is_err());
+        assert!(parse("-1.5").is_err());
+        assert!(parse(r#""fast""#).is_err());
+    }
+}
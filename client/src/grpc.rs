@@ -6,6 +6,7 @@ use {
     crate::{error::ReceiveError, stream::SubscribeStream},
     bytes::{Buf, Bytes},
     futures::{
+        FutureExt,
         channel::mpsc,
         sink::{Sink, SinkExt},
         stream::{Stream, StreamExt},
@@ -25,7 +26,7 @@ use {
     },
     richat_shared::{
         config::{deserialize_humansize_usize, deserialize_maybe_x_token},
-        transports::grpc::{ConfigGrpcCompression, ConfigGrpcServer},
+        transports::grpc::{ConfigGrpcCompression, ConfigGrpcServer, REPLAY_FINISHED_METADATA_KEY},
     },
     serde::Deserialize,
     std::{
@@ -34,7 +35,7 @@ use {
         marker::PhantomData,
         path::PathBuf,
         pin::Pin,
-        task::{Context, Poll},
+        task::{Context, Poll, ready},
         time::Duration,
     },
     thiserror::Error,
@@ -611,12 +612,16 @@ pin_project! {
     pub struct GrpcClientStream {
         #[pin]
         stream: Streaming<Vec<u8>>,
+        finished: bool,
     }
 }
 
 impl GrpcClientStream {
     pub const fn new(stream: Streaming<Vec<u8>>) -> Self {
-        Self { stream }
+        Self {
+            stream,
+            finished: false,
+        }
     }
 
     pub fn into_parsed(self) -> SubscribeStream {
@@ -634,7 +639,25 @@ impl Stream for GrpcClientStream {
     type Item = Result<Vec<u8>, ReceiveError>;
 
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        let me = self.project();
-        me.stream.poll_next(cx).map_err(Into::into)
+        let mut me = self.project();
+        if *me.finished {
+            return Poll::Ready(None);
+        }
+
+        match ready!(me.stream.as_mut().poll_next(cx)) {
+            Some(item) => Poll::Ready(Some(item.map_err(Into::into))),
+            None => {
+                *me.finished = true;
+                // trailers are already received with the end of the stream
+                let messages = match me.stream.get_mut().trailers().now_or_never() {
+                    Some(Ok(Some(metadata))) => metadata
+                        .get(REPLAY_FINISHED_METADATA_KEY)
+                        .and_then(|value| value.to_str().ok())
+                        .and_then(|value| value.parse().ok()),
+                    _ => None,
+                };
+                Poll::Ready(messages.map(|messages| Err(ReceiveError::ReplayFinished(messages))))
+            }
+        }
     }
 }
@@ -1,4 +1,5 @@
 pub mod error;
+pub mod file;
 pub mod grpc;
 pub mod quic;
 pub mod stream;
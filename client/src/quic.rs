@@ -425,9 +425,11 @@ impl QuicClient {
         QuicClientBuilder::new()
     }
 
+    /// With `replay_to_slot` stream ends once that slot is finalized.
     pub async fn subscribe(
         self,
         replay_from_slot: Option<Slot>,
+        replay_to_slot: Option<Slot>,
         filter: Option<RichatFilter>,
     ) -> Result<QuicClientStream, SubscribeError> {
         let message = QuicSubscribeRequest {
@@ -436,6 +438,7 @@ impl QuicClient {
             max_backlog: self.max_backlog,
             replay_from_slot,
             filter,
+            replay_to_slot,
         }
         .encode_to_vec();
 
@@ -459,6 +462,7 @@ impl QuicClient {
             version,
             messages: HashMap::default(),
             msg_id: 0,
+            msg_id_finished: None,
             readers,
             index: 0,
         })
@@ -493,6 +497,7 @@ pin_project! {
         version: String,
         messages: HashMap<u64, Vec<u8>, RandomState>,
         msg_id: u64,
+        msg_id_finished: Option<u64>,
         #[pin]
         readers: Vec<QuicClientStreamReader>,
         index: usize,
@@ -527,8 +532,12 @@ impl Stream for QuicClientStream {
             *me.msg_id += 1;
             return Poll::Ready(Some(Ok(msg)));
         }
+        if *me.msg_id_finished == Some(*me.msg_id) {
+            return Poll::Ready(None);
+        }
 
         let mut polled = 0;
+        let mut done = 0;
         loop {
             // try to get value and increment index
             let value = Pin::new(&mut me.readers[*me.index]).poll_next(cx);
@@ -542,7 +551,16 @@ impl Stream for QuicClientStream {
                         me.messages.insert(msg_id, msg);
                     }
                 }
+                // replay is finished, messages on other streams can be still in flight
+                Poll::Ready(Some(Err(ReceiveError::ReplayFinished(messages)))) => {
+                    *me.msg_id_finished = Some(messages);
+                    if *me.msg_id == messages {
+                        return Poll::Ready(None);
+                    }
+                    done += 1;
+                }
                 Poll::Ready(Some(Err(error))) => return Poll::Ready(Some(Err(error))),
+                Poll::Ready(None) if me.msg_id_finished.is_some() => done += 1,
                 Poll::Ready(None) => return Poll::Ready(None),
                 Poll::Pending => {}
             }
@@ -550,7 +568,11 @@ impl Stream for QuicClientStream {
             // return pending if already polled all streams
             polled += 1;
             if polled == me.readers.len() {
-                return Poll::Pending;
+                return if done == me.readers.len() {
+                    Poll::Ready(None)
+                } else {
+                    Poll::Pending
+                };
             }
         }
     }
@@ -565,6 +587,7 @@ pin_project! {
         Read {
             #[pin] future: BoxFuture<'static, Result<(RecvStream, u64, Vec<u8>), ReceiveError>>,
         },
+        Done,
     }
 }
 
@@ -594,6 +617,7 @@ impl Stream for QuicClientStreamReader {
                             Some(Ok((msg_id, buffer)))
                         }
                         Err(error) => {
+                            self.set(Self::Done);
                             if error.is_eof() {
                                 None
                             } else {
@@ -602,6 +626,7 @@ impl Stream for QuicClientStreamReader {
                         }
                     });
                 }
+                QuicClientStreamReaderProj::Done => return Poll::Ready(None),
             }
         }
     }
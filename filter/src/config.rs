@@ -1,5 +1,6 @@
 use {
     base64::{Engine, engine::general_purpose::STANDARD as base64_engine},
+    prost::Message,
     richat_proto::geyser::{
         CommitmentLevel as CommitmentLevelProto, SubscribeRequest,
         SubscribeRequestAccountsDataSlice, SubscribeRequestFilterAccounts,
@@ -10,6 +11,12 @@ use {
         subscribe_request_filter_accounts_filter_lamports::Cmp as AccountsFilterLamports,
         subscribe_request_filter_accounts_filter_memcmp::Data as AccountsFilterMemcmpOneof,
     },
+    richat_proto::richat::{
+        FilterExpression, FilterExpressionList, SubscribeRequestAccountsConflation,
+        SubscribeRequestExtensions, SubscribeRequestFilterAccountsExtensions,
+        SubscribeRequestFilterTransactionsExtensions,
+        SubscribeRequestFilterTransactionsInstruction, filter_expression::Expression,
+    },
     richat_shared::{
         config::{
             deserialize_maybe_signature, deserialize_num_str, deserialize_pubkey_set,
@@ -57,6 +64,14 @@ pub enum ConfigLimitsError {
     DataSliceOverlap,
     #[error("`include_{0}` is not allowed")]
     BlocksNotAllowed(&'static str),
+    #[error("Expression depth exceeds limit, max {max}")]
+    ExpressionDepthOverflow { max: usize },
+    #[error("Max amount of expression nodes reached, only {max} allowed")]
+    ExpressionNodesOverflow { max: usize },
+    #[error("Accounts conflation is not allowed")]
+    ConflationNotAllowed,
+    #[error("Accounts conflation interval exceeds limit, max {max}ms")]
+    ConflationIntervalOverflow { max: u64 },
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -122,6 +137,20 @@ impl ConfigLimits {
         }
     }
 
+    fn check_expression<T>(
+        expression: &ConfigFilterExpression<T>,
+        depth_max: usize,
+        nodes_max: usize,
+    ) -> Result<(), ConfigLimitsError> {
+        if expression.depth() > depth_max {
+            return Err(ConfigLimitsError::ExpressionDepthOverflow { max: depth_max });
+        }
+        if expression.nodes() > nodes_max {
+            return Err(ConfigLimitsError::ExpressionNodesOverflow { max: nodes_max });
+        }
+        Ok(())
+    }
+
     const fn check_pubkey_max(len: usize, max: usize) -> Result<(), ConfigLimitsError> {
         if len <= max {
             Ok(())
@@ -145,6 +174,7 @@ impl ConfigLimits {
         self.slots.check_filter(self.name_max, &filter.slots)?;
         self.accounts
             .check_filter(self.name_max, &filter.accounts, &filter.accounts_data_slice)?;
+        self.accounts.check_conflation(filter.accounts_conflation)?;
         self.transactions
             .check_filter(self.name_max, &filter.transactions)?;
         self.transactions_status
@@ -194,6 +224,11 @@ pub struct ConfigLimitsAccounts {
     #[serde(deserialize_with = "deserialize_pubkey_set")]
     pub owner_reject: HashSet<Pubkey>,
     pub data_slice_max: usize,
+    pub expression_depth_max: usize,
+    pub expression_nodes_max: usize,
+    pub conflation: bool,
+    #[serde(deserialize_with = "deserialize_num_str")]
+    pub conflation_interval_ms_max: u64,
 }
 
 impl Default for ConfigLimitsAccounts {
@@ -206,6 +241,10 @@ impl Default for ConfigLimitsAccounts {
             owner_max: usize::MAX,
             owner_reject: HashSet::new(),
             data_slice_max: usize::MAX,
+            expression_depth_max: 4,
+            expression_nodes_max: 16,
+            conflation: true,
+            conflation_interval_ms_max: 1_000,
         }
     }
 }
@@ -222,41 +261,22 @@ impl ConfigLimitsAccounts {
 
         for filter in filters.values() {
             ConfigLimits::check_any(
-                filter.account.is_empty() && filter.owner.is_empty(),
+                Self::is_unkeyed(filter)
+                    && filter
+                        .expression
+                        .as_ref()
+                        .is_none_or(|expression| expression.is_unkeyed(Self::is_unkeyed)),
                 self.any,
             )?;
-            ConfigLimits::check_pubkey_max(filter.account.len(), self.account_max)?;
-            ConfigLimits::check_pubkey_max(filter.owner.len(), self.owner_max)?;
-
-            for pubkey in filter.account.iter() {
-                ConfigLimits::check_pubkey_reject(pubkey, &self.account_reject)?;
-            }
-            for pubkey in filter.owner.iter() {
-                ConfigLimits::check_pubkey_reject(pubkey, &self.owner_reject)?;
-            }
-
-            if filter.filters.len() > MAX_FILTERS {
-                return Err(ConfigLimitsError::TooMuchFilters { max: MAX_FILTERS });
-            }
-            let mut datasize_defined = false;
-            for filter in filter.filters.iter() {
-                match filter {
-                    ConfigFilterAccountsFilter::Memcmp {
-                        offset: _offset,
-                        data,
-                    } => {
-                        if data.len() > MAX_DATA_SIZE {
-                            return Err(ConfigLimitsError::FilterDataOverflow);
-                        }
-                    }
-                    ConfigFilterAccountsFilter::DataSize(_) => {
-                        if datasize_defined {
-                            return Err(ConfigLimitsError::DatasizeDuplicated);
-                        }
-                        datasize_defined = true;
-                    }
-                    ConfigFilterAccountsFilter::TokenAccountState => {}
-                    ConfigFilterAccountsFilter::Lamports(_) => {}
+            self.check_filter_fields(filter)?;
+            if let Some(expression) = &filter.expression {
+                ConfigLimits::check_expression(
+                    expression,
+                    self.expression_depth_max,
+                    self.expression_nodes_max,
+                )?;
+                for filter in expression.filters() {
+                    self.check_filter_fields(filter)?;
                 }
             }
         }
@@ -280,6 +300,68 @@ impl ConfigLimitsAccounts {
 
         Ok(())
     }
+
+    pub const fn check_conflation(
+        &self,
+        conflation: Option<ConfigFilterAccountsConflation>,
+    ) -> Result<(), ConfigLimitsError> {
+        match conflation {
+            None => Ok(()),
+            Some(_) if !self.conflation => Err(ConfigLimitsError::ConflationNotAllowed),
+            Some(ConfigFilterAccountsConflation::IntervalMs(interval))
+                if interval > self.conflation_interval_ms_max =>
+            {
+                Err(ConfigLimitsError::ConflationIntervalOverflow {
+                    max: self.conflation_interval_ms_max,
+                })
+            }
+            Some(_) => Ok(()),
+        }
+    }
+
+    /// Filter without accounts and owners is checked against every account
+    fn is_unkeyed(filter: &ConfigFilterAccounts) -> bool {
+        filter.account.is_empty() && filter.owner.is_empty()
+    }
+
+    fn check_filter_fields(&self, filter: &ConfigFilterAccounts) -> Result<(), ConfigLimitsError> {
+        ConfigLimits::check_pubkey_max(filter.account.len(), self.account_max)?;
+        ConfigLimits::check_pubkey_max(filter.owner.len(), self.owner_max)?;
+
+        for pubkey in filter.account.iter() {
+            ConfigLimits::check_pubkey_reject(pubkey, &self.account_reject)?;
+        }
+        for pubkey in filter.owner.iter() {
+            ConfigLimits::check_pubkey_reject(pubkey, &self.owner_reject)?;
+        }
+
+        if filter.filters.len() > MAX_FILTERS {
+            return Err(ConfigLimitsError::TooMuchFilters { max: MAX_FILTERS });
+        }
+        let mut datasize_defined = false;
+        for filter in filter.filters.iter() {
+            match filter {
+                ConfigFilterAccountsFilter::Memcmp {
+                    offset: _offset,
+                    data,
+                } => {
+                    if data.len() > MAX_DATA_SIZE {
+                        return Err(ConfigLimitsError::FilterDataOverflow);
+                    }
+                }
+                ConfigFilterAccountsFilter::DataSize(_) => {
+                    if datasize_defined {
+                        return Err(ConfigLimitsError::DatasizeDuplicated);
+                    }
+                    datasize_defined = true;
+                }
+                ConfigFilterAccountsFilter::TokenAccountState => {}
+                ConfigFilterAccountsFilter::Lamports(_) => {}
+            }
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -296,6 +378,12 @@ pub struct ConfigLimitsTransactions {
     pub account_exclude_max: usize,
     #[serde(deserialize_with = "deserialize_num_str")]
     pub account_required_max: usize,
+    #[serde(deserialize_with = "deserialize_num_str")]
+    pub instructions_max: usize,
+    #[serde(deserialize_with = "deserialize_num_str")]
+    pub expression_depth_max: usize,
+    #[serde(deserialize_with = "deserialize_num_str")]
+    pub expression_nodes_max: usize,
 }
 
 impl Default for ConfigLimitsTransactions {
@@ -307,6 +395,9 @@ impl Default for ConfigLimitsTransactions {
             account_include_reject: HashSet::new(),
             account_exclude_max: usize::MAX,
             account_required_max: usize::MAX,
+            instructions_max: usize::MAX,
+            expression_depth_max: 4,
+            expression_nodes_max: 16,
         }
     }
 }
@@ -322,22 +413,58 @@ impl ConfigLimitsTransactions {
 
         for filter in filters.values() {
             ConfigLimits::check_any(
-                filter.vote.is_none()
-                    && filter.failed.is_none()
-                    && filter.account_include.is_empty()
-                    && filter.account_exclude.is_empty()
-                    && filter.account_required.is_empty(),
+                Self::is_unkeyed(filter)
+                    && filter
+                        .expression
+                        .as_ref()
+                        .is_none_or(|expression| expression.is_unkeyed(Self::is_unkeyed)),
                 self.any,
             )?;
-            ConfigLimits::check_pubkey_max(filter.account_include.len(), self.account_include_max)?;
-            ConfigLimits::check_pubkey_max(filter.account_exclude.len(), self.account_exclude_max)?;
-            ConfigLimits::check_pubkey_max(
-                filter.account_required.len(),
-                self.account_required_max,
-            )?;
+            self.check_filter_fields(filter)?;
+            if let Some(expression) = &filter.expression {
+                ConfigLimits::check_expression(
+                    expression,
+                    self.expression_depth_max,
+                    self.expression_nodes_max,
+                )?;
+                for filter in expression.filters() {
+                    self.check_filter_fields(filter)?;
+                }
+            }
+        }
 
-            for pubkey in filter.account_include.iter() {
-                ConfigLimits::check_pubkey_reject(pubkey, &self.account_include_reject)?;
+        Ok(())
+    }
+
+    fn is_unkeyed(filter: &ConfigFilterTransactions) -> bool {
+        filter.vote.is_none()
+            && filter.failed.is_none()
+            && filter.account_include.is_empty()
+            && filter.account_exclude.is_empty()
+            && filter.account_required.is_empty()
+            && filter.instructions.is_empty()
+    }
+
+    fn check_filter_fields(
+        &self,
+        filter: &ConfigFilterTransactions,
+    ) -> Result<(), ConfigLimitsError> {
+        ConfigLimits::check_pubkey_max(filter.account_include.len(), self.account_include_max)?;
+        ConfigLimits::check_pubkey_max(filter.account_exclude.len(), self.account_exclude_max)?;
+        ConfigLimits::check_pubkey_max(filter.account_required.len(), self.account_required_max)?;
+
+        for pubkey in filter.account_include.iter() {
+            ConfigLimits::check_pubkey_reject(pubkey, &self.account_include_reject)?;
+        }
+
+        ConfigLimits::check_max(filter.instructions.len(), self.instructions_max)?;
+        for instruction in filter.instructions.iter() {
+            if instruction
+                .data_prefix
+                .as_ref()
+                .is_some_and(|data| data.len() > MAX_DATA_SIZE)
+            {
+                return Err(ConfigLimitsError::FilterDataOverflow);
             }
         }
 
@@ -470,6 +597,12 @@ pub enum ConfigFilterError {
     Signature(String, ParseSignatureError),
     #[error("Unknown commitment level: {0}")]
     UnknownCommitment(i32),
+    #[error("Failed to decode expression filter: {0}")]
+    ExpressionDecode(prost::DecodeError),
+    #[error("Nested expression in expression filter is not allowed")]
+    ExpressionNested,
+    #[error("Extensions for unknown {0} filter `{1}`")]
+    ExtensionsUnknownFilter(&'static str, String),
 }
 
 #[derive(Debug, Clone, Default, Deserialize, Serialize)]
@@ -484,6 +617,10 @@ pub struct ConfigFilter {
     pub blocks_meta: HashSet<String>,
     pub blocks: HashMap<String, ConfigFilterBlocks>,
     pub commitment: Option<ConfigFilterCommitment>,
+    /// Richat extension, see [`SubscribeRequestExtensions`]
+    pub accounts_conflation: Option<ConfigFilterAccountsConflation>,
+    /// Richat extension, see [`SubscribeRequestExtensions`]
+    pub rollback_notifications: bool,
 }
 
 impl ConfigFilter {
@@ -524,6 +661,41 @@ impl ConfigFilter {
             .map(|pk| pubkey_encode(&pk.to_bytes()))
             .collect()
     }
+
+    /// Apply Richat extensions decoded from the same `SubscribeRequest`.
+    pub fn set_extensions(
+        &mut self,
+        extensions: SubscribeRequestExtensions,
+    ) -> Result<(), ConfigFilterError> {
+        self.accounts_conflation = extensions.accounts_conflation.map(Into::into);
+        self.rollback_notifications = extensions.rollback_notifications.is_some();
+        for (name, extensions) in extensions.accounts {
+            match self.accounts.get_mut(&name) {
+                Some(filter) => filter.set_extensions(extensions)?,
+                None => return Err(ConfigFilterError::ExtensionsUnknownFilter("accounts", name)),
+            }
+        }
+        for (kind, filters, extensions) in [
+            (
+                "transactions",
+                &mut self.transactions,
+                extensions.transactions,
+            ),
+            (
+                "transactions_status",
+                &mut self.transactions_status,
+                extensions.transactions_status,
+            ),
+        ] {
+            for (name, extensions) in extensions {
+                match filters.get_mut(&name) {
+                    Some(filter) => filter.set_extensions(extensions)?,
+                    None => return Err(ConfigFilterError::ExtensionsUnknownFilter(kind, name)),
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 impl TryFrom<SubscribeRequest> for ConfigFilter {
@@ -544,6 +716,8 @@ impl TryFrom<SubscribeRequest> for ConfigFilter {
             blocks_meta: value.blocks_meta.into_keys().collect(),
             blocks: Self::try_conv_map(value.blocks)?,
             commitment: value.commitment.map(|value| value.try_into()).transpose()?,
+            accounts_conflation: None,
+            rollback_notifications: false,
         })
     }
 }
@@ -606,6 +780,8 @@ pub struct ConfigFilterAccounts {
     pub owner: Vec<Pubkey>,
     pub filters: Vec<ConfigFilterAccountsFilter>,
     pub nonempty_txn_signature: Option<bool>,
+    /// Richat extension, see [`SubscribeRequestExtensions`]
+    pub expression: Option<Box<ConfigFilterExpression<ConfigFilterAccounts>>>,
 }
 
 impl TryFrom<SubscribeRequestFilterAccounts> for ConfigFilterAccounts {
@@ -630,10 +806,37 @@ impl TryFrom<SubscribeRequestFilterAccounts> for ConfigFilterAccounts {
             owner,
             filters,
             nonempty_txn_signature: value.nonempty_txn_signature,
+            expression: None,
         })
     }
 }
 
+impl ConfigFilterAccounts {
+    fn set_extensions(
+        &mut self,
+        extensions: SubscribeRequestFilterAccountsExtensions,
+    ) -> Result<(), ConfigFilterError> {
+        self.expression = extensions
+            .expression
+            .map(|expression| {
+                ConfigFilterExpression::try_from_proto(expression, &|data: Vec<u8>| {
+                    let extensions =
+                        SubscribeRequestFilterAccountsExtensions::decode(data.as_slice())
+                            .map_err(ConfigFilterError::ExpressionDecode)?;
+                    if extensions.expression.is_some() {
+                        return Err(ConfigFilterError::ExpressionNested);
+                    }
+                    SubscribeRequestFilterAccounts::decode(data.as_slice())
+                        .map_err(ConfigFilterError::ExpressionDecode)?
+                        .try_into()
+                })
+                .map(Box::new)
+            })
+            .transpose()?;
+        Ok(())
+    }
+}
+
 impl From<ConfigFilterAccounts> for SubscribeRequestFilterAccounts {
     fn from(value: ConfigFilterAccounts) -> Self {
         Self {
@@ -813,6 +1016,23 @@ impl From<ConfigFilterAccountsFilterLamports> for SubscribeRequestFilterAccounts
     }
 }
 
+/// Deliver only the last account update per pubkey within the slot or the time window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields, rename_all = "snake_case")]
+pub enum ConfigFilterAccountsConflation {
+    Slot,
+    IntervalMs(u64),
+}
+
+impl From<SubscribeRequestAccountsConflation> for ConfigFilterAccountsConflation {
+    fn from(value: SubscribeRequestAccountsConflation) -> Self {
+        match value.interval_ms {
+            Some(interval) => Self::IntervalMs(interval),
+            None => Self::Slot,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct ConfigFilterAccountsDataSlice {
@@ -853,6 +1073,10 @@ pub struct ConfigFilterTransactions {
     pub account_exclude: Vec<Pubkey>,
     #[serde(deserialize_with = "deserialize_pubkey_vec")]
     pub account_required: Vec<Pubkey>,
+    /// Richat extension, see [`SubscribeRequestExtensions`]
+    pub instructions: Vec<ConfigFilterTransactionsInstruction>,
+    /// Richat extension, see [`SubscribeRequestExtensions`]
+    pub expression: Option<Box<ConfigFilterExpression<ConfigFilterTransactions>>>,
 }
 
 impl TryFrom<SubscribeRequestFilterTransactions> for ConfigFilterTransactions {
@@ -871,6 +1095,64 @@ impl TryFrom<SubscribeRequestFilterTransactions> for ConfigFilterTransactions {
             account_include: ConfigFilter::parse_vec_pubkeys(value.account_include)?,
             account_exclude: ConfigFilter::parse_vec_pubkeys(value.account_exclude)?,
             account_required: ConfigFilter::parse_vec_pubkeys(value.account_required)?,
+            instructions: vec![],
+            expression: None,
+        })
+    }
+}
+
+impl ConfigFilterTransactions {
+    fn set_extensions(
+        &mut self,
+        extensions: SubscribeRequestFilterTransactionsExtensions,
+    ) -> Result<(), ConfigFilterError> {
+        self.instructions = extensions
+            .instructions
+            .into_iter()
+            .map(TryInto::try_into)
+            .collect::<Result<_, _>>()?;
+        self.expression = extensions
+            .expression
+            .map(|expression| {
+                ConfigFilterExpression::try_from_proto(expression, &|data: Vec<u8>| {
+                    let extensions =
+                        SubscribeRequestFilterTransactionsExtensions::decode(data.as_slice())
+                            .map_err(ConfigFilterError::ExpressionDecode)?;
+                    if extensions.expression.is_some() {
+                        return Err(ConfigFilterError::ExpressionNested);
+                    }
+                    let mut filter = Self::try_from(
+                        SubscribeRequestFilterTransactions::decode(data.as_slice())
+                            .map_err(ConfigFilterError::ExpressionDecode)?,
+                    )?;
+                    filter.set_extensions(extensions)?;
+                    Ok(filter)
+                })
+                .map(Box::new)
+            })
+            .transpose()?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFilterTransactionsInstruction {
+    pub program_id: Pubkey,
+    #[serde(default)]
+    pub data_prefix: Option<Vec<u8>>,
+}
+
+impl TryFrom<SubscribeRequestFilterTransactionsInstruction>
+    for ConfigFilterTransactionsInstruction
+{
+    type Error = ConfigFilterError;
+
+    fn try_from(value: SubscribeRequestFilterTransactionsInstruction) -> Result<Self, Self::Error> {
+        Ok(Self {
+            program_id: pubkey_decode(&value.program_id)
+                .map_err(|error| ConfigFilterError::Pubkey(value.program_id, error))?,
+            data_prefix: value.data_prefix,
         })
     }
 }
@@ -888,6 +1170,102 @@ impl From<ConfigFilterTransactions> for SubscribeRequestFilterTransactions {
     }
 }
 
+/// Boolean expression over filters of the same type, see [`FilterExpression`].
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields, rename_all = "snake_case")]
+pub enum ConfigFilterExpression<T> {
+    And(Vec<ConfigFilterExpression<T>>),
+    Or(Vec<ConfigFilterExpression<T>>),
+    Not(Box<ConfigFilterExpression<T>>),
+    Filter(T),
+}
+
+impl<T> ConfigFilterExpression<T> {
+    fn try_from_proto(
+        value: FilterExpression,
+        parse_filter: &impl Fn(Vec<u8>) -> Result<T, ConfigFilterError>,
+    ) -> Result<Self, ConfigFilterError> {
+        let parse_list = |list: FilterExpressionList| {
+            list.expressions
+                .into_iter()
+                .map(|expression| Self::try_from_proto(expression, parse_filter))
+                .collect::<Result<Vec<_>, _>>()
+        };
+
+        Ok(
+            match value
+                .expression
+                .ok_or(ConfigFilterError::FieldNotDefined("expression"))?
+            {
+                Expression::And(list) => Self::And(parse_list(list)?),
+                Expression::Or(list) => Self::Or(parse_list(list)?),
+                Expression::Not(expression) => {
+                    Self::Not(Box::new(Self::try_from_proto(*expression, parse_filter)?))
+                }
+                Expression::Filter(data) => Self::Filter(parse_filter(data)?),
+            },
+        )
+    }
+
+    /// Depth of the expression, filter has depth `1`
+    pub fn depth(&self) -> usize {
+        match self {
+            Self::And(list) | Self::Or(list) => {
+                list.iter().map(Self::depth).max().unwrap_or_default() + 1
+            }
+            Self::Not(expression) => expression.depth() + 1,
+            Self::Filter(_) => 1,
+        }
+    }
+
+    /// Number of operators and filters in the expression
+    pub fn nodes(&self) -> usize {
+        match self {
+            Self::And(list) | Self::Or(list) => list.iter().map(Self::nodes).sum::<usize>() + 1,
+            Self::Not(expression) => expression.nodes() + 1,
+            Self::Filter(_) => 1,
+        }
+    }
+
+    /// Whether the expression can match messages without keys (accounts, owners, etc.),
+    /// `is_unkeyed` checks filters in the expression
+    pub fn is_unkeyed(&self, is_unkeyed: impl Fn(&T) -> bool + Copy) -> bool {
+        match self {
+            Self::And(list) => list
+                .iter()
+                .all(|expression| expression.is_unkeyed(is_unkeyed)),
+            Self::Or(list) => {
+                list.is_empty()
+                    || list
+                        .iter()
+                        .any(|expression| expression.is_unkeyed(is_unkeyed))
+            }
+            // negation matches everything except keyed messages
+            Self::Not(_) => true,
+            Self::Filter(filter) => is_unkeyed(filter),
+        }
+    }
+
+    /// Filters used in the expression
+    pub fn filters(&self) -> Vec<&T> {
+        let mut filters = vec![];
+        self.collect_filters(&mut filters);
+        filters
+    }
+
+    fn collect_filters<'a>(&'a self, filters: &mut Vec<&'a T>) {
+        match self {
+            Self::And(list) | Self::Or(list) => {
+                for expression in list {
+                    expression.collect_filters(filters);
+                }
+            }
+            Self::Not(expression) => expression.collect_filters(filters),
+            Self::Filter(filter) => filters.push(filter),
+        }
+    }
+}
+
 #[derive(Debug, Default, Clone, Deserialize, Serialize)]
 #[serde(deny_unknown_fields, default)]
 pub struct ConfigFilterBlocks {
@@ -970,3 +1348,175 @@ impl From<ConfigFilterCommitment> for CommitmentLevel {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{
+            ConfigFilter, ConfigFilterAccounts, ConfigFilterError, ConfigFilterExpression,
+            ConfigFilterTransactions, ConfigLimits, ConfigLimitsError,
+        },
+        richat_proto::richat::SubscribeRequestExtensions,
+        solana_pubkey::Pubkey,
+    };
+
+    fn limits() -> ConfigLimits {
+        let mut limits = ConfigLimits::default();
+        limits.accounts.any = false;
+        limits.transactions.any = false;
+        limits
+    }
+
+    fn check_accounts(
+        expression: Option<ConfigFilterExpression<ConfigFilterAccounts>>,
+    ) -> Result<(), ConfigLimitsError> {
+        limits().check_filter(&ConfigFilter {
+            accounts: [(
+                "".to_owned(),
+                ConfigFilterAccounts {
+                    expression: expression.map(Box::new),
+                    ..Default::default()
+                },
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        })
+    }
+
+    fn account(account: Vec<Pubkey>) -> ConfigFilterExpression<ConfigFilterAccounts> {
+        ConfigFilterExpression::Filter(ConfigFilterAccounts {
+            account,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_limits_expression_any() {
+        let keyed = || account(vec![Pubkey::new_unique()]);
+        let unkeyed = || account(vec![]);
+
+        assert!(matches!(
+            check_accounts(None),
+            Err(ConfigLimitsError::EmptyNotAllowed)
+        ));
+        assert!(check_accounts(Some(keyed())).is_ok());
+        assert!(matches!(
+            check_accounts(Some(unkeyed())),
+            Err(ConfigLimitsError::EmptyNotAllowed)
+        ));
+        // `not(account = <random>)` is the full stream
+        assert!(matches!(
+            check_accounts(Some(ConfigFilterExpression::Not(Box::new(keyed())))),
+            Err(ConfigLimitsError::EmptyNotAllowed)
+        ));
+        assert!(matches!(
+            check_accounts(Some(ConfigFilterExpression::Or(vec![keyed(), unkeyed()]))),
+            Err(ConfigLimitsError::EmptyNotAllowed)
+        ));
+        assert!(check_accounts(Some(ConfigFilterExpression::Or(vec![keyed(), keyed()]))).is_ok());
+        assert!(
+            check_accounts(Some(ConfigFilterExpression::And(vec![
+                keyed(),
+                ConfigFilterExpression::Not(Box::new(keyed())),
+            ])))
+            .is_ok()
+        );
+        assert!(matches!(
+            check_accounts(Some(ConfigFilterExpression::And(vec![]))),
+            Err(ConfigLimitsError::EmptyNotAllowed)
+        ));
+
+        // top-level keys restrict the expression
+        let filter = ConfigFilter {
+            transactions: [(
+                "".to_owned(),
+                ConfigFilterTransactions {
+                    account_include: vec![Pubkey::new_unique()],
+                    expression: Some(Box::new(ConfigFilterExpression::Not(Box::new(
+                        ConfigFilterExpression::Filter(ConfigFilterTransactions {
+                            account_include: vec![Pubkey::new_unique()],
+                            ..Default::default()
+                        }),
+                    )))),
+                    ..Default::default()
+                },
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        assert!(limits().check_filter(&filter).is_ok());
+        let mut filter = filter;
+        filter
+            .transactions
+            .get_mut("")
+            .unwrap()
+            .account_include
+            .clear();
+        assert!(matches!(
+            limits().check_filter(&filter),
+            Err(ConfigLimitsError::EmptyNotAllowed)
+        ));
+    }
+
+    #[test]
+    fn test_limits_expression_size() {
+        let mut expression = account(vec![Pubkey::new_unique()]);
+        for _ in 0..4 {
+            expression = ConfigFilterExpression::And(vec![expression]);
+        }
+        assert!(matches!(
+            check_accounts(Some(expression)),
+            Err(ConfigLimitsError::ExpressionDepthOverflow { max: 4 })
+        ));
+
+        let expression = ConfigFilterExpression::Or(
+            (0..16)
+                .map(|_| account(vec![Pubkey::new_unique()]))
+                .collect(),
+        );
+        assert!(matches!(
+            check_accounts(Some(expression)),
+            Err(ConfigLimitsError::ExpressionNodesOverflow { max: 16 })
+        ));
+    }
+
+    #[test]
+    fn test_extensions_unknown_filter() {
+        let mut config = ConfigFilter {
+            accounts: [("accounts".to_owned(), ConfigFilterAccounts::default())]
+                .into_iter()
+                .collect(),
+            transactions: [("tx".to_owned(), ConfigFilterTransactions::default())]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        };
+
+        let mut extensions = SubscribeRequestExtensions::default();
+        extensions
+            .accounts
+            .insert("accounts".to_owned(), Default::default());
+        extensions
+            .transactions
+            .insert("tx".to_owned(), Default::default());
+        assert!(config.set_extensions(extensions.clone()).is_ok());
+
+        let mut unknown = extensions.clone();
+        unknown.accounts.insert("tx".to_owned(), Default::default());
+        assert!(matches!(
+            config.set_extensions(unknown),
+            Err(ConfigFilterError::ExtensionsUnknownFilter("accounts", name)) if name == "tx"
+        ));
+
+        let mut unknown = extensions;
+        unknown
+            .transactions_status
+            .insert("tx".to_owned(), Default::default());
+        assert!(matches!(
+            config.set_extensions(unknown),
+            Err(ConfigFilterError::ExtensionsUnknownFilter("transactions_status", name)) if name == "tx"
+        ));
+    }
+}
@@ -1,11 +1,13 @@
 use {
     crate::{
         config::{
-            ConfigFilter, ConfigFilterAccounts, ConfigFilterAccountsDataSlice,
-            ConfigFilterAccountsFilter, ConfigFilterAccountsFilterLamports, ConfigFilterBlocks,
-            ConfigFilterCommitment, ConfigFilterSlots, ConfigFilterTransactions, MAX_DATA_SIZE,
-            MAX_FILTERS,
+            ConfigFilter, ConfigFilterAccounts, ConfigFilterAccountsConflation,
+            ConfigFilterAccountsDataSlice, ConfigFilterAccountsFilter,
+            ConfigFilterAccountsFilterLamports, ConfigFilterBlocks, ConfigFilterCommitment,
+            ConfigFilterExpression, ConfigFilterSlots, ConfigFilterTransactions,
+            ConfigFilterTransactionsInstruction, MAX_DATA_SIZE, MAX_FILTERS,
         },
+        index::FilterIndexMatches,
         message::{
             Message, MessageAccount, MessageBlock, MessageBlockCreatedAt, MessageBlockMeta,
             MessageEntry, MessageRef, MessageSlot, MessageTransaction,
@@ -23,6 +25,7 @@ use {
         SubscribeUpdateSlot, SubscribeUpdateTransaction, SubscribeUpdateTransactionStatus,
         subscribe_update::UpdateOneof,
     },
+    serde::Serialize,
     smallvec::{SmallVec, smallvec_inline},
     solana_account::ReadableAccount,
     solana_commitment_config::CommitmentLevel,
@@ -34,13 +37,14 @@ use {
     std::{
         borrow::{Borrow, Cow},
         collections::{HashMap, HashSet},
+        hash::Hash,
         ops::{Not, Range},
         sync::Arc,
     },
 };
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-struct FilterName(Arc<String>);
+pub struct FilterName(Arc<String>);
 
 impl AsRef<str> for FilterName {
     #[inline]
@@ -77,14 +81,16 @@ impl FilterNames {
 #[derive(Debug, Clone)]
 pub struct Filter {
     slots: FilterSlots,
-    accounts: FilterAccounts,
+    pub(crate) accounts: FilterAccounts,
     accounts_data_slices: FilterAccountDataSlices,
-    transactions: FilterTransactions,
-    transactions_status: FilterTransactions,
+    pub(crate) transactions: FilterTransactions,
+    pub(crate) transactions_status: FilterTransactions,
     entries: FilterEntries,
     blocks_meta: FilterBlocksMeta,
     blocks: FilterBlocks,
     commitment: ConfigFilterCommitment,
+    accounts_conflation: Option<ConfigFilterAccountsConflation>,
+    rollback_notifications: bool,
 }
 
 impl Default for Filter {
@@ -105,10 +111,25 @@ impl Default for Filter {
             blocks_meta: FilterBlocksMeta::default(),
             blocks: FilterBlocks::default(),
             commitment: ConfigFilterCommitment::default(),
+            accounts_conflation: None,
+            rollback_notifications: false,
         }
     }
 }
 
+/// Number of named filters by type, describes the filter without details.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct FilterSummary {
+    pub commitment: ConfigFilterCommitment,
+    pub slots: usize,
+    pub accounts: usize,
+    pub transactions: usize,
+    pub transactions_status: usize,
+    pub entries: usize,
+    pub blocks_meta: usize,
+    pub blocks: usize,
+}
+
 impl Filter {
     pub fn new(config: &ConfigFilter) -> Self {
         let mut names = FilterNames::default();
@@ -132,6 +153,8 @@ impl Filter {
             commitment: config
                 .commitment
                 .unwrap_or(ConfigFilterCommitment::Processed),
+            accounts_conflation: config.accounts_conflation,
+            rollback_notifications: config.rollback_notifications,
         }
     }
 
@@ -143,6 +166,35 @@ impl Filter {
         self.commitment
     }
 
+    pub const fn accounts_conflation(&self) -> Option<ConfigFilterAccountsConflation> {
+        self.accounts_conflation
+    }
+
+    pub const fn rollback_notifications(&self) -> bool {
+        self.rollback_notifications
+    }
+
+    /// Returns `true` if every accounts filter matches only accounts of owners accepted by `is_owner`.
+    pub fn accounts_owned_by(&self, is_owner: impl Fn(&Pubkey) -> bool) -> bool {
+        self.accounts
+            .filters
+            .values()
+            .all(|filter| !filter.owner.is_empty() && filter.owner.iter().all(&is_owner))
+    }
+
+    pub fn summary(&self) -> FilterSummary {
+        FilterSummary {
+            commitment: self.commitment,
+            slots: self.slots.filters.len(),
+            accounts: self.accounts.filters.len(),
+            transactions: self.transactions.filters.len(),
+            transactions_status: self.transactions_status.filters.len(),
+            entries: self.entries.filters.len(),
+            blocks_meta: self.blocks_meta.filters.len(),
+            blocks: self.blocks.filters.len(),
+        }
+    }
+
     pub fn get_updates<'a>(
         &'a self,
         message: &'a Message,
@@ -197,6 +249,52 @@ impl Filter {
         }
         vec
     }
+
+    /// Same as [`Filter::get_updates_ref`], but accounts and transactions are
+    /// taken from matches created by [`FilterIndex`](crate::index::FilterIndex) for this filter `key`.
+    pub fn get_updates_indexed<'a, K: Eq + Hash>(
+        &'a self,
+        message: MessageRef<'a>,
+        commitment: CommitmentLevel,
+        matches: &'a FilterIndexMatches<K>,
+        key: &K,
+    ) -> SmallVec<[FilteredUpdate<'a>; 2]> {
+        let mut vec = SmallVec::<[FilteredUpdate; 2]>::new();
+        match message {
+            MessageRef::Account(message) => {
+                if let Some(names) = matches.get_accounts(key) {
+                    vec.push(FilteredUpdate {
+                        filters: names.iter().map(AsRef::as_ref).collect(),
+                        filtered_update: FilteredUpdateType::Account {
+                            message,
+                            data_slices: &self.accounts_data_slices,
+                        },
+                    });
+                }
+            }
+            MessageRef::Transaction(message) => {
+                for (names, filter_type) in [
+                    (
+                        matches.get_transactions(key),
+                        FilterTransactionsType::Transaction,
+                    ),
+                    (
+                        matches.get_transactions_status(key),
+                        FilterTransactionsType::TransactionStatus,
+                    ),
+                ] {
+                    if let Some(names) = names {
+                        vec.push(FilteredUpdate {
+                            filters: names.iter().map(AsRef::as_ref).collect(),
+                            filtered_update: filter_type.create_update(message),
+                        });
+                    }
+                }
+            }
+            message => return self.get_updates_ref(message, commitment),
+        }
+        vec
+    }
 }
 
 #[derive(Debug, Default, Clone, Copy)]
@@ -269,6 +367,39 @@ impl FilterSlots {
     }
 }
 
+/// Compiled [`ConfigFilterExpression`]
+#[derive(Debug, Clone)]
+enum FilterExpression<T> {
+    And(Vec<FilterExpression<T>>),
+    Or(Vec<FilterExpression<T>>),
+    Not(Box<FilterExpression<T>>),
+    Filter(T),
+}
+
+impl<T> FilterExpression<T> {
+    fn new<C>(config: &ConfigFilterExpression<C>, create: &impl Fn(&C) -> T) -> Self {
+        match config {
+            ConfigFilterExpression::And(list) => {
+                Self::And(list.iter().map(|item| Self::new(item, create)).collect())
+            }
+            ConfigFilterExpression::Or(list) => {
+                Self::Or(list.iter().map(|item| Self::new(item, create)).collect())
+            }
+            ConfigFilterExpression::Not(item) => Self::Not(Box::new(Self::new(item, create))),
+            ConfigFilterExpression::Filter(filter) => Self::Filter(create(filter)),
+        }
+    }
+
+    fn is_match(&self, is_match: &impl Fn(&T) -> bool) -> bool {
+        match self {
+            Self::And(list) => list.iter().all(|item| item.is_match(is_match)),
+            Self::Or(list) => list.iter().any(|item| item.is_match(is_match)),
+            Self::Not(item) => !item.is_match(is_match),
+            Self::Filter(filter) => is_match(filter),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum FilterAccountsLamports {
     Eq(u64),
@@ -353,35 +484,83 @@ impl FilterAccountsState {
 }
 
 #[derive(Debug, Clone)]
-struct FilterAccountsInner {
-    account: HashSet<Pubkey>,
-    owner: HashSet<Pubkey>,
+pub(crate) struct FilterAccountsInner {
+    pub(crate) account: HashSet<Pubkey>,
+    pub(crate) owner: HashSet<Pubkey>,
     filters: Option<FilterAccountsState>,
     nonempty_txn_signature: Option<bool>,
+    expression: Option<Box<FilterExpression<FilterAccountsInner>>>,
+}
+
+impl FilterAccountsInner {
+    fn new(filter: &ConfigFilterAccounts) -> Self {
+        Self {
+            account: filter.account.iter().copied().collect(),
+            owner: filter.owner.iter().copied().collect(),
+            filters: if filter.filters.is_empty() {
+                None
+            } else {
+                Some(FilterAccountsState::new(&filter.filters))
+            },
+            nonempty_txn_signature: filter.nonempty_txn_signature,
+            expression: filter
+                .expression
+                .as_ref()
+                .map(|expression| Box::new(FilterExpression::new(expression, &Self::new))),
+        }
+    }
+
+    pub(crate) fn is_match(
+        &self,
+        pubkey: &Pubkey,
+        owner: &Pubkey,
+        lamports: u64,
+        data: &[u8],
+        nonempty_txn_signature: bool,
+    ) -> bool {
+        if !self.account.is_empty() && !self.account.contains(pubkey) {
+            return false;
+        }
+
+        if !self.owner.is_empty() && !self.owner.contains(owner) {
+            return false;
+        }
+
+        if let Some(filters) = &self.filters {
+            if !filters.is_match(lamports, data) {
+                return false;
+            }
+        }
+
+        if let Some(expected) = self.nonempty_txn_signature {
+            if expected != nonempty_txn_signature {
+                return false;
+            }
+        }
+
+        if let Some(expression) = &self.expression {
+            if !expression.is_match(&|filter| {
+                filter.is_match(pubkey, owner, lamports, data, nonempty_txn_signature)
+            }) {
+                return false;
+            }
+        }
+
+        true
+    }
 }
 
 #[derive(Debug, Default, Clone)]
-struct FilterAccounts {
-    filters: HashMap<FilterName, FilterAccountsInner>,
+pub(crate) struct FilterAccounts {
+    pub(crate) filters: HashMap<FilterName, Arc<FilterAccountsInner>>,
 }
 
 impl FilterAccounts {
     fn new(names: &mut FilterNames, configs: &HashMap<String, ConfigFilterAccounts>) -> Self {
         let mut me = Self::default();
         for (name, filter) in configs {
-            me.filters.insert(
-                names.get(name),
-                FilterAccountsInner {
-                    account: filter.account.iter().copied().collect(),
-                    owner: filter.owner.iter().copied().collect(),
-                    filters: if filter.filters.is_empty() {
-                        None
-                    } else {
-                        Some(FilterAccountsState::new(&filter.filters))
-                    },
-                    nonempty_txn_signature: filter.nonempty_txn_signature,
-                },
-            );
+            me.filters
+                .insert(names.get(name), Arc::new(FilterAccountsInner::new(filter)));
         }
         me
     }
@@ -400,29 +579,16 @@ impl FilterAccounts {
         let filters = self
             .filters
             .iter()
-            .filter_map(|(name, filter)| {
-                if !filter.account.is_empty() && !filter.account.contains(msg_pubkey) {
-                    return None;
-                }
-
-                if !filter.owner.is_empty() && !filter.owner.contains(msg_owner) {
-                    return None;
-                }
-
-                if let Some(filters) = &filter.filters {
-                    if !filters.is_match(msg_lamports, msg_data) {
-                        return None;
-                    }
-                }
-
-                if let Some(nonempty_txn_signature) = filter.nonempty_txn_signature {
-                    if nonempty_txn_signature != msg_nonempty_txn_signature {
-                        return None;
-                    }
-                }
-
-                Some(name.as_ref())
+            .filter(|(_name, filter)| {
+                filter.is_match(
+                    msg_pubkey,
+                    msg_owner,
+                    msg_lamports,
+                    msg_data,
+                    msg_nonempty_txn_signature,
+                )
             })
+            .map(|(name, _filter)| name.as_ref())
             .collect::<FilteredUpdateFilters>();
 
         filters.is_empty().not().then(|| FilteredUpdate {
@@ -487,19 +653,155 @@ enum FilterTransactionsType {
 }
 
 #[derive(Debug, Clone)]
-struct FilterTransactionsInner {
+pub(crate) struct FilterTransactionsInner {
     vote: Option<bool>,
     failed: Option<bool>,
     signature: Option<Signature>,
-    account_include: HashSet<Pubkey>,
+    pub(crate) account_include: HashSet<Pubkey>,
     account_exclude: HashSet<Pubkey>,
-    account_required: HashSet<Pubkey>,
+    pub(crate) account_required: HashSet<Pubkey>,
+    pub(crate) instructions: Vec<ConfigFilterTransactionsInstruction>,
+    expression: Option<Box<FilterExpression<FilterTransactionsInner>>>,
+}
+
+impl FilterTransactionsInner {
+    fn new(filter: &ConfigFilterTransactions) -> Self {
+        Self {
+            vote: filter.vote,
+            failed: filter.failed,
+            signature: filter.signature,
+            account_include: filter.account_include.iter().copied().collect(),
+            account_exclude: filter.account_exclude.iter().copied().collect(),
+            account_required: filter.account_required.iter().copied().collect(),
+            instructions: filter.instructions.clone(),
+            expression: filter
+                .expression
+                .as_ref()
+                .map(|expression| Box::new(FilterExpression::new(expression, &Self::new))),
+        }
+    }
+
+    pub(crate) fn is_match(&self, message: &MessageTransaction) -> bool {
+        let account_keys = message.account_keys();
+
+        if let Some(is_vote) = self.vote {
+            if is_vote != message.vote() {
+                return false;
+            }
+        }
+
+        if let Some(is_failed) = self.failed {
+            if is_failed != message.failed() {
+                return false;
+            }
+        }
+
+        if let Some(expected) = &self.signature {
+            if expected.as_ref() != message.signature_ref() {
+                return false;
+            }
+        }
+
+        if !self.account_include.is_empty()
+            && self
+                .account_include
+                .intersection(account_keys)
+                .next()
+                .is_none()
+        {
+            return false;
+        }
+
+        if !self.account_exclude.is_empty()
+            && self
+                .account_exclude
+                .intersection(account_keys)
+                .next()
+                .is_some()
+        {
+            return false;
+        }
+
+        if !self.account_required.is_empty() && !self.account_required.is_subset(account_keys) {
+            return false;
+        }
+
+        // instructions require decoded transaction, checked last
+        if !self.instructions.is_empty() && !self.is_match_instructions(message) {
+            return false;
+        }
+
+        if let Some(expression) = &self.expression {
+            if !expression.is_match(&|filter| filter.is_match(message)) {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    fn is_match_instructions(&self, message: &MessageTransaction) -> bool {
+        // every invoked program is in account keys
+        if !self
+            .instructions
+            .iter()
+            .any(|instruction| message.account_keys().contains(&instruction.program_id))
+        {
+            return false;
+        }
+
+        let Ok(transaction) = message.transaction() else {
+            return false;
+        };
+        let (Some(tx_message), Some(meta)) = (
+            transaction
+                .transaction
+                .as_ref()
+                .and_then(|tx| tx.message.as_ref()),
+            transaction.meta.as_ref(),
+        ) else {
+            return false;
+        };
+
+        // account keys of the message in the order used by instructions indexes
+        let program_id = |index: u32| {
+            tx_message
+                .account_keys
+                .iter()
+                .chain(meta.loaded_writable_addresses.iter())
+                .chain(meta.loaded_readonly_addresses.iter())
+                .nth(index as usize)
+                .map(Vec::as_slice)
+        };
+
+        tx_message
+            .instructions
+            .iter()
+            .map(|ix| (ix.program_id_index, ix.data.as_slice()))
+            .chain(meta.inner_instructions.iter().flat_map(|inner| {
+                inner
+                    .instructions
+                    .iter()
+                    .map(|ix| (ix.program_id_index, ix.data.as_slice()))
+            }))
+            .any(|(program_id_index, data)| {
+                program_id(program_id_index).is_some_and(|program_id| {
+                    self.instructions.iter().any(|instruction| {
+                        instruction.program_id.as_ref() == program_id
+                            && instruction
+                                .data_prefix
+                                .as_ref()
+                                .is_none_or(|prefix| data.starts_with(prefix))
+                    })
+                })
+            })
+    }
 }
 
 #[derive(Debug, Clone)]
-struct FilterTransactions {
+pub(crate) struct FilterTransactions {
     filter_type: FilterTransactionsType,
-    filters: HashMap<FilterName, FilterTransactionsInner>,
+    pub(crate) filters: HashMap<FilterName, Arc<FilterTransactionsInner>>,
 }
 
 impl FilterTransactions {
@@ -512,14 +814,7 @@ impl FilterTransactions {
         for (name, filter) in configs {
             filters.insert(
                 names.get(name),
-                FilterTransactionsInner {
-                    vote: filter.vote,
-                    failed: filter.failed,
-                    signature: filter.signature,
-                    account_include: filter.account_include.iter().copied().collect(),
-                    account_exclude: filter.account_exclude.iter().copied().collect(),
-                    account_required: filter.account_required.iter().copied().collect(),
-                },
+                Arc::new(FilterTransactionsInner::new(filter)),
             );
         }
         Self {
@@ -529,75 +824,29 @@ impl FilterTransactions {
     }
 
     fn get_update<'a>(&'a self, message: &'a MessageTransaction) -> Option<FilteredUpdate<'a>> {
-        let msg_vote = message.vote();
-        let msg_failed = message.failed();
-        let msg_signature = message.signature_ref();
-        let msg_account_keys = message.account_keys();
-
         let filters = self
             .filters
             .iter()
-            .filter_map(|(name, filter)| {
-                if let Some(is_vote) = filter.vote {
-                    if is_vote != msg_vote {
-                        return None;
-                    }
-                }
-
-                if let Some(is_failed) = filter.failed {
-                    if is_failed != msg_failed {
-                        return None;
-                    }
-                }
-
-                if let Some(signature) = &filter.signature {
-                    if signature.as_ref() != msg_signature {
-                        return None;
-                    }
-                }
-
-                if !filter.account_include.is_empty()
-                    && filter
-                        .account_include
-                        .intersection(msg_account_keys)
-                        .next()
-                        .is_none()
-                {
-                    return None;
-                }
-
-                if !filter.account_exclude.is_empty()
-                    && filter
-                        .account_exclude
-                        .intersection(msg_account_keys)
-                        .next()
-                        .is_some()
-                {
-                    return None;
-                }
-
-                if !filter.account_required.is_empty()
-                    && !filter.account_required.is_subset(msg_account_keys)
-                {
-                    return None;
-                }
-
-                Some(name.as_ref())
-            })
+            .filter(|(_name, filter)| filter.is_match(message))
+            .map(|(name, _filter)| name.as_ref())
             .collect::<FilteredUpdateFilters>();
 
         filters.is_empty().not().then(|| FilteredUpdate {
             filters,
-            filtered_update: match self.filter_type {
-                FilterTransactionsType::Transaction => FilteredUpdateType::Transaction { message },
-                FilterTransactionsType::TransactionStatus => {
-                    FilteredUpdateType::TransactionStatus { message }
-                }
-            },
+            filtered_update: self.filter_type.create_update(message),
         })
     }
 }
 
+impl FilterTransactionsType {
+    const fn create_update(self, message: &MessageTransaction) -> FilteredUpdateType<'_> {
+        match self {
+            Self::Transaction => FilteredUpdateType::Transaction { message },
+            Self::TransactionStatus => FilteredUpdateType::TransactionStatus { message },
+        }
+    }
+}
+
 #[derive(Debug, Default, Clone)]
 struct FilterEntries {
     filters: Vec<FilterName>,
@@ -0,0 +1,725 @@
+use {
+    crate::{
+        filter::{
+            Filter, FilterAccountsInner, FilterName, FilterTransactions, FilterTransactionsInner,
+        },
+        message::{MessageAccount, MessageRef, MessageTransaction},
+    },
+    smallvec::SmallVec,
+    solana_account::ReadableAccount,
+    solana_pubkey::Pubkey,
+    std::{
+        collections::{HashMap, HashSet},
+        hash::Hash,
+        sync::Arc,
+    },
+};
+
+pub type FilterIndexNames = SmallVec<[FilterName; 2]>;
+
+/// Inverted index over accounts and transactions filters of many subscribers.
+///
+/// Every named filter is stored in a bucket of the most selective pubkey it
+/// requires (account, owner, included or required transaction account), so a
+/// message is matched with one lookup per key instead of evaluating every
+/// filter of every subscriber. Filters without such keys are evaluated for
+/// every message.
+#[derive(Debug)]
+pub struct FilterIndex<K> {
+    generation: u64,
+    subscribers: HashMap<K, FilterIndexKeys>,
+    accounts: FilterIndexAccounts<K>,
+    transactions: FilterIndexTransactions<K>,
+    transactions_status: FilterIndexTransactions<K>,
+}
+
+impl<K> Default for FilterIndex<K> {
+    fn default() -> Self {
+        Self {
+            generation: 0,
+            subscribers: HashMap::new(),
+            accounts: FilterIndexAccounts::default(),
+            transactions: FilterIndexTransactions::default(),
+            transactions_status: FilterIndexTransactions::default(),
+        }
+    }
+}
+
+impl<K: Copy + Eq + Hash> FilterIndex<K> {
+    /// Returns `false` if filters of the subscriber were changed after matches are created.
+    pub fn is_actual(&self, matches: &FilterIndexMatches<K>, key: &K) -> bool {
+        self.subscribers
+            .get(key)
+            .is_some_and(|keys| keys.generation <= matches.generation)
+    }
+
+    pub fn len(&self) -> usize {
+        self.subscribers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.subscribers.is_empty()
+    }
+
+    /// Add or replace filters of the subscriber.
+    pub fn insert(&mut self, key: K, filter: &Filter) {
+        self.remove(&key);
+
+        self.generation += 1;
+        let mut keys = FilterIndexKeys {
+            generation: self.generation,
+            ..Default::default()
+        };
+        for (name, inner) in filter.accounts.filters.iter() {
+            self.accounts.insert(&mut keys, key, name, inner);
+        }
+        keys.transactions =
+            Self::insert_transactions(&mut self.transactions, key, &filter.transactions);
+        keys.transactions_status = Self::insert_transactions(
+            &mut self.transactions_status,
+            key,
+            &filter.transactions_status,
+        );
+
+        self.subscribers.insert(key, keys);
+    }
+
+    fn insert_transactions(
+        index: &mut FilterIndexTransactions<K>,
+        key: K,
+        filter: &FilterTransactions,
+    ) -> Vec<Pubkey> {
+        let mut keys = vec![];
+        for (name, inner) in filter.filters.iter() {
+            index.insert(&mut keys, key, name, inner);
+        }
+        keys
+    }
+
+    pub fn remove(&mut self, key: &K) {
+        let Some(keys) = self.subscribers.remove(key) else {
+            return;
+        };
+
+        FilterIndexBucket::remove(&mut self.accounts.by_account, &keys.accounts, key);
+        FilterIndexBucket::remove(&mut self.accounts.by_owner, &keys.owners, key);
+        self.accounts.any.retain(|item| item.key != *key);
+        FilterIndexBucket::remove(&mut self.transactions.by_account, &keys.transactions, key);
+        self.transactions.any.retain(|item| item.key != *key);
+        FilterIndexBucket::remove(
+            &mut self.transactions_status.by_account,
+            &keys.transactions_status,
+            key,
+        );
+        self.transactions_status.any.retain(|item| item.key != *key);
+    }
+
+    /// Returns `true` if matches can be created for the message.
+    pub const fn is_indexed(message: MessageRef<'_>) -> bool {
+        matches!(message, MessageRef::Account(_) | MessageRef::Transaction(_))
+    }
+
+    pub fn get_matches(&self, message: MessageRef<'_>) -> FilterIndexMatches<K> {
+        let mut matches = FilterIndexMatches {
+            generation: self.generation,
+            ..Default::default()
+        };
+        match message {
+            MessageRef::Account(message) => {
+                self.accounts.get_matches(message, &mut matches.accounts);
+            }
+            MessageRef::Transaction(message) => {
+                self.transactions
+                    .get_matches(message, &mut matches.transactions);
+                self.transactions_status
+                    .get_matches(message, &mut matches.transactions_status);
+            }
+            _ => {}
+        }
+        matches
+    }
+}
+
+/// Filter names matched by the message, grouped by subscriber.
+#[derive(Debug)]
+pub struct FilterIndexMatches<K> {
+    generation: u64,
+    accounts: HashMap<K, FilterIndexNames>,
+    transactions: HashMap<K, FilterIndexNames>,
+    transactions_status: HashMap<K, FilterIndexNames>,
+}
+
+impl<K> Default for FilterIndexMatches<K> {
+    fn default() -> Self {
+        Self {
+            generation: 0,
+            accounts: HashMap::new(),
+            transactions: HashMap::new(),
+            transactions_status: HashMap::new(),
+        }
+    }
+}
+
+impl<K: Eq + Hash> FilterIndexMatches<K> {
+    pub fn get_accounts(&self, key: &K) -> Option<&FilterIndexNames> {
+        self.accounts.get(key)
+    }
+
+    pub fn get_transactions(&self, key: &K) -> Option<&FilterIndexNames> {
+        self.transactions.get(key)
+    }
+
+    pub fn get_transactions_status(&self, key: &K) -> Option<&FilterIndexNames> {
+        self.transactions_status.get(key)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.accounts.is_empty()
+            && self.transactions.is_empty()
+            && self.transactions_status.is_empty()
+    }
+}
+
+#[derive(Debug, Default)]
+struct FilterIndexKeys {
+    // generation of the index when filters were inserted
+    generation: u64,
+    accounts: Vec<Pubkey>,
+    owners: Vec<Pubkey>,
+    transactions: Vec<Pubkey>,
+    transactions_status: Vec<Pubkey>,
+}
+
+#[derive(Debug)]
+struct FilterIndexItem<K, T> {
+    key: K,
+    name: FilterName,
+    filter: Arc<T>,
+}
+
+impl<K: Copy, T> FilterIndexItem<K, T> {
+    fn new(key: K, name: &FilterName, filter: &Arc<T>) -> Self {
+        Self {
+            key,
+            name: name.clone(),
+            filter: Arc::clone(filter),
+        }
+    }
+}
+
+type FilterIndexBucketMap<K, T> = HashMap<Pubkey, Vec<FilterIndexItem<K, T>>>;
+
+struct FilterIndexBucket;
+
+impl FilterIndexBucket {
+    fn insert<K, T>(
+        bucket: &mut FilterIndexBucketMap<K, T>,
+        keys: &mut Vec<Pubkey>,
+        pubkey: Pubkey,
+        item: FilterIndexItem<K, T>,
+    ) {
+        bucket.entry(pubkey).or_default().push(item);
+        keys.push(pubkey);
+    }
+
+    fn remove<K: Eq, T>(bucket: &mut FilterIndexBucketMap<K, T>, keys: &[Pubkey], key: &K) {
+        for pubkey in keys {
+            if let Some(items) = bucket.get_mut(pubkey) {
+                items.retain(|item| item.key != *key);
+                if items.is_empty() {
+                    bucket.remove(pubkey);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct FilterIndexAccounts<K> {
+    by_account: FilterIndexBucketMap<K, FilterAccountsInner>,
+    by_owner: FilterIndexBucketMap<K, FilterAccountsInner>,
+    any: Vec<FilterIndexItem<K, FilterAccountsInner>>,
+}
+
+impl<K> Default for FilterIndexAccounts<K> {
+    fn default() -> Self {
+        Self {
+            by_account: HashMap::new(),
+            by_owner: HashMap::new(),
+            any: vec![],
+        }
+    }
+}
+
+impl<K: Copy + Eq + Hash> FilterIndexAccounts<K> {
+    fn insert(
+        &mut self,
+        keys: &mut FilterIndexKeys,
+        key: K,
+        name: &FilterName,
+        filter: &Arc<FilterAccountsInner>,
+    ) {
+        if !filter.account.is_empty() {
+            for pubkey in filter.account.iter() {
+                let item = FilterIndexItem::new(key, name, filter);
+                FilterIndexBucket::insert(&mut self.by_account, &mut keys.accounts, *pubkey, item);
+            }
+        } else if !filter.owner.is_empty() {
+            for pubkey in filter.owner.iter() {
+                let item = FilterIndexItem::new(key, name, filter);
+                FilterIndexBucket::insert(&mut self.by_owner, &mut keys.owners, *pubkey, item);
+            }
+        } else {
+            self.any.push(FilterIndexItem::new(key, name, filter));
+        }
+    }
+
+    fn get_matches(&self, message: &MessageAccount, matches: &mut HashMap<K, FilterIndexNames>) {
+        let msg_pubkey = message.pubkey();
+        let msg_owner = message.owner();
+        let msg_lamports = message.lamports();
+        let msg_data = message.data();
+        let msg_nonempty_txn_signature = message.nonempty_txn_signature();
+
+        // every filter is stored only in one bucket and the message has only one
+        // pubkey and one owner, so candidates are unique
+        let candidates = self
+            .by_account
+            .get(msg_pubkey)
+            .into_iter()
+            .flatten()
+            .chain(self.by_owner.get(msg_owner).into_iter().flatten())
+            .chain(self.any.iter());
+        for item in candidates {
+            if item.filter.is_match(
+                msg_pubkey,
+                msg_owner,
+                msg_lamports,
+                msg_data,
+                msg_nonempty_txn_signature,
+            ) {
+                matches.entry(item.key).or_default().push(item.name.clone());
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct FilterIndexTransactions<K> {
+    by_account: FilterIndexBucketMap<K, FilterTransactionsInner>,
+    any: Vec<FilterIndexItem<K, FilterTransactionsInner>>,
+}
+
+impl<K> Default for FilterIndexTransactions<K> {
+    fn default() -> Self {
+        Self {
+            by_account: HashMap::new(),
+            any: vec![],
+        }
+    }
+}
+
+impl<K: Copy + Eq + Hash> FilterIndexTransactions<K> {
+    fn insert(
+        &mut self,
+        keys: &mut Vec<Pubkey>,
+        key: K,
+        name: &FilterName,
+        filter: &Arc<FilterTransactionsInner>,
+    ) {
+        if !filter.account_include.is_empty() {
+            for pubkey in filter.account_include.iter() {
+                let item = FilterIndexItem::new(key, name, filter);
+                FilterIndexBucket::insert(&mut self.by_account, keys, *pubkey, item);
+            }
+        } else if let Some(pubkey) = filter.account_required.iter().next() {
+            // all required accounts should be in the transaction, any of them works as key
+            let item = FilterIndexItem::new(key, name, filter);
+            FilterIndexBucket::insert(&mut self.by_account, keys, *pubkey, item);
+        } else if !filter.instructions.is_empty() {
+            // invoked programs are in the transaction account keys
+            let mut programs = filter
+                .instructions
+                .iter()
+                .map(|instruction| instruction.program_id)
+                .collect::<Vec<_>>();
+            programs.sort_unstable();
+            programs.dedup();
+            for pubkey in programs {
+                let item = FilterIndexItem::new(key, name, filter);
+                FilterIndexBucket::insert(&mut self.by_account, keys, pubkey, item);
+            }
+        } else {
+            self.any.push(FilterIndexItem::new(key, name, filter));
+        }
+    }
+
+    fn get_matches(
+        &self,
+        message: &MessageTransaction,
+        matches: &mut HashMap<K, FilterIndexNames>,
+    ) {
+        let msg_account_keys = message.account_keys();
+
+        // filter with few keys can be found in few buckets
+        let mut checked = HashSet::new();
+        let candidates = msg_account_keys
+            .iter()
+            .filter_map(|pubkey| self.by_account.get(pubkey))
+            .flatten()
+            .filter(|item| checked.insert(Arc::as_ptr(&item.filter)))
+            .chain(self.any.iter());
+        for item in candidates {
+            if item.filter.is_match(message) {
+                matches.entry(item.key).or_default().push(item.name.clone());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::FilterIndex,
+        crate::{
+            config::{
+                ConfigFilter, ConfigFilterAccounts, ConfigFilterExpression,
+                ConfigFilterTransactions,
+            },
+            filter::Filter,
+            message::{Message, MessageParserEncoding, MessageRef},
+        },
+        prost::Message as _,
+        richat_proto::{
+            geyser::{
+                SubscribeUpdate, SubscribeUpdateTransaction, SubscribeUpdateTransactionInfo,
+                subscribe_update::UpdateOneof,
+            },
+            solana::storage::confirmed_block::{
+                Message as TransactionMessage, Transaction, TransactionStatusMeta,
+            },
+        },
+        solana_account::ReadableAccount,
+        solana_commitment_config::CommitmentLevel,
+        solana_pubkey::Pubkey,
+    };
+
+    // same account update as in `message` tests
+    static MESSAGE: &str = "0a0012af010aa6010a2088f1ffa3a2dfe617bdc4e3573251a322e3fcae81e5a457390e64751c00a465e210e0d54a1a2006aa09548b50476ad462f91f89a3015033264fc9abd5270020a9d142334742fb28ffffffffffffffffff013208c921f474e044612838e3e1acc2b53042405bd620fab28d3c0b78b3ead9f04d1c4d6dffeac4ffa7c679a6570b0226557c10b4c4016d937e06044b4e49d9d7916524d5dfa26297c5f638c3d11f846410bc0510e5ddaca2015a0c08e1c79ec10610ebef838601";
+
+    fn create_filter(accounts: Vec<(&str, ConfigFilterAccounts)>) -> Filter {
+        Filter::new(&ConfigFilter {
+            accounts: accounts
+                .into_iter()
+                .map(|(name, filter)| (name.to_owned(), filter))
+                .collect(),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_accounts_matches() {
+        let message = Message::parse(
+            const_hex::decode(MESSAGE).expect("valid hex").into(),
+            MessageParserEncoding::Limited,
+        )
+        .expect("valid message");
+        let Message::Account(account) = &message else {
+            panic!("expected account message");
+        };
+        let pubkey = *account.pubkey();
+        let owner = *account.owner();
+
+        let filters = [
+            create_filter(vec![(
+                "pubkey",
+                ConfigFilterAccounts {
+                    account: vec![pubkey, Pubkey::new_unique()],
+                    ..Default::default()
+                },
+            )]),
+            create_filter(vec![
+                (
+                    "owner",
+                    ConfigFilterAccounts {
+                        owner: vec![owner],
+                        ..Default::default()
+                    },
+                ),
+                ("any", ConfigFilterAccounts::default()),
+            ]),
+            create_filter(vec![(
+                "other",
+                ConfigFilterAccounts {
+                    account: vec![Pubkey::new_unique()],
+                    ..Default::default()
+                },
+            )]),
+        ];
+
+        let mut index = FilterIndex::default();
+        for (key, filter) in filters.iter().enumerate() {
+            index.insert(key, filter);
+        }
+        assert_eq!(index.len(), 3);
+
+        let message_ref: MessageRef = (&message).into();
+        let matches = index.get_matches(message_ref);
+        for (key, filter) in filters.iter().enumerate() {
+            let mut expected = filter
+                .get_updates_ref(message_ref, CommitmentLevel::Processed)
+                .into_iter()
+                .flat_map(|update| update.filters.into_iter().map(ToOwned::to_owned))
+                .collect::<Vec<_>>();
+            expected.sort();
+
+            let mut indexed = filter
+                .get_updates_indexed(message_ref, CommitmentLevel::Processed, &matches, &key)
+                .into_iter()
+                .flat_map(|update| update.filters.into_iter().map(ToOwned::to_owned))
+                .collect::<Vec<_>>();
+            indexed.sort();
+
+            assert_eq!(expected, indexed, "mismatch for filter#{key}");
+        }
+
+        assert!(index.is_actual(&matches, &0));
+        index.insert(1, &filters[1]);
+        assert!(index.is_actual(&matches, &0));
+        assert!(!index.is_actual(&matches, &1));
+        index.remove(&0);
+        index.remove(&1);
+        assert_eq!(index.len(), 1);
+        assert!(!index.is_actual(&matches, &0));
+        assert!(index.get_matches(message_ref).is_empty());
+
+        index.insert(0, &create_filter(vec![]));
+        assert!(index.get_matches(message_ref).is_empty());
+    }
+
+    #[test]
+    fn test_accounts_expression() {
+        let message = Message::parse(
+            const_hex::decode(MESSAGE).expect("valid hex").into(),
+            MessageParserEncoding::Limited,
+        )
+        .expect("valid message");
+        let Message::Account(account) = &message else {
+            panic!("expected account message");
+        };
+        let pubkey = *account.pubkey();
+        let owner = *account.owner();
+
+        let by_account = |account| {
+            ConfigFilterExpression::Filter(ConfigFilterAccounts {
+                account: vec![account],
+                ..Default::default()
+            })
+        };
+        let by_owner = |owner| {
+            ConfigFilterExpression::Filter(ConfigFilterAccounts {
+                owner: vec![owner],
+                ..Default::default()
+            })
+        };
+        let with_expression = |expression| ConfigFilterAccounts {
+            expression: Some(Box::new(expression)),
+            ..Default::default()
+        };
+
+        let filter = create_filter(vec![
+            (
+                "owner_not_other",
+                with_expression(ConfigFilterExpression::And(vec![
+                    by_owner(owner),
+                    ConfigFilterExpression::Not(Box::new(by_account(Pubkey::new_unique()))),
+                ])),
+            ),
+            (
+                "not_owner",
+                with_expression(ConfigFilterExpression::Not(Box::new(by_owner(owner)))),
+            ),
+            (
+                "other_or_pubkey",
+                ConfigFilterAccounts {
+                    owner: vec![owner],
+                    expression: Some(Box::new(ConfigFilterExpression::Or(vec![
+                        by_account(Pubkey::new_unique()),
+                        by_account(pubkey),
+                    ]))),
+                    ..Default::default()
+                },
+            ),
+        ]);
+
+        let mut index = FilterIndex::default();
+        index.insert(0, &filter);
+
+        let message_ref: MessageRef = (&message).into();
+        let matches = index.get_matches(message_ref);
+        for updates in [
+            filter.get_updates_ref(message_ref, CommitmentLevel::Processed),
+            filter.get_updates_indexed(message_ref, CommitmentLevel::Processed, &matches, &0),
+        ] {
+            let mut names = updates
+                .into_iter()
+                .flat_map(|update| update.filters.into_iter().map(ToOwned::to_owned))
+                .collect::<Vec<_>>();
+            names.sort();
+            assert_eq!(names, ["other_or_pubkey", "owner_not_other"]);
+        }
+    }
+
+    fn get_names<K: Copy + Eq + std::hash::Hash>(
+        index: &FilterIndex<K>,
+        filter: &Filter,
+        message: MessageRef<'_>,
+        key: K,
+    ) -> (Vec<String>, Vec<String>) {
+        let mut expected = filter
+            .get_updates_ref(message, CommitmentLevel::Processed)
+            .into_iter()
+            .flat_map(|update| update.filters.into_iter().map(ToOwned::to_owned))
+            .collect::<Vec<_>>();
+        expected.sort();
+
+        let matches = index.get_matches(message);
+        let mut indexed = filter
+            .get_updates_indexed(message, CommitmentLevel::Processed, &matches, &key)
+            .into_iter()
+            .flat_map(|update| update.filters.into_iter().map(ToOwned::to_owned))
+            .collect::<Vec<_>>();
+        indexed.sort();
+
+        (expected, indexed)
+    }
+
+    #[test]
+    fn test_transactions_matches() {
+        let keys = [
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        ];
+        let other = Pubkey::new_unique();
+
+        let data = SubscribeUpdate {
+            filters: vec![],
+            update_oneof: Some(UpdateOneof::Transaction(SubscribeUpdateTransaction {
+                transaction: Some(SubscribeUpdateTransactionInfo {
+                    signature: vec![1; 64],
+                    transaction: Some(Transaction {
+                        signatures: vec![vec![1; 64]],
+                        message: Some(TransactionMessage {
+                            account_keys: keys[..2]
+                                .iter()
+                                .map(|pubkey| pubkey.to_bytes().to_vec())
+                                .collect(),
+                            ..Default::default()
+                        }),
+                    }),
+                    meta: Some(TransactionStatusMeta {
+                        loaded_readonly_addresses: vec![keys[2].to_bytes().to_vec()],
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                }),
+                slot: 1,
+            })),
+            created_at: Some(Default::default()),
+        }
+        .encode_to_vec();
+        let message =
+            Message::parse(data.into(), MessageParserEncoding::Prost).expect("valid message");
+        let message_ref: MessageRef = (&message).into();
+
+        let create_filter = |transactions: Vec<(&str, ConfigFilterTransactions)>| {
+            Filter::new(&ConfigFilter {
+                transactions: transactions
+                    .into_iter()
+                    .map(|(name, filter)| (name.to_owned(), filter))
+                    .collect(),
+                ..Default::default()
+            })
+        };
+        let filters = [
+            create_filter(vec![
+                (
+                    // matched by every included key
+                    "include",
+                    ConfigFilterTransactions {
+                        account_include: keys.to_vec(),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "include_other",
+                    ConfigFilterTransactions {
+                        account_include: vec![other],
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "required",
+                    ConfigFilterTransactions {
+                        account_required: vec![keys[0], keys[2]],
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "required_other",
+                    ConfigFilterTransactions {
+                        account_required: vec![keys[0], other],
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "exclude",
+                    ConfigFilterTransactions {
+                        account_include: vec![keys[0]],
+                        account_exclude: vec![keys[1]],
+                        ..Default::default()
+                    },
+                ),
+                ("any", ConfigFilterTransactions::default()),
+            ]),
+            create_filter(vec![(
+                "include",
+                ConfigFilterTransactions {
+                    account_include: vec![keys[1], other],
+                    ..Default::default()
+                },
+            )]),
+            create_filter(vec![(
+                "exclude",
+                ConfigFilterTransactions {
+                    account_exclude: vec![keys[2]],
+                    ..Default::default()
+                },
+            )]),
+        ];
+
+        let mut index = FilterIndex::default();
+        for (key, filter) in filters.iter().enumerate() {
+            index.insert(key, filter);
+        }
+
+        let expected = [vec!["any", "include", "required"], vec!["include"], vec![]];
+        for (key, filter) in filters.iter().enumerate() {
+            let (names, indexed) = get_names(&index, filter, message_ref, key);
+            assert_eq!(names, expected[key], "mismatch for filter#{key}");
+            assert_eq!(names, indexed, "mismatch for filter#{key}");
+        }
+
+        // remove and insert do not affect matches of other subscribers
+        let matches = index.get_matches(message_ref);
+        index.remove(&1);
+        index.insert(2, &filters[1]);
+        assert!(index.is_actual(&matches, &0));
+        assert!(!index.is_actual(&matches, &1));
+        assert!(!index.is_actual(&matches, &2));
+        let (names, indexed) = get_names(&index, &filters[1], message_ref, 2);
+        assert_eq!(names, ["include"]);
+        assert_eq!(names, indexed);
+    }
+}
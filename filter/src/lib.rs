@@ -1,4 +1,5 @@
 pub mod config;
 pub mod filter;
+pub mod index;
 pub mod message;
 pub mod protobuf;
@@ -983,7 +983,7 @@ impl MessageTransaction {
         }
     }
 
-    fn transaction(&self) -> Result<&SubscribeUpdateTransactionInfo, &'static str> {
+    pub fn transaction(&self) -> Result<&SubscribeUpdateTransactionInfo, &'static str> {
         match self {
             Self::Limited {
                 transaction,
@@ -1156,6 +1156,21 @@ impl MessageBlockMeta {
         }
     }
 
+    pub const fn parent_slot(&self) -> Slot {
+        match self {
+            Self::Limited { block_meta, .. } => block_meta.parent_slot,
+            Self::Prost { block_meta, .. } => block_meta.parent_slot,
+        }
+    }
+
+    pub fn block_time(&self) -> Option<i64> {
+        match self {
+            Self::Limited { block_meta, .. } => block_meta.block_time.as_ref(),
+            Self::Prost { block_meta, .. } => block_meta.block_time.as_ref(),
+        }
+        .map(|block_time| block_time.timestamp)
+    }
+
     pub const fn executed_transaction_count(&self) -> u64 {
         match self {
             Self::Limited { block_meta, .. } => block_meta.executed_transaction_count,
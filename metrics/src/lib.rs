@@ -9,7 +9,7 @@ mod recorder;
 pub use recorder::MaybeRecorder;
 
 mod server;
-pub use server::spawn_server;
+pub use server::{AdminHandler, spawn_server};
 
 #[inline]
 pub fn duration_to_seconds(d: std::time::Duration) -> f64 {
@@ -1,6 +1,6 @@
 use {
     crate::config::ConfigMetrics,
-    http_body_util::{BodyExt, Full as BodyFull},
+    http_body_util::{BodyExt, Full as BodyFull, Limited},
     hyper::{
         Request, Response, StatusCode,
         body::{Bytes, Incoming as BodyIncoming},
@@ -10,16 +10,25 @@ use {
         rt::tokio::{TokioExecutor, TokioIo},
         server::conn::auto::Builder as ServerBuilder,
     },
-    std::future::Future,
+    std::{future::Future, sync::Arc},
     tokio::{net::TcpListener, task::JoinError},
     tracing::{error, info},
 };
 
+const ADMIN_PATH_PREFIX: &str = "/admin/";
+const ADMIN_BODY_SIZE_MAX: usize = 64 * 1024;
+
+/// Handler of requests with `/admin/` path prefix, served next to metrics.
+pub trait AdminHandler: Send + Sync + 'static {
+    fn handle(&self, request: Request<Bytes>) -> Response<Bytes>;
+}
+
 pub async fn spawn_server(
     ConfigMetrics { endpoint }: ConfigMetrics,
     gather_metrics: impl Fn() -> Vec<u8> + Clone + Send + 'static,
     is_health_check: impl Fn() -> bool + Clone + Send + 'static,
     is_ready_check: impl Fn() -> bool + Clone + Send + 'static,
+    admin: Option<Arc<dyn AdminHandler>>,
     shutdown: impl Future<Output = ()> + Send + 'static,
 ) -> std::io::Result<impl Future<Output = Result<(), JoinError>>> {
     let listener = TcpListener::bind(endpoint).await?;
@@ -46,6 +55,7 @@ pub async fn spawn_server(
             let gather_metrics = gather_metrics.clone();
             let is_health_check = is_health_check.clone();
             let is_ready_check = is_ready_check.clone();
+            let admin = admin.clone();
             tokio::spawn(async move {
                 if let Err(error) = ServerBuilder::new(TokioExecutor::new())
                     .serve_connection(
@@ -54,7 +64,28 @@ pub async fn spawn_server(
                             let gather_metrics = gather_metrics.clone();
                             let is_health_check = is_health_check.clone();
                             let is_ready_check = is_ready_check.clone();
+                            let admin = admin.clone();
                             async move {
+                                if let Some(admin) = admin
+                                    .filter(|_| req.uri().path().starts_with(ADMIN_PATH_PREFIX))
+                                {
+                                    let (parts, body) = req.into_parts();
+                                    let response = match Limited::new(body, ADMIN_BODY_SIZE_MAX)
+                                        .collect()
+                                        .await
+                                    {
+                                        Ok(body) => admin
+                                            .handle(Request::from_parts(parts, body.to_bytes())),
+                                        Err(_error) => {
+                                            let mut response =
+                                                Response::new(Bytes::from("failed to read body"));
+                                            *response.status_mut() = StatusCode::BAD_REQUEST;
+                                            response
+                                        }
+                                    };
+                                    return Ok(response.map(|body| BodyFull::new(body).boxed()));
+                                }
+
                                 let (status, bytes) = match req.uri().path() {
                                     "/health" => {
                                         if is_health_check() {
@@ -14,7 +14,10 @@ use {
     richat_proto::richat::RichatFilter,
     richat_shared::{
         mutex_lock,
-        transports::{RecvError, RecvItem, RecvStream, Subscribe, SubscribeError},
+        transports::{
+            RecvError, RecvItem, RecvStream, Subscribe, SubscribeConnection, SubscribeError,
+            filter::RichatFilterMatcher,
+        },
     },
     smallvec::SmallVec,
     solana_clock::Slot,
@@ -23,7 +26,10 @@ use {
         fmt,
         future::Future,
         pin::Pin,
-        sync::{Arc, Mutex, MutexGuard},
+        sync::{
+            Arc, Mutex, MutexGuard,
+            atomic::{AtomicUsize, Ordering},
+        },
         task::{Context, Poll, Waker},
     },
 };
@@ -42,6 +48,7 @@ impl Sender {
             buffer.push(Mutex::new(Item {
                 pos: i as u64,
                 slot: 0,
+                finalized: false,
                 data: None,
                 closed: false,
             }));
@@ -58,6 +65,7 @@ impl Sender {
             }),
             mask: (max_messages - 1) as u64,
             buffer: buffer.into_boxed_slice(),
+            pubkeys_receivers: AtomicUsize::new(0),
         });
 
         Self { shared, recorder }
@@ -162,7 +170,15 @@ impl Sender {
         }
         item.pos = state.tail;
         item.slot = slot;
-        item.data = Some((PluginNotification::from(&message), Arc::new(data)));
+        item.finalized = matches!(
+            &message,
+            ProtobufMessage::Slot { status, .. } if **status == SlotStatus::Rooted
+        );
+        let with_pubkeys = self.shared.pubkeys_receivers.load(Ordering::Relaxed) > 0;
+        item.data = Some((
+            PluginNotification::new(&message, with_pubkeys),
+            Arc::new(data),
+        ));
         drop(item);
 
         // drop extra messages by max bytes
@@ -231,10 +247,16 @@ impl Sender {
 impl Subscribe for Sender {
     fn subscribe(
         &self,
+        _connection: SubscribeConnection<'_>,
         replay_from_slot: Option<Slot>,
+        replay_to_slot: Option<Slot>,
         filter: Option<RichatFilter>,
     ) -> Result<RecvStream, SubscribeError> {
+        let filter = RichatFilterMatcher::new(filter.as_ref())?;
         let shared = Arc::clone(&self.shared);
+        if filter.is_pubkeys_required() {
+            shared.pubkeys_receivers.fetch_add(1, Ordering::Relaxed);
+        }
 
         let state = shared.state_lock();
         let next = match replay_from_slot {
@@ -250,15 +272,13 @@ impl Subscribe for Sender {
         };
         drop(state);
 
-        let filter = filter.unwrap_or_default();
-
         Ok(Receiver {
             shared,
             next,
             finished: false,
-            enable_notifications_accounts: !filter.disable_accounts,
-            enable_notifications_transactions: !filter.disable_transactions,
-            enable_notifications_entries: !filter.disable_entries,
+            replay_to_slot,
+            replay_finished: false,
+            filter,
         }
         .boxed())
     }
@@ -269,9 +289,19 @@ pub struct Receiver {
     shared: Arc<Shared>,
     next: u64,
     finished: bool,
-    enable_notifications_accounts: bool,
-    enable_notifications_transactions: bool,
-    enable_notifications_entries: bool,
+    replay_to_slot: Option<Slot>,
+    replay_finished: bool,
+    filter: RichatFilterMatcher,
+}
+
+impl Drop for Receiver {
+    fn drop(&mut self) {
+        if self.filter.is_pubkeys_required() {
+            self.shared
+                .pubkeys_receivers
+                .fetch_sub(1, Ordering::Relaxed);
+        }
+    }
 }
 
 impl Receiver {
@@ -280,6 +310,10 @@ impl Receiver {
     }
 
     pub fn recv_ref(&mut self, waker: &Waker) -> Result<Option<RecvItem>, RecvError> {
+        if self.replay_finished {
+            return Err(RecvError::ReplayFinished);
+        }
+
         loop {
             // read item with next value
             let idx = self.shared.get_idx(self.next);
@@ -311,16 +345,21 @@ impl Receiver {
             }
 
             self.next = self.next.wrapping_add(1);
-            let (plugin_notification, item) = item.data.clone().ok_or(RecvError::Lagged)?;
-            match plugin_notification {
-                PluginNotification::Account if !self.enable_notifications_accounts => continue,
-                PluginNotification::Transaction if !self.enable_notifications_transactions => {
+            if let Some(replay_to_slot) = self.replay_to_slot {
+                if item.finalized && item.slot >= replay_to_slot {
+                    self.replay_finished = true;
+                    if item.slot > replay_to_slot {
+                        return Err(RecvError::ReplayFinished);
+                    }
+                } else if item.slot > replay_to_slot {
                     continue;
                 }
-                PluginNotification::Entry if !self.enable_notifications_entries => continue,
-                _ => {}
             }
-            break Ok(Some(item));
+            let (plugin_notification, item) = item.data.as_mut().ok_or(RecvError::Lagged)?;
+            if !plugin_notification.is_match(&self.filter, item) {
+                continue;
+            }
+            break Ok(Some(Arc::clone(item)));
         }
     }
 }
@@ -374,6 +413,8 @@ struct Shared {
     state: Mutex<State>,
     mask: u64,
     buffer: Box<[Mutex<Item>]>,
+    // receivers with filter by pubkeys
+    pubkeys_receivers: AtomicUsize,
 }
 
 impl fmt::Debug for Shared {
@@ -418,6 +459,7 @@ struct SlotInfo {
 struct Item {
     pos: u64,
     slot: Slot,
+    finalized: bool,
     data: Option<(PluginNotification, RecvItem)>,
     closed: bool,
 }
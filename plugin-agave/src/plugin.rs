@@ -13,33 +13,153 @@ use {
     },
     futures::future::BoxFuture,
     log::error,
+    prost::Message as _,
     richat_metrics::{MaybeRecorder, gauge},
-    richat_shared::transports::{grpc::GrpcServer, quic::QuicServer},
+    richat_proto::geyser::{
+        SubscribeUpdate, SubscribeUpdateAccount, SubscribeUpdateTransaction,
+        subscribe_update::UpdateOneof,
+    },
+    richat_shared::transports::{filter::RichatFilterMatcher, grpc::GrpcServer, quic::QuicServer},
     solana_clock::Slot,
+    solana_pubkey::Pubkey,
     std::{fmt, sync::Arc, time::Duration},
     tokio::{runtime::Runtime, task::JoinError},
     tokio_util::sync::CancellationToken,
 };
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Notification type with fields required to evaluate [`RichatFilterMatcher`].
+///
+/// Pubkeys are collected only while some subscriber filters by them, otherwise they are
+/// decoded from the message on the first request.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum PluginNotification {
     Slot,
-    Account,
-    Transaction,
+    Account {
+        pubkeys: Option<(Pubkey, Pubkey)>,
+    },
+    Transaction {
+        is_vote: bool,
+        is_failed: bool,
+        account_keys: Option<Box<[Pubkey]>>,
+    },
     Entry,
     BlockMeta,
 }
 
-impl From<&ProtobufMessage<'_>> for PluginNotification {
-    fn from(value: &ProtobufMessage<'_>) -> Self {
-        match value {
-            ProtobufMessage::Account { .. } => Self::Account,
+impl PluginNotification {
+    pub fn new(message: &ProtobufMessage<'_>, with_pubkeys: bool) -> Self {
+        match message {
+            ProtobufMessage::Account { account, .. } => Self::Account {
+                pubkeys: with_pubkeys.then(|| {
+                    (
+                        Pubkey::try_from(account.pubkey).unwrap_or_default(),
+                        Pubkey::try_from(account.owner).unwrap_or_default(),
+                    )
+                }),
+            },
             ProtobufMessage::Slot { .. } => Self::Slot,
-            ProtobufMessage::Transaction { .. } => Self::Transaction,
+            ProtobufMessage::Transaction { transaction, .. } => {
+                let meta = transaction.transaction_status_meta;
+                Self::Transaction {
+                    is_vote: transaction.is_vote,
+                    is_failed: meta.status.is_err(),
+                    account_keys: with_pubkeys.then(|| {
+                        transaction
+                            .transaction
+                            .message
+                            .static_account_keys()
+                            .iter()
+                            .chain(meta.loaded_addresses.writable.iter())
+                            .chain(meta.loaded_addresses.readonly.iter())
+                            .copied()
+                            .collect()
+                    }),
+                }
+            }
             ProtobufMessage::Entry { .. } => Self::Entry,
             ProtobufMessage::BlockMeta { .. } => Self::BlockMeta,
         }
     }
+
+    /// `data` is the encoded message, used to decode missed pubkeys.
+    pub fn is_match(&mut self, filter: &RichatFilterMatcher, data: &[u8]) -> bool {
+        if filter.is_pubkeys_required() {
+            self.decode_pubkeys(data);
+        }
+
+        match self {
+            Self::Slot | Self::BlockMeta => true,
+            Self::Account { pubkeys } => match pubkeys {
+                Some((pubkey, owner)) => filter.match_account(pubkey, owner),
+                None => filter.match_account(&Pubkey::default(), &Pubkey::default()),
+            },
+            Self::Transaction {
+                is_vote,
+                is_failed,
+                account_keys,
+            } => filter.match_transaction(
+                *is_vote,
+                *is_failed,
+                account_keys.as_deref().unwrap_or_default().iter(),
+            ),
+            Self::Entry => filter.match_entry(),
+        }
+    }
+
+    fn decode_pubkeys(&mut self, data: &[u8]) {
+        let update_oneof = match self {
+            Self::Account { pubkeys: None }
+            | Self::Transaction {
+                account_keys: None, ..
+            } => match SubscribeUpdate::decode(data) {
+                Ok(update) => update.update_oneof,
+                Err(error) => {
+                    error!("failed to decode message for filter: {error}");
+                    None
+                }
+            },
+            _ => return,
+        };
+
+        match (self, update_oneof) {
+            (
+                Self::Account { pubkeys },
+                Some(UpdateOneof::Account(SubscribeUpdateAccount {
+                    account: Some(account),
+                    ..
+                })),
+            ) => {
+                *pubkeys = Some((
+                    Pubkey::try_from(account.pubkey.as_slice()).unwrap_or_default(),
+                    Pubkey::try_from(account.owner.as_slice()).unwrap_or_default(),
+                ));
+            }
+            (
+                Self::Transaction { account_keys, .. },
+                Some(UpdateOneof::Transaction(SubscribeUpdateTransaction {
+                    transaction: Some(transaction),
+                    ..
+                })),
+            ) => {
+                let meta = transaction.meta.unwrap_or_default();
+                *account_keys = Some(
+                    transaction
+                        .transaction
+                        .and_then(|tx| tx.message)
+                        .map(|message| message.account_keys)
+                        .unwrap_or_default()
+                        .iter()
+                        .chain(meta.loaded_writable_addresses.iter())
+                        .chain(meta.loaded_readonly_addresses.iter())
+                        .map(|pubkey| Pubkey::try_from(pubkey.as_slice()).unwrap_or_default())
+                        .collect(),
+                );
+            }
+            (Self::Account { pubkeys }, _) => *pubkeys = Some(Default::default()),
+            (Self::Transaction { account_keys, .. }, _) => *account_keys = Some(Box::default()),
+            _ => {}
+        }
+    }
 }
 
 struct PluginTask(BoxFuture<'static, Result<(), JoinError>>);
@@ -101,7 +221,8 @@ impl PluginInner {
                                 VERSION,
                                 shutdown.clone(),
                             )
-                            .await?,
+                            .await?
+                            .1,
                         )),
                     ));
                 }
@@ -121,7 +242,8 @@ impl PluginInner {
                                 VERSION,
                                 shutdown.clone(),
                             )
-                            .await?,
+                            .await?
+                            .1,
                         )),
                     ));
                 }
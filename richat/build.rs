@@ -59,7 +59,7 @@ fn generate_grpc_geyser() -> anyhow::Result<()> {
             Method::builder()
                 .name("subscribe")
                 .route_name("Subscribe")
-                .input_type("richat_proto::geyser::SubscribeRequest")
+                .input_type("crate::grpc::extensions::SubscribeRequestWithExtensions")
                 .output_type("Vec<u8>")
                 .codec_path("richat_shared::transports::grpc::SubscribeCodec")
                 .client_streaming()
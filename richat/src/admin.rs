@@ -0,0 +1,361 @@
+use {
+    crate::channel::Messages,
+    hyper::{Method, Request, Response, StatusCode, body::Bytes},
+    richat_metrics::AdminHandler,
+    richat_shared::mutex_lock,
+    serde::{Deserialize, Serialize},
+    std::{
+        collections::{BTreeMap, HashSet},
+        fmt,
+        net::SocketAddr,
+        sync::{
+            Arc, Mutex,
+            atomic::{AtomicU64, Ordering},
+        },
+        time::{SystemTime, UNIX_EPOCH},
+    },
+    tokio_util::sync::CancellationToken,
+    tracing::info,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AdminClientApp {
+    Grpc,
+    Pubsub,
+    RichatQuic,
+    RichatGrpc,
+}
+
+impl AdminClientApp {
+    pub fn richat(transport: &str) -> Self {
+        if transport == "quic" {
+            Self::RichatQuic
+        } else {
+            Self::RichatGrpc
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct AdminClientLag {
+    pub messages: Option<u64>,
+    pub slots: Option<u64>,
+    pub queue_bytes: Option<u64>,
+}
+
+/// Current state of the connected client, requested only on listing.
+pub trait AdminClientState: Send + Sync {
+    fn filter(&self) -> serde_json::Value;
+
+    fn lag(&self, messages: &Messages) -> AdminClientLag;
+}
+
+#[derive(Debug, Clone)]
+pub struct AdminClientInfo {
+    pub app: AdminClientApp,
+    pub remote_addr: Option<SocketAddr>,
+    pub x_token: Option<Vec<u8>>,
+    pub x_subscription_id: Arc<str>,
+}
+
+struct AdminClient {
+    info: AdminClientInfo,
+    connected_at: SystemTime,
+    state: Arc<dyn AdminClientState>,
+    disconnect: CancellationToken,
+}
+
+impl fmt::Debug for AdminClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AdminClient")
+            .field("info", &self.info)
+            .field("connected_at", &self.connected_at)
+            .finish()
+    }
+}
+
+/// Registry of connected clients of all apps.
+#[derive(Debug, Default, Clone)]
+pub struct AdminClients {
+    clients: Arc<Mutex<BTreeMap<u64, AdminClient>>>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl AdminClients {
+    pub fn register(
+        &self,
+        info: AdminClientInfo,
+        state: Arc<dyn AdminClientState>,
+    ) -> AdminClientHandle {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let disconnect = CancellationToken::new();
+        mutex_lock(&self.clients).insert(
+            id,
+            AdminClient {
+                info,
+                connected_at: SystemTime::now(),
+                state,
+                disconnect: disconnect.clone(),
+            },
+        );
+        AdminClientHandle {
+            id,
+            clients: Arc::clone(&self.clients),
+            disconnect,
+        }
+    }
+
+    fn list(&self, messages: &Messages) -> Vec<AdminClientView> {
+        let clients = mutex_lock(&self.clients)
+            .iter()
+            .map(|(id, client)| {
+                (
+                    *id,
+                    client.info.clone(),
+                    client.connected_at,
+                    Arc::clone(&client.state),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        // state is requested without registry lock, it can lock client state
+        clients
+            .into_iter()
+            .map(|(id, info, connected_at, state)| AdminClientView {
+                id,
+                app: info.app,
+                remote_addr: info.remote_addr,
+                x_token: info.x_token.as_deref().map(mask_x_token),
+                x_subscription_id: info.x_subscription_id.to_string(),
+                connected_at: connected_at
+                    .duration_since(UNIX_EPOCH)
+                    .map(|ts| ts.as_secs())
+                    .unwrap_or_default(),
+                filter: state.filter(),
+                lag: state.lag(messages),
+            })
+            .collect()
+    }
+
+    /// Disconnect clients which match all provided fields, returns number of clients.
+    fn disconnect(&self, request: &AdminDisconnectRequest) -> usize {
+        let clients = mutex_lock(&self.clients);
+        let mut count = 0;
+        for (id, client) in clients.iter() {
+            if request.id.is_some_and(|value| value != *id)
+                || request
+                    .x_token
+                    .as_ref()
+                    .is_some_and(|value| client.info.x_token.as_deref() != Some(value.as_bytes()))
+                || request
+                    .x_subscription_id
+                    .as_ref()
+                    .is_some_and(|value| *client.info.x_subscription_id != **value)
+            {
+                continue;
+            }
+            info!(id, app = ?client.info.app, "disconnect client by admin request");
+            client.disconnect.cancel();
+            count += 1;
+        }
+        count
+    }
+}
+
+fn mask_x_token(x_token: &[u8]) -> String {
+    let prefix = String::from_utf8_lossy(&x_token[..x_token.len().min(4)]).into_owned();
+    format!("{prefix}***")
+}
+
+/// Registration of the client, removed from registry on drop.
+#[derive(Debug)]
+pub struct AdminClientHandle {
+    id: u64,
+    clients: Arc<Mutex<BTreeMap<u64, AdminClient>>>,
+    disconnect: CancellationToken,
+}
+
+impl Drop for AdminClientHandle {
+    fn drop(&mut self) {
+        mutex_lock(&self.clients).remove(&self.id);
+    }
+}
+
+impl AdminClientHandle {
+    pub const fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Cancelled once client should be disconnected.
+    pub fn disconnect(&self) -> CancellationToken {
+        self.disconnect.clone()
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct AdminClientView {
+    id: u64,
+    app: AdminClientApp,
+    remote_addr: Option<SocketAddr>,
+    x_token: Option<String>,
+    x_subscription_id: String,
+    connected_at: u64,
+    filter: serde_json::Value,
+    lag: AdminClientLag,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AdminDisconnectRequest {
+    id: Option<u64>,
+    x_token: Option<String>,
+    x_subscription_id: Option<String>,
+}
+
+/// `GET /admin/clients` and `POST /admin/clients/disconnect` on metrics server.
+#[derive(Debug)]
+pub struct AdminServer {
+    clients: AdminClients,
+    messages: Messages,
+    x_tokens: HashSet<Vec<u8>>,
+}
+
+impl AdminServer {
+    pub const fn new(
+        clients: AdminClients,
+        messages: Messages,
+        x_tokens: HashSet<Vec<u8>>,
+    ) -> Self {
+        Self {
+            clients,
+            messages,
+            x_tokens,
+        }
+    }
+
+    fn response(status: StatusCode, body: impl Into<Bytes>) -> Response<Bytes> {
+        let mut response = Response::new(body.into());
+        *response.status_mut() = status;
+        response
+    }
+
+    fn response_json<T: Serialize>(value: &T) -> Response<Bytes> {
+        let body = serde_json::to_vec(value).expect("json serialization never fail");
+        let mut response = Self::response(StatusCode::OK, body);
+        response.headers_mut().insert(
+            hyper::header::CONTENT_TYPE,
+            hyper::header::HeaderValue::from_static("application/json"),
+        );
+        response
+    }
+}
+
+impl AdminHandler for AdminServer {
+    fn handle(&self, request: Request<Bytes>) -> Response<Bytes> {
+        let x_token = request
+            .headers()
+            .get("x-token")
+            .map(|value| value.as_bytes());
+        if !x_token.is_some_and(|x_token| self.x_tokens.contains(x_token)) {
+            return Self::response(StatusCode::UNAUTHORIZED, "No valid auth token");
+        }
+
+        match (request.method(), request.uri().path()) {
+            (&Method::GET, "/admin/clients") => {
+                Self::response_json(&self.clients.list(&self.messages))
+            }
+            (&Method::POST, "/admin/clients/disconnect") => {
+                match serde_json::from_slice::<AdminDisconnectRequest>(request.body()) {
+                    Ok(request)
+                        if request.id.is_some()
+                            || request.x_token.is_some()
+                            || request.x_subscription_id.is_some() =>
+                    {
+                        let disconnected = self.clients.disconnect(&request);
+                        Self::response_json(&serde_json::json!({ "disconnected": disconnected }))
+                    }
+                    Ok(_) => Self::response(
+                        StatusCode::BAD_REQUEST,
+                        "at least one of `id`, `x_token`, `x_subscription_id` is required",
+                    ),
+                    Err(error) => Self::response(StatusCode::BAD_REQUEST, error.to_string()),
+                }
+            }
+            _ => Self::response(StatusCode::NOT_FOUND, Bytes::new()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{
+            AdminClientApp, AdminClientInfo, AdminClientLag, AdminClientState, AdminClients,
+            AdminDisconnectRequest,
+        },
+        crate::{channel::Messages, config::ConfigAdmin},
+        std::sync::Arc,
+    };
+
+    struct State;
+
+    impl AdminClientState for State {
+        fn filter(&self) -> serde_json::Value {
+            serde_json::Value::Null
+        }
+
+        fn lag(&self, _messages: &Messages) -> AdminClientLag {
+            AdminClientLag::default()
+        }
+    }
+
+    fn register(clients: &AdminClients, x_token: &[u8]) -> super::AdminClientHandle {
+        clients.register(
+            AdminClientInfo {
+                app: AdminClientApp::Grpc,
+                remote_addr: None,
+                x_token: Some(x_token.to_vec()),
+                x_subscription_id: "".into(),
+            },
+            Arc::new(State),
+        )
+    }
+
+    #[test]
+    fn test_disconnect() {
+        let clients = AdminClients::default();
+        let client1 = register(&clients, b"token1");
+        let client2 = register(&clients, b"token1");
+        let client3 = register(&clients, b"token2");
+
+        let request = AdminDisconnectRequest {
+            id: None,
+            x_token: Some("token1".to_owned()),
+            x_subscription_id: None,
+        };
+        assert_eq!(clients.disconnect(&request), 2);
+        assert!(client1.disconnect().is_cancelled());
+        assert!(client2.disconnect().is_cancelled());
+        assert!(!client3.disconnect().is_cancelled());
+
+        drop(client1);
+        let request = AdminDisconnectRequest {
+            id: Some(client2.id()),
+            x_token: None,
+            x_subscription_id: None,
+        };
+        assert_eq!(clients.disconnect(&request), 1);
+        drop(client2);
+        assert_eq!(clients.disconnect(&request), 0);
+    }
+
+    #[test]
+    fn test_config_x_tokens_required() {
+        let parse = serde_json::from_str::<ConfigAdmin>;
+        assert!(parse("{}").is_err());
+        assert!(parse(r#"{"x_tokens": []}"#).is_err());
+        let config = parse(r#"{"x_tokens": ["admin-token"]}"#).unwrap();
+        assert!(config.x_tokens.contains(b"admin-token".as_slice()));
+    }
+}
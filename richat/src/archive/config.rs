@@ -0,0 +1,74 @@
+use {
+    parquet::basic::{Compression, ZstdLevel},
+    richat_shared::config::{
+        deserialize_affinity, deserialize_humansize_usize, deserialize_num_str,
+    },
+    serde::{
+        Deserialize,
+        de::{self, Deserializer},
+    },
+    std::{path::PathBuf, time::Duration},
+};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct ConfigAppsArchive {
+    /// Directory for files in progress
+    pub path: PathBuf,
+    /// Finished files are moved to this directory (copied if on another filesystem),
+    /// `path` is used if not set
+    pub path_finished: Option<PathBuf>,
+    /// Rotate files once total size of transactions, accounts and blocks files reach this size
+    #[serde(deserialize_with = "deserialize_humansize_usize")]
+    pub rotate_size: usize,
+    /// Rotate files once they were created this time ago
+    #[serde(with = "humantime_serde")]
+    pub rotate_interval: Duration,
+    /// Number of rows buffered in memory before writing to the file
+    #[serde(deserialize_with = "deserialize_num_str")]
+    pub batch_rows: usize,
+    #[serde(deserialize_with = "deserialize_compression")]
+    pub compression: Compression,
+    #[serde(deserialize_with = "deserialize_affinity")]
+    pub affinity: Option<Vec<usize>>,
+}
+
+impl Default for ConfigAppsArchive {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("archive"),
+            path_finished: None,
+            rotate_size: 1024 * 1024 * 1024, // 1GiB
+            rotate_interval: Duration::from_secs(3600),
+            batch_rows: 8_192,
+            compression: Compression::ZSTD(ZstdLevel::default()),
+            affinity: None,
+        }
+    }
+}
+
+impl ConfigAppsArchive {
+    pub fn path_finished(&self) -> &PathBuf {
+        self.path_finished.as_ref().unwrap_or(&self.path)
+    }
+}
+
+fn deserialize_compression<'de, D>(deserializer: D) -> Result<Compression, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let Some(s) = Option::<String>::deserialize(deserializer)? else {
+        return Ok(Compression::UNCOMPRESSED);
+    };
+    let Some(level) = s.strip_prefix("zstd-") else {
+        return Err(de::Error::custom(format!(
+            "invalid compression format: expected null or \"zstd-<level>\" (e.g. \"zstd-3\"), got \"{s}\""
+        )));
+    };
+    level
+        .parse::<i32>()
+        .ok()
+        .and_then(|level| ZstdLevel::try_new(level).ok())
+        .map(Compression::ZSTD)
+        .ok_or_else(|| de::Error::custom(format!("invalid zstd compression level: \"{level}\"")))
+}
@@ -0,0 +1,745 @@
+use {
+    crate::{
+        archive::config::ConfigAppsArchive,
+        channel::{Messages, ParsedMessage},
+        config::ConfigAppsWorkers,
+        metrics,
+    },
+    ::metrics::{counter, gauge},
+    anyhow::Context,
+    arrow_array::{
+        RecordBatch,
+        builder::{
+            ArrayBuilder, BooleanBuilder, FixedSizeBinaryBuilder, Int64Builder, ListBuilder,
+            StringBuilder, UInt64Builder,
+        },
+    },
+    arrow_schema::{DataType, Field, Schema, SchemaRef},
+    parquet::{arrow::ArrowWriter, file::properties::WriterProperties},
+    richat_filter::message::{MessageAccount, MessageBlockMeta, MessageTransaction},
+    richat_proto::{convert_from, geyser::SlotStatus},
+    richat_shared::transports::RecvError,
+    sha2::{Digest, Sha256},
+    solana_account::ReadableAccount,
+    solana_clock::Slot,
+    solana_commitment_config::CommitmentLevel,
+    solana_pubkey::Pubkey,
+    solana_signature::Signature,
+    std::{
+        fs::{self, File},
+        future::Future,
+        io,
+        path::{Path, PathBuf},
+        sync::{Arc, LazyLock},
+        thread::sleep,
+        time::{Duration, Instant},
+    },
+    tokio_util::sync::CancellationToken,
+    tracing::{error, info, warn},
+};
+
+/// Writes finalized slots to Parquet files, files of transactions, accounts and blocks are
+/// rotated together and named by the range of archived slots.
+#[derive(Debug)]
+pub struct ArchiveWriter {
+    config: ConfigAppsArchive,
+    properties: WriterProperties,
+    files: Option<ArchiveFiles>,
+    slot: Option<Slot>,
+}
+
+impl ArchiveWriter {
+    pub fn new(config: ConfigAppsArchive) -> anyhow::Result<Self> {
+        fs::create_dir_all(&config.path)?;
+        fs::create_dir_all(config.path_finished())?;
+
+        // parquet footer is written on finish, files in progress before restart are broken
+        let mut dirs = vec![&config.path, config.path_finished()];
+        dirs.dedup();
+        for dir in dirs {
+            for entry in fs::read_dir(dir)? {
+                let path = entry?.path();
+                if path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.ends_with(".parquet.tmp"))
+                {
+                    warn!("remove not finished archive file {path:?}");
+                    fs::remove_file(&path)?;
+                }
+            }
+        }
+
+        let properties = WriterProperties::builder()
+            .set_compression(config.compression)
+            .build();
+
+        Ok(Self {
+            config,
+            properties,
+            files: None,
+            slot: None,
+        })
+    }
+
+    pub fn spawn(
+        mut config: ConfigAppsArchive,
+        messages: Messages,
+        shutdown: CancellationToken,
+    ) -> anyhow::Result<impl Future<Output = anyhow::Result<()>>> {
+        let affinity = config.affinity.take();
+        let writer = Self::new(config)?;
+        info!("start archive at {:?}", writer.config.path);
+
+        ConfigAppsWorkers::run_once(
+            0,
+            "richatArchive".to_owned(),
+            affinity,
+            {
+                let shutdown = shutdown.clone();
+                move |_index| writer.run(messages, shutdown)
+            },
+            shutdown,
+        )
+    }
+
+    /// Blocking loop reading the finalized channel, should be executed in a dedicated thread.
+    fn run(mut self, messages: Messages, shutdown: CancellationToken) -> anyhow::Result<()> {
+        let receiver = messages.to_receiver();
+        let mut head = messages.get_current_tail(CommitmentLevel::Finalized);
+
+        const COUNTER_LIMIT: i32 = 10_000;
+        let mut counter = 0;
+        loop {
+            counter += 1;
+            if counter > COUNTER_LIMIT {
+                counter = 0;
+                if shutdown.is_cancelled() {
+                    info!("archive thread shutdown");
+                    return self.finish_files();
+                }
+            }
+
+            let message = match receiver.try_recv(CommitmentLevel::Finalized, head) {
+                Ok(Some(message)) => message,
+                Ok(None) => {
+                    counter = COUNTER_LIMIT;
+                    sleep(Duration::from_micros(100));
+                    continue;
+                }
+                Err(RecvError::Lagged) => {
+                    error!("archive lagged, files are finished and slots skipped until next one");
+                    self.finish_files()?;
+                    head = messages.get_current_tail(CommitmentLevel::Finalized);
+                    continue;
+                }
+                Err(error) => {
+                    self.finish_files()?;
+                    return Err(error.into());
+                }
+            };
+            head += 1;
+
+            self.push(&message)?;
+        }
+    }
+
+    /// In the finalized channel messages of the slot are pushed right after `SlotFinalized`.
+    fn push(&mut self, message: &ParsedMessage) -> anyhow::Result<()> {
+        let slot = match message {
+            ParsedMessage::Slot(msg) => {
+                if msg.status() == SlotStatus::SlotFinalized {
+                    self.push_slot(msg.slot())?;
+                }
+                return Ok(());
+            }
+            ParsedMessage::Account(msg) => msg.slot(),
+            ParsedMessage::Transaction(msg) => msg.slot(),
+            ParsedMessage::BlockMeta(msg) => msg.slot(),
+            ParsedMessage::Entry(_) | ParsedMessage::Block(_) => return Ok(()),
+        };
+
+        let Some(files) = self.files.as_mut() else {
+            return Ok(());
+        };
+        if self.slot != Some(slot) {
+            return Ok(());
+        }
+
+        let batch_rows = self.config.batch_rows;
+        match message {
+            ParsedMessage::Account(msg) => files.accounts.push(msg, batch_rows),
+            ParsedMessage::Transaction(msg) => files.transactions.push(msg, batch_rows),
+            ParsedMessage::BlockMeta(msg) => files.blocks.push(msg, batch_rows),
+            _ => Ok(()),
+        }
+    }
+
+    fn push_slot(&mut self, slot: Slot) -> anyhow::Result<()> {
+        if self.files.as_ref().is_some_and(|files| {
+            files.size() >= self.config.rotate_size
+                || files.created_at.elapsed() >= self.config.rotate_interval
+        }) {
+            self.finish_files()?;
+        }
+
+        let files = match self.files.as_mut() {
+            Some(files) => files,
+            None => self.files.insert(ArchiveFiles::create(
+                &self.config.path,
+                slot,
+                &self.properties,
+            )?),
+        };
+        files.last_slot = slot;
+        self.slot = Some(slot);
+        gauge!(metrics::ARCHIVE_SLOT).set(slot as f64);
+        Ok(())
+    }
+
+    fn finish_files(&mut self) -> anyhow::Result<()> {
+        self.slot = None;
+        match self.files.take() {
+            Some(files) => files.finish(self.config.path_finished()),
+            None => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ArchiveFiles {
+    first_slot: Slot,
+    last_slot: Slot,
+    created_at: Instant,
+    transactions: ArchiveFile<TransactionsRows>,
+    accounts: ArchiveFile<AccountsRows>,
+    blocks: ArchiveFile<BlocksRows>,
+}
+
+impl ArchiveFiles {
+    fn create(dir: &Path, slot: Slot, properties: &WriterProperties) -> anyhow::Result<Self> {
+        Ok(Self {
+            first_slot: slot,
+            last_slot: slot,
+            created_at: Instant::now(),
+            transactions: ArchiveFile::create(dir, slot, properties)?,
+            accounts: ArchiveFile::create(dir, slot, properties)?,
+            blocks: ArchiveFile::create(dir, slot, properties)?,
+        })
+    }
+
+    fn size(&self) -> usize {
+        self.transactions.size() + self.accounts.size() + self.blocks.size()
+    }
+
+    fn finish(self, dir: &Path) -> anyhow::Result<()> {
+        let name = format!("{}_{}.parquet", self.first_slot, self.last_slot);
+        self.transactions.finish(dir, &name)?;
+        self.accounts.finish(dir, &name)?;
+        self.blocks.finish(dir, &name)
+    }
+}
+
+#[derive(Debug)]
+struct ArchiveFile<T> {
+    path: PathBuf,
+    writer: ArrowWriter<File>,
+    rows: T,
+}
+
+impl<T: ArchiveRows> ArchiveFile<T> {
+    fn create(dir: &Path, slot: Slot, properties: &WriterProperties) -> anyhow::Result<Self> {
+        let path = dir.join(format!("{}_{slot}.parquet.tmp", T::KIND));
+        let file = File::create(&path)?;
+        let writer = ArrowWriter::try_new(file, T::schema(), Some(properties.clone()))?;
+        Ok(Self {
+            path,
+            writer,
+            rows: T::default(),
+        })
+    }
+
+    fn push(&mut self, message: &T::Message, batch_rows: usize) -> anyhow::Result<()> {
+        self.rows.push(message);
+        if self.rows.len() >= batch_rows {
+            self.write_rows()?;
+        }
+        Ok(())
+    }
+
+    fn write_rows(&mut self) -> anyhow::Result<()> {
+        let rows = self.rows.len();
+        if rows > 0 {
+            let batch = self.rows.finish()?;
+            self.writer.write(&batch)?;
+            counter!(metrics::ARCHIVE_ROWS_TOTAL, "kind" => T::KIND).increment(rows as u64);
+        }
+        Ok(())
+    }
+
+    fn size(&self) -> usize {
+        self.writer.bytes_written() + self.writer.in_progress_size()
+    }
+
+    fn finish(mut self, dir: &Path, name: &str) -> anyhow::Result<()> {
+        self.write_rows()?;
+        self.writer.into_inner()?.sync_all()?;
+
+        let path = dir.join(format!("{}_{name}", T::KIND));
+        move_file(&self.path, &path)
+            .with_context(|| format!("failed to move {:?} to {path:?}", self.path))?;
+        counter!(metrics::ARCHIVE_FILES_TOTAL, "kind" => T::KIND).increment(1);
+        info!("archive file {path:?} finished");
+        Ok(())
+    }
+}
+
+/// Rename file, fallback to copy if directories are on different filesystems.
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Err(error) if error.kind() == io::ErrorKind::CrossesDevices => {
+            // copy with temporary name, so finished name always points to complete file
+            let tmp = to.with_extension("parquet.tmp");
+            fs::copy(from, &tmp)?;
+            File::open(&tmp)?.sync_all()?;
+            fs::rename(&tmp, to)?;
+            fs::remove_file(from)
+        }
+        result => result,
+    }
+}
+
+trait ArchiveRows: Default {
+    const KIND: &str;
+
+    type Message;
+
+    fn schema() -> SchemaRef;
+
+    fn len(&self) -> usize;
+
+    fn push(&mut self, message: &Self::Message);
+
+    fn finish(&mut self) -> anyhow::Result<RecordBatch>;
+}
+
+fn list_field(name: &str) -> Field {
+    Field::new_list(name, Field::new_list_field(DataType::Utf8, true), true)
+}
+
+#[derive(Debug, Default)]
+struct TransactionsRows {
+    slot: UInt64Builder,
+    index: UInt64Builder,
+    signature: StringBuilder,
+    is_vote: BooleanBuilder,
+    error: StringBuilder,
+    fee: UInt64Builder,
+    compute_units_consumed: UInt64Builder,
+    account_keys: ListBuilder<StringBuilder>,
+    log_messages: ListBuilder<StringBuilder>,
+}
+
+impl ArchiveRows for TransactionsRows {
+    const KIND: &str = "transactions";
+
+    type Message = MessageTransaction;
+
+    fn schema() -> SchemaRef {
+        static SCHEMA: LazyLock<SchemaRef> = LazyLock::new(|| {
+            Arc::new(Schema::new(vec![
+                Field::new("slot", DataType::UInt64, false),
+                Field::new("index", DataType::UInt64, false),
+                Field::new("signature", DataType::Utf8, false),
+                Field::new("is_vote", DataType::Boolean, false),
+                Field::new("error", DataType::Utf8, true),
+                Field::new("fee", DataType::UInt64, true),
+                Field::new("compute_units_consumed", DataType::UInt64, true),
+                list_field("account_keys"),
+                list_field("log_messages"),
+            ]))
+        });
+        Arc::clone(&SCHEMA)
+    }
+
+    fn len(&self) -> usize {
+        self.slot.len()
+    }
+
+    fn push(&mut self, message: &Self::Message) {
+        self.slot.append_value(message.slot());
+        self.index.append_value(message.index());
+        self.signature.append_value(message.signature().to_string());
+        self.is_vote.append_value(message.vote());
+        self.error.append_option(
+            convert_from::create_tx_error(message.error().as_ref())
+                .ok()
+                .flatten()
+                .map(|error| error.to_string()),
+        );
+
+        // transaction can be not decoded with `limited` parser
+        let transaction = message.transaction().ok();
+        let meta = transaction.and_then(|tx| tx.meta.as_ref());
+        self.fee.append_option(meta.map(|meta| meta.fee));
+        self.compute_units_consumed
+            .append_option(meta.and_then(|meta| meta.compute_units_consumed));
+
+        let static_keys = transaction
+            .and_then(|tx| tx.transaction.as_ref())
+            .and_then(|tx| tx.message.as_ref())
+            .map(|message| message.account_keys.as_slice())
+            .unwrap_or_default();
+        let loaded_keys = meta.into_iter().flat_map(|meta| {
+            meta.loaded_writable_addresses
+                .iter()
+                .chain(meta.loaded_readonly_addresses.iter())
+        });
+        for pubkey in static_keys.iter().chain(loaded_keys) {
+            self.account_keys.values().append_option(
+                Pubkey::try_from(pubkey.as_slice())
+                    .ok()
+                    .map(|pubkey| pubkey.to_string()),
+            );
+        }
+        self.account_keys.append(transaction.is_some());
+
+        match meta.filter(|meta| !meta.log_messages_none) {
+            Some(meta) => {
+                for log in meta.log_messages.iter() {
+                    self.log_messages.values().append_value(log);
+                }
+                self.log_messages.append(true);
+            }
+            None => self.log_messages.append(false),
+        }
+    }
+
+    fn finish(&mut self) -> anyhow::Result<RecordBatch> {
+        RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                Arc::new(self.slot.finish()),
+                Arc::new(self.index.finish()),
+                Arc::new(self.signature.finish()),
+                Arc::new(self.is_vote.finish()),
+                Arc::new(self.error.finish()),
+                Arc::new(self.fee.finish()),
+                Arc::new(self.compute_units_consumed.finish()),
+                Arc::new(self.account_keys.finish()),
+                Arc::new(self.log_messages.finish()),
+            ],
+        )
+        .map_err(Into::into)
+    }
+}
+
+#[derive(Debug)]
+struct AccountsRows {
+    slot: UInt64Builder,
+    pubkey: StringBuilder,
+    owner: StringBuilder,
+    lamports: UInt64Builder,
+    executable: BooleanBuilder,
+    rent_epoch: UInt64Builder,
+    write_version: UInt64Builder,
+    data_len: UInt64Builder,
+    data_hash: FixedSizeBinaryBuilder,
+    txn_signature: StringBuilder,
+}
+
+impl Default for AccountsRows {
+    fn default() -> Self {
+        Self {
+            slot: UInt64Builder::default(),
+            pubkey: StringBuilder::default(),
+            owner: StringBuilder::default(),
+            lamports: UInt64Builder::default(),
+            executable: BooleanBuilder::default(),
+            rent_epoch: UInt64Builder::default(),
+            write_version: UInt64Builder::default(),
+            data_len: UInt64Builder::default(),
+            data_hash: FixedSizeBinaryBuilder::new(Self::DATA_HASH_SIZE),
+            txn_signature: StringBuilder::default(),
+        }
+    }
+}
+
+impl AccountsRows {
+    /// SHA-256 of account data
+    const DATA_HASH_SIZE: i32 = 32;
+}
+
+impl ArchiveRows for AccountsRows {
+    const KIND: &str = "accounts";
+
+    type Message = MessageAccount;
+
+    fn schema() -> SchemaRef {
+        static SCHEMA: LazyLock<SchemaRef> = LazyLock::new(|| {
+            Arc::new(Schema::new(vec![
+                Field::new("slot", DataType::UInt64, false),
+                Field::new("pubkey", DataType::Utf8, false),
+                Field::new("owner", DataType::Utf8, false),
+                Field::new("lamports", DataType::UInt64, false),
+                Field::new("executable", DataType::Boolean, false),
+                Field::new("rent_epoch", DataType::UInt64, false),
+                Field::new("write_version", DataType::UInt64, false),
+                Field::new("data_len", DataType::UInt64, false),
+                Field::new(
+                    "data_hash",
+                    DataType::FixedSizeBinary(AccountsRows::DATA_HASH_SIZE),
+                    false,
+                ),
+                Field::new("txn_signature", DataType::Utf8, true),
+            ]))
+        });
+        Arc::clone(&SCHEMA)
+    }
+
+    fn len(&self) -> usize {
+        self.slot.len()
+    }
+
+    fn push(&mut self, message: &Self::Message) {
+        self.slot.append_value(message.slot());
+        self.pubkey.append_value(message.pubkey().to_string());
+        self.owner.append_value(message.owner().to_string());
+        self.lamports.append_value(message.lamports());
+        self.executable.append_value(message.executable());
+        self.rent_epoch.append_value(message.rent_epoch());
+        self.write_version.append_value(message.write_version());
+        self.data_len.append_value(message.data().len() as u64);
+        self.data_hash
+            .append_value(Sha256::digest(message.data()))
+            .expect("valid hash size");
+        self.txn_signature.append_option(
+            message
+                .txn_signature()
+                .and_then(|signature| Signature::try_from(signature).ok())
+                .map(|signature| signature.to_string()),
+        );
+    }
+
+    fn finish(&mut self) -> anyhow::Result<RecordBatch> {
+        RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                Arc::new(self.slot.finish()),
+                Arc::new(self.pubkey.finish()),
+                Arc::new(self.owner.finish()),
+                Arc::new(self.lamports.finish()),
+                Arc::new(self.executable.finish()),
+                Arc::new(self.rent_epoch.finish()),
+                Arc::new(self.write_version.finish()),
+                Arc::new(self.data_len.finish()),
+                Arc::new(self.data_hash.finish()),
+                Arc::new(self.txn_signature.finish()),
+            ],
+        )
+        .map_err(Into::into)
+    }
+}
+
+#[derive(Debug, Default)]
+struct BlocksRows {
+    slot: UInt64Builder,
+    parent_slot: UInt64Builder,
+    blockhash: StringBuilder,
+    block_height: UInt64Builder,
+    block_time: Int64Builder,
+    executed_transaction_count: UInt64Builder,
+    entries_count: UInt64Builder,
+}
+
+impl ArchiveRows for BlocksRows {
+    const KIND: &str = "blocks";
+
+    type Message = MessageBlockMeta;
+
+    fn schema() -> SchemaRef {
+        static SCHEMA: LazyLock<SchemaRef> = LazyLock::new(|| {
+            Arc::new(Schema::new(vec![
+                Field::new("slot", DataType::UInt64, false),
+                Field::new("parent_slot", DataType::UInt64, false),
+                Field::new("blockhash", DataType::Utf8, false),
+                Field::new("block_height", DataType::UInt64, false),
+                Field::new("block_time", DataType::Int64, true),
+                Field::new("executed_transaction_count", DataType::UInt64, false),
+                Field::new("entries_count", DataType::UInt64, false),
+            ]))
+        });
+        Arc::clone(&SCHEMA)
+    }
+
+    fn len(&self) -> usize {
+        self.slot.len()
+    }
+
+    fn push(&mut self, message: &Self::Message) {
+        self.slot.append_value(message.slot());
+        self.parent_slot.append_value(message.parent_slot());
+        self.blockhash.append_value(message.blockhash());
+        self.block_height.append_value(message.block_height());
+        self.block_time.append_option(message.block_time());
+        self.executed_transaction_count
+            .append_value(message.executed_transaction_count());
+        self.entries_count.append_value(message.entries_count());
+    }
+
+    fn finish(&mut self) -> anyhow::Result<RecordBatch> {
+        RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                Arc::new(self.slot.finish()),
+                Arc::new(self.parent_slot.finish()),
+                Arc::new(self.blockhash.finish()),
+                Arc::new(self.block_height.finish()),
+                Arc::new(self.block_time.finish()),
+                Arc::new(self.executed_transaction_count.finish()),
+                Arc::new(self.entries_count.finish()),
+            ],
+        )
+        .map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::ArchiveWriter,
+        crate::{archive::config::ConfigAppsArchive, channel::ParsedMessage},
+        parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder,
+        prost::Message as _,
+        richat_filter::message::{Message, MessageParserEncoding},
+        richat_proto::{
+            geyser::{
+                SlotStatus, SubscribeUpdate, SubscribeUpdateAccount, SubscribeUpdateAccountInfo,
+                SubscribeUpdateBlockMeta, SubscribeUpdateSlot, subscribe_update::UpdateOneof,
+            },
+            solana::storage::confirmed_block::BlockHeight,
+        },
+        solana_clock::Slot,
+        solana_pubkey::Pubkey,
+        std::{borrow::Cow, fs::File, time::Duration},
+    };
+
+    fn parse(update_oneof: UpdateOneof) -> ParsedMessage {
+        let data = SubscribeUpdate {
+            filters: vec![],
+            update_oneof: Some(update_oneof),
+            created_at: Some(Default::default()),
+        }
+        .encode_to_vec();
+        Message::parse(Cow::Owned(data), MessageParserEncoding::Prost)
+            .expect("valid message")
+            .into()
+    }
+
+    fn slot(slot: Slot, status: SlotStatus) -> ParsedMessage {
+        parse(UpdateOneof::Slot(SubscribeUpdateSlot {
+            slot,
+            parent: None,
+            status: status as i32,
+            dead_error: None,
+        }))
+    }
+
+    fn account(slot: Slot) -> ParsedMessage {
+        parse(UpdateOneof::Account(SubscribeUpdateAccount {
+            account: Some(SubscribeUpdateAccountInfo {
+                pubkey: Pubkey::new_unique().to_bytes().to_vec(),
+                owner: Pubkey::new_unique().to_bytes().to_vec(),
+                data: vec![1, 2, 3],
+                ..Default::default()
+            }),
+            slot,
+            is_startup: false,
+        }))
+    }
+
+    fn block_meta(slot: Slot) -> ParsedMessage {
+        parse(UpdateOneof::BlockMeta(SubscribeUpdateBlockMeta {
+            slot,
+            blockhash: "blockhash".to_owned(),
+            block_height: Some(BlockHeight { block_height: slot }),
+            parent_slot: slot - 1,
+            ..Default::default()
+        }))
+    }
+
+    fn rows(path: std::path::PathBuf) -> usize {
+        let file = File::open(path).expect("finished file");
+        ParquetRecordBatchReaderBuilder::try_new(file)
+            .expect("valid parquet")
+            .build()
+            .expect("valid reader")
+            .map(|batch| batch.expect("valid batch").num_rows())
+            .sum()
+    }
+
+    #[test]
+    fn test_rotation() {
+        let path = std::env::temp_dir().join(format!("richat-archive-{}", std::process::id()));
+        let mut writer = ArchiveWriter::new(ConfigAppsArchive {
+            path: path.join("tmp"),
+            path_finished: Some(path.clone()),
+            rotate_interval: Duration::ZERO,
+            batch_rows: 2,
+            ..Default::default()
+        })
+        .expect("valid config");
+
+        for message in [
+            // not finalized slot messages are skipped
+            account(9),
+            slot(10, SlotStatus::SlotConfirmed),
+            slot(10, SlotStatus::SlotFinalized),
+            account(10),
+            account(10),
+            account(10),
+            block_meta(10),
+            slot(11, SlotStatus::SlotFinalized),
+            account(11),
+        ] {
+            writer.push(&message).expect("pushed");
+        }
+        writer.finish_files().expect("finished");
+
+        assert_eq!(rows(path.join("accounts_10_10.parquet")), 3);
+        assert_eq!(rows(path.join("blocks_10_10.parquet")), 1);
+        assert_eq!(rows(path.join("transactions_10_10.parquet")), 0);
+        assert_eq!(rows(path.join("accounts_11_11.parquet")), 1);
+        assert!(
+            std::fs::read_dir(path.join("tmp"))
+                .expect("tmp dir")
+                .next()
+                .is_none()
+        );
+        let _ = std::fs::remove_dir_all(path);
+    }
+
+    #[test]
+    fn test_remove_not_finished() {
+        let path = std::env::temp_dir().join(format!("richat-archive-tmp-{}", std::process::id()));
+        std::fs::create_dir_all(&path).expect("created dir");
+        std::fs::write(path.join("accounts_10.parquet.tmp"), [1, 2, 3]).expect("written");
+        std::fs::write(path.join("accounts_9_9.parquet"), [1, 2, 3]).expect("written");
+
+        let mut writer = ArchiveWriter::new(ConfigAppsArchive {
+            path: path.clone(),
+            ..Default::default()
+        })
+        .expect("valid config");
+        assert!(!path.join("accounts_10.parquet.tmp").exists());
+        assert!(path.join("accounts_9_9.parquet").exists());
+
+        // restart at the same slot
+        for message in [slot(10, SlotStatus::SlotFinalized), account(10)] {
+            writer.push(&message).expect("pushed");
+        }
+        writer.finish_files().expect("finished");
+        assert_eq!(rows(path.join("accounts_10_10.parquet")), 1);
+        let _ = std::fs::remove_dir_all(path);
+    }
+}
@@ -0,0 +1,507 @@
+use {
+    anyhow::Context,
+    clap::{Parser, Subcommand, ValueEnum},
+    prost::encoding::encode_varint,
+    richat::{
+        channel::ParsedMessage,
+        storage::{
+            metadata::Metadata,
+            recovery::verify_chunk,
+            segments::{ChunkCompression, SegmentReader},
+        },
+    },
+    richat_filter::{
+        filter::{FilteredUpdate, FilteredUpdateFilters},
+        message::{MessageParserEncoding, MessageRef},
+    },
+    richat_proto::geyser::SlotStatus,
+    serde_json::{Value, json},
+    solana_account::ReadableAccount,
+    solana_clock::Slot,
+    std::{
+        fs::File,
+        io::{self, BufWriter, Write},
+        path::PathBuf,
+    },
+};
+
+#[cfg(not(target_env = "msvc"))]
+#[global_allocator]
+static GLOBAL: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc;
+
+#[derive(Debug, Parser)]
+#[clap(
+    author,
+    version,
+    about = "Richat Storage Tool: inspect, verify and export offline replay storage"
+)]
+struct Args {
+    /// Path to storage, same as `channel.config.storage.path`
+    #[clap(short, long)]
+    path: PathBuf,
+
+    #[command(subcommand)]
+    action: ArgsAction,
+}
+
+#[derive(Debug, Subcommand)]
+enum ArgsAction {
+    /// List retained slots
+    Slots,
+    /// List segment files
+    Segments,
+    /// List chunks
+    Chunks,
+    /// Decode every chunk and report broken ones
+    Verify,
+    /// Dump messages for the slot range
+    Dump(ArgsActionDump),
+}
+
+#[derive(Debug, clap::Args)]
+struct ArgsActionDump {
+    /// First slot to dump
+    #[clap(long)]
+    from_slot: Slot,
+
+    /// Last slot to dump, same as `from_slot` if not specified
+    #[clap(long)]
+    to_slot: Option<Slot>,
+
+    /// Output format
+    #[clap(long, default_value_t = ArgsDumpFormat::Json, value_enum)]
+    format: ArgsDumpFormat,
+
+    /// Output file, stdout if not specified
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ArgsDumpFormat {
+    /// JSON object per line, a summary of the message (ids, sizes and key fields)
+    Json,
+    /// Length-delimited protobuf `SubscribeUpdate` messages, full export of the messages
+    Protobuf,
+}
+
+fn main() -> anyhow::Result<()> {
+    run(Args::parse(), &mut io::stdout().lock())
+}
+
+fn run(args: Args, stdout: &mut dyn Write) -> anyhow::Result<()> {
+    let metadata_path = args.path.join("metadata");
+    anyhow::ensure!(
+        metadata_path.is_dir(),
+        "metadata directory doesn't exist: {metadata_path:?}"
+    );
+    let metadata = Metadata::open_read_only(&metadata_path, args.path.join("segments"))?;
+
+    match args.action {
+        ArgsAction::Slots => {
+            for meta in metadata.catalog().slots.values() {
+                writeln!(
+                    stdout,
+                    "slot={} finalized={} first_index={} segment_id={}",
+                    meta.slot, meta.finalized, meta.first_index, meta.segment_id
+                )?;
+            }
+        }
+        ArgsAction::Segments => {
+            let catalog = metadata.catalog();
+            writeln!(
+                stdout,
+                "next_segment_id={} active_segment_id={}",
+                catalog.state.next_segment_id, catalog.state.active_segment_id
+            )?;
+            for meta in catalog.segments.values() {
+                writeln!(
+                    stdout,
+                    "segment_id={} sealed={} last_index={} file_len={} chunk_count={}",
+                    meta.segment_id, meta.sealed, meta.last_index, meta.file_len, meta.chunk_count
+                )?;
+            }
+        }
+        ArgsAction::Chunks => {
+            for meta in metadata.catalog().chunks.iter() {
+                writeln!(
+                    stdout,
+                    "first_index={} last_index={} segment_id={} offset={} size={} compression={}",
+                    meta.first_index,
+                    meta.last_index,
+                    meta.segment_id,
+                    meta.offset,
+                    meta.size,
+                    ChunkCompression::from_tag(meta.compression)
+                        .map(ChunkCompression::as_str)
+                        .unwrap_or("unknown")
+                )?;
+            }
+        }
+        ArgsAction::Verify => verify(&metadata, stdout)?,
+        ArgsAction::Dump(action) => action.run(&metadata, stdout)?,
+    }
+
+    Ok(())
+}
+
+fn verify(metadata: &Metadata, stdout: &mut dyn Write) -> anyhow::Result<()> {
+    let chunks = metadata.catalog().chunks.clone();
+
+    let mut failed = 0;
+    for meta in chunks.iter() {
+        if let Err(error) = verify_chunk(metadata, meta, MessageParserEncoding::Prost) {
+            failed += 1;
+            writeln!(
+                stdout,
+                "chunk first_index={} segment_id={} offset={}: {error:#}",
+                meta.first_index, meta.segment_id, meta.offset
+            )?;
+        }
+    }
+
+    writeln!(stdout, "verified {} chunks, failed: {failed}", chunks.len())?;
+    anyhow::ensure!(
+        failed == 0,
+        "storage has broken chunks, use `richat --repair-storage` to drop them"
+    );
+    Ok(())
+}
+
+impl ArgsActionDump {
+    fn run(self, metadata: &Metadata, stdout: &mut dyn Write) -> anyhow::Result<()> {
+        let to_slot = self.to_slot.unwrap_or(self.from_slot);
+        anyhow::ensure!(
+            self.from_slot <= to_slot,
+            "from_slot should be less than or equal to to_slot"
+        );
+
+        let Some(start_index) = metadata
+            .catalog()
+            .slots
+            .range(self.from_slot..=to_slot)
+            .map(|(_slot, meta)| meta.first_index)
+            .min()
+        else {
+            anyhow::bail!("no slots in range {}..={to_slot}", self.from_slot);
+        };
+
+        let mut output: BufWriter<Box<dyn Write + '_>> = BufWriter::new(match &self.output {
+            Some(path) => {
+                Box::new(File::create(path).with_context(|| format!("failed to create {path:?}"))?)
+            }
+            None => Box::new(stdout),
+        });
+
+        let mut buf = Vec::new();
+        let mut len_buf = Vec::with_capacity(10);
+        'outer: for chunk in SegmentReader::new(metadata, start_index, MessageParserEncoding::Prost)
+        {
+            for result in chunk? {
+                let (index, message) = result?;
+                let slot = message.slot();
+                if (self.from_slot..=to_slot).contains(&slot) {
+                    match self.format {
+                        ArgsDumpFormat::Json => {
+                            serde_json::to_writer(&mut output, &message_to_json(index, &message))?;
+                            output.write_all(b"\n")?;
+                        }
+                        ArgsDumpFormat::Protobuf => {
+                            buf.clear();
+                            len_buf.clear();
+                            FilteredUpdate {
+                                filters: FilteredUpdateFilters::new(),
+                                filtered_update: MessageRef::from(&message).into(),
+                            }
+                            .encode(&mut buf);
+                            encode_varint(buf.len() as u64, &mut len_buf);
+                            output.write_all(&len_buf)?;
+                            output.write_all(&buf)?;
+                        }
+                    }
+                }
+
+                // no new messages for the range after finalization of the last slot
+                if let ParsedMessage::Slot(message) = &message {
+                    if message.status() == SlotStatus::SlotFinalized && slot >= to_slot {
+                        break 'outer;
+                    }
+                }
+            }
+        }
+
+        output.flush()?;
+        Ok(())
+    }
+}
+
+fn message_to_json(index: u64, message: &ParsedMessage) -> Value {
+    let mut value = json!({
+        "index": index,
+        "slot": message.slot(),
+        "type": message.as_str_type(),
+        "size": message.size(),
+    });
+    let extra = match message {
+        ParsedMessage::Slot(message) => json!({
+            "status": message.status().as_str_name(),
+            "parent": message.parent(),
+            "dead_error": message.dead_error(),
+        }),
+        ParsedMessage::Account(message) => json!({
+            "pubkey": message.pubkey().to_string(),
+            "owner": message.owner().to_string(),
+            "lamports": message.lamports(),
+            "data_len": message.data().len(),
+            "write_version": message.write_version(),
+        }),
+        ParsedMessage::Transaction(message) => json!({
+            "signature": message.signature().to_string(),
+            "failed": message.failed(),
+            "vote": message.vote(),
+        }),
+        ParsedMessage::Entry(message) => json!({
+            "entry_index": message.index(),
+            "executed_transaction_count": message.executed_transaction_count(),
+        }),
+        ParsedMessage::BlockMeta(message) => json!({
+            "blockhash": message.blockhash(),
+            "block_height": message.block_height(),
+            "executed_transaction_count": message.executed_transaction_count(),
+        }),
+        ParsedMessage::Block(_) => json!({}),
+    };
+    if let (Value::Object(value), Value::Object(extra)) = (&mut value, extra) {
+        value.extend(extra);
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{Args, run},
+        clap::Parser,
+        prost::{Message as _, encoding::decode_varint},
+        richat::{
+            channel::ParsedMessage,
+            config::ConfigStorage,
+            storage::{Storage, segments::segment_file_name},
+            util::SpawnedThreads,
+        },
+        richat_filter::message::{Message, MessageParserEncoding},
+        richat_proto::geyser::{
+            SlotStatus, SubscribeUpdate, SubscribeUpdateSlot, subscribe_update::UpdateOneof,
+        },
+        serde_json::Value,
+        solana_clock::Slot,
+        std::{
+            borrow::Cow,
+            fs,
+            io::{Seek, SeekFrom, Write},
+            path::Path,
+            thread,
+            time::{Duration, Instant},
+        },
+        tokio_util::sync::CancellationToken,
+    };
+
+    fn slot(slot: Slot, status: SlotStatus) -> ParsedMessage {
+        let data = SubscribeUpdate {
+            filters: vec![],
+            update_oneof: Some(UpdateOneof::Slot(SubscribeUpdateSlot {
+                slot,
+                parent: slot.checked_sub(1),
+                status: status as i32,
+                dead_error: None,
+            })),
+            created_at: Some(Default::default()),
+        }
+        .encode_to_vec();
+        Message::parse(Cow::Owned(data), MessageParserEncoding::Prost)
+            .expect("valid message")
+            .into()
+    }
+
+    /// Storage with processed slots 1..=3 and finalized slot 2, one message per chunk.
+    fn open_storage(name: &str) -> (ConfigStorage, Storage, CancellationToken, SpawnedThreads) {
+        let path =
+            std::env::temp_dir().join(format!("richat-storage-tool-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        let config = serde_json::json!({
+            "path": path,
+            "chunk_target_size": "1B",
+            "replay_threads": 1,
+        });
+        let config: ConfigStorage = serde_json::from_str(&config.to_string()).unwrap();
+        let shutdown = CancellationToken::new();
+        let (storage, threads) = Storage::open(
+            config.clone(),
+            MessageParserEncoding::Prost,
+            shutdown.clone(),
+        )
+        .unwrap();
+
+        let messages = [
+            (1, slot(1, SlotStatus::SlotProcessed)),
+            (2, slot(2, SlotStatus::SlotProcessed)),
+            (3, slot(3, SlotStatus::SlotProcessed)),
+            (2, slot(2, SlotStatus::SlotFinalized)),
+        ];
+        let mut heads = std::collections::HashMap::new();
+        for (index, (slot, message)) in messages.into_iter().enumerate() {
+            let index = index as u64;
+            let init = !heads.contains_key(&slot);
+            let head = *heads.entry(slot).or_insert(index);
+            storage.push_message(init, slot, head, index, message);
+        }
+        let ts = Instant::now();
+        while storage.next_index() < 4 {
+            assert!(
+                ts.elapsed() < Duration::from_secs(10),
+                "messages are not written"
+            );
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        (config, storage, shutdown, threads)
+    }
+
+    fn close_storage(storage: Storage, shutdown: CancellationToken, threads: SpawnedThreads) {
+        shutdown.cancel();
+        drop(storage);
+        for (_name, jh) in threads {
+            if let Some(jh) = jh {
+                jh.join().unwrap().unwrap();
+            }
+        }
+    }
+
+    fn run_args(path: &Path, args: &[&str]) -> anyhow::Result<String> {
+        let args = Args::try_parse_from(
+            ["richat-storage", "--path", path.to_str().unwrap()]
+                .into_iter()
+                .chain(args.iter().copied()),
+        )?;
+        let mut stdout = vec![];
+        run(args, &mut stdout)?;
+        Ok(String::from_utf8(stdout)?)
+    }
+
+    #[test]
+    fn test_list() {
+        let (config, storage, shutdown, threads) = open_storage("list");
+
+        // metadata is opened in read-only mode while storage is in use
+        let slots = run_args(&config.path, &["slots"]).unwrap();
+        assert_eq!(
+            slots.lines().collect::<Vec<_>>(),
+            [
+                "slot=1 finalized=false first_index=0 segment_id=1",
+                "slot=2 finalized=true first_index=1 segment_id=1",
+                "slot=3 finalized=false first_index=2 segment_id=1",
+            ]
+        );
+        let segments = run_args(&config.path, &["segments"]).unwrap();
+        assert!(segments.starts_with("next_segment_id=2 active_segment_id=1\n"));
+        assert!(segments.contains("segment_id=1 sealed=false last_index=3 "));
+        let chunks = run_args(&config.path, &["chunks"]).unwrap();
+        assert_eq!(chunks.lines().count(), 4);
+        assert!(chunks.starts_with("first_index=0 last_index=0 segment_id=1 offset=0 "));
+
+        close_storage(storage, shutdown, threads);
+        let _ = fs::remove_dir_all(config.path);
+    }
+
+    #[test]
+    fn test_verify() {
+        let (config, storage, shutdown, threads) = open_storage("verify");
+        close_storage(storage, shutdown, threads);
+
+        let output = run_args(&config.path, &["verify"]).unwrap();
+        assert_eq!(output, "verified 4 chunks, failed: 0\n");
+
+        // overwrite payload of the first chunk
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .open(config.segments_path().join(segment_file_name(1)))
+            .unwrap();
+        file.seek(SeekFrom::Start(0)).unwrap();
+        file.write_all(&[0xff; 8]).unwrap();
+        drop(file);
+
+        let error = run_args(&config.path, &["verify"]).unwrap_err();
+        assert!(error.to_string().contains("storage has broken chunks"));
+
+        let _ = fs::remove_dir_all(config.path);
+    }
+
+    #[test]
+    fn test_dump() {
+        let (config, storage, shutdown, threads) = open_storage("dump");
+        close_storage(storage, shutdown, threads);
+
+        // dump is stopped by finalized status of the last slot
+        let output = run_args(
+            &config.path,
+            &["dump", "--from-slot", "2", "--format", "json"],
+        )
+        .unwrap();
+        let values = output
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap())
+            .map(|value| (value["index"].clone(), value["status"].clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            values,
+            [
+                (1.into(), "SLOT_PROCESSED".into()),
+                (3.into(), "SLOT_FINALIZED".into()),
+            ]
+        );
+
+        let path = config.path.join("dump.bin");
+        let output = run_args(
+            &config.path,
+            &[
+                "dump",
+                "--from-slot",
+                "1",
+                "--to-slot",
+                "3",
+                "--format",
+                "protobuf",
+                "--output",
+                path.to_str().unwrap(),
+            ],
+        )
+        .unwrap();
+        assert!(output.is_empty());
+        let data = fs::read(&path).unwrap();
+        let mut buf = data.as_slice();
+        let mut slots = vec![];
+        while !buf.is_empty() {
+            let len = decode_varint(&mut buf).unwrap() as usize;
+            let update = SubscribeUpdate::decode(&buf[..len]).unwrap();
+            buf = &buf[len..];
+            match update.update_oneof {
+                Some(UpdateOneof::Slot(msg)) => slots.push((msg.slot, msg.status)),
+                update => panic!("unexpected update: {update:?}"),
+            }
+        }
+        assert_eq!(
+            slots,
+            [
+                (1, SlotStatus::SlotProcessed as i32),
+                (2, SlotStatus::SlotProcessed as i32),
+                (3, SlotStatus::SlotProcessed as i32),
+                (2, SlotStatus::SlotFinalized as i32),
+            ]
+        );
+
+        let error = run_args(&config.path, &["dump", "--from-slot", "4"]).unwrap_err();
+        assert_eq!(error.to_string(), "no slots in range 4..=4");
+
+        let _ = fs::remove_dir_all(config.path);
+    }
+}
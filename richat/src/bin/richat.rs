@@ -6,15 +6,24 @@ use {
         stream::StreamExt,
     },
     richat::{
+        admin::{AdminClients, AdminServer},
+        archive::writer::ArchiveWriter,
         channel::Messages,
         config::Config,
         grpc::server::GrpcServer,
+        jsonrpc::server::JsonrpcServer,
+        kafka::producer::KafkaProducer,
         pubsub::server::PubSubServer,
+        reload::AppsReload,
         richat::server::RichatServer,
         source::{ReceiveError, Subscriptions},
+        storage::Storage,
+        tenants::Tenants,
         version::VERSION,
+        webhooks::sender::Webhooks,
     },
     richat_filter::message::MessageParserEncoding,
+    richat_metrics::AdminHandler,
     signal_hook::{
         consts::{SIGHUP, SIGINT},
         iterator::Signals,
@@ -47,6 +56,10 @@ struct Args {
     /// Only check config and exit
     #[clap(long, default_value_t = false)]
     pub check: bool,
+
+    /// Verify and repair storage (keep the longest run of valid chunks at the tail, data before the damage is dropped) and exit
+    #[clap(long, default_value_t = false)]
+    pub repair_storage: bool,
 }
 
 fn main() -> anyhow::Result<()> {
@@ -60,6 +73,10 @@ fn main() -> anyhow::Result<()> {
     let args = Args::parse();
     let config: Config = richat_shared::config::load_from_file_sync(&args.config)
         .with_context(|| format!("failed to load config from {}", args.config))?;
+    anyhow::ensure!(
+        config.admin.is_none() || config.metrics.is_some(),
+        "admin API is served by metrics server, `metrics` should be configured"
+    );
     if args.check {
         info!("Config is OK!");
         return Ok(());
@@ -75,6 +92,15 @@ fn main() -> anyhow::Result<()> {
     richat_shared::tracing::setup(config.logs.json)?;
     info!("version: {} / {}", VERSION.version, VERSION.git);
 
+    if args.repair_storage {
+        let Some(storage) = &config.channel.config.storage else {
+            anyhow::bail!("storage is not configured");
+        };
+        Storage::repair(storage, config.channel.get_messages_parser())?;
+        info!("Storage is OK!");
+        return Ok(());
+    }
+
     // Shutdown channel/flag
     let shutdown = CancellationToken::new();
     let is_ready = Arc::new(AtomicBool::new(false));
@@ -89,13 +115,29 @@ fn main() -> anyhow::Result<()> {
     let streams_total = config.channel.sources.len();
     let dedup_required = sources_sighup_reload || streams_total > 1;
     let reload_notify = Arc::new(Notify::new());
+    let apps_sighup_reload = config.apps.sighup_reload;
+    let apps_reload_notify = Arc::new(Notify::new());
 
+    // confirmed / finalized channels are required only for apps with commitment
+    let channel_confirmed = config.apps.grpc.is_some()
+        || config.apps.pubsub.is_some()
+        || config.apps.kafka.is_some()
+        || config.apps.webhooks.is_some();
+    let channel_finalized = channel_confirmed || config.apps.archive.is_some();
+    let blocks_enabled = config.channel.blocks_enabled();
+    if let Some(config) = &config.apps.pubsub {
+        anyhow::ensure!(
+            blocks_enabled || !config.enable_block_subscription,
+            "pubsub `enable_block_subscription` requires blocks, but transactions are filtered by sources"
+        );
+    }
     let (mut messages, mut threads) = Messages::new(
         sources_parser,
         config.channel.config,
         config.apps.richat.is_some(),
-        config.apps.grpc.is_some(),
-        config.apps.pubsub.is_some(),
+        channel_confirmed,
+        channel_finalized,
+        blocks_enabled,
         shutdown.clone(),
     )?;
     let (sender, replay_from_slot) = messages.to_sender(streams_total)?;
@@ -122,6 +164,7 @@ fn main() -> anyhow::Result<()> {
 
                     let mut stream = Subscriptions::new(
                         config.channel.sources,
+                        config.channel.sources_policy,
                         replay_from_slot,
                     )
                     .await?;
@@ -149,7 +192,7 @@ fn main() -> anyhow::Result<()> {
                             },
                             _ = reload_notify.notified(), if sources_sighup_reload && !reload_in_progress => {
                                 info!("SIGHUP: reloading sources...");
-                                match load_config_for_reloading(&config_path, sources_parser).await {
+                                match load_config_for_reloading(&config_path, sources_parser, blocks_enabled).await {
                                     Ok(config) => {
                                         reload_in_progress = true;
                                         reload_prepare_task = stream.prepare_reload(config.channel.sources).boxed();
@@ -179,29 +222,113 @@ fn main() -> anyhow::Result<()> {
     // Create runtime for incoming connections
     let apps_jh = thread::Builder::new().name("richatApp".to_owned()).spawn({
         let shutdown = shutdown.clone();
+        let config_path = args.config.clone();
+        let apps_reload_notify = Arc::clone(&apps_reload_notify);
         move || {
             let runtime = config.apps.tokio.build_runtime("richatApp")?;
             runtime.block_on(async move {
+                let tenants = Tenants::new(config.apps.tenants);
+                let admin = AdminClients::default();
+                let mut apps_reload = AppsReload {
+                    tenants: tenants.clone(),
+                    richat: None,
+                    grpc: None,
+                    pubsub: None,
+                };
+
                 let richat_fut = if let Some(config) = config.apps.richat {
-                    RichatServer::spawn(config, messages.clone(), shutdown.clone())
-                        .await?
-                        .boxed()
+                    let (reload, fut) = RichatServer::spawn(
+                        config,
+                        messages.clone(),
+                        tenants.clone(),
+                        admin.clone(),
+                        shutdown.clone(),
+                    )
+                    .await?;
+                    apps_reload.richat = Some(reload);
+                    fut.boxed()
                 } else {
                     ready(Ok(())).boxed()
                 };
 
                 let grpc_fut = if let Some(config) = config.apps.grpc {
-                    GrpcServer::spawn(config, messages.clone(), shutdown.clone())?.boxed()
+                    let (reload, fut) = GrpcServer::spawn(
+                        config,
+                        messages.clone(),
+                        tenants.clone(),
+                        admin.clone(),
+                        shutdown.clone(),
+                    )?;
+                    apps_reload.grpc = Some(reload);
+                    fut.boxed()
+                } else {
+                    ready(Ok(())).boxed()
+                };
+
+                let jsonrpc_fut = if let Some(config) = config.apps.jsonrpc {
+                    JsonrpcServer::spawn(config, messages.clone(), shutdown.clone())?.boxed()
                 } else {
                     ready(Ok(())).boxed()
                 };
 
                 let pubsub_fut = if let Some(config) = config.apps.pubsub {
-                    PubSubServer::spawn(config, messages, shutdown.clone())?.boxed()
+                    let (reload, fut) =
+                        PubSubServer::spawn(
+                            config,
+                            messages.clone(),
+                            tenants,
+                            admin.clone(),
+                            shutdown.clone(),
+                        )?;
+                    apps_reload.pubsub = Some(reload);
+                    fut.boxed()
+                } else {
+                    ready(Ok(())).boxed()
+                };
+
+                let archive_fut = if let Some(config) = config.apps.archive {
+                    ArchiveWriter::spawn(config, messages.clone(), shutdown.clone())?.boxed()
+                } else {
+                    ready(Ok(())).boxed()
+                };
+
+                let kafka_fut = if let Some(config) = config.apps.kafka {
+                    KafkaProducer::spawn(config, messages.clone(), shutdown.clone())?.boxed()
+                } else {
+                    ready(Ok(())).boxed()
+                };
+
+                let webhooks_fut = if let Some(config) = config.apps.webhooks {
+                    Webhooks::spawn(config, messages.clone(), shutdown.clone())?.boxed()
+                } else {
+                    ready(Ok(())).boxed()
+                };
+
+                let reload_fut = if apps_sighup_reload {
+                    let shutdown = shutdown.clone();
+                    async move {
+                        loop {
+                            tokio::select! {
+                                () = apps_reload_notify.notified() => {
+                                    info!("SIGHUP: reloading apps...");
+                                    match load_apps_config_for_reloading(&config_path, &apps_reload).await {
+                                        Ok(()) => info!("SIGHUP: apps reloaded"),
+                                        Err(error) => error!("SIGHUP: failed to reload apps: {error:?}"),
+                                    }
+                                },
+                                () = shutdown.cancelled() => return Ok(()),
+                            }
+                        }
+                    }
+                    .boxed()
                 } else {
                     ready(Ok(())).boxed()
                 };
 
+                let admin = config.admin.map(|config| {
+                    Arc::new(AdminServer::new(admin, messages, config.x_tokens))
+                        as Arc<dyn AdminHandler>
+                });
                 let metrics_fut = if let (Some(config), Some(metrics_handle)) =
                     (config.metrics, metrics_handle)
                 {
@@ -209,6 +336,7 @@ fn main() -> anyhow::Result<()> {
                         config,
                         metrics_handle,
                         is_ready,
+                        admin,
                         shutdown.cancelled_owned(),
                     )
                     .await?
@@ -218,9 +346,19 @@ fn main() -> anyhow::Result<()> {
                     ready(Ok(())).boxed()
                 };
 
-                try_join_all(vec![richat_fut, grpc_fut, pubsub_fut, metrics_fut])
-                    .await
-                    .map(|_| ())
+                try_join_all(vec![
+                    richat_fut,
+                    grpc_fut,
+                    jsonrpc_fut,
+                    pubsub_fut,
+                    archive_fut,
+                    kafka_fut,
+                    webhooks_fut,
+                    metrics_fut,
+                    reload_fut,
+                ])
+                .await
+                .map(|_| ())
             })
         }
     })?;
@@ -242,8 +380,15 @@ fn main() -> anyhow::Result<()> {
                     if sources_sighup_reload {
                         info!("SIGHUP received, triggering source reload...");
                         reload_notify.notify_one();
-                    } else {
-                        warn!("SIGHUP received but sources_sighup_reload is disabled");
+                    }
+                    if apps_sighup_reload {
+                        info!("SIGHUP received, triggering apps reload...");
+                        apps_reload_notify.notify_one();
+                    }
+                    if !sources_sighup_reload && !apps_sighup_reload {
+                        warn!(
+                            "SIGHUP received but both sources_sighup_reload and apps.sighup_reload are disabled"
+                        );
                     }
                 }
                 _ => unreachable!(),
@@ -272,6 +417,7 @@ fn main() -> anyhow::Result<()> {
 async fn load_config_for_reloading(
     config_path: &str,
     current_parser: MessageParserEncoding,
+    current_blocks_enabled: bool,
 ) -> anyhow::Result<Config> {
     let config: Config = richat_shared::config::load_from_file(config_path)
         .await
@@ -284,7 +430,28 @@ async fn load_config_for_reloading(
         "MessageParserEncoding cannot be changed (current: {current_parser:?}, new: {new_parser:?})"
     );
 
+    // Blocks can't be enabled or disabled for running apps
+    anyhow::ensure!(
+        config.channel.blocks_enabled() == current_blocks_enabled,
+        "transactions filter of sources cannot be enabled or disabled (blocks are {})",
+        if current_blocks_enabled {
+            "enabled"
+        } else {
+            "disabled"
+        }
+    );
+
     config.channel.ensure_sources_have_reconnect()?;
 
     Ok(config)
 }
+
+async fn load_apps_config_for_reloading(
+    config_path: &str,
+    apps_reload: &AppsReload,
+) -> anyhow::Result<()> {
+    let config: Config = richat_shared::config::load_from_file(config_path)
+        .await
+        .with_context(|| format!("failed to load config from {config_path}"))?;
+    apps_reload.reload(config.apps)
+}
@@ -1,6 +1,9 @@
 use {
     crate::{
-        config::ConfigChannelInner, grpc::server::SubscribeClient, metrics, storage::Storage,
+        config::ConfigChannelInner,
+        grpc::server::SubscribeClient,
+        metrics,
+        storage::{Storage, segments::SegmentReader},
         util::SpawnedThreads,
     },
     ::metrics::{Gauge, counter, gauge},
@@ -9,16 +12,20 @@ use {
     richat_filter::{
         filter::FilteredUpdate,
         message::{
-            Message, MessageAccount, MessageBlock, MessageBlockMeta, MessageEntry,
-            MessageParserEncoding, MessageRef, MessageSlot, MessageTransaction,
+            Message, MessageAccount, MessageBlock, MessageBlockCreatedAt, MessageBlockMeta,
+            MessageEntry, MessageParserEncoding, MessageRef, MessageSlot, MessageTransaction,
         },
     },
     richat_proto::{geyser::SlotStatus, richat::RichatFilter},
     richat_shared::{
         mutex_lock,
-        transports::{RecvError, RecvItem, RecvStream, Subscribe, SubscribeError},
+        transports::{
+            RecvError, RecvItem, RecvStream, Subscribe, SubscribeConnection, SubscribeError,
+            filter::RichatFilterMatcher,
+        },
     },
     smallvec::SmallVec,
+    solana_account::ReadableAccount,
     solana_clock::Slot,
     solana_commitment_config::CommitmentLevel,
     solana_nohash_hasher::IntSet,
@@ -41,7 +48,8 @@ use {
         time::Duration,
     },
     tokio_util::sync::CancellationToken,
-    tracing::debug,
+    tonic::Code,
+    tracing::{debug, warn},
 };
 
 #[derive(Debug, Clone)]
@@ -57,7 +65,7 @@ struct GlobalReplayFromSlotInner {
 }
 
 impl GlobalReplayFromSlot {
-    fn new(value: Option<Slot>, sources_total: usize) -> Self {
+    pub fn new(value: Option<Slot>, sources_total: usize) -> Self {
         Self {
             inner: Arc::new(Mutex::new(GlobalReplayFromSlotInner {
                 value,
@@ -151,6 +159,19 @@ impl ParsedMessage {
         }
     }
 
+    // clippy bug? `Arc` deref is not const
+    #[allow(clippy::missing_const_for_fn)]
+    pub fn created_at(&self) -> MessageBlockCreatedAt {
+        match self {
+            Self::Slot(msg) => msg.created_at(),
+            Self::Account(msg) => msg.created_at(),
+            Self::Transaction(msg) => msg.created_at(),
+            Self::Entry(msg) => msg.created_at(),
+            Self::BlockMeta(msg) => msg.created_at(),
+            Self::Block(msg) => msg.created_at(),
+        }
+    }
+
     pub fn size(&self) -> usize {
         match self {
             Self::Slot(msg) => msg.size(),
@@ -243,6 +264,7 @@ pub struct Messages {
     storage: Option<Storage>,
     storage_max_slots: usize,
     replay_info: Option<Arc<Mutex<BTreeMap<Slot, ReplayInfo>>>>,
+    blocks: bool,
 }
 
 impl Messages {
@@ -250,8 +272,9 @@ impl Messages {
         parser: MessageParserEncoding,
         config: ConfigChannelInner,
         richat: bool,
-        grpc: bool,
-        pubsub: bool,
+        confirmed: bool,
+        finalized: bool,
+        blocks: bool,
         shutdown: CancellationToken,
     ) -> anyhow::Result<(Self, SpawnedThreads)> {
         let storage_max_slots = config
@@ -275,16 +298,15 @@ impl Messages {
         set_optional_slot_gauge(metrics::CHANNEL_STORAGE_LAST_SLOT, None);
         let messages = Self {
             shared_processed: Arc::new(SharedChannel::new(max_messages, richat)),
-            shared_confirmed: (grpc || pubsub)
-                .then(|| Arc::new(SharedChannel::new(max_messages, richat))),
-            shared_finalized: (grpc || pubsub)
-                .then(|| Arc::new(SharedChannel::new(max_messages, richat))),
+            shared_confirmed: confirmed.then(|| Arc::new(SharedChannel::new(max_messages, richat))),
+            shared_finalized: finalized.then(|| Arc::new(SharedChannel::new(max_messages, richat))),
             max_messages,
             max_bytes: config.max_bytes,
             parser,
             storage,
             storage_max_slots,
             replay_info: None,
+            blocks,
         };
         Ok((messages, threads))
     }
@@ -307,22 +329,28 @@ impl Messages {
             gauge!(metrics::CHANNEL_STORAGE_SLOTS_TOTAL).set(replay.len() as f64);
             update_storage_slot_metrics(&replay);
 
-            if let Some(finalized_slot) = slots
+            index = storage.next_index();
+            let finalized_slot = slots
                 .iter()
                 .filter(|(_slot, value)| value.finalized)
                 .map(|(slot, _value)| *slot)
-                .max()
-            {
-                slot_finalized = finalized_slot;
-                let Some(replay_index) = slots.get(&(finalized_slot + 1)).map(|item| item.head)
-                else {
-                    anyhow::bail!("failed to get replay index to load messages");
-                };
-                for chunk_result in storage.read_messages_from_index(replay_index, self.parser) {
+                .max();
+            if finalized_slot.is_none() && !slots.is_empty() {
+                warn!("no finalized slot in existed db, load all stored slots");
+            }
+
+            // messages after the last finalized slot (or all, if nothing finalized) are loaded
+            // for dedup, because sources would replay them again
+            let mut replay_slots = match finalized_slot {
+                Some(finalized_slot) => slots.range(finalized_slot + 1..),
+                None => slots.range(..),
+            };
+            if let Some((first_slot, item)) = replay_slots.next() {
+                for chunk_result in storage.read_messages_from_index(item.head, self.parser) {
                     let mut chunk = chunk_result?;
                     for result in &mut chunk {
-                        let (msg_index, msg) = result?;
-                        if msg.slot() <= finalized_slot {
+                        let (_msg_index, msg) = result?;
+                        if finalized_slot.is_some_and(|slot| msg.slot() <= slot) {
                             continue;
                         }
 
@@ -334,13 +362,13 @@ impl Messages {
                         };
                         let messages = replay.messages.get_or_insert_default();
                         messages.insert(msg.get_id(hasher.build_hasher()));
-                        index = msg_index + 1;
                     }
                 }
+                replay_from_slot = Some(*first_slot);
+            } else if let Some(finalized_slot) = finalized_slot {
                 replay_from_slot = Some(finalized_slot + 1);
-            } else {
-                anyhow::ensure!(slots.is_empty(), "no finalized slot in existed db");
             }
+            slot_finalized = finalized_slot.unwrap_or_default();
         }
 
         let replay = Arc::new(Mutex::new(replay));
@@ -367,10 +395,20 @@ impl Messages {
             hasher,
             replay,
             index,
+            blocks: self.blocks,
         };
         Ok((sender, global_replay_from_slot))
     }
 
+    pub const fn parser(&self) -> MessageParserEncoding {
+        self.parser
+    }
+
+    /// Blocks are not created if transactions are filtered by sources.
+    pub const fn blocks_enabled(&self) -> bool {
+        self.blocks
+    }
+
     pub fn to_receiver(&self) -> ReceiverSync {
         ReceiverSync {
             shared_processed: Arc::clone(&self.shared_processed),
@@ -395,34 +433,55 @@ impl Messages {
         self.get_shared(commitment).tail.load(Ordering::Relaxed)
     }
 
+    /// Position of the first message of the first slot after `slot` in the commitment channel,
+    /// `None` if messages of `slot` are already removed.
+    pub fn get_head_after_slot(&self, commitment: CommitmentLevel, slot: Slot) -> Option<u64> {
+        let shared = self.get_shared(commitment);
+        let slots = shared.slots_lock();
+        if *slots.first_key_value()?.0 > slot {
+            return None;
+        }
+        Some(match slots.range(slot + 1..).next() {
+            Some((_slot, info)) => info.head,
+            None => shared.tail.load(Ordering::Relaxed),
+        })
+    }
+
     pub fn get_current_tail_with_replay(
         &self,
         commitment: CommitmentLevel,
         replay_from_slot: Option<Slot>,
+        replay_to_slot: Option<Slot>,
     ) -> Result<IndexLocation, String> {
+        if let (Some(from_slot), Some(to_slot)) = (replay_from_slot, replay_to_slot) {
+            if to_slot < from_slot {
+                return Err(format!(
+                    "replay `to_slot` ({to_slot}) should be greater than or equal to `from_slot` ({from_slot})"
+                ));
+            }
+        }
+
         if let Some(replay_from_slot) = replay_from_slot {
-            if commitment == CommitmentLevel::Processed {
-                if let Some(index) = self
-                    .get_shared(commitment)
-                    .slots_lock()
-                    .get(&replay_from_slot)
-                    .map(|obj| obj.head)
-                {
-                    Ok(IndexLocation::Memory(index))
-                } else if let Some(index) = self
-                    .replay_info
-                    .as_deref()
-                    .map(mutex_lock)
-                    .and_then(|replay| replay.get(&replay_from_slot).map(|obj| obj.head))
-                {
-                    Ok(IndexLocation::Storage(index))
-                } else {
-                    Err(format!(
-                        "failed to get replay position for slot {replay_from_slot}"
-                    ))
-                }
+            // storage keeps messages only in processed order, confirmed / finalized
+            // messages are re-ordered by the replay worker
+            if let Some(index) = self
+                .get_shared(commitment)
+                .slots_lock()
+                .get(&replay_from_slot)
+                .map(|obj| obj.head)
+            {
+                Ok(IndexLocation::Memory(index))
+            } else if let Some(index) = self
+                .replay_info
+                .as_deref()
+                .map(mutex_lock)
+                .and_then(|replay| replay.get(&replay_from_slot).map(|obj| obj.head))
+            {
+                Ok(IndexLocation::Storage(index))
             } else {
-                Err("replay `from_slot` available only for `processed` commitment".to_owned())
+                Err(format!(
+                    "failed to get replay position for slot {replay_from_slot}"
+                ))
             }
         } else {
             let index = self.get_shared(commitment).tail.load(Ordering::Relaxed);
@@ -449,10 +508,38 @@ impl Messages {
         }
     }
 
+    #[cfg(test)]
+    pub const fn storage(&self) -> Option<&Storage> {
+        self.storage.as_ref()
+    }
+
     pub fn storage_disk_size_poll_config(&self) -> Option<(PathBuf, PathBuf, Duration)> {
         self.storage.as_ref().map(|s| s.disk_size_poll_config())
     }
 
+    /// Read stored messages starting from the head of the oldest slot within the last
+    /// `slots_max` slots, `None` if storage is not configured or empty.
+    pub fn read_storage_last_slots(&self, slots_max: usize) -> Option<SegmentReader> {
+        let storage = self.storage.as_ref()?;
+        let index = storage
+            .read_slots()
+            .values()
+            .rev()
+            .take(slots_max)
+            .map(|item| item.head)
+            .min()?;
+        Some(storage.read_messages_from_index(index, self.parser))
+    }
+
+    /// Returns `true` if storage still has messages after `replay_index`.
+    pub fn is_replay_index_available(&self, replay_index: u64) -> bool {
+        self.replay_info
+            .as_deref()
+            .map(mutex_lock)
+            .and_then(|replay| replay.first_key_value().map(|(_slot, info)| info.head))
+            .is_some_and(|head| head <= replay_index + 1)
+    }
+
     pub fn replay_from_storage(
         &self,
         client: SubscribeClient,
@@ -461,44 +548,149 @@ impl Messages {
         self.storage
             .as_ref()
             .ok_or("storage should exists to replay messages")?
-            .replay(client, Arc::clone(&self.shared_processed), metric_cpu_usage)
+            .replay(client, self.to_receiver(), metric_cpu_usage)
+    }
+}
+
+/// Encodes message for Richat protocol subscribers, `None` if message is disabled by filter.
+pub fn encode_richat_message(
+    filter: &RichatFilterMatcher,
+    message: &ParsedMessage,
+) -> Option<Vec<u8>> {
+    match message {
+        ParsedMessage::Account(msg) if !filter.match_account(msg.pubkey(), msg.owner()) => None,
+        ParsedMessage::Transaction(msg)
+            if !filter.match_transaction(msg.vote(), msg.failed(), msg.account_keys().iter()) =>
+        {
+            None
+        }
+        ParsedMessage::Entry(_) if !filter.match_entry() => None,
+        ParsedMessage::Block(_) => None,
+        _ => Some(
+            FilteredUpdate {
+                filters: SmallVec::new_const(),
+                filtered_update: MessageRef::from(message).into(),
+            }
+            .encode_to_vec(),
+        ),
+    }
+}
+
+/// Position of the message relative to `replay_to_slot` of Richat subscription.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayBound {
+    /// Message should be sent
+    Within,
+    /// Message is after the bound and should be skipped
+    Skip,
+    /// Last message, subscription is finished after it
+    Last,
+    /// Subscription is finished, message should not be sent
+    Finished,
+}
+
+impl ReplayBound {
+    pub fn check(replay_to_slot: Option<Slot>, message: &ParsedMessage) -> Self {
+        let Some(replay_to_slot) = replay_to_slot else {
+            return Self::Within;
+        };
+
+        let slot = message.slot();
+        match message {
+            ParsedMessage::Slot(msg)
+                if msg.status() == SlotStatus::SlotFinalized && slot >= replay_to_slot =>
+            {
+                if slot == replay_to_slot {
+                    Self::Last
+                } else {
+                    Self::Finished
+                }
+            }
+            _ if slot > replay_to_slot => Self::Skip,
+            _ => Self::Within,
+        }
     }
 }
 
 impl Subscribe for Messages {
     fn subscribe(
         &self,
+        _connection: SubscribeConnection<'_>,
         replay_from_slot: Option<Slot>,
+        replay_to_slot: Option<Slot>,
         filter: Option<RichatFilter>,
     ) -> Result<RecvStream, SubscribeError> {
-        let head = if let Some(replay_from_slot) = replay_from_slot {
-            let state = self.shared_processed.slots_lock();
-            match state.get(&replay_from_slot) {
-                Some(obj) => obj.head,
-                None => {
-                    return Err(match state.keys().min().copied() {
-                        Some(first_available) => {
-                            SubscribeError::SlotNotAvailable { first_available }
-                        }
-                        None => SubscribeError::NotInitialized,
-                    });
-                }
+        self.subscribe_richat(replay_from_slot, replay_to_slot, filter.as_ref())
+            .map(StreamExt::boxed)
+    }
+}
+
+impl Messages {
+    pub fn subscribe_richat(
+        &self,
+        replay_from_slot: Option<Slot>,
+        replay_to_slot: Option<Slot>,
+        filter: Option<&RichatFilter>,
+    ) -> Result<ReceiverAsync, SubscribeError> {
+        let filter = RichatFilterMatcher::new(filter)?;
+
+        let (head, replay) = match self.get_current_tail_with_replay(
+            CommitmentLevel::Processed,
+            replay_from_slot,
+            replay_to_slot,
+        ) {
+            Ok(IndexLocation::Storage(head)) => {
+                let storage = self
+                    .storage
+                    .as_ref()
+                    .ok_or(SubscribeError::NotInitialized)?;
+                let client = SubscribeClient::new_richat_replay(
+                    storage.replay_richat_messages_len_max(),
+                    head,
+                    replay_to_slot,
+                    filter.clone(),
+                );
+                let metric_cpu_usage = gauge!(metrics::RICHAT_SUBSCRIBE_REPLAY_DISK_SECONDS_TOTAL);
+                storage
+                    .replay(client.clone(), self.to_receiver(), metric_cpu_usage)
+                    .map_err(|_error| SubscribeError::ReplayQueueFull)?;
+                (0, Some(client))
+            }
+            Ok(IndexLocation::Memory(head)) => (head, None),
+            Ok(IndexLocation::Unknown) | Err(_) => {
+                return Err(match self.get_first_available_slot() {
+                    Some(first_available) => SubscribeError::SlotNotAvailable { first_available },
+                    None => SubscribeError::NotInitialized,
+                });
             }
-        } else {
-            self.shared_processed.tail.load(Ordering::Relaxed)
         };
 
-        let filter = filter.unwrap_or_default();
-
         Ok(ReceiverAsync {
             shared: Arc::clone(&self.shared_processed),
             head,
+            position: Arc::new(AtomicU64::new(u64::MAX)),
+            replay,
+            replay_to_slot,
+            replay_finished: false,
             finished: false,
-            enable_notifications_accounts: !filter.disable_accounts,
-            enable_notifications_transactions: !filter.disable_transactions,
-            enable_notifications_entries: !filter.disable_entries,
-        }
-        .boxed())
+            filter,
+        })
+    }
+
+    /// Number of messages and slots between `head` and the tail of the commitment channel,
+    /// `None` for slots if `head` is not in the memory channel.
+    pub fn get_lag(&self, commitment: CommitmentLevel, head: u64) -> (u64, Option<u64>) {
+        let shared = self.get_shared(commitment);
+        let tail = shared.tail.load(Ordering::Relaxed);
+        let slots = shared.slots_lock();
+        let lag_slots = slots.last_key_value().and_then(|(slot_last, _head)| {
+            slots
+                .iter()
+                .rev()
+                .find(|(_slot, info)| info.head <= head)
+                .map(|(slot, _info)| slot_last - slot)
+        });
+        (tail.saturating_sub(head), lag_slots)
     }
 }
 
@@ -517,6 +709,7 @@ pub struct Sender {
     index: u64,
     hasher: RandomState,
     replay: Arc<Mutex<BTreeMap<Slot, ReplayInfo>>>,
+    blocks: bool,
 }
 
 impl Sender {
@@ -633,7 +826,7 @@ impl Sender {
             let mut slot_init = false;
             let slot_info = self.slots.entry(slot).or_insert_with(|| {
                 slot_init = true;
-                SlotInfo::new(slot, self.index)
+                SlotInfo::new(slot, self.index, self.blocks)
             });
             let slot_index_head = slot_info.index;
             let block_message = slot_info.get_block_message(&message);
@@ -661,13 +854,12 @@ impl Sender {
                         let processed_slots_len = self.processed.shared.slots_lock().len();
                         debug!(
                             "new processed {slot} / {} messages / {} slots / {} bytes",
-                            self.processed.tail - self.processed.head,
+                            self.processed.len(),
                             processed_slots_len,
                             self.processed.bytes_total
                         );
 
-                        gauge!(metrics::CHANNEL_MESSAGES_TOTAL)
-                            .set((self.processed.tail - self.processed.head) as f64);
+                        gauge!(metrics::CHANNEL_MESSAGES_TOTAL).set(self.processed.len() as f64);
                         gauge!(metrics::CHANNEL_SLOTS_TOTAL).set(processed_slots_len as f64);
                         gauge!(metrics::CHANNEL_BYTES_TOTAL).set(self.processed.bytes_total as f64);
                     }
@@ -801,6 +993,11 @@ impl SenderShared {
         }
     }
 
+    // tail is the position of the last message, head is greater by one for empty channel
+    const fn len(&self) -> u64 {
+        self.tail + 1 - self.head
+    }
+
     fn push(&mut self, slot: Slot, message: ParsedMessage, replay_index: Option<u64>) {
         let mut removed_max_slot = None;
 
@@ -867,14 +1064,57 @@ impl SenderShared {
 pub struct ReceiverAsync {
     shared: Arc<SharedChannel>,
     head: u64,
+    // `head` visible to other threads, `u64::MAX` while replaying from storage
+    position: Arc<AtomicU64>,
+    replay: Option<SubscribeClient>,
+    replay_to_slot: Option<Slot>,
+    replay_finished: bool,
     finished: bool,
-    enable_notifications_accounts: bool,
-    enable_notifications_transactions: bool,
-    enable_notifications_entries: bool,
+    filter: RichatFilterMatcher,
+}
+
+impl Drop for ReceiverAsync {
+    fn drop(&mut self) {
+        if let Some(client) = &self.replay {
+            client.state_lock().finished = true;
+        }
+    }
 }
 
 impl ReceiverAsync {
+    pub fn position(&self) -> Arc<AtomicU64> {
+        Arc::clone(&self.position)
+    }
+
     fn recv_ref(&mut self, waker: &Waker) -> Result<Option<RecvItem>, RecvError> {
+        if self.replay_finished {
+            return Err(RecvError::ReplayFinished);
+        }
+
+        // messages replayed from storage, until replay is switched to memory
+        if let Some(client) = &self.replay {
+            client.register_waker(waker);
+            let state = client.state_lock();
+            match client.pop_message() {
+                Some(Ok((_message, data))) => return Ok(Some(Arc::new(data))),
+                Some(Err(status)) if status.code() == Code::Ok => {
+                    return Err(RecvError::ReplayFinished);
+                }
+                Some(Err(status)) => {
+                    warn!("failed to replay messages from storage: {status}");
+                    return Err(RecvError::Closed);
+                }
+                None => match state.head {
+                    IndexLocation::Memory(head) => {
+                        drop(state);
+                        self.head = head;
+                        self.replay = None;
+                    }
+                    _ => return Ok(None),
+                },
+            }
+        }
+
         let tail = self.shared.tail.load(Ordering::Relaxed);
         while self.head <= tail {
             let idx = self.shared.get_idx(self.head);
@@ -885,22 +1125,16 @@ impl ReceiverAsync {
             self.head = self.head.wrapping_add(1);
 
             let item = item.data.as_ref().ok_or(RecvError::Lagged)?;
-            match item {
-                ParsedMessage::Account(_) if !self.enable_notifications_accounts => continue,
-                ParsedMessage::Transaction(_) if !self.enable_notifications_transactions => {
-                    continue;
-                }
-                ParsedMessage::Entry(_) if !self.enable_notifications_entries => continue,
-                ParsedMessage::Block(_) => continue,
-                _ => {}
+            match ReplayBound::check(self.replay_to_slot, item) {
+                ReplayBound::Within => {}
+                ReplayBound::Skip => continue,
+                ReplayBound::Last => self.replay_finished = true,
+                ReplayBound::Finished => return Err(RecvError::ReplayFinished),
             }
 
-            let data = FilteredUpdate {
-                filters: SmallVec::new_const(),
-                filtered_update: MessageRef::from(item).into(),
+            if let Some(data) = encode_richat_message(&self.filter, item) {
+                return Ok(Some(Arc::new(data)));
             }
-            .encode_to_vec();
-            return Ok(Some(Arc::new(data)));
         }
 
         if let Some(mut wakers) = self.shared.wakers_lock() {
@@ -919,7 +1153,11 @@ impl Stream for ReceiverAsync {
             return Poll::Ready(None);
         }
 
-        match me.recv_ref(cx.waker()) {
+        let item = me.recv_ref(cx.waker());
+        if me.replay.is_none() {
+            me.position.store(me.head, Ordering::Relaxed);
+        }
+        match item {
             Ok(Some(value)) => Poll::Ready(Some(Ok(value))),
             Ok(None) => Poll::Pending,
             Err(error) => {
@@ -943,6 +1181,17 @@ impl ReceiverSync {
         commitment: CommitmentLevel,
         head: u64,
     ) -> Result<Option<ParsedMessage>, RecvError> {
+        self.try_recv_indexed(commitment, head)
+            .map(|item| item.map(|(message, _replay_index)| message))
+    }
+
+    /// Same as [`Self::try_recv`] but also returns storage replay index of the message,
+    /// `u64::MAX` if message is not stored.
+    pub fn try_recv_indexed(
+        &self,
+        commitment: CommitmentLevel,
+        head: u64,
+    ) -> Result<Option<(ParsedMessage, u64)>, RecvError> {
         let Some(shared) = (match commitment {
             CommitmentLevel::Processed => Some(&self.shared_processed),
             CommitmentLevel::Confirmed => self.shared_confirmed.as_ref(),
@@ -959,11 +1208,46 @@ impl ReceiverSync {
                 return Err(RecvError::Lagged);
             }
 
-            return item.data.clone().ok_or(RecvError::Lagged).map(Some);
+            let replay_index = item.replay_index;
+            return item
+                .data
+                .clone()
+                .ok_or(RecvError::Lagged)
+                .map(|message| Some((message, replay_index)));
         }
 
         Ok(None)
     }
+
+    pub fn get_head_by_replay_index(&self, replay_index: u64) -> Option<u64> {
+        self.shared_processed.get_head_by_replay_index(replay_index)
+    }
+
+    /// Position after the slot status message that opened `slot` in the commitment channel.
+    pub fn get_head_after_slot_status(
+        &self,
+        commitment: CommitmentLevel,
+        slot: Slot,
+        status: SlotStatus,
+    ) -> Option<u64> {
+        let shared = match commitment {
+            CommitmentLevel::Processed => Some(&self.shared_processed),
+            CommitmentLevel::Confirmed => self.shared_confirmed.as_ref(),
+            CommitmentLevel::Finalized => self.shared_finalized.as_ref(),
+        }?;
+
+        let head = shared.slots_lock().get(&slot)?.head;
+        let item = shared.buffer_idx(shared.get_idx(head));
+        if item.pos != head {
+            return None;
+        }
+        match item.data.as_ref()? {
+            ParsedMessage::Slot(msg) if msg.slot() == slot && msg.status() == status => {
+                Some(head + 1)
+            }
+            _ => None,
+        }
+    }
 }
 
 pub struct SharedChannel {
@@ -1116,6 +1400,7 @@ struct SlotHead {
 #[derive(Debug, Default)]
 struct SlotInfo {
     slot: Slot,
+    blocks: bool,
     block_created: bool,
     failed: bool,
     landed: bool,
@@ -1129,7 +1414,7 @@ struct SlotInfo {
 
 impl Drop for SlotInfo {
     fn drop(&mut self) {
-        if !self.block_created && !self.failed && self.landed {
+        if self.blocks && !self.block_created && !self.failed && self.landed {
             let mut reasons = vec![];
             if let Some(block_meta) = &self.block_meta {
                 let executed_transaction_count = block_meta.executed_transaction_count() as usize;
@@ -1156,9 +1441,10 @@ impl Drop for SlotInfo {
 }
 
 impl SlotInfo {
-    fn new(slot: Slot, index: u64) -> Self {
+    fn new(slot: Slot, index: u64, blocks: bool) -> Self {
         Self {
             slot,
+            blocks,
             block_created: false,
             failed: false,
             landed: false,
@@ -1247,6 +1533,9 @@ impl SlotInfo {
         }
 
         //  attempt to create Block
+        if !self.blocks {
+            return None;
+        }
         if let Some(block_meta) = &self.block_meta {
             if block_meta.executed_transaction_count() as usize == self.transactions_count
                 && block_meta.entries_count() as usize == self.entries_count
@@ -1313,10 +1602,91 @@ impl ReplayInfo {
 #[cfg(test)]
 mod tests {
     use {
-        super::{optional_slot_gauge_value, update_storage_slot_metrics},
-        std::collections::BTreeMap,
+        super::{
+            Messages, ParsedMessage, ReplayBound, SlotInfo, encode_richat_message,
+            optional_slot_gauge_value, update_storage_slot_metrics,
+        },
+        crate::config::ConfigChannelInner,
+        futures::{FutureExt, StreamExt},
+        prost::Message as _,
+        richat_filter::message::{Message, MessageParserEncoding},
+        richat_proto::{
+            geyser::{
+                SlotStatus, SubscribeUpdate, SubscribeUpdateAccount, SubscribeUpdateAccountInfo,
+                SubscribeUpdateBlockMeta, SubscribeUpdateSlot, subscribe_update::UpdateOneof,
+            },
+            richat::RichatFilter,
+        },
+        richat_shared::transports::{RecvError, filter::RichatFilterMatcher},
+        solana_clock::Slot,
+        solana_pubkey::Pubkey,
+        std::{borrow::Cow, collections::BTreeMap},
+        tokio_util::sync::CancellationToken,
     };
 
+    fn parse(update_oneof: UpdateOneof) -> Message {
+        let data = SubscribeUpdate {
+            filters: vec![],
+            update_oneof: Some(update_oneof),
+            created_at: Some(Default::default()),
+        }
+        .encode_to_vec();
+        Message::parse(Cow::Owned(data), MessageParserEncoding::Prost).expect("valid message")
+    }
+
+    fn account_at(slot: Slot, pubkey: Pubkey, owner: Pubkey) -> Message {
+        parse(UpdateOneof::Account(SubscribeUpdateAccount {
+            account: Some(SubscribeUpdateAccountInfo {
+                pubkey: pubkey.to_bytes().to_vec(),
+                owner: owner.to_bytes().to_vec(),
+                ..Default::default()
+            }),
+            slot,
+            is_startup: false,
+        }))
+    }
+
+    fn account(pubkey: Pubkey, owner: Pubkey) -> ParsedMessage {
+        account_at(1, pubkey, owner).into()
+    }
+
+    fn slot(slot: Slot, status: SlotStatus) -> Message {
+        parse(UpdateOneof::Slot(SubscribeUpdateSlot {
+            slot,
+            parent: slot.checked_sub(1),
+            status: status as i32,
+            dead_error: None,
+        }))
+    }
+
+    #[test]
+    fn richat_filter_accounts() {
+        let pubkey = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let other = Pubkey::new_unique();
+
+        let filter = RichatFilterMatcher::new(None).unwrap();
+        assert!(encode_richat_message(&filter, &account(pubkey, owner)).is_some());
+
+        let filter = RichatFilterMatcher::new(Some(&RichatFilter {
+            accounts_pubkey: vec![pubkey.to_bytes().to_vec()],
+            accounts_owner: vec![owner.to_bytes().to_vec()],
+            ..Default::default()
+        }))
+        .unwrap();
+        assert!(encode_richat_message(&filter, &account(pubkey, owner)).is_some());
+        assert!(encode_richat_message(&filter, &account(pubkey, other)).is_none());
+        assert!(encode_richat_message(&filter, &account(other, owner)).is_none());
+
+        assert!(
+            RichatFilterMatcher::new(Some(&RichatFilter {
+                accounts_owner: vec![vec![0; 31]],
+                ..Default::default()
+            }))
+            .is_err()
+        );
+    }
+
     #[test]
     fn storage_slot_metrics_report_empty_as_negative_one() {
         update_storage_slot_metrics(&BTreeMap::new());
@@ -1324,6 +1694,145 @@ mod tests {
         assert_eq!(optional_slot_gauge_value(None), -1.0);
         assert_eq!(optional_slot_gauge_value(Some(42)), 42.0);
     }
+
+    #[test]
+    fn slot_info_blocks_disabled() {
+        let block_meta =
+            ParsedMessage::from(parse(UpdateOneof::BlockMeta(SubscribeUpdateBlockMeta {
+                slot: 1,
+                block_height: Some(Default::default()),
+                executed_transaction_count: 0,
+                entries_count: 0,
+                ..Default::default()
+            })));
+        let landed = ParsedMessage::from(slot(1, SlotStatus::SlotConfirmed));
+        for blocks in [true, false] {
+            let mut slot_info = SlotInfo::new(1, 0, blocks);
+            assert!(slot_info.get_block_message(&landed).is_none());
+            let block = slot_info.get_block_message(&block_meta);
+            assert_eq!(block.is_some(), blocks);
+            // messages are kept for confirmed / finalized channels
+            assert_eq!(slot_info.get_messages_cloned().count(), 1 + blocks as usize);
+        }
+    }
+
+    #[test]
+    fn replay_bound_check() {
+        let pubkey = Pubkey::new_unique();
+        for (replay_to_slot, message, bound) in [
+            (
+                None,
+                slot(10, SlotStatus::SlotFinalized),
+                ReplayBound::Within,
+            ),
+            (Some(5), account_at(5, pubkey, pubkey), ReplayBound::Within),
+            (
+                Some(5),
+                slot(5, SlotStatus::SlotConfirmed),
+                ReplayBound::Within,
+            ),
+            (
+                Some(5),
+                slot(4, SlotStatus::SlotFinalized),
+                ReplayBound::Within,
+            ),
+            (Some(5), account_at(6, pubkey, pubkey), ReplayBound::Skip),
+            (
+                Some(5),
+                slot(6, SlotStatus::SlotProcessed),
+                ReplayBound::Skip,
+            ),
+            (
+                Some(5),
+                slot(5, SlotStatus::SlotFinalized),
+                ReplayBound::Last,
+            ),
+            // bound slot is not finalized, e.g. skipped
+            (
+                Some(5),
+                slot(6, SlotStatus::SlotFinalized),
+                ReplayBound::Finished,
+            ),
+        ] {
+            let message = ParsedMessage::from(message);
+            assert_eq!(
+                ReplayBound::check(replay_to_slot, &message),
+                bound,
+                "{replay_to_slot:?} {} {}",
+                message.as_str_type(),
+                message.slot()
+            );
+        }
+    }
+
+    #[test]
+    fn replay_bound_stream() {
+        let shutdown = CancellationToken::new();
+        let (mut messages, _threads) = Messages::new(
+            MessageParserEncoding::Prost,
+            ConfigChannelInner {
+                max_messages: 1024,
+                max_bytes: 16 * 1024 * 1024,
+                storage: None,
+            },
+            true,
+            false,
+            false,
+            true,
+            shutdown.clone(),
+        )
+        .unwrap();
+        let (mut sender, _replay_from_slot) = messages.to_sender(1).unwrap();
+
+        let pubkey = Pubkey::new_unique();
+        for message in [
+            slot(1, SlotStatus::SlotProcessed),
+            account_at(1, pubkey, pubkey),
+            slot(2, SlotStatus::SlotProcessed),
+            slot(3, SlotStatus::SlotProcessed),
+            account_at(3, pubkey, pubkey),
+            slot(1, SlotStatus::SlotFinalized),
+            slot(2, SlotStatus::SlotFinalized),
+            slot(3, SlotStatus::SlotFinalized),
+        ] {
+            sender.push(false, "test", message);
+        }
+        let mut stream = messages.subscribe_richat(Some(1), Some(2), None).unwrap();
+
+        let mut received = vec![];
+        let error = loop {
+            match stream.next().now_or_never() {
+                Some(Some(Ok(data))) => {
+                    let update = SubscribeUpdate::decode(data.as_slice()).unwrap();
+                    received.push(match update.update_oneof {
+                        Some(UpdateOneof::Slot(msg)) => (msg.slot, Some(msg.status)),
+                        Some(UpdateOneof::Account(msg)) => (msg.slot, None),
+                        update => panic!("unexpected update: {update:?}"),
+                    });
+                }
+                Some(Some(Err(error))) => break error,
+                item => panic!("stream should be finished: {item:?}"),
+            }
+        };
+        assert_eq!(error, RecvError::ReplayFinished);
+        assert!(matches!(stream.next().now_or_never(), Some(None)));
+
+        // messages after `replay_to_slot` are skipped, stream ends on its finalization
+        let finalized = Some(SlotStatus::SlotFinalized as i32);
+        assert_eq!(
+            received,
+            [
+                (1, Some(SlotStatus::SlotProcessed as i32)),
+                (1, None),
+                (2, Some(SlotStatus::SlotProcessed as i32)),
+                (1, finalized),
+                (2, finalized),
+            ]
+        );
+
+        drop(sender);
+        shutdown.cancel();
+    }
 }
 
 #[derive(Debug)]
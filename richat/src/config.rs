@@ -1,15 +1,19 @@
 use {
     crate::{
-        grpc::config::ConfigAppsGrpc, pubsub::config::ConfigAppsPubsub,
-        richat::config::ConfigAppsRichat, storage::segments::ChunkCompression,
+        archive::config::ConfigAppsArchive, grpc::config::ConfigAppsGrpc,
+        jsonrpc::config::ConfigAppsJsonrpc, kafka::config::ConfigAppsKafka,
+        pubsub::config::ConfigAppsPubsub, richat::config::ConfigAppsRichat,
+        storage::segments::ChunkCompression, webhooks::config::ConfigAppsWebhooks,
     },
     futures::future::{TryFutureExt, ready, try_join_all},
-    richat_client::{grpc::ConfigGrpcClient, quic::ConfigQuicClient},
-    richat_filter::message::MessageParserEncoding,
+    richat_client::{file::ConfigFileClient, grpc::ConfigGrpcClient, quic::ConfigQuicClient},
+    richat_filter::{config::ConfigLimits as ConfigFilterLimits, message::MessageParserEncoding},
     richat_metrics::ConfigMetrics,
     richat_shared::{
         config::{
-            ConfigTokio, deserialize_affinity, deserialize_humansize_usize, deserialize_num_str,
+            ConfigTokio, deserialize_affinity, deserialize_humansize_usize,
+            deserialize_maybe_humansize_usize, deserialize_maybe_num_str, deserialize_num_str,
+            deserialize_pubkey_vec, deserialize_x_tokens_set,
         },
         tracing::ConfigTracing,
     },
@@ -17,6 +21,7 @@ use {
         Deserialize,
         de::{self, Deserializer},
     },
+    solana_pubkey::Pubkey,
     std::{collections::HashSet, path::PathBuf, thread::Builder},
     tokio::time::{Duration, sleep},
     tokio_util::sync::CancellationToken,
@@ -29,11 +34,37 @@ pub struct Config {
     pub logs: ConfigTracing,
     #[serde(default)]
     pub metrics: Option<ConfigMetrics>,
+    /// Admin API on metrics server
+    #[serde(default)]
+    pub admin: Option<ConfigAdmin>,
     pub channel: ConfigChannel,
     #[serde(default)]
     pub apps: ConfigApps,
 }
 
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigAdmin {
+    /// Required `x-token` header values, at least one token should be set
+    #[serde(deserialize_with = "ConfigAdmin::deserialize_x_tokens")]
+    pub x_tokens: HashSet<Vec<u8>>,
+}
+
+impl ConfigAdmin {
+    fn deserialize_x_tokens<'de, D>(deserializer: D) -> Result<HashSet<Vec<u8>>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let x_tokens = deserialize_x_tokens_set(deserializer)?;
+        if x_tokens.is_empty() {
+            return Err(de::Error::custom(
+                "at least one x-token is required for admin API",
+            ));
+        }
+        Ok(x_tokens)
+    }
+}
+
 #[derive(Debug, Clone, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct ConfigChannel {
@@ -45,6 +76,8 @@ pub struct ConfigChannel {
     #[serde(deserialize_with = "ConfigChannel::deserialize_sources")]
     pub sources: Vec<ConfigChannelSource>,
     #[serde(default)]
+    pub sources_policy: ConfigChannelSourcesPolicy,
+    #[serde(default)]
     pub config: ConfigChannelInner,
 }
 
@@ -54,16 +87,26 @@ impl ConfigChannel {
             return match source {
                 ConfigChannelSource::Quic { general, .. } => general.parser,
                 ConfigChannelSource::Grpc { general, .. } => general.parser,
+                ConfigChannelSource::File { general, .. } => general.parser,
             };
         }
         unreachable!("deserialize should check sources")
     }
 
+    /// Blocks are created only if all transactions are received from sources.
+    pub fn blocks_enabled(&self) -> bool {
+        !self
+            .sources
+            .iter()
+            .any(|source| source.filter().is_transactions_filtered())
+    }
+
     pub fn ensure_sources_have_reconnect(&self) -> anyhow::Result<()> {
         for source in &self.sources {
             let has_reconnect = match source {
                 ConfigChannelSource::Quic { general, .. } => general.reconnect.is_some(),
                 ConfigChannelSource::Grpc { general, .. } => general.reconnect.is_some(),
+                ConfigChannelSource::File { general, .. } => general.reconnect.is_some(),
             };
             anyhow::ensure!(
                 has_reconnect,
@@ -95,6 +138,10 @@ impl ConfigChannel {
                     names.insert(&general.name);
                     parsers.insert(general.parser);
                 }
+                ConfigChannelSource::File { general, .. } => {
+                    names.insert(&general.name);
+                    parsers.insert(general.parser);
+                }
             }
         }
 
@@ -132,6 +179,13 @@ pub enum ConfigChannelSource {
         #[serde(flatten)]
         config: ConfigGrpcClient,
     },
+    #[serde(rename = "file")]
+    File {
+        #[serde(flatten)]
+        general: ConfigChannelSourceGeneral,
+        #[serde(flatten)]
+        config: ConfigFileClient,
+    },
 }
 
 impl ConfigChannelSource {
@@ -140,6 +194,7 @@ impl ConfigChannelSource {
         match self {
             Self::Quic { general, .. } => &general.name,
             Self::Grpc { general, .. } => &general.name,
+            Self::File { general, .. } => &general.name,
         }
     }
 
@@ -147,6 +202,15 @@ impl ConfigChannelSource {
         match self {
             Self::Quic { general, .. } => general.exclude_on_finish,
             Self::Grpc { general, .. } => general.exclude_on_finish,
+            Self::File { general, .. } => general.exclude_on_finish,
+        }
+    }
+
+    pub const fn filter(&self) -> &ConfigChannelSourceFilter {
+        match self {
+            Self::Quic { general, .. } => &general.filter,
+            Self::Grpc { general, .. } => &general.filter,
+            Self::File { general, .. } => &general.filter,
         }
     }
 }
@@ -164,6 +228,8 @@ pub struct ConfigChannelSourceGeneral {
     pub reconnect: Option<ConfigChannelSourceReconnect>,
     #[serde(default = "ConfigChannelSourceGeneral::default_channel_size")]
     pub channel_size: usize,
+    #[serde(default)]
+    pub filter: ConfigChannelSourceFilter,
 }
 
 impl ConfigChannelSourceGeneral {
@@ -172,6 +238,28 @@ impl ConfigChannelSourceGeneral {
     }
 }
 
+/// Server-side filter of the source, not matched accounts and transactions are not sent.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct ConfigChannelSourceFilter {
+    #[serde(deserialize_with = "deserialize_pubkey_vec")]
+    pub accounts_pubkey: Vec<Pubkey>,
+    #[serde(deserialize_with = "deserialize_pubkey_vec")]
+    pub accounts_owner: Vec<Pubkey>,
+    #[serde(deserialize_with = "deserialize_pubkey_vec")]
+    pub transactions_account_include: Vec<Pubkey>,
+    pub transactions_exclude_vote: bool,
+    pub transactions_exclude_failed: bool,
+}
+
+impl ConfigChannelSourceFilter {
+    pub fn is_transactions_filtered(&self) -> bool {
+        !self.transactions_account_include.is_empty()
+            || self.transactions_exclude_vote
+            || self.transactions_exclude_failed
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Deserialize)]
 pub struct ConfigChannelSourceReconnect {
     #[serde(
@@ -202,6 +290,50 @@ impl ConfigChannelSourceReconnect {
     }
 }
 
+/// Selection of sources which messages are pushed to the channel, other sources are
+/// kept connected as backups and only used for health stats.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct ConfigChannelSourcesPolicy {
+    pub mode: ConfigChannelSourcesPolicyMode,
+    /// Number of active sources for `fastest-n`
+    #[serde(deserialize_with = "deserialize_num_str")]
+    pub fastest_n: usize,
+    /// Number of latest slots used to calculate lag and gaps
+    #[serde(deserialize_with = "deserialize_num_str")]
+    pub window_slots: usize,
+    /// Max average lag relative to the fastest source for a healthy source
+    #[serde(with = "humantime_serde")]
+    pub lag_max: Duration,
+    /// Source without messages for this time is not healthy
+    #[serde(with = "humantime_serde")]
+    pub stall_timeout: Duration,
+}
+
+impl Default for ConfigChannelSourcesPolicy {
+    fn default() -> Self {
+        Self {
+            mode: ConfigChannelSourcesPolicyMode::default(),
+            fastest_n: 1,
+            window_slots: 64,
+            lag_max: Duration::from_millis(200),
+            stall_timeout: Duration::from_secs(2),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub enum ConfigChannelSourcesPolicyMode {
+    /// Push messages from all sources
+    #[default]
+    All,
+    /// Push messages from the first healthy source in config order
+    PrimaryWithFailover,
+    /// Push messages from `fastest_n` healthy sources with the lowest lag
+    FastestN,
+}
+
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
 #[serde(deny_unknown_fields, rename_all = "snake_case")]
 pub enum ConfigGrpcClientSource {
@@ -261,10 +393,16 @@ pub struct ConfigStorage {
         deserialize_with = "deserialize_humansize_usize"
     )]
     pub chunk_target_size: usize,
-    /// Optional compression for chunk payloads (e.g. "zstd-3").
+    /// Optional compression for chunk payloads ("lz4", "zstd-3" or "zstd-dict-3").
     /// Omit or set to null to disable compression.
     #[serde(default, deserialize_with = "deserialize_chunk_compression")]
     pub chunk_compression: Option<ChunkCompression>,
+    /// Max size of the zstd dictionary trained for "zstd-dict-<level>" compression.
+    #[serde(
+        default = "ConfigStorage::default_chunk_compression_dict_size",
+        deserialize_with = "deserialize_humansize_usize"
+    )]
+    pub chunk_compression_dict_size: usize,
     /// Maximum number of in-flight replay-from-disk requests.
     #[serde(
         default = "ConfigStorage::default_replay_channel_capacity",
@@ -286,6 +424,19 @@ pub struct ConfigStorage {
         deserialize_with = "deserialize_num_str"
     )]
     pub replay_decode_per_tick: usize,
+    /// Max size of replayed messages buffered for each Richat protocol subscription.
+    #[serde(
+        default = "ConfigStorage::default_replay_richat_messages_len_max",
+        deserialize_with = "deserialize_humansize_usize"
+    )]
+    pub replay_richat_messages_len_max: usize,
+    /// Max size of messages held per confirmed / finalized replay until their slot
+    /// reaches the commitment, replay fails once it's exceeded.
+    #[serde(
+        default = "ConfigStorage::default_replay_commitment_buffer_max",
+        deserialize_with = "deserialize_humansize_usize"
+    )]
+    pub replay_commitment_buffer_max: usize,
     /// Number of serialization and compression worker threads.
     #[serde(
         default = "ConfigStorage::default_compressor_threads",
@@ -342,6 +493,10 @@ impl ConfigStorage {
         4 * 1024 * 1024
     }
 
+    const fn default_chunk_compression_dict_size() -> usize {
+        112 * 1024
+    }
+
     const fn default_replay_channel_capacity() -> usize {
         1024
     }
@@ -354,6 +509,14 @@ impl ConfigStorage {
         256
     }
 
+    const fn default_replay_richat_messages_len_max() -> usize {
+        16 * 1024 * 1024
+    }
+
+    const fn default_replay_commitment_buffer_max() -> usize {
+        1024 * 1024 * 1024
+    }
+
     const fn default_compressor_threads() -> usize {
         1
     }
@@ -384,8 +547,108 @@ pub struct ConfigApps {
     pub richat: Option<ConfigAppsRichat>,
     /// gRPC app (fully compatible with Yellowstone Dragon's Mouth)
     pub grpc: Option<ConfigAppsGrpc>,
+    /// HTTP JSON-RPC app (subset of Solana RPC methods)
+    pub jsonrpc: Option<ConfigAppsJsonrpc>,
     /// WebSocket app (fully compatible with Solana PubSub)
     pub pubsub: Option<ConfigAppsPubsub>,
+    /// Parquet archive of finalized slots
+    pub archive: Option<ConfigAppsArchive>,
+    /// Kafka producer of filtered messages
+    pub kafka: Option<ConfigAppsKafka>,
+    /// HTTP webhooks of filtered messages
+    pub webhooks: Option<ConfigAppsWebhooks>,
+    /// Per x-token limits shared by all apps
+    #[serde(deserialize_with = "ConfigApps::deserialize_tenants")]
+    pub tenants: Vec<ConfigAppsTenant>,
+    /// Reload x_tokens, tenants, filter limits and TLS certificates on SIGHUP
+    pub sighup_reload: bool,
+}
+
+impl ConfigApps {
+    fn deserialize_tenants<'de, D>(deserializer: D) -> Result<Vec<ConfigAppsTenant>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let tenants = Vec::<ConfigAppsTenant>::deserialize(deserializer)?;
+
+        let mut names = HashSet::new();
+        let mut x_tokens = HashSet::new();
+        for tenant in tenants.iter() {
+            if !names.insert(&tenant.name) {
+                return Err(de::Error::custom(format!(
+                    "tenant name should be unique: {}",
+                    tenant.name
+                )));
+            }
+            if tenant.x_tokens.is_empty() {
+                return Err(de::Error::custom(format!(
+                    "tenant {} should have at least one x-token",
+                    tenant.name
+                )));
+            }
+            for x_token in tenant.x_tokens.iter() {
+                if !x_tokens.insert(x_token) {
+                    return Err(de::Error::custom(format!(
+                        "x-token of tenant {} is used by another tenant",
+                        tenant.name
+                    )));
+                }
+            }
+        }
+
+        Ok(tenants)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigAppsTenant {
+    /// Used as metrics label instead of `x-subscription-id`
+    pub name: String,
+    #[serde(deserialize_with = "deserialize_x_tokens_set")]
+    pub x_tokens: HashSet<Vec<u8>>,
+    #[serde(default = "ConfigAppsTenant::default_apps")]
+    pub apps: HashSet<ConfigAppsTenantApp>,
+    /// gRPC filter limits, `apps.grpc.filter_limits` is used if not set
+    #[serde(default)]
+    pub filter_limits: Option<ConfigFilterLimits>,
+    /// Max number of concurrent subscriptions (gRPC and Richat streams, PubSub connections)
+    #[serde(default, deserialize_with = "deserialize_maybe_num_str")]
+    pub subscriptions_max: Option<usize>,
+    /// Max sent bytes per second for all subscriptions of the tenant, messages are not
+    /// skipped: throttled stream falls behind and is disconnected as lagged
+    #[serde(default, deserialize_with = "deserialize_maybe_humansize_usize")]
+    pub bytes_per_sec_max: Option<usize>,
+}
+
+impl ConfigAppsTenant {
+    fn default_apps() -> HashSet<ConfigAppsTenantApp> {
+        [
+            ConfigAppsTenantApp::Grpc,
+            ConfigAppsTenantApp::Pubsub,
+            ConfigAppsTenantApp::Richat,
+        ]
+        .into_iter()
+        .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "snake_case")]
+pub enum ConfigAppsTenantApp {
+    Grpc,
+    Pubsub,
+    Richat,
+}
+
+impl ConfigAppsTenantApp {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Grpc => "grpc",
+            Self::Pubsub => "pubsub",
+            Self::Richat => "richat",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -474,18 +737,22 @@ where
     let Some(s) = Option::<String>::deserialize(deserializer)? else {
         return Ok(None);
     };
+    if s == "lz4" {
+        return Ok(Some(ChunkCompression::Lz4));
+    }
     let Some((algo, level)) = s.rsplit_once('-') else {
         return Err(de::Error::custom(format!(
-            "invalid chunk_compression format: expected \"<algo>-<level>\" (e.g. \"zstd-3\"), got \"{s}\""
+            "invalid chunk_compression format: expected \"lz4\" or \"<algo>-<level>\" (e.g. \"zstd-3\"), got \"{s}\""
         )));
     };
+    let parse_level = || {
+        level
+            .parse::<i32>()
+            .map_err(|_| de::Error::custom(format!("invalid zstd compression level: \"{level}\"")))
+    };
     match algo {
-        "zstd" => {
-            let level: i32 = level.parse().map_err(|_| {
-                de::Error::custom(format!("invalid zstd compression level: \"{level}\""))
-            })?;
-            Ok(Some(ChunkCompression::Zstd(level)))
-        }
+        "zstd" => Ok(Some(ChunkCompression::Zstd(parse_level()?))),
+        "zstd-dict" => Ok(Some(ChunkCompression::ZstdDict(parse_level()?))),
         other => Err(de::Error::custom(format!(
             "unsupported compression algorithm: \"{other}\""
         ))),
@@ -0,0 +1,899 @@
+use {
+    crate::{
+        channel::{Messages, ParsedMessage},
+        config::ConfigAppsWorkers,
+        grpc::config::{ConfigAppsGrpcAccountsCache, ConfigAppsGrpcAccountsCacheSeed},
+        metrics,
+    },
+    ::metrics::{counter, gauge},
+    anyhow::Context,
+    foldhash::quality::RandomState,
+    futures::future::{TryFutureExt, try_join_all},
+    prost::Message as _,
+    reqwest::{Client, header::CONTENT_TYPE},
+    richat_filter::{
+        filter::{Filter, FilteredUpdate, FilteredUpdateFilters},
+        message::{Message, MessageAccount, MessageParserEncoding, MessageRef},
+    },
+    richat_proto::geyser::{
+        SlotStatus, SubscribeUpdate, SubscribeUpdateAccount, SubscribeUpdateAccountInfo,
+        subscribe_update::UpdateOneof,
+    },
+    richat_shared::transports::RecvError,
+    rocksdb::{
+        ColumnFamily, ColumnFamilyDescriptor, DB, IteratorMode, Options, Snapshot, WriteBatch,
+    },
+    serde::Deserialize,
+    solana_account::{Account, ReadableAccount},
+    solana_clock::Slot,
+    solana_commitment_config::CommitmentLevel,
+    solana_pubkey::Pubkey,
+    solana_rpc_client_api::response::{Response as RpcResponse, RpcKeyedAccount},
+    std::{
+        borrow::Cow,
+        collections::{HashMap, HashSet},
+        future::Future,
+        path::Path,
+        str::FromStr,
+        sync::{Arc, RwLock},
+        thread::sleep,
+        time::Duration,
+    },
+    tokio::runtime::Runtime,
+    tokio_util::sync::CancellationToken,
+    tonic::Status,
+    tracing::{error, info},
+};
+
+const fn commitment_str(commitment: CommitmentLevel) -> &'static str {
+    match commitment {
+        CommitmentLevel::Processed => "processed",
+        CommitmentLevel::Confirmed => "confirmed",
+        CommitmentLevel::Finalized => "finalized",
+    }
+}
+
+/// Stored accounts are shared with snapshots, updates do not wait for snapshot scans.
+#[derive(Debug, Clone)]
+enum AccountsStore {
+    // copied on write while a snapshot is scanned
+    Memory(Arc<HashMap<Pubkey, Arc<MessageAccount>, RandomState>>),
+    Rocksdb(Arc<DB>),
+}
+
+impl AccountsStore {
+    const ROCKSDB_CF: &str = "accounts";
+
+    fn open(path: Option<&Path>) -> anyhow::Result<Self> {
+        let Some(path) = path else {
+            return Ok(Self::Memory(Arc::default()));
+        };
+
+        // accounts missed while richat was stopped would be stale, always start from scratch
+        if path.exists() {
+            std::fs::remove_dir_all(path)
+                .with_context(|| format!("failed to remove accounts cache at {path:?}"))?;
+        }
+        std::fs::create_dir_all(path)
+            .with_context(|| format!("failed to create accounts cache path: {path:?}"))?;
+
+        let mut options = Options::default();
+        options.create_if_missing(true);
+        options.create_missing_column_families(true);
+        let cf_descriptors = [ColumnFamilyDescriptor::new(
+            Self::ROCKSDB_CF,
+            Options::default(),
+        )];
+        DB::open_cf_descriptors(&options, path, cf_descriptors)
+            .map(|db| Self::Rocksdb(Arc::new(db)))
+            .with_context(|| format!("failed to open accounts cache rocksdb at {path:?}"))
+    }
+
+    fn cf_handle(db: &DB) -> &ColumnFamily {
+        db.cf_handle(Self::ROCKSDB_CF)
+            .expect("should never get an unknown column")
+    }
+
+    /// Accounts with zero lamports or not owned by `owners` are removed.
+    fn apply<'a>(
+        &mut self,
+        accounts: impl Iterator<Item = &'a Arc<MessageAccount>>,
+        owners: &HashSet<Pubkey>,
+    ) -> anyhow::Result<()> {
+        match self {
+            Self::Memory(store) => {
+                let store = Arc::make_mut(store);
+                for account in accounts {
+                    if account.lamports() == 0 || !owners.contains(account.owner()) {
+                        store.remove(account.pubkey());
+                    } else {
+                        store.insert(*account.pubkey(), Arc::clone(account));
+                    }
+                }
+            }
+            Self::Rocksdb(db) => {
+                let cf = Self::cf_handle(db);
+                let mut batch = WriteBatch::new();
+                let mut buf = Vec::with_capacity(1024);
+                for account in accounts {
+                    if account.lamports() == 0 || !owners.contains(account.owner()) {
+                        // removal of not stored key is cheap, keys are not tracked in memory
+                        batch.delete_cf(cf, account.pubkey().as_ref());
+                    } else {
+                        buf.clear();
+                        FilteredUpdate {
+                            filters: FilteredUpdateFilters::new(),
+                            filtered_update: MessageRef::Account(account).into(),
+                        }
+                        .encode(&mut buf);
+                        batch.put_cf(cf, account.pubkey().as_ref(), &buf);
+                    }
+                }
+                if !batch.is_empty() {
+                    db.write(batch)
+                        .context("failed to write accounts cache batch")?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self) -> anyhow::Result<()> {
+        match self {
+            Self::Memory(store) => *store = Arc::default(),
+            Self::Rocksdb(db) => {
+                // keys are 32 bytes pubkeys, longer end key is greater than any of them
+                db.delete_range_cf(
+                    Self::cf_handle(db),
+                    [0u8; 32].as_slice(),
+                    [u8::MAX; 33].as_slice(),
+                )
+                .context("failed to clear accounts cache")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Point-in-time view, should be created while updates are blocked.
+    fn view(&self) -> AccountsStoreView<'_> {
+        match self {
+            Self::Memory(store) => AccountsStoreView::Memory(store),
+            Self::Rocksdb(db) => AccountsStoreView::Rocksdb(db, db.snapshot()),
+        }
+    }
+}
+
+enum AccountsStoreView<'a> {
+    Memory(&'a HashMap<Pubkey, Arc<MessageAccount>, RandomState>),
+    Rocksdb(&'a DB, Snapshot<'a>),
+}
+
+impl AccountsStoreView<'_> {
+    fn for_each(
+        &self,
+        parser: MessageParserEncoding,
+        mut f: impl FnMut(&MessageAccount) -> Result<(), Status>,
+    ) -> Result<(), Status> {
+        match self {
+            Self::Memory(store) => {
+                for account in store.values() {
+                    f(account)?;
+                }
+            }
+            Self::Rocksdb(db, snapshot) => {
+                let cf = AccountsStore::cf_handle(db);
+                for item in snapshot.iterator_cf(cf, IteratorMode::Start) {
+                    let (_key, value) = item.map_err(|error| {
+                        Status::internal(format!("failed to read accounts cache: {error}"))
+                    })?;
+                    match Message::parse(Cow::Borrowed(&value), parser) {
+                        Ok(Message::Account(account)) => f(&account)?,
+                        Ok(_) => return Err(Status::internal("unexpected accounts cache message")),
+                        Err(error) => {
+                            return Err(Status::internal(format!(
+                                "failed to parse accounts cache message: {error}"
+                            )));
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct AccountsCacheState {
+    /// Position in the channel, all messages before it are applied
+    head: u64,
+    slot: Slot,
+    /// Account updates at or before the slot are part of loaded accounts
+    seed_slot: Slot,
+    /// Accounts are loaded and all messages up to the tail at the moment are applied
+    ready: bool,
+    store: AccountsStore,
+}
+
+/// Encoded matched accounts and the channel position to continue the stream from.
+#[derive(Debug)]
+pub struct AccountsSnapshot {
+    pub head: u64,
+    pub slot: Slot,
+    pub updates: Vec<Vec<u8>>,
+}
+
+/// Latest state of accounts of seed owners in the commitment channel.
+///
+/// Geyser does not stream accounts which are not modified, so accounts are loaded
+/// with `getProgramAccounts` and updated from the channel after that. Snapshots are
+/// not available until accounts are loaded and after lag, until accounts are re-loaded.
+#[derive(Debug)]
+pub struct AccountsCache {
+    commitment: CommitmentLevel,
+    parser: MessageParserEncoding,
+    snapshot_accounts_max: usize,
+    seed: ConfigAppsGrpcAccountsCacheSeed,
+    state: RwLock<AccountsCacheState>,
+}
+
+impl AccountsCache {
+    pub fn new(
+        commitment: CommitmentLevel,
+        path: Option<&Path>,
+        parser: MessageParserEncoding,
+        snapshot_accounts_max: usize,
+        seed: ConfigAppsGrpcAccountsCacheSeed,
+    ) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            commitment != CommitmentLevel::Processed,
+            "accounts cache is not supported for processed commitment"
+        );
+        anyhow::ensure!(
+            !seed.owners.is_empty(),
+            "accounts cache requires at least one seed owner"
+        );
+        Ok(Self {
+            commitment,
+            parser,
+            snapshot_accounts_max,
+            seed,
+            state: RwLock::new(AccountsCacheState {
+                head: 0,
+                slot: 0,
+                seed_slot: 0,
+                ready: false,
+                store: AccountsStore::open(path)?,
+            }),
+        })
+    }
+
+    const fn commitment_str(&self) -> &'static str {
+        commitment_str(self.commitment)
+    }
+
+    /// Replaces accounts with loaded at `slot`, messages are applied from `head`.
+    fn load(&self, accounts: &[Arc<MessageAccount>], slot: Slot, head: u64) -> anyhow::Result<()> {
+        let mut state = self.state.write().expect("accounts cache poisoned");
+        state.store.clear()?;
+        state.store.apply(accounts.iter(), &self.seed.owners)?;
+        state.head = head;
+        state.slot = slot;
+        state.seed_slot = slot;
+        state.ready = false;
+        Ok(())
+    }
+
+    /// Marks the cache as ready to serve snapshots.
+    fn set_ready(&self) {
+        self.state.write().expect("accounts cache poisoned").ready = true;
+        info!("accounts cache {} is ready", self.commitment_str());
+        gauge!(metrics::GRPC_ACCOUNTS_CACHE_READY, "commitment" => self.commitment_str()).set(1.0);
+    }
+
+    /// Removes accounts, snapshots are not available until accounts are loaded again.
+    fn invalidate(&self) -> anyhow::Result<()> {
+        let mut state = self.state.write().expect("accounts cache poisoned");
+        state.ready = false;
+        state.store.clear()?;
+        gauge!(metrics::GRPC_ACCOUNTS_CACHE_READY, "commitment" => self.commitment_str()).set(0.0);
+        Ok(())
+    }
+
+    /// Applies messages read from the channel, `head` is position after the last message.
+    pub fn apply(&self, messages: &[ParsedMessage], head: u64) -> anyhow::Result<()> {
+        let slot_status = match self.commitment {
+            CommitmentLevel::Processed => SlotStatus::SlotProcessed,
+            CommitmentLevel::Confirmed => SlotStatus::SlotConfirmed,
+            CommitmentLevel::Finalized => SlotStatus::SlotFinalized,
+        };
+        let slot = messages.iter().rev().find_map(|message| match message {
+            ParsedMessage::Slot(msg) if msg.status() == slot_status => Some(msg.slot()),
+            _ => None,
+        });
+
+        let mut state = self.state.write().expect("accounts cache poisoned");
+        let seed_slot = state.seed_slot;
+        state.store.apply(
+            messages.iter().filter_map(|message| match message {
+                ParsedMessage::Account(msg) if msg.slot() > seed_slot => Some(msg),
+                _ => None,
+            }),
+            &self.seed.owners,
+        )?;
+        state.head = head;
+        if let Some(slot) = slot.filter(|slot| *slot > state.slot) {
+            state.slot = slot;
+            gauge!(metrics::GRPC_ACCOUNTS_CACHE_SLOT, "commitment" => self.commitment_str())
+                .set(slot as f64);
+        }
+        Ok(())
+    }
+
+    /// Accounts matched by the filter, the store is scanned without blocking updates.
+    pub fn snapshot(&self, filter: &Filter) -> Result<AccountsSnapshot, Status> {
+        if !filter.accounts_owned_by(|owner| self.seed.owners.contains(owner)) {
+            return Err(Status::failed_precondition(
+                "accounts snapshot is available only for accounts filters by cached owners",
+            ));
+        }
+
+        let state = self.state.read().expect("accounts cache poisoned");
+        if !state.ready {
+            return Err(Status::unavailable("accounts cache is not loaded yet"));
+        }
+        let (head, slot) = (state.head, state.slot);
+        let store = state.store.clone();
+        let view = store.view();
+        drop(state);
+
+        let mut updates = vec![];
+        view.for_each(self.parser, |account| {
+            for update in filter.get_updates_ref(MessageRef::Account(account), self.commitment) {
+                if updates.len() >= self.snapshot_accounts_max {
+                    return Err(Status::resource_exhausted(format!(
+                        "accounts snapshot exceeds the limit of {} accounts",
+                        self.snapshot_accounts_max
+                    )));
+                }
+                updates.push(update.encode_to_vec());
+            }
+            Ok(())
+        })?;
+
+        counter!(metrics::GRPC_ACCOUNTS_CACHE_SNAPSHOT_ACCOUNTS_TOTAL, "commitment" => self.commitment_str())
+            .increment(updates.len() as u64);
+        Ok(AccountsSnapshot {
+            head,
+            slot,
+            updates,
+        })
+    }
+
+    /// Loads accounts of seed owners, returns the smallest context slot of responses.
+    async fn fetch_accounts(&self) -> anyhow::Result<(Slot, Vec<Arc<MessageAccount>>)> {
+        #[derive(Deserialize)]
+        struct JsonRpcResponse {
+            result: Option<RpcResponse<Vec<RpcKeyedAccount>>>,
+            error: Option<serde_json::Value>,
+        }
+
+        let client = Client::builder().timeout(self.seed.timeout).build()?;
+        let mut slot = Slot::MAX;
+        let mut accounts = vec![];
+        for owner in self.seed.owners.iter() {
+            let body = serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "getProgramAccounts",
+                "params": [owner.to_string(), {
+                    "commitment": self.commitment_str(),
+                    "encoding": "base64",
+                    "withContext": true,
+                }],
+            });
+            let bytes = client
+                .post(&self.seed.endpoint)
+                .header(CONTENT_TYPE, "application/json")
+                .body(serde_json::to_vec(&body)?)
+                .send()
+                .await?
+                .error_for_status()?
+                .bytes()
+                .await?;
+            let response: JsonRpcResponse = serde_json::from_slice(&bytes)
+                .context("failed to parse getProgramAccounts response")?;
+            let Some(result) = response.result else {
+                anyhow::bail!(
+                    "getProgramAccounts failed for {owner}: {}",
+                    response.error.unwrap_or_default()
+                );
+            };
+
+            slot = slot.min(result.context.slot);
+            for keyed in result.value {
+                let pubkey = Pubkey::from_str(&keyed.pubkey)
+                    .with_context(|| format!("invalid account pubkey: {}", keyed.pubkey))?;
+                let account = keyed
+                    .account
+                    .decode::<Account>()
+                    .with_context(|| format!("failed to decode account {pubkey}"))?;
+                accounts.push(self.create_account(pubkey, account, result.context.slot)?);
+            }
+            info!(
+                "accounts cache {} loaded accounts of {owner} at slot {}",
+                self.commitment_str(),
+                result.context.slot
+            );
+        }
+        Ok((slot, accounts))
+    }
+
+    fn create_account(
+        &self,
+        pubkey: Pubkey,
+        account: Account,
+        slot: Slot,
+    ) -> anyhow::Result<Arc<MessageAccount>> {
+        let data = SubscribeUpdate {
+            filters: vec![],
+            update_oneof: Some(UpdateOneof::Account(SubscribeUpdateAccount {
+                account: Some(SubscribeUpdateAccountInfo {
+                    pubkey: pubkey.to_bytes().to_vec(),
+                    lamports: account.lamports,
+                    owner: account.owner.to_bytes().to_vec(),
+                    executable: account.executable,
+                    rent_epoch: account.rent_epoch,
+                    data: account.data,
+                    write_version: 0,
+                    txn_signature: None,
+                }),
+                slot,
+                is_startup: true,
+            })),
+            created_at: None,
+        }
+        .encode_to_vec();
+        match Message::parse(Cow::Owned(data), self.parser)? {
+            Message::Account(account) => Ok(Arc::new(account)),
+            _ => unreachable!("account message expected"),
+        }
+    }
+
+    /// Loads accounts and finds channel position to apply updates from, `None` on shutdown.
+    fn seed(
+        &self,
+        runtime: &Runtime,
+        messages: &Messages,
+        shutdown: &CancellationToken,
+    ) -> anyhow::Result<Option<u64>> {
+        let result = runtime.block_on(async {
+            tokio::select! {
+                result = self.fetch_accounts() => result.map(Some),
+                () = shutdown.cancelled() => Ok(None),
+            }
+        });
+        let Some((slot, accounts)) = result? else {
+            return Ok(None);
+        };
+        let head = messages
+            .get_head_after_slot(self.commitment, slot)
+            .with_context(|| {
+                format!("loaded accounts at slot {slot} are older than the channel")
+            })?;
+        self.load(&accounts, slot, head)?;
+        info!(
+            "accounts cache {} loaded {} accounts at slot {slot}",
+            self.commitment_str(),
+            accounts.len()
+        );
+        Ok(Some(head))
+    }
+
+    /// Blocking loop updating the cache, should be executed in a dedicated thread.
+    fn run_worker(&self, messages: Messages, shutdown: CancellationToken) -> anyhow::Result<()> {
+        const BATCH_MAX: usize = 1_024;
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .context("failed to create accounts cache runtime")?;
+        let receiver = messages.to_receiver();
+        let mut head = None;
+        let mut ready = false;
+        let mut batch = Vec::with_capacity(BATCH_MAX);
+
+        const COUNTER_LIMIT: i32 = 10_000;
+        let mut counter = 0;
+        loop {
+            counter += 1;
+            if counter > COUNTER_LIMIT {
+                counter = 0;
+                if shutdown.is_cancelled() {
+                    info!("accounts cache {} thread shutdown", self.commitment_str());
+                    return Ok(());
+                }
+            }
+
+            let Some(head_current) = head else {
+                match self.seed(&runtime, &messages, &shutdown) {
+                    Ok(Some(head_seed)) => head = Some(head_seed),
+                    Ok(None) => counter = COUNTER_LIMIT,
+                    Err(error) => {
+                        error!(
+                            "failed to load accounts into accounts cache {}: {error:?}",
+                            self.commitment_str()
+                        );
+                        counter!(metrics::GRPC_ACCOUNTS_CACHE_SEED_ERRORS_TOTAL, "commitment" => self.commitment_str())
+                            .increment(1);
+                        runtime.block_on(async {
+                            tokio::select! {
+                                () = tokio::time::sleep(self.seed.retry_interval) => {},
+                                () = shutdown.cancelled() => {},
+                            }
+                        });
+                        counter = COUNTER_LIMIT;
+                    }
+                }
+                continue;
+            };
+
+            let mut head_next = head_current;
+            let mut caught_up = false;
+            let mut lagged = false;
+            while batch.len() < BATCH_MAX {
+                match receiver.try_recv(self.commitment, head_next) {
+                    Ok(Some(message)) => {
+                        head_next += 1;
+                        if matches!(message, ParsedMessage::Slot(_) | ParsedMessage::Account(_)) {
+                            batch.push(message);
+                        }
+                    }
+                    Ok(None) => {
+                        caught_up = true;
+                        break;
+                    }
+                    Err(RecvError::Lagged) => {
+                        error!(
+                            "accounts cache {} lagged, accounts would be re-loaded",
+                            self.commitment_str()
+                        );
+                        counter!(metrics::GRPC_ACCOUNTS_CACHE_LAGGED_TOTAL, "commitment" => self.commitment_str())
+                            .increment(1);
+                        self.invalidate()?;
+                        lagged = true;
+                        break;
+                    }
+                    Err(error) => return Err(error.into()),
+                }
+            }
+            if lagged {
+                ready = false;
+                head = None;
+                batch.clear();
+                continue;
+            }
+
+            if head_next != head_current {
+                self.apply(&batch, head_next)?;
+                batch.clear();
+                head = Some(head_next);
+            }
+            if caught_up {
+                if !ready {
+                    self.set_ready();
+                    ready = true;
+                }
+                if head_next == head_current {
+                    counter = COUNTER_LIMIT;
+                    sleep(Duration::from_micros(100));
+                }
+            }
+        }
+    }
+}
+
+/// Caches for configured commitment levels.
+#[derive(Debug, Default)]
+pub struct AccountsCaches {
+    caches: Vec<Arc<AccountsCache>>,
+}
+
+impl AccountsCaches {
+    pub fn spawn(
+        config: ConfigAppsGrpcAccountsCache,
+        messages: Messages,
+        shutdown: CancellationToken,
+    ) -> anyhow::Result<(Self, impl Future<Output = anyhow::Result<()>>)> {
+        let mut caches = Self::default();
+        let mut jhs = Vec::with_capacity(config.commitments.len());
+        for (index, commitment) in config.commitments.into_iter().enumerate() {
+            let commitment: CommitmentLevel = commitment.into();
+            anyhow::ensure!(
+                caches.get(commitment).is_none(),
+                "accounts cache commitment {commitment:?} is duplicated"
+            );
+            anyhow::ensure!(
+                commitment != CommitmentLevel::Processed,
+                "accounts cache is not supported for processed commitment"
+            );
+
+            let cache = Arc::new(AccountsCache::new(
+                commitment,
+                config
+                    .path
+                    .as_ref()
+                    .map(|path| path.join(commitment_str(commitment)))
+                    .as_deref(),
+                messages.parser(),
+                config.snapshot_accounts_max,
+                config.seed.clone(),
+            )?);
+            info!("start accounts cache for {commitment:?} commitment");
+
+            jhs.push(ConfigAppsWorkers::run_once(
+                index,
+                format!("richatGrpcAC{index:02}"),
+                config.affinity.clone(),
+                {
+                    let cache = Arc::clone(&cache);
+                    let messages = messages.clone();
+                    let shutdown = shutdown.clone();
+                    move |_index| cache.run_worker(messages, shutdown)
+                },
+                shutdown.clone(),
+            )?);
+            caches.caches.push(cache);
+        }
+
+        Ok((caches, try_join_all(jhs).map_ok(|_| ())))
+    }
+
+    pub fn get(&self, commitment: CommitmentLevel) -> Option<Arc<AccountsCache>> {
+        self.caches
+            .iter()
+            .find(|cache| cache.commitment == commitment)
+            .map(Arc::clone)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{AccountsCache, AccountsStore, AccountsStoreView},
+        crate::{channel::ParsedMessage, grpc::config::ConfigAppsGrpcAccountsCacheSeed},
+        maplit::hashmap,
+        prost::Message as _,
+        richat_filter::{
+            config::{ConfigFilter, ConfigFilterAccounts, ConfigFilterCommitment},
+            filter::Filter,
+            message::{Message, MessageParserEncoding},
+        },
+        richat_proto::geyser::{
+            SlotStatus, SubscribeUpdate, SubscribeUpdateAccount, SubscribeUpdateAccountInfo,
+            SubscribeUpdateSlot, subscribe_update::UpdateOneof,
+        },
+        solana_account::ReadableAccount,
+        solana_commitment_config::CommitmentLevel,
+        solana_pubkey::Pubkey,
+        std::borrow::Cow,
+        tonic::Code,
+    };
+
+    fn parse(update_oneof: UpdateOneof) -> ParsedMessage {
+        let data = SubscribeUpdate {
+            filters: vec![],
+            update_oneof: Some(update_oneof),
+            created_at: Some(Default::default()),
+        }
+        .encode_to_vec();
+        Message::parse(Cow::Owned(data), MessageParserEncoding::Limited)
+            .expect("valid message")
+            .into()
+    }
+
+    fn account(pubkey: Pubkey, owner: Pubkey, lamports: u64, slot: u64) -> ParsedMessage {
+        parse(UpdateOneof::Account(SubscribeUpdateAccount {
+            account: Some(SubscribeUpdateAccountInfo {
+                pubkey: pubkey.to_bytes().to_vec(),
+                owner: owner.to_bytes().to_vec(),
+                lamports,
+                ..Default::default()
+            }),
+            slot,
+            is_startup: false,
+        }))
+    }
+
+    fn slot(slot: u64, status: SlotStatus) -> ParsedMessage {
+        parse(UpdateOneof::Slot(SubscribeUpdateSlot {
+            slot,
+            parent: None,
+            status: status as i32,
+            dead_error: None,
+        }))
+    }
+
+    fn decode_lamports(data: &[u8]) -> u64 {
+        match SubscribeUpdate::decode(data).unwrap().update_oneof {
+            Some(UpdateOneof::Account(msg)) => msg.account.unwrap().lamports,
+            _ => panic!("expected account"),
+        }
+    }
+
+    fn lamports(view: &AccountsStoreView<'_>) -> Vec<u64> {
+        let mut lamports = vec![];
+        view.for_each(MessageParserEncoding::Limited, |account| {
+            lamports.push(account.lamports());
+            Ok(())
+        })
+        .unwrap();
+        lamports.sort_unstable();
+        lamports
+    }
+
+    fn seed(owner: Pubkey) -> ConfigAppsGrpcAccountsCacheSeed {
+        ConfigAppsGrpcAccountsCacheSeed {
+            owners: [owner].into_iter().collect(),
+            ..Default::default()
+        }
+    }
+
+    fn filter(owner: Pubkey) -> Filter {
+        Filter::new(&ConfigFilter {
+            accounts: hashmap! {
+                "owner".to_owned() => ConfigFilterAccounts {
+                    owner: vec![owner],
+                    ..Default::default()
+                }
+            },
+            commitment: Some(ConfigFilterCommitment::Confirmed),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let dir =
+            std::env::temp_dir().join(format!("richat-accounts-cache-{}", std::process::id()));
+        for path in [None, Some(dir.as_path())] {
+            let owner = Pubkey::new_unique();
+            let cache = AccountsCache::new(
+                CommitmentLevel::Confirmed,
+                path,
+                MessageParserEncoding::Limited,
+                2,
+                seed(owner),
+            )
+            .unwrap();
+            let filter = filter(owner);
+            assert_eq!(
+                cache.snapshot(&filter).unwrap_err().code(),
+                Code::Unavailable
+            );
+
+            let (pk1, pk2, pk3, pk4) = (
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+            );
+            let seed_accounts = [account(pk1, owner, 1, 5), account(pk2, owner, 1, 5)]
+                .into_iter()
+                .map(|message| match message {
+                    ParsedMessage::Account(account) => account,
+                    _ => unreachable!(),
+                })
+                .collect::<Vec<_>>();
+            cache.load(&seed_accounts, 5, 10).unwrap();
+            // not caught up with the channel yet
+            assert_eq!(
+                cache.snapshot(&filter).unwrap_err().code(),
+                Code::Unavailable
+            );
+
+            cache
+                .apply(
+                    &[
+                        // already included into loaded accounts
+                        account(pk1, owner, 7, 5),
+                        slot(6, SlotStatus::SlotConfirmed),
+                        account(pk2, owner, 0, 6),
+                        account(pk3, owner, 3, 6),
+                        account(pk4, Pubkey::new_unique(), 1, 6),
+                    ],
+                    17,
+                )
+                .unwrap();
+            cache.set_ready();
+
+            let snapshot = cache.snapshot(&filter).unwrap();
+            assert_eq!(snapshot.head, 17);
+            assert_eq!(snapshot.slot, 6);
+            let mut lamports = snapshot
+                .updates
+                .iter()
+                .map(|data| decode_lamports(data))
+                .collect::<Vec<_>>();
+            lamports.sort_unstable();
+            assert_eq!(lamports, [1, 3]);
+
+            // accounts of other owners are not cached
+            assert_eq!(
+                cache
+                    .snapshot(&Filter::new(&ConfigFilter {
+                        accounts: hashmap! {
+                            "account".to_owned() => ConfigFilterAccounts {
+                                account: vec![pk4],
+                                ..Default::default()
+                            }
+                        },
+                        commitment: Some(ConfigFilterCommitment::Confirmed),
+                        ..Default::default()
+                    }))
+                    .unwrap_err()
+                    .code(),
+                Code::FailedPrecondition
+            );
+
+            // owner changed
+            cache
+                .apply(&[account(pk3, Pubkey::new_unique(), 3, 7)], 18)
+                .unwrap();
+            assert_eq!(cache.snapshot(&filter).unwrap().updates.len(), 1);
+
+            // limit of accounts in one snapshot
+            cache
+                .apply(&[account(pk2, owner, 3, 8), account(pk3, owner, 3, 8)], 19)
+                .unwrap();
+            assert_eq!(
+                cache.snapshot(&filter).unwrap_err().code(),
+                Code::ResourceExhausted
+            );
+
+            // lagged cache is not used until accounts are re-loaded
+            cache.invalidate().unwrap();
+            assert_eq!(
+                cache.snapshot(&filter).unwrap_err().code(),
+                Code::Unavailable
+            );
+        }
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_store_view() {
+        let dir =
+            std::env::temp_dir().join(format!("richat-accounts-store-{}", std::process::id()));
+        for path in [None, Some(dir.as_path())] {
+            let owner = Pubkey::new_unique();
+            let owners = [owner].into_iter().collect();
+            let (pk1, pk2) = (Pubkey::new_unique(), Pubkey::new_unique());
+            let accounts = [
+                account(pk1, owner, 1, 1),
+                account(pk2, owner, 2, 1),
+                account(pk1, owner, 0, 2),
+            ]
+            .into_iter()
+            .map(|message| match message {
+                ParsedMessage::Account(account) => account,
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+
+            let mut store = AccountsStore::open(path).unwrap();
+            store.apply(accounts[..2].iter(), &owners).unwrap();
+
+            // updates after the view are not visible
+            let store_view = store.clone();
+            let view = store_view.view();
+            store.apply(accounts[2..].iter(), &owners).unwrap();
+            assert_eq!(lamports(&view), [1, 2]);
+            assert_eq!(lamports(&store.view()), [2]);
+
+            store.clear().unwrap();
+            assert_eq!(lamports(&view), [1, 2]);
+            assert!(lamports(&store.view()).is_empty());
+        }
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -1,13 +1,19 @@
 use {
-    crate::{channel::ParsedMessage, metrics},
+    crate::{
+        channel::{Messages, ParsedMessage},
+        metrics,
+    },
     ::metrics::gauge,
     foldhash::quality::RandomState,
     futures::future::TryFutureExt,
     richat_proto::geyser::{CommitmentLevel as CommitmentLevelProto, SlotStatus},
     solana_clock::{MAX_PROCESSING_AGE, Slot},
-    std::{collections::HashMap, future::Future, sync::Arc},
+    solana_commitment_config::CommitmentLevel,
+    std::{collections::HashMap, future::Future, sync::Arc, thread::sleep, time::Duration},
     tokio::sync::oneshot,
+    tokio_util::sync::CancellationToken,
     tonic::Status,
+    tracing::info,
 };
 
 #[derive(Debug, Default, Clone)]
@@ -81,7 +87,7 @@ impl BlockMetaStorage {
                             gauge!(metrics::GRPC_BLOCK_META_SLOT, "commitment" => "finalized").set(slot as f64);
 
                             // cleanup
-                            blockhashes.retain(|_blockhash, bentry| bentry.last_valid_block_height < entry.block_height);
+                            blockhashes.retain(|_blockhash, bentry| bentry.last_valid_block_height >= entry.block_height);
                             blocks.retain(|bslot, _block| *bslot >= slot);
                         }
                     }
@@ -119,13 +125,12 @@ impl BlockMetaStorage {
                                 CommitmentLevelProto::Confirmed => confirmed,
                                 CommitmentLevelProto::Finalized => finalized,
                             };
-                            let block = blocks.get(&slot).cloned();
-                            let value = if let (Some(block), Some(entry)) = (block, blockhashes.get(&blockhash)) {
-                                let valid = block.block_height < entry.last_valid_block_height;
-                                Some((valid, block.slot))
-                            } else {
-                                None
-                            };
+                            let value = blocks.get(&slot).map(|block| {
+                                let valid = blockhashes
+                                    .get(&blockhash)
+                                    .map(|entry| block.block_height < entry.last_valid_block_height);
+                                (valid, block.slot)
+                            });
                             let _ = tx.send(value);
                         }
                         Err(_) => break,
@@ -139,6 +144,44 @@ impl BlockMetaStorage {
         let _ = self.messages_tx.try_send(message);
     }
 
+    /// Blocking loop forwarding slot and block meta messages from the processed
+    /// channel, should be executed in a dedicated thread.
+    pub fn run_worker(
+        &self,
+        messages: Messages,
+        shutdown: CancellationToken,
+    ) -> anyhow::Result<()> {
+        let receiver = messages.to_receiver();
+        let mut head = messages.get_current_tail(CommitmentLevel::Processed) + 1;
+
+        const COUNTER_LIMIT: i32 = 10_000;
+        let mut counter = 0;
+        loop {
+            counter += 1;
+            if counter > COUNTER_LIMIT {
+                counter = 0;
+                if shutdown.is_cancelled() {
+                    info!("block meta thread shutdown");
+                    return Ok(());
+                }
+            }
+
+            let Some(message) = receiver.try_recv(CommitmentLevel::Processed, head)? else {
+                counter = COUNTER_LIMIT;
+                sleep(Duration::from_micros(100));
+                continue;
+            };
+            head += 1;
+
+            if matches!(
+                message,
+                ParsedMessage::Slot(_) | ParsedMessage::BlockMeta(_)
+            ) {
+                self.push(message);
+            }
+        }
+    }
+
     async fn send_request<T>(
         &self,
         request: Request,
@@ -167,6 +210,18 @@ impl BlockMetaStorage {
         blockhash: String,
         commitment: CommitmentLevelProto,
     ) -> tonic::Result<(bool, Slot)> {
+        match self.get_blockhash_status(blockhash, commitment).await? {
+            (Some(valid), slot) => Ok((valid, slot)),
+            (None, _slot) => Err(Status::aborted("failed to get result")),
+        }
+    }
+
+    /// Same as [`Self::is_blockhash_valid`], but unknown blockhash is not an error
+    pub async fn get_blockhash_status(
+        &self,
+        blockhash: String,
+        commitment: CommitmentLevelProto,
+    ) -> tonic::Result<(Option<bool>, Slot)> {
         let (tx, rx) = oneshot::channel();
         let request = Request::IsBlockhashValid(tx, blockhash, commitment);
         self.send_request(request, rx).await
@@ -176,7 +231,7 @@ impl BlockMetaStorage {
 enum Request {
     GetBlock(oneshot::Sender<Option<BlockMeta>>, CommitmentLevelProto),
     IsBlockhashValid(
-        oneshot::Sender<Option<(bool, Slot)>>,
+        oneshot::Sender<Option<(Option<bool>, Slot)>>,
         String,
         CommitmentLevelProto,
     ),
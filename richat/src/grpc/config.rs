@@ -1,15 +1,16 @@
 use {
     crate::config::ConfigAppsWorkers,
-    richat_filter::config::ConfigLimits as ConfigFilterLimits,
+    richat_filter::config::{ConfigFilterCommitment, ConfigLimits as ConfigFilterLimits},
     richat_shared::{
         config::{
             deserialize_affinity, deserialize_humansize_usize, deserialize_maybe_num_str,
-            deserialize_num_str, deserialize_x_tokens_set,
+            deserialize_num_str, deserialize_pubkey_set, deserialize_x_tokens_set,
         },
         transports::grpc::ConfigGrpcServer as ConfigAppGrpcServer,
     },
     serde::Deserialize,
-    std::{collections::HashSet, time::Duration},
+    solana_pubkey::Pubkey,
+    std::{collections::HashSet, path::PathBuf, time::Duration},
 };
 
 #[derive(Debug, Default, Clone, Deserialize)]
@@ -19,6 +20,8 @@ pub struct ConfigAppsGrpc {
     pub workers: ConfigAppsGrpcWorkers,
     pub stream: ConfigAppsGrpcStream,
     pub unary: ConfigAppsGrpcUnary,
+    /// Latest state of accounts for snapshot on subscribe
+    pub accounts_cache: Option<ConfigAppsGrpcAccountsCache>,
     pub filter_limits: ConfigFilterLimits,
     #[serde(deserialize_with = "deserialize_x_tokens_set")]
     pub x_tokens: HashSet<Vec<u8>>,
@@ -33,6 +36,7 @@ pub struct ConfigAppsGrpcWorkers {
     pub messages_cached_max: usize,
     #[serde(default, deserialize_with = "deserialize_maybe_num_str")]
     pub ticks_without_messages_max: Option<usize>,
+    pub filter_index: bool,
 }
 
 impl Default for ConfigAppsGrpcWorkers {
@@ -41,6 +45,7 @@ impl Default for ConfigAppsGrpcWorkers {
             threads: ConfigAppsWorkers::default(),
             messages_cached_max: 1_024,
             ticks_without_messages_max: None,
+            filter_index: false,
         }
     }
 }
@@ -56,6 +61,8 @@ pub struct ConfigAppsGrpcStream {
     pub messages_replay_len_max: usize,
     #[serde(with = "humantime_serde")]
     pub ping_interval: Duration,
+    /// Move lagged `processed` clients to storage replay instead of disconnect
+    pub lagged_replay: bool,
 }
 
 impl Default for ConfigAppsGrpcStream {
@@ -65,6 +72,7 @@ impl Default for ConfigAppsGrpcStream {
             messages_max_per_tick: 100,
             messages_replay_len_max: 256 * 1024 * 1024,
             ping_interval: Duration::from_secs(15),
+            lagged_replay: false,
         }
     }
 }
@@ -88,3 +96,55 @@ impl Default for ConfigAppsGrpcUnary {
         }
     }
 }
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct ConfigAppsGrpcAccountsCache {
+    /// Separate cache for every commitment, `processed` is not supported
+    pub commitments: Vec<ConfigFilterCommitment>,
+    /// RocksDB directory, removed on start; accounts are kept in memory if not set
+    pub path: Option<PathBuf>,
+    /// Max number of accounts in one snapshot
+    #[serde(deserialize_with = "deserialize_num_str")]
+    pub snapshot_accounts_max: usize,
+    pub seed: ConfigAppsGrpcAccountsCacheSeed,
+    #[serde(deserialize_with = "deserialize_affinity")]
+    pub affinity: Option<Vec<usize>>,
+}
+
+impl Default for ConfigAppsGrpcAccountsCache {
+    fn default() -> Self {
+        Self {
+            commitments: vec![ConfigFilterCommitment::Confirmed],
+            path: None,
+            snapshot_accounts_max: 1_000_000,
+            seed: ConfigAppsGrpcAccountsCacheSeed::default(),
+            affinity: None,
+        }
+    }
+}
+
+/// Accounts of `owners` are loaded with `getProgramAccounts` on start and after lag,
+/// only these accounts are kept in the cache.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct ConfigAppsGrpcAccountsCacheSeed {
+    pub endpoint: String,
+    #[serde(deserialize_with = "deserialize_pubkey_set")]
+    pub owners: HashSet<Pubkey>,
+    #[serde(with = "humantime_serde")]
+    pub timeout: Duration,
+    #[serde(with = "humantime_serde")]
+    pub retry_interval: Duration,
+}
+
+impl Default for ConfigAppsGrpcAccountsCacheSeed {
+    fn default() -> Self {
+        Self {
+            endpoint: "http://127.0.0.1:8899".to_owned(),
+            owners: HashSet::new(),
+            timeout: Duration::from_secs(600),
+            retry_interval: Duration::from_secs(10),
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use {
+    crate::{grpc::server::SubscribeClient, metrics::GrpcSubscribeMessage},
+    quanta::Instant,
+    richat_filter::{config::ConfigFilterAccountsConflation, message::MessageRef},
+    smallvec::SmallVec,
+    solana_clock::Slot,
+    solana_pubkey::Pubkey,
+    std::{collections::HashMap, time::Duration},
+};
+
+pub type AccountsConflationItems = SmallVec<[(GrpcSubscribeMessage, Vec<u8>); 2]>;
+
+/// Account updates of the subscription delayed by conflation, only the last update per
+/// pubkey is kept. Updates are stored in order of the last write, so delivered updates
+/// are still ordered by `write_version`.
+#[derive(Debug, Default)]
+pub struct AccountsConflation {
+    slot: Slot,
+    created_at: Option<Instant>,
+    updates: Vec<Option<AccountsConflationItems>>,
+    index: HashMap<Pubkey, usize>,
+    bytes: usize,
+}
+
+impl AccountsConflation {
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    /// Size of delayed updates, counted in the stream messages limit.
+    pub const fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    fn items_bytes(items: &AccountsConflationItems) -> usize {
+        items.iter().map(|(_message, data)| data.len()).sum()
+    }
+
+    /// Returns `true` if delayed updates should be pushed before the message (if any).
+    pub fn is_flush_required(
+        &self,
+        conflation: Option<ConfigFilterAccountsConflation>,
+        message: Option<MessageRef<'_>>,
+        now: Instant,
+    ) -> bool {
+        if self.is_empty() {
+            return false;
+        }
+
+        match conflation {
+            None => true,
+            Some(ConfigFilterAccountsConflation::Slot) => match message {
+                Some(MessageRef::Account(message)) => message.slot() != self.slot,
+                Some(MessageRef::Slot(message)) => message.slot() >= self.slot,
+                _ => false,
+            },
+            Some(ConfigFilterAccountsConflation::IntervalMs(interval)) => {
+                self.created_at.is_some_and(|created_at| {
+                    now.duration_since(created_at) >= Duration::from_millis(interval)
+                })
+            }
+        }
+    }
+
+    pub fn push(
+        &mut self,
+        pubkey: Pubkey,
+        slot: Slot,
+        items: AccountsConflationItems,
+        now: Instant,
+    ) {
+        if self.is_empty() {
+            self.slot = slot;
+            self.created_at = Some(now);
+        }
+        if let Some(index) = self.index.insert(pubkey, self.updates.len()) {
+            if let Some(items) = self.updates[index].take() {
+                self.bytes -= Self::items_bytes(&items);
+            }
+        }
+        self.bytes += Self::items_bytes(&items);
+        self.updates.push(Some(items));
+    }
+
+    /// Push delayed updates to the client, returns size of pushed messages.
+    pub fn flush(&mut self, client: &SubscribeClient) -> usize {
+        self.index.clear();
+        self.created_at = None;
+        self.bytes = 0;
+
+        let mut messages_len = 0;
+        for (message, data) in self.updates.drain(..).flatten().flatten() {
+            messages_len += data.len();
+            client.push_message(message, data);
+        }
+        messages_len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{AccountsConflation, AccountsConflationItems},
+        crate::{grpc::server::SubscribeClient, metrics::GrpcSubscribeMessage},
+        quanta::Instant,
+        richat_filter::config::ConfigFilterAccountsConflation,
+        richat_shared::transports::filter::RichatFilterMatcher,
+        smallvec::smallvec,
+        solana_pubkey::Pubkey,
+        std::time::Duration,
+    };
+
+    fn items(data: u8) -> AccountsConflationItems {
+        smallvec![(GrpcSubscribeMessage::Account, vec![data])]
+    }
+
+    #[test]
+    fn test_last_write_per_pubkey() {
+        let client =
+            SubscribeClient::new_richat_replay(usize::MAX, 0, None, RichatFilterMatcher::default());
+        let (pubkey1, pubkey2) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let now = Instant::now();
+
+        let mut conflation = AccountsConflation::default();
+        conflation.push(pubkey1, 1, items(1), now);
+        conflation.push(pubkey2, 1, items(2), now);
+        assert_eq!(conflation.bytes(), 2);
+        conflation.push(pubkey1, 1, items(3), now);
+        assert_eq!(conflation.bytes(), 2);
+
+        let slot = Some(ConfigFilterAccountsConflation::Slot);
+        assert!(!conflation.is_flush_required(slot, None, now));
+        let interval = Some(ConfigFilterAccountsConflation::IntervalMs(10));
+        assert!(!conflation.is_flush_required(interval, None, now));
+        let later = now + Duration::from_millis(10);
+        assert!(conflation.is_flush_required(interval, None, later));
+        assert!(conflation.is_flush_required(None, None, now));
+
+        assert_eq!(conflation.flush(&client), 2);
+        assert!(conflation.is_empty());
+        assert_eq!(conflation.bytes(), 0);
+        let mut data = vec![];
+        while let Some(message) = client.pop_message() {
+            data.extend(message.expect("no errors").1);
+        }
+        assert_eq!(data, [2, 3]);
+    }
+}
@@ -0,0 +1,274 @@
+use {
+    prost::{
+        DecodeError, Message,
+        bytes::{Buf, BufMut, Bytes, BytesMut},
+        encoding::{DecodeContext, WireType, bytes, encode_varint, encoded_len_varint},
+    },
+    richat_proto::{geyser::SubscribeRequest, richat::SubscribeRequestExtensions},
+};
+
+/// Yellowstone gRPC `SubscribeRequest` with Richat extensions decoded from the same message.
+///
+/// Only `request` is encoded, extensions are used on the server side only.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SubscribeRequestWithExtensions {
+    pub request: SubscribeRequest,
+    pub extensions: SubscribeRequestExtensions,
+}
+
+impl SubscribeRequestWithExtensions {
+    /// Tags of `SubscribeRequest` fields which are extended in `SubscribeRequestExtensions`
+    const fn is_extended(tag: u32) -> bool {
+        matches!(tag, 1 | 3 | 10)
+    }
+
+    /// Tags of `SubscribeRequestExtensions` fields which are unknown for `SubscribeRequest`
+    const fn is_extension(tag: u32) -> bool {
+        tag >= 100
+    }
+}
+
+impl Message for SubscribeRequestWithExtensions {
+    fn encode_raw(&self, buf: &mut impl BufMut) {
+        self.request.encode_raw(buf)
+    }
+
+    fn merge_field(
+        &mut self,
+        tag: u32,
+        wire_type: WireType,
+        buf: &mut impl Buf,
+        ctx: DecodeContext,
+    ) -> Result<(), DecodeError> {
+        if Self::is_extension(tag) {
+            return self.extensions.merge_field(tag, wire_type, buf, ctx);
+        }
+        if !Self::is_extended(tag) {
+            return self.request.merge_field(tag, wire_type, buf, ctx);
+        }
+
+        let mut value = Bytes::new();
+        bytes::merge(wire_type, &mut value, buf, ctx.clone())?;
+        let mut data =
+            BytesMut::with_capacity(encoded_len_varint(value.len() as u64) + value.len());
+        encode_varint(value.len() as u64, &mut data);
+        data.put(value);
+        let mut data = data.freeze();
+
+        self.request
+            .merge_field(tag, wire_type, &mut data.clone(), ctx.clone())?;
+        self.extensions.merge_field(tag, wire_type, &mut data, ctx)
+    }
+
+    fn encoded_len(&self) -> usize {
+        self.request.encoded_len()
+    }
+
+    fn clear(&mut self) {
+        self.request.clear();
+        self.extensions.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::SubscribeRequestWithExtensions,
+        prost::{
+            Message as _,
+            encoding::{WireType, encode_key, encode_varint},
+        },
+        richat_filter::{
+            config::{ConfigFilter, ConfigFilterError, ConfigFilterExpression},
+            filter::Filter,
+            message::{Message, MessageParserEncoding, MessageRef},
+        },
+        richat_proto::{
+            geyser::{
+                SubscribeRequestFilterAccounts, SubscribeRequestFilterTransactions,
+                SubscribeUpdate, SubscribeUpdateTransaction, SubscribeUpdateTransactionInfo,
+                subscribe_update::UpdateOneof,
+            },
+            richat::{
+                FilterExpression, SubscribeRequestFilterAccountsExtensions,
+                SubscribeRequestFilterTransactionsExtensions,
+                SubscribeRequestFilterTransactionsInstruction, filter_expression::Expression,
+            },
+            solana::storage::confirmed_block::{
+                CompiledInstruction, InnerInstruction, InnerInstructions,
+                Message as TransactionMessage, Transaction, TransactionStatusMeta,
+            },
+        },
+        solana_commitment_config::CommitmentLevel,
+        solana_pubkey::Pubkey,
+        std::borrow::Cow,
+    };
+
+    fn encode_len_delimited(tag: u32, data: &[u8], buf: &mut Vec<u8>) {
+        encode_key(tag, WireType::LengthDelimited, buf);
+        encode_varint(data.len() as u64, buf);
+        buf.extend_from_slice(data);
+    }
+
+    // `SubscribeRequest` encoded with extended `SubscribeRequestFilterTransactions`
+    fn encode_request(program_id: &Pubkey, data_prefix: &[u8]) -> Vec<u8> {
+        let mut value = SubscribeRequestFilterTransactions {
+            vote: Some(false),
+            ..Default::default()
+        }
+        .encode_to_vec();
+        SubscribeRequestFilterTransactionsExtensions {
+            instructions: vec![SubscribeRequestFilterTransactionsInstruction {
+                program_id: program_id.to_string(),
+                data_prefix: Some(data_prefix.to_vec()),
+            }],
+            expression: None,
+        }
+        .encode(&mut value)
+        .expect("enough capacity");
+
+        let mut entry = vec![];
+        encode_len_delimited(1, b"tx", &mut entry);
+        encode_len_delimited(2, &value, &mut entry);
+        let mut request = vec![];
+        encode_len_delimited(3, &entry, &mut request);
+        request
+    }
+
+    // `SubscribeRequest` with accounts filter extended by `not(filter)` expression
+    fn encode_request_expression(filter: Vec<u8>) -> Vec<u8> {
+        let value = SubscribeRequestFilterAccountsExtensions {
+            expression: Some(FilterExpression {
+                expression: Some(Expression::Not(Box::new(FilterExpression {
+                    expression: Some(Expression::Filter(filter)),
+                }))),
+            }),
+        }
+        .encode_to_vec();
+
+        let mut entry = vec![];
+        encode_len_delimited(1, b"accounts", &mut entry);
+        encode_len_delimited(2, &value, &mut entry);
+        let mut request = vec![];
+        encode_len_delimited(1, &entry, &mut request);
+        request
+    }
+
+    fn create_transaction(program_id: &Pubkey, inner_data: &[u8]) -> Message {
+        let data = SubscribeUpdate {
+            filters: vec![],
+            update_oneof: Some(UpdateOneof::Transaction(SubscribeUpdateTransaction {
+                transaction: Some(SubscribeUpdateTransactionInfo {
+                    signature: vec![1; 64],
+                    is_vote: false,
+                    transaction: Some(Transaction {
+                        signatures: vec![vec![1; 64]],
+                        message: Some(TransactionMessage {
+                            account_keys: vec![
+                                Pubkey::new_unique().to_bytes().to_vec(),
+                                Pubkey::new_unique().to_bytes().to_vec(),
+                                program_id.to_bytes().to_vec(),
+                            ],
+                            instructions: vec![CompiledInstruction {
+                                program_id_index: 1,
+                                accounts: vec![],
+                                data: vec![],
+                            }],
+                            ..Default::default()
+                        }),
+                    }),
+                    meta: Some(TransactionStatusMeta {
+                        inner_instructions: vec![InnerInstructions {
+                            index: 0,
+                            instructions: vec![InnerInstruction {
+                                program_id_index: 2,
+                                accounts: vec![],
+                                data: inner_data.to_vec(),
+                                stack_height: Some(2),
+                            }],
+                        }],
+                        ..Default::default()
+                    }),
+                    index: 0,
+                }),
+                slot: 1,
+            })),
+            created_at: Some(Default::default()),
+        }
+        .encode_to_vec();
+        Message::parse(Cow::Owned(data), MessageParserEncoding::Prost).expect("valid message")
+    }
+
+    #[test]
+    fn test_instructions_filter() {
+        let program_id = Pubkey::new_unique();
+        let request =
+            SubscribeRequestWithExtensions::decode(encode_request(&program_id, &[1, 2]).as_slice())
+                .expect("valid request");
+        assert_eq!(request.request.transactions["tx"].vote, Some(false));
+
+        let mut config = ConfigFilter::try_from(request.request).expect("valid filter");
+        config
+            .set_extensions(request.extensions)
+            .expect("valid extensions");
+        assert_eq!(config.transactions["tx"].instructions.len(), 1);
+        let filter = Filter::new(&config);
+
+        for (data, updates) in [(vec![1, 2, 3], 1), (vec![1, 3], 0)] {
+            let message = create_transaction(&program_id, &data);
+            let message_ref: MessageRef = (&message).into();
+            assert_eq!(
+                filter
+                    .get_updates_ref(message_ref, CommitmentLevel::Processed)
+                    .len(),
+                updates
+            );
+        }
+    }
+
+    #[test]
+    fn test_accounts_expression() {
+        let pubkey = Pubkey::new_unique();
+        let filter = SubscribeRequestFilterAccounts {
+            account: vec![pubkey.to_string()],
+            ..Default::default()
+        }
+        .encode_to_vec();
+        let request = SubscribeRequestWithExtensions::decode(
+            encode_request_expression(filter.clone()).as_slice(),
+        )
+        .expect("valid request");
+        assert!(request.request.accounts.contains_key("accounts"));
+
+        let mut config = ConfigFilter::try_from(request.request).expect("valid filter");
+        config
+            .set_extensions(request.extensions)
+            .expect("valid extensions");
+        let Some(ConfigFilterExpression::Not(expression)) =
+            config.accounts["accounts"].expression.as_deref()
+        else {
+            panic!("expected `not` expression");
+        };
+        let ConfigFilterExpression::Filter(filter) = expression.as_ref() else {
+            panic!("expected filter");
+        };
+        assert_eq!(filter.account, [pubkey]);
+
+        // leaf filters can not be extended with own expression
+        let nested = encode_request_expression(
+            SubscribeRequestFilterAccountsExtensions {
+                expression: Some(FilterExpression {
+                    expression: Some(Expression::Filter(vec![])),
+                }),
+            }
+            .encode_to_vec(),
+        );
+        let request =
+            SubscribeRequestWithExtensions::decode(nested.as_slice()).expect("valid request");
+        let mut config = ConfigFilter::try_from(request.request).expect("valid filter");
+        assert!(matches!(
+            config.set_extensions(request.extensions),
+            Err(ConfigFilterError::ExpressionNested)
+        ));
+    }
+}
@@ -0,0 +1,213 @@
+use {
+    richat_filter::message::MessageSlot,
+    richat_proto::{
+        geyser::SlotStatus,
+        richat::{RollbackCause, SubscribeUpdateRollback},
+    },
+    solana_clock::Slot,
+    std::collections::{BTreeMap, HashSet},
+};
+
+/// Parent chain of not finalized slots, used to report slots of abandoned forks
+/// to subscriptions with rollback notifications.
+#[derive(Debug, Default)]
+pub struct Forks {
+    slot_finalized: Slot,
+    slots: BTreeMap<Slot, ForkSlot>,
+}
+
+#[derive(Debug, Default)]
+struct ForkSlot {
+    parent: Option<Slot>,
+    bank: bool,
+    dead: bool,
+}
+
+impl Forks {
+    /// Update parent chain, returns rolled back slots if any.
+    pub fn update(&mut self, message: &MessageSlot) -> Option<SubscribeUpdateRollback> {
+        let slot = message.slot();
+        if slot <= self.slot_finalized {
+            return None;
+        }
+
+        let entry = self.slots.entry(slot).or_default();
+        if let Some(parent) = message.parent() {
+            entry.parent = Some(parent);
+        }
+
+        match message.status() {
+            SlotStatus::SlotCreatedBank | SlotStatus::SlotProcessed | SlotStatus::SlotConfirmed => {
+                entry.bank = true;
+                None
+            }
+            SlotStatus::SlotDead => {
+                if entry.dead {
+                    return None;
+                }
+                let mut slots = vec![slot];
+                slots.extend(
+                    self.slots
+                        .range(slot + 1..)
+                        .filter(|(child, info)| {
+                            info.bank
+                                && !info.dead
+                                && self.get_ancestor(**child, slot) == Some(slot)
+                        })
+                        .map(|(slot, _info)| *slot),
+                );
+                self.rollback(slots, slot, RollbackCause::SlotDead)
+            }
+            SlotStatus::SlotFinalized => {
+                let slots = self.get_abandoned(slot, self.slot_finalized);
+                let rollback = self.rollback(slots, slot, RollbackCause::NotFinalized);
+                self.slots = self.slots.split_off(&(slot + 1));
+                self.slot_finalized = slot;
+                rollback
+            }
+            _ => None,
+        }
+    }
+
+    // first ancestor at or below `slot_min`, `None` if some parent is unknown
+    fn get_ancestor(&self, mut slot: Slot, slot_min: Slot) -> Option<Slot> {
+        while slot > slot_min {
+            slot = self.slots.get(&slot)?.parent?;
+        }
+        Some(slot)
+    }
+
+    fn get_abandoned(&self, slot: Slot, slot_finalized: Slot) -> Vec<Slot> {
+        // slots below unknown parent can't be checked
+        let mut chain = HashSet::new();
+        let mut current = slot;
+        let slot_min = loop {
+            chain.insert(current);
+            match self.slots.get(&current).and_then(|info| info.parent) {
+                Some(parent) if parent > slot_finalized => current = parent,
+                Some(_parent) => break slot_finalized,
+                None => break current,
+            }
+        };
+
+        self.slots
+            .range(slot_min + 1..)
+            .filter(|(current, info)| {
+                info.bank
+                    && !info.dead
+                    && !chain.contains(*current)
+                    && (**current < slot
+                        || self
+                            .get_ancestor(**current, slot)
+                            .is_some_and(|ancestor| ancestor != slot))
+            })
+            .map(|(slot, _info)| *slot)
+            .collect()
+    }
+
+    fn rollback(
+        &mut self,
+        slots: Vec<Slot>,
+        cause_slot: Slot,
+        cause: RollbackCause,
+    ) -> Option<SubscribeUpdateRollback> {
+        let (slot_first, slot_last) = (*slots.first()?, *slots.last()?);
+        for slot in slots.iter() {
+            if let Some(info) = self.slots.get_mut(slot) {
+                info.dead = true;
+            }
+        }
+        Some(SubscribeUpdateRollback {
+            slot_first,
+            slot_last,
+            slots,
+            cause_slot,
+            cause: cause as i32,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::Forks,
+        prost::Message as _,
+        richat_filter::message::{Message, MessageParserEncoding, MessageSlot},
+        richat_proto::{
+            geyser::{
+                SlotStatus, SubscribeUpdate, SubscribeUpdateSlot, subscribe_update::UpdateOneof,
+            },
+            richat::{RollbackCause, SubscribeUpdateRollback},
+        },
+        solana_clock::Slot,
+        std::borrow::Cow,
+    };
+
+    fn slot(slot: Slot, parent: Option<Slot>, status: SlotStatus) -> MessageSlot {
+        let data = SubscribeUpdate {
+            filters: vec![],
+            update_oneof: Some(UpdateOneof::Slot(SubscribeUpdateSlot {
+                slot,
+                parent,
+                status: status as i32,
+                dead_error: None,
+            })),
+            created_at: Some(Default::default()),
+        }
+        .encode_to_vec();
+        match Message::parse(Cow::Owned(data), MessageParserEncoding::Prost) {
+            Ok(Message::Slot(message)) => message,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_finalized() {
+        let mut forks = Forks::default();
+        forks.update(&slot(10, None, SlotStatus::SlotFinalized));
+        for (s, parent) in [(11, 10), (12, 11), (13, 11), (14, 12), (15, 13)] {
+            assert_eq!(
+                forks.update(&slot(s, Some(parent), SlotStatus::SlotCreatedBank)),
+                None
+            );
+        }
+        // fork 11 -> 13 -> 15 is abandoned
+        assert_eq!(
+            forks.update(&slot(14, None, SlotStatus::SlotFinalized)),
+            Some(SubscribeUpdateRollback {
+                slot_first: 13,
+                slot_last: 15,
+                slots: vec![13, 15],
+                cause_slot: 14,
+                cause: RollbackCause::NotFinalized as i32,
+            })
+        );
+        // finalized slots are not tracked anymore
+        assert_eq!(forks.update(&slot(12, None, SlotStatus::SlotDead)), None);
+    }
+
+    #[test]
+    fn test_dead() {
+        let mut forks = Forks::default();
+        forks.update(&slot(10, None, SlotStatus::SlotFinalized));
+        for (s, parent) in [(11, 10), (12, 11), (13, 12), (14, 11)] {
+            forks.update(&slot(s, Some(parent), SlotStatus::SlotCreatedBank));
+        }
+        assert_eq!(
+            forks.update(&slot(12, None, SlotStatus::SlotDead)),
+            Some(SubscribeUpdateRollback {
+                slot_first: 12,
+                slot_last: 13,
+                slots: vec![12, 13],
+                cause_slot: 12,
+                cause: RollbackCause::SlotDead as i32,
+            })
+        );
+        // already dead slots are not reported again
+        assert_eq!(forks.update(&slot(12, None, SlotStatus::SlotDead)), None);
+        assert_eq!(
+            forks.update(&slot(14, None, SlotStatus::SlotFinalized)),
+            None
+        );
+    }
+}
@@ -1,3 +1,7 @@
+pub mod accounts_cache;
 pub mod block_meta;
 pub mod config;
+pub mod conflation;
+pub mod extensions;
+pub mod forks;
 pub mod server;
@@ -1,9 +1,21 @@
 use {
     crate::{
+        admin::{
+            AdminClientApp, AdminClientHandle, AdminClientInfo, AdminClientLag, AdminClientState,
+            AdminClients,
+        },
         channel::{IndexLocation, Messages, ParsedMessage, ReceiverSync},
-        config::ConfigAppsWorkers,
-        grpc::{block_meta::BlockMetaStorage, config::ConfigAppsGrpc},
+        config::{ConfigAppsTenantApp, ConfigAppsWorkers},
+        grpc::{
+            accounts_cache::{AccountsCaches, AccountsSnapshot},
+            block_meta::BlockMetaStorage,
+            config::ConfigAppsGrpc,
+            conflation::{AccountsConflation, AccountsConflationItems},
+            extensions::SubscribeRequestWithExtensions,
+            forks::Forks,
+        },
         metrics::{self, GrpcSubscribeMessage},
+        tenants::{TenantSubscription, Tenants},
         version::VERSION,
     },
     ::metrics::{Gauge, counter, gauge},
@@ -17,10 +29,11 @@ use {
     quanta::Instant,
     richat_filter::{
         config::{
-            ConfigFilter, ConfigFilterAccounts, ConfigFilterSlots,
+            ConfigFilter, ConfigFilterAccounts, ConfigFilterCommitment, ConfigFilterSlots,
             ConfigLimits as ConfigFilterLimits,
         },
         filter::Filter,
+        index::{FilterIndex, FilterIndexMatches},
         message::MessageRef,
     },
     richat_metrics::duration_to_seconds,
@@ -30,13 +43,22 @@ use {
             GetLatestBlockhashRequest, GetLatestBlockhashResponse, GetSlotRequest, GetSlotResponse,
             GetVersionRequest, GetVersionResponse, IsBlockhashValidRequest,
             IsBlockhashValidResponse, PingRequest, PongResponse, SubscribeReplayInfoRequest,
-            SubscribeReplayInfoResponse, SubscribeRequest, SubscribeUpdate, SubscribeUpdatePing,
-            SubscribeUpdatePong, subscribe_update::UpdateOneof,
+            SubscribeReplayInfoResponse, SubscribeUpdate, SubscribeUpdatePing, SubscribeUpdatePong,
+            subscribe_update::UpdateOneof,
+        },
+        richat::{
+            SubscribeAccountsRequest, SubscribeUpdateAccountsSnapshot, SubscribeUpdateRollback,
+        },
+    },
+    richat_shared::{
+        jsonrpc::helpers::X_SUBSCRIPTION_ID,
+        mutex_lock,
+        transports::{
+            RecvError,
+            filter::RichatFilterMatcher,
+            reload::{Reloadable, ServerReload, ServerReloadError},
         },
-        richat::SubscribeAccountsRequest,
     },
-    richat_shared::{jsonrpc::helpers::X_SUBSCRIPTION_ID, mutex_lock, transports::RecvError},
-    smallvec::SmallVec,
     solana_clock::{MAX_PROCESSING_AGE, Slot},
     solana_commitment_config::CommitmentLevel,
     solana_pubkey::Pubkey,
@@ -47,10 +69,10 @@ use {
         future::Future,
         pin::Pin,
         sync::{
-            Arc, Mutex, MutexGuard,
+            Arc, Mutex, MutexGuard, RwLock,
             atomic::{AtomicU64, AtomicUsize, Ordering},
         },
-        task::{Context, Poll},
+        task::{Context, Poll, Waker, ready},
         thread::sleep,
         time::{Duration, SystemTime},
     },
@@ -74,21 +96,55 @@ pub struct GrpcServer {
     shutdown: CancellationToken,
     messages: Messages,
     block_meta: Option<Arc<BlockMetaStorage>>,
-    filter_limits: Arc<ConfigFilterLimits>,
+    accounts_caches: Option<Arc<AccountsCaches>>,
+    filter_limits: Reloadable<ConfigFilterLimits>,
+    tenants: Tenants,
+    admin: AdminClients,
+    filter_index: Option<Arc<RwLock<FilterIndex<u64>>>>,
     ping_interval: Duration,
     subscribe_id: Arc<AtomicU64>,
     subscribe_clients: Arc<SegQueue<SubscribeClient>>,
     subscribe_messages_len_max: usize,
     subscribe_messages_replay_len_max: usize,
+    subscribe_lagged_replay: bool,
+}
+
+/// Settings of the running gRPC app applied to new requests.
+#[derive(Debug, Clone)]
+pub struct GrpcServerReload {
+    server: ServerReload,
+    filter_limits: Reloadable<ConfigFilterLimits>,
+}
+
+impl GrpcServerReload {
+    pub const fn check(&self, config: &ConfigAppsGrpc) -> Result<(), ServerReloadError> {
+        self.server.check(config.server.tls_config.as_ref())
+    }
+
+    pub fn reload(&self, config: ConfigAppsGrpc) -> Result<(), ServerReloadError> {
+        self.server
+            .reload(config.x_tokens, config.server.tls_config.as_ref())?;
+        self.filter_limits.store(config.filter_limits);
+        Ok(())
+    }
 }
 
 impl GrpcServer {
     pub fn spawn(
-        config: ConfigAppsGrpc,
+        mut config: ConfigAppsGrpc,
         messages: Messages,
+        tenants: Tenants,
+        admin: AdminClients,
         shutdown: CancellationToken,
-    ) -> anyhow::Result<impl Future<Output = anyhow::Result<()>>> {
+    ) -> anyhow::Result<(GrpcServerReload, impl Future<Output = anyhow::Result<()>>)> {
         // Create gRPC server
+        let reload = GrpcServerReload {
+            server: ServerReload::new(
+                std::mem::take(&mut config.x_tokens),
+                config.server.tls_config.as_mut(),
+            ),
+            filter_limits: Reloadable::new(std::mem::take(&mut config.filter_limits)),
+        };
         let (incoming, server_builder) = config.server.create_server_builder()?;
         info!("start server at {}", config.server.endpoint);
 
@@ -104,7 +160,7 @@ impl GrpcServer {
                     let messages = messages.clone();
                     let meta = meta.clone();
                     let shutdown = shutdown.clone();
-                    move |_index| Self::worker_block_meta(messages, meta, shutdown)
+                    move |_index| meta.run_worker(messages, shutdown)
                 },
                 shutdown.clone(),
             )?;
@@ -114,17 +170,35 @@ impl GrpcServer {
             (None, ready(Ok(())).boxed(), ready(Ok(())).boxed())
         };
 
+        // Accounts cache threads
+        let (accounts_caches, accounts_caches_jh) = match config.accounts_cache.take() {
+            Some(config) => {
+                let (caches, jh) =
+                    AccountsCaches::spawn(config, messages.clone(), shutdown.clone())?;
+                (Some(Arc::new(caches)), jh.boxed())
+            }
+            None => (None, ready(Ok(())).boxed()),
+        };
+
         // gRPC service
         let grpc_server = Self {
             shutdown: shutdown.clone(),
             messages,
             block_meta,
-            filter_limits: Arc::new(config.filter_limits),
+            accounts_caches,
+            filter_limits: reload.filter_limits.clone(),
+            tenants: tenants.clone(),
+            admin,
+            filter_index: config
+                .workers
+                .filter_index
+                .then(|| Arc::new(RwLock::new(FilterIndex::default()))),
             ping_interval: config.stream.ping_interval,
             subscribe_id: Arc::new(AtomicU64::new(0)),
             subscribe_clients: Arc::new(SegQueue::new()),
             subscribe_messages_len_max: config.stream.messages_len_max,
             subscribe_messages_replay_len_max: config.stream.messages_replay_len_max,
+            subscribe_lagged_replay: config.stream.lagged_replay,
         };
 
         let mut service = geyser_gen::geyser_server::GeyserServer::new(grpc_server.clone())
@@ -162,18 +236,26 @@ impl GrpcServer {
             .boxed();
 
         // Spawn server
+        let server_reload = reload.server.clone();
         let server = tokio::spawn(async move {
             if let Err(error) = server_builder
                 .layer(InterceptorLayer::new(move |request: Request<()>| {
-                    if config.x_tokens.is_empty() {
-                        Ok(request)
-                    } else {
-                        match request.metadata().get("x-token") {
-                            Some(token) if config.x_tokens.contains(token.as_bytes()) => {
-                                Ok(request)
-                            }
-                            _ => Err(Status::unauthenticated("No valid auth token")),
+                    let x_token = request
+                        .metadata()
+                        .get("x-token")
+                        .map(|token| token.as_bytes());
+                    let x_tokens = server_reload.x_tokens();
+                    match tenants.get(ConfigAppsTenantApp::Grpc, x_token)? {
+                        Some(_tenant) => Ok(request),
+                        None if x_tokens.is_empty()
+                            && !tenants.has_app(ConfigAppsTenantApp::Grpc) =>
+                        {
+                            Ok(request)
                         }
+                        None => match x_token {
+                            Some(token) if x_tokens.contains(token) => Ok(request),
+                            _ => Err(Status::unauthenticated("No valid auth token")),
+                        },
                     }
                 }))
                 .add_service(service)
@@ -189,15 +271,40 @@ impl GrpcServer {
         .boxed();
 
         // Wait spawned features
-        Ok(try_join_all([block_meta_jh, block_meta_task_jh, workers, server]).map_ok(|_| ()))
+        Ok((
+            reload,
+            try_join_all([
+                block_meta_jh,
+                block_meta_task_jh,
+                accounts_caches_jh,
+                workers,
+                server,
+            ])
+            .map_ok(|_| ()),
+        ))
     }
 
-    fn get_x_subscription_id<T>(request: &Request<T>) -> String {
+    fn get_x_token<T>(request: &Request<T>) -> Option<&[u8]> {
+        request
+            .metadata()
+            .get("x-token")
+            .map(|token| token.as_bytes())
+    }
+
+    /// Tenant name for tenants x-tokens, otherwise value of `x-subscription-id` header.
+    fn get_x_subscription_id<T>(&self, request: &Request<T>) -> Arc<str> {
+        if let Ok(Some(tenant)) = self
+            .tenants
+            .get(ConfigAppsTenantApp::Grpc, Self::get_x_token(request))
+        {
+            return Arc::clone(tenant.name());
+        }
         request
             .metadata()
             .get(X_SUBSCRIPTION_ID)
-            .and_then(|value| value.to_str().ok().map(ToOwned::to_owned))
+            .and_then(|value| value.to_str().ok())
             .unwrap_or_default()
+            .into()
     }
 
     fn parse_commitment(commitment: Option<i32>) -> Result<CommitmentLevelProto, Status> {
@@ -250,42 +357,6 @@ impl GrpcServer {
         self.subscribe_clients.pop()
     }
 
-    fn worker_block_meta(
-        messages: Messages,
-        block_meta: BlockMetaStorage,
-        shutdown: CancellationToken,
-    ) -> anyhow::Result<()> {
-        let receiver = messages.to_receiver();
-        let mut head = messages.get_current_tail(CommitmentLevel::Processed) + 1;
-
-        const COUNTER_LIMIT: i32 = 10_000;
-        let mut counter = 0;
-        loop {
-            counter += 1;
-            if counter > COUNTER_LIMIT {
-                counter = 0;
-                if shutdown.is_cancelled() {
-                    info!("gRPC block meta thread shutdown");
-                    return Ok(());
-                }
-            }
-
-            let Some(message) = receiver.try_recv(CommitmentLevel::Processed, head)? else {
-                counter = COUNTER_LIMIT;
-                sleep(Duration::from_micros(100));
-                continue;
-            };
-            head += 1;
-
-            if matches!(
-                message,
-                ParsedMessage::Slot(_) | ParsedMessage::BlockMeta(_)
-            ) {
-                block_meta.push(message);
-            }
-        }
-    }
-
     fn worker_messages(
         &self,
         index: usize,
@@ -300,6 +371,7 @@ impl GrpcServer {
         let mut messages_cache_finalized = MessagesCache::new(messages_cached_max);
 
         let receiver = self.messages.to_receiver();
+        let filter_index = self.filter_index.as_deref();
         let mut ticks_without_messages = 0;
         const SHUTDOWN_COUNTER_LIMIT: i32 = 50_000;
         let mut shutdown_counter = 0;
@@ -346,41 +418,117 @@ impl GrpcServer {
             let mut pushed = false;
             let mut messages_counter = 0;
             let mut messages_len = client.messages_len.load(Ordering::Relaxed);
-            while messages_len <= client.messages_len_max
+            let conflation = state.filter.as_ref().and_then(Filter::accounts_conflation);
+            if state
+                .accounts_conflation
+                .is_flush_required(conflation, None, ts)
+            {
+                messages_len += state.accounts_conflation.flush(&client);
+                pushed = true;
+            }
+            if state.accounts_snapshot.is_some() {
+                pushed = true;
+            }
+            while state.push_accounts_snapshot(
+                &client,
+                &mut messages_len,
+                &mut messages_counter,
+                messages_max_per_tick,
+            ) && messages_len <= client.messages_len_max
                 && messages_counter < messages_max_per_tick
             {
-                let message = match messages_cache.try_recv(&receiver, state.commitment, head) {
-                    Ok(Some(message)) => {
+                let (message, matches, replay_index) = match messages_cache.try_recv(
+                    &receiver,
+                    state.commitment,
+                    head,
+                    filter_index,
+                    state.id,
+                ) {
+                    Ok(Some(item)) => {
                         messages_counter += 1;
                         head += 1;
                         state.head = IndexLocation::Memory(head);
-                        message
+                        item
                     }
                     Ok(None) => break,
                     Err(RecvError::Lagged) => {
-                        client.push_error(Status::data_loss("lagged"));
-                        errored = true;
+                        // delayed updates are older than replayed messages
+                        if !state.accounts_conflation.is_empty() {
+                            state.accounts_conflation.flush(&client);
+                            pushed = true;
+                        }
+                        if !self.replay_lagged(&client, &mut state) {
+                            client.push_error(Status::data_loss("lagged"));
+                            errored = true;
+                        }
                         break;
                     }
-                    Err(RecvError::Closed) => {
+                    Err(RecvError::Closed | RecvError::ReplayFinished) => {
                         client.push_error(Status::data_loss("closed"));
                         errored = true;
                         break;
                     }
                 };
 
+                if replay_index != u64::MAX {
+                    state.last_replay_index = Some(replay_index);
+                }
+
+                let rollback_len = state.push_rollback(&client, &message);
+                if rollback_len > 0 {
+                    messages_len += rollback_len;
+                    pushed = true;
+                }
+
                 let message_ref: MessageRef = message.as_ref().into();
-                if let Some(filter) = state.filter.as_ref() {
-                    let items = filter
-                        .get_updates_ref(message_ref, state.commitment)
+                let Some(filter) = state.filter.as_ref() else {
+                    continue;
+                };
+                let items = {
+                    let updates = match matches.as_deref() {
+                        Some(matches) => filter.get_updates_indexed(
+                            message_ref,
+                            state.commitment,
+                            matches,
+                            &state.id,
+                        ),
+                        None => filter.get_updates_ref(message_ref, state.commitment),
+                    };
+                    updates
                         .iter()
                         .map(|msg| ((&msg.filtered_update).into(), msg.encode_to_vec()))
-                        .collect::<SmallVec<[(GrpcSubscribeMessage, Vec<u8>); 2]>>();
+                        .collect::<AccountsConflationItems>()
+                };
 
-                    for (message, data) in items {
-                        messages_len += data.len();
-                        client.push_message(message, data);
-                        pushed = true;
+                if state
+                    .accounts_conflation
+                    .is_flush_required(conflation, Some(message_ref), ts)
+                {
+                    messages_len += state.accounts_conflation.flush(&client);
+                    pushed = true;
+                }
+                match message_ref {
+                    MessageRef::Account(message) if conflation.is_some() && !items.is_empty() => {
+                        state.accounts_conflation.push(
+                            *message.pubkey(),
+                            message.slot(),
+                            items,
+                            ts,
+                        );
+                        // delayed updates are bounded by the stream limit too
+                        if messages_len + state.accounts_conflation.bytes()
+                            > client.messages_len_max
+                        {
+                            messages_len += state.accounts_conflation.flush(&client);
+                            pushed = true;
+                        }
+                    }
+                    _ => {
+                        for (message, data) in items {
+                            messages_len += data.len();
+                            client.push_message(message, data);
+                            pushed = true;
+                        }
                     }
                 }
             }
@@ -413,16 +561,100 @@ impl GrpcServer {
         }
     }
 
-    fn subscribe2<T: 'static>(
+    /// Scan accounts cache on a blocking thread, client state is not locked meanwhile.
+    async fn get_accounts_snapshot(
+        accounts_caches: Option<&AccountsCaches>,
+        subscribe_from_slot: Option<Slot>,
+        filter: Filter,
+    ) -> Result<(Filter, Option<AccountsSnapshot>), Status> {
+        if subscribe_from_slot.is_some() {
+            return Err(Status::invalid_argument(
+                "accounts snapshot is not compatible with from_slot",
+            ));
+        }
+        let cache = accounts_caches
+            .and_then(|caches| caches.get(filter.commitment().into()))
+            .ok_or_else(|| {
+                Status::failed_precondition("accounts cache is not enabled for the commitment")
+            })?;
+        tokio::task::spawn_blocking(move || {
+            let snapshot = cache.snapshot(&filter)?;
+            Ok((filter, Some(snapshot)))
+        })
+        .await
+        .map_err(|error| Status::internal(format!("failed to create accounts snapshot: {error}")))?
+    }
+
+    /// Move lagged client to storage replay from the last sent message, replay worker
+    /// switches it back to the memory channel once storage is read.
+    fn replay_lagged(&self, client: &SubscribeClient, state: &mut SubscribeClientState) -> bool {
+        if !self.subscribe_lagged_replay
+            || state.commitment != CommitmentLevel::Processed
+            || state
+                .filter
+                .as_ref()
+                .is_none_or(|filter| filter.contains_blocks())
+        {
+            return false;
+        }
+        let Some(replay_index) = state.last_replay_index else {
+            return false;
+        };
+        if !self.messages.is_replay_index_available(replay_index) {
+            return false;
+        }
+
+        state.head = IndexLocation::Storage(replay_index);
+        let metric_cpu_usage = gauge!(
+            metrics::GRPC_SUBSCRIBE_REPLAY_DISK_SECONDS_TOTAL,
+            "x_subscription_id" => Arc::clone(&state.x_subscription_id)
+        );
+        if let Err(error) = self
+            .messages
+            .replay_from_storage(client.clone(), metric_cpu_usage)
+        {
+            warn!(
+                id = state.id,
+                error, "failed to replay lagged client from storage"
+            );
+            return false;
+        }
+
+        info!(
+            id = state.id,
+            replay_index, "lagged client moved to storage replay"
+        );
+        counter!(
+            metrics::GRPC_SUBSCRIBE_LAGGED_REPLAY_TOTAL,
+            "x_subscription_id" => Arc::clone(&state.x_subscription_id)
+        )
+        .increment(1);
+        true
+    }
+
+    fn subscribe2<T: Send + 'static>(
         &self,
         request: Request<Streaming<T>>,
         method: &'static str,
         get_ping: impl Fn(&T) -> Option<i32> + Send + 'static,
-        mut get_filter: impl FnMut(&ConfigFilterLimits, T) -> (Option<Slot>, Result<Filter, Status>)
+        mut get_filter: impl FnMut(
+            &ConfigFilterLimits,
+            T,
+        ) -> (Option<Slot>, bool, Result<Filter, Status>)
         + Send
         + 'static,
     ) -> TonicResult<Response<ReceiverStream>> {
-        let x_subscription_id: Arc<str> = Self::get_x_subscription_id(&request).into();
+        let subscription = self
+            .tenants
+            .subscribe(ConfigAppsTenantApp::Grpc, Self::get_x_token(&request))?;
+        let x_subscription_id = match &subscription {
+            Some(subscription) => Arc::clone(subscription.name()),
+            None => self.get_x_subscription_id(&request),
+        };
+        let limits = subscription
+            .as_ref()
+            .and_then(|subscription| subscription.filter_limits())
+            .unwrap_or_else(|| self.filter_limits.load());
         counter!(
             metrics::GRPC_REQUESTS_TOTAL,
             "x_subscription_id" => Arc::clone(&x_subscription_id),
@@ -436,11 +668,22 @@ impl GrpcServer {
             self.subscribe_messages_len_max,
             self.subscribe_messages_replay_len_max,
             Arc::clone(&x_subscription_id),
+            self.filter_index.as_ref().map(Arc::clone),
         );
         self.push_client(client.clone());
+        let admin = self.admin.register(
+            AdminClientInfo {
+                app: AdminClientApp::Grpc,
+                remote_addr: request.remote_addr(),
+                x_token: Self::get_x_token(&request).map(ToOwned::to_owned),
+                x_subscription_id: Arc::clone(&x_subscription_id),
+            },
+            Arc::new(client.clone()),
+        );
 
         tokio::spawn({
             let shutdown = self.shutdown.clone();
+            let disconnect = admin.disconnect();
             let ping_interval = self.ping_interval;
             let client = client.clone();
             async move {
@@ -452,6 +695,10 @@ impl GrpcServer {
                             client.push_error(Status::internal("shutdown"));
                             break
                         }
+                        () = disconnect.cancelled() => {
+                            client.push_error(Status::aborted("disconnected by admin"));
+                            break
+                        }
                         () = tokio::time::sleep(Duration::from_millis(500)) => {
                             let state = client.state_lock();
                             if state.finished {
@@ -474,9 +721,9 @@ impl GrpcServer {
 
         tokio::spawn({
             let mut stream = request.into_inner();
-            let limits = Arc::clone(&self.filter_limits);
             let client = client.clone();
             let messages = self.messages.clone();
+            let accounts_caches = self.accounts_caches.clone();
             async move {
                 loop {
                     match stream.message().await {
@@ -488,15 +735,38 @@ impl GrpcServer {
                                 continue;
                             }
 
-                            let (subscribe_from_slot, new_filter) = get_filter(&limits, message);
+                            let (subscribe_from_slot, accounts_snapshot, new_filter) =
+                                get_filter(&limits, message);
+                            let new_filter = match new_filter {
+                                Ok(filter) if accounts_snapshot => {
+                                    Self::get_accounts_snapshot(
+                                        accounts_caches.as_deref(),
+                                        subscribe_from_slot,
+                                        filter,
+                                    )
+                                    .await
+                                }
+                                result => result.map(|filter| (filter, None)),
+                            };
                             let mut state = client.state_lock();
-                            if let Err(error) = new_filter.and_then(|filter| {
+                            if let Err(error) = new_filter.and_then(|(filter, accounts_snapshot)| {
                                 if filter.contains_blocks() && subscribe_from_slot.is_some() {
                                     return Err(Status::invalid_argument(
                                         "blocks are not possible to replay",
                                     ));
                                 }
-
+                                if filter.contains_blocks() && !messages.blocks_enabled() {
+                                    return Err(Status::invalid_argument(
+                                        "blocks are not available, transactions are filtered by sources",
+                                    ));
+                                }
+                                if filter.rollback_notifications()
+                                    && filter.commitment() != ConfigFilterCommitment::Processed
+                                {
+                                    return Err(Status::invalid_argument(
+                                        "rollback notifications are available only for processed commitment",
+                                    ));
+                                }
                                 let commitment_prev = state.commitment;
                                 state.commitment = filter.commitment().into();
                                 if state.filter.is_none() || state.commitment != commitment_prev {
@@ -505,8 +775,10 @@ impl GrpcServer {
                                         .get_current_tail_with_replay(
                                             state.commitment,
                                             subscribe_from_slot,
+                                            None,
                                         )
                                         .map_err(Status::invalid_argument)?;
+                                    state.replay_from_slot = subscribe_from_slot;
                                     if !matches!(current_head, IndexLocation::Storage(_))
                                         && matches!(state.head, IndexLocation::Storage(_))
                                     {
@@ -519,6 +791,35 @@ impl GrpcServer {
                                             .map_err(Status::internal)?;
                                     }
                                 }
+                                if let Some(snapshot) = accounts_snapshot {
+                                    if matches!(state.head, IndexLocation::Storage(_)) {
+                                        return Err(Status::failed_precondition(
+                                            "accounts snapshot is not available on replay from storage",
+                                        ));
+                                    }
+                                    // delayed updates are older than the snapshot
+                                    if !state.accounts_conflation.is_empty() {
+                                        state.accounts_conflation.flush(&client);
+                                    }
+                                    state.head = IndexLocation::Memory(snapshot.head);
+                                    state.accounts_snapshot = Some(AccountsSnapshotQueue {
+                                        slot: snapshot.slot,
+                                        accounts: snapshot.updates.len() as u64,
+                                        updates: snapshot.updates.into_iter(),
+                                    });
+                                    client.wake();
+                                }
+                                if !filter.rollback_notifications() {
+                                    state.forks = None;
+                                } else if state.forks.is_none() {
+                                    state.forks = Some(Forks::default());
+                                }
+                                if let Some(filter_index) = &state.filter_index {
+                                    filter_index
+                                        .write()
+                                        .expect("filter index poisoned")
+                                        .insert(id, &filter);
+                                }
                                 state.filter = Some(filter);
                                 Ok::<(), Status>(())
                             }) {
@@ -539,7 +840,11 @@ impl GrpcServer {
             }
         });
 
-        Ok(Response::new(ReceiverStream::new(client)))
+        Ok(Response::new(ReceiverStream::new(
+            client,
+            subscription,
+            admin,
+        )))
     }
 }
 
@@ -550,15 +855,20 @@ impl geyser_gen::geyser_server::Geyser for GrpcServer {
 
     async fn subscribe(
         &self,
-        request: Request<Streaming<SubscribeRequest>>,
+        request: Request<Streaming<SubscribeRequestWithExtensions>>,
     ) -> TonicResult<Response<Self::SubscribeStream>> {
         self.subscribe2(
             request,
             "subscribe",
-            |message| message.ping.map(|msg| msg.id),
+            |message| message.request.ping.map(|msg| msg.id),
             |limits, message| {
-                let subscribe_from_slot = message.from_slot;
-                let new_filter = ConfigFilter::try_from(message)
+                let subscribe_from_slot = message.request.from_slot;
+                let accounts_snapshot = message.extensions.accounts_snapshot.is_some();
+                let new_filter = ConfigFilter::try_from(message.request)
+                    .and_then(|mut config| {
+                        config.set_extensions(message.extensions)?;
+                        Ok(config)
+                    })
                     .map_err(|error| {
                         Status::invalid_argument(format!("failed to create filter: {error:?}"))
                     })
@@ -572,7 +882,7 @@ impl geyser_gen::geyser_server::Geyser for GrpcServer {
                                 ))
                             })
                     });
-                (subscribe_from_slot, new_filter)
+                (subscribe_from_slot, accounts_snapshot, new_filter)
             },
         )
     }
@@ -596,17 +906,23 @@ impl geyser_gen::geyser_server::Geyser for GrpcServer {
                     })
                 }
 
-                let new_filter = Ok(&mut pubkeys)
-                    .and_then(|pubkeys| {
+                let new_filter = message
+                    .commitment
+                    .map(ConfigFilterCommitment::try_from)
+                    .transpose()
+                    .map_err(|error| format!("failed to create filter: {error:?}"))
+                    .map(Option::unwrap_or_default)
+                    .map(|commitment| (&mut pubkeys, commitment))
+                    .and_then(|(pubkeys, commitment)| {
                         for item in try_conv(message.add) {
                             pubkeys.insert(item?);
                         }
                         for item in try_conv(message.remove) {
                             pubkeys.remove(&item?);
                         }
-                        Ok(pubkeys)
+                        Ok((pubkeys, commitment))
                     })
-                    .and_then(|pubkeys| {
+                    .and_then(|(pubkeys, commitment)| {
                         let config = ConfigFilter {
                             slots: [(message.filter, ConfigFilterSlots::default())]
                                 .into_iter()
@@ -621,6 +937,7 @@ impl geyser_gen::geyser_server::Geyser for GrpcServer {
                             .into_iter()
                             .collect(),
                             blocks_meta: ["".to_owned()].into_iter().collect(),
+                            commitment: Some(commitment),
                             ..Default::default()
                         };
                         limits
@@ -630,7 +947,7 @@ impl geyser_gen::geyser_server::Geyser for GrpcServer {
                     })
                     .map_err(Status::invalid_argument);
 
-                (message.from_slot, new_filter)
+                (message.from_slot, message.snapshot, new_filter)
             },
         )
     }
@@ -648,7 +965,7 @@ impl geyser_gen::geyser_server::Geyser for GrpcServer {
     async fn ping(&self, request: Request<PingRequest>) -> TonicResult<Response<PongResponse>> {
         counter!(
             metrics::GRPC_REQUESTS_TOTAL,
-            "x_subscription_id" => Self::get_x_subscription_id(&request),
+            "x_subscription_id" => self.get_x_subscription_id(&request),
             "method" => "ping"
         )
         .increment(1);
@@ -664,7 +981,7 @@ impl geyser_gen::geyser_server::Geyser for GrpcServer {
     ) -> TonicResult<Response<GetLatestBlockhashResponse>> {
         counter!(
             metrics::GRPC_REQUESTS_TOTAL,
-            "x_subscription_id" => Self::get_x_subscription_id(&request),
+            "x_subscription_id" => self.get_x_subscription_id(&request),
             "method" => "get_latest_blockhash"
         )
         .increment(1);
@@ -687,7 +1004,7 @@ impl geyser_gen::geyser_server::Geyser for GrpcServer {
     ) -> TonicResult<Response<GetBlockHeightResponse>> {
         counter!(
             metrics::GRPC_REQUESTS_TOTAL,
-            "x_subscription_id" => Self::get_x_subscription_id(&request),
+            "x_subscription_id" => self.get_x_subscription_id(&request),
             "method" => "get_block_height"
         )
         .increment(1);
@@ -708,7 +1025,7 @@ impl geyser_gen::geyser_server::Geyser for GrpcServer {
     ) -> TonicResult<Response<GetSlotResponse>> {
         counter!(
             metrics::GRPC_REQUESTS_TOTAL,
-            "x_subscription_id" => Self::get_x_subscription_id(&request),
+            "x_subscription_id" => self.get_x_subscription_id(&request),
             "method" => "get_slot"
         )
         .increment(1);
@@ -727,7 +1044,7 @@ impl geyser_gen::geyser_server::Geyser for GrpcServer {
     ) -> TonicResult<Response<IsBlockhashValidResponse>> {
         counter!(
             metrics::GRPC_REQUESTS_TOTAL,
-            "x_subscription_id" => Self::get_x_subscription_id(&request),
+            "x_subscription_id" => self.get_x_subscription_id(&request),
             "method" => "is_blockhash_valid"
         )
         .increment(1);
@@ -748,7 +1065,7 @@ impl geyser_gen::geyser_server::Geyser for GrpcServer {
     ) -> TonicResult<Response<GetVersionResponse>> {
         counter!(
             metrics::GRPC_REQUESTS_TOTAL,
-            "x_subscription_id" => Self::get_x_subscription_id(&request),
+            "x_subscription_id" => self.get_x_subscription_id(&request),
             "method" => "get_version"
         )
         .increment(1);
@@ -759,7 +1076,7 @@ impl geyser_gen::geyser_server::Geyser for GrpcServer {
     }
 }
 
-type SubscribeMessage = Result<(GrpcSubscribeMessage, Vec<u8>), Status>;
+pub type SubscribeMessage = Result<(GrpcSubscribeMessage, Vec<u8>), Status>;
 
 #[derive(Debug, Clone)]
 pub struct SubscribeClient {
@@ -778,8 +1095,9 @@ impl SubscribeClient {
         messages_len_max: usize,
         messages_replay_len_max: usize,
         x_subscription_id: Arc<str>,
+        filter_index: Option<Arc<RwLock<FilterIndex<u64>>>>,
     ) -> Self {
-        let state = SubscribeClientState::new(id, Arc::clone(&x_subscription_id));
+        let state = SubscribeClientState::new(id, Arc::clone(&x_subscription_id), filter_index);
         Self {
             state: Arc::new(Mutex::new(state)),
             messages: Arc::new(SegQueue::new()),
@@ -791,11 +1109,47 @@ impl SubscribeClient {
         }
     }
 
+    /// Client for Richat protocol subscription replayed from storage, messages are
+    /// consumed by `ReceiverAsync` until replay is switched to the memory channel.
+    pub fn new_richat_replay(
+        messages_replay_len_max: usize,
+        head: u64,
+        replay_to_slot: Option<Slot>,
+        filter: RichatFilterMatcher,
+    ) -> Self {
+        let client = Self::new(
+            0,
+            messages_replay_len_max,
+            messages_replay_len_max,
+            "richat-replay".into(),
+            None,
+        );
+        let mut state = client.state_lock();
+        state.commitment = CommitmentLevel::Processed;
+        state.head = IndexLocation::Storage(head);
+        state.replay_to_slot = replay_to_slot;
+        state.richat_filter = Some(filter);
+        drop(state);
+        client
+    }
+
     #[inline]
     pub fn state_lock(&self) -> MutexGuard<'_, SubscribeClientState> {
         mutex_lock(&self.state)
     }
 
+    pub fn register_waker(&self, waker: &Waker) {
+        self.waker.register(waker);
+    }
+
+    pub fn pop_message(&self) -> Option<SubscribeMessage> {
+        let item = self.messages.pop()?;
+        if let Ok((_message, data)) = &item {
+            self.messages_len.fetch_sub(data.len(), Ordering::Relaxed);
+        }
+        Some(item)
+    }
+
     pub fn push_message(&self, message: GrpcSubscribeMessage, data: Vec<u8>) {
         self.messages_len.fetch_add(data.len(), Ordering::Relaxed);
         self.messages.push(Ok((message, data)));
@@ -807,20 +1161,74 @@ impl SubscribeClient {
         self.waker.wake();
     }
 
+    /// Marks the end of bounded replay, pushed after the last message.
+    pub fn push_finished(&self) {
+        self.messages.push(Err(Status::ok("replay finished")));
+        self.waker.wake();
+    }
+
     /// Wake the stream's poll_next after pushing messages.
     pub fn wake(&self) {
         self.waker.wake();
     }
 }
 
+impl AdminClientState for SubscribeClient {
+    fn filter(&self) -> serde_json::Value {
+        let state = self.state_lock();
+        state
+            .filter
+            .as_ref()
+            .map(|filter| serde_json::to_value(filter.summary()).expect("valid summary"))
+            .unwrap_or_default()
+    }
+
+    fn lag(&self, messages: &Messages) -> AdminClientLag {
+        let (commitment, head) = {
+            let state = self.state_lock();
+            (state.commitment, state.head)
+        };
+        let (lag_messages, lag_slots) = match head {
+            IndexLocation::Memory(head) => {
+                let (lag_messages, lag_slots) = messages.get_lag(commitment, head);
+                (Some(lag_messages), lag_slots)
+            }
+            IndexLocation::Storage(_) | IndexLocation::Unknown => (None, None),
+        };
+        AdminClientLag {
+            messages: lag_messages,
+            slots: lag_slots,
+            queue_bytes: Some(self.messages_len.load(Ordering::Relaxed) as u64),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct AccountsSnapshotQueue {
+    slot: Slot,
+    accounts: u64,
+    updates: std::vec::IntoIter<Vec<u8>>,
+}
+
 #[derive(Debug)]
 pub struct SubscribeClientState {
     pub finished: bool, // check in workers with acquired mutex
     id: u64,
     x_subscription_id: Arc<str>,
-    commitment: CommitmentLevel,
+    pub commitment: CommitmentLevel,
     pub head: IndexLocation,
     pub filter: Option<Filter>,
+    pub richat_filter: Option<RichatFilterMatcher>,
+    pub replay_from_slot: Option<Slot>,
+    pub replay_to_slot: Option<Slot>,
+    // storage index of the last sent message, used to replay lagged client
+    pub last_replay_index: Option<u64>,
+    accounts_conflation: AccountsConflation,
+    // sent before messages from the channel, within the same limits
+    accounts_snapshot: Option<AccountsSnapshotQueue>,
+    // parent chain of slots for rollback notifications
+    forks: Option<Forks>,
+    filter_index: Option<Arc<RwLock<FilterIndex<u64>>>>,
     metric_cpu_usage: Gauge,
 }
 
@@ -831,13 +1239,23 @@ impl Drop for SubscribeClientState {
             x_subscription_id = self.x_subscription_id.as_ref(),
             "drop client state"
         );
+        if let Some(filter_index) = &self.filter_index {
+            filter_index
+                .write()
+                .expect("filter index poisoned")
+                .remove(&self.id);
+        }
         gauge!(metrics::GRPC_SUBSCRIBE_TOTAL, "x_subscription_id" => Arc::clone(&self.x_subscription_id))
             .decrement(1);
     }
 }
 
 impl SubscribeClientState {
-    fn new(id: u64, x_subscription_id: Arc<str>) -> Self {
+    fn new(
+        id: u64,
+        x_subscription_id: Arc<str>,
+        filter_index: Option<Arc<RwLock<FilterIndex<u64>>>>,
+    ) -> Self {
         info!(
             id,
             x_subscription_id = x_subscription_id.as_ref(),
@@ -858,10 +1276,72 @@ impl SubscribeClientState {
             commitment: CommitmentLevel::default(),
             head: IndexLocation::Unknown,
             filter: None,
+            richat_filter: None,
+            replay_from_slot: None,
+            replay_to_slot: None,
+            last_replay_index: None,
+            accounts_conflation: AccountsConflation::default(),
+            accounts_snapshot: None,
+            forks: None,
+            filter_index,
             metric_cpu_usage,
         }
     }
 
+    /// Push accounts snapshot updates while the queue is under the limit, the snapshot
+    /// notification is pushed after the last update. Returns `true` if the snapshot is sent.
+    fn push_accounts_snapshot(
+        &mut self,
+        client: &SubscribeClient,
+        messages_len: &mut usize,
+        messages_counter: &mut usize,
+        messages_max_per_tick: usize,
+    ) -> bool {
+        let Some(snapshot) = self.accounts_snapshot.as_mut() else {
+            return true;
+        };
+        while *messages_len <= client.messages_len_max && *messages_counter < messages_max_per_tick
+        {
+            *messages_counter += 1;
+            let Some(data) = snapshot.updates.next() else {
+                let data = Self::create_accounts_snapshot(snapshot.slot, snapshot.accounts);
+                *messages_len += data.len();
+                client.push_message(GrpcSubscribeMessage::AccountsSnapshot, data);
+                self.accounts_snapshot = None;
+                return true;
+            };
+            *messages_len += data.len();
+            client.push_message(GrpcSubscribeMessage::Account, data);
+        }
+        false
+    }
+
+    /// Push rollback notification if the slot message abandons a fork, returns size of pushed data.
+    pub fn push_rollback(&mut self, client: &SubscribeClient, message: &ParsedMessage) -> usize {
+        let (Some(forks), ParsedMessage::Slot(msg)) = (self.forks.as_mut(), message) else {
+            return 0;
+        };
+        let Some(rollback) = forks.update(msg) else {
+            return 0;
+        };
+        info!(
+            id = self.id,
+            slot_first = rollback.slot_first,
+            slot_last = rollback.slot_last,
+            "rollback notification"
+        );
+
+        // delayed updates can belong to rolled back slots
+        let mut messages_len = 0;
+        if !self.accounts_conflation.is_empty() {
+            messages_len += self.accounts_conflation.flush(client);
+        }
+        let data = Self::create_rollback(rollback);
+        messages_len += data.len();
+        client.push_message(GrpcSubscribeMessage::Rollback, data);
+        messages_len
+    }
+
     #[inline]
     fn serialize_ping_pong(oneof: UpdateOneof) -> Vec<u8> {
         SubscribeUpdate {
@@ -881,18 +1361,47 @@ impl SubscribeClientState {
     fn create_pong(id: i32) -> Vec<u8> {
         Self::serialize_ping_pong(UpdateOneof::Pong(SubscribeUpdatePong { id }))
     }
+
+    /// Richat updates are not part of `geyser.SubscribeUpdate`, encoded as unknown
+    /// `update_oneof` field for clients without richat extensions.
+    fn serialize_extension(tag: u32, message: &impl Message) -> Vec<u8> {
+        let mut data = SubscribeUpdate {
+            filters: vec![],
+            update_oneof: None,
+            created_at: Some(SystemTime::now().into()),
+        }
+        .encode_to_vec();
+        prost::encoding::message::encode(tag, message, &mut data);
+        data
+    }
+
+    fn create_accounts_snapshot(slot: Slot, accounts: u64) -> Vec<u8> {
+        Self::serialize_extension(100, &SubscribeUpdateAccountsSnapshot { slot, accounts })
+    }
+
+    fn create_rollback(rollback: SubscribeUpdateRollback) -> Vec<u8> {
+        Self::serialize_extension(101, &rollback)
+    }
 }
 
 #[derive(Debug)]
 pub struct ReceiverStream {
     client: SubscribeClient,
+    subscription: Option<TenantSubscription>,
+    _admin: AdminClientHandle,
     finished: bool,
 }
 
 impl ReceiverStream {
-    const fn new(client: SubscribeClient) -> Self {
+    const fn new(
+        client: SubscribeClient,
+        subscription: Option<TenantSubscription>,
+        admin: AdminClientHandle,
+    ) -> Self {
         Self {
             client,
+            subscription,
+            _admin: admin,
             finished: false,
         }
     }
@@ -913,14 +1422,17 @@ impl Stream for ReceiverStream {
             return Poll::Ready(None);
         }
 
-        self.client.waker.register(cx.waker());
+        self.client.register_waker(cx.waker());
+        if let Some(subscription) = self.subscription.as_mut() {
+            ready!(subscription.poll_budget(cx));
+        }
 
-        if let Some(item) = self.client.messages.pop() {
+        if let Some(item) = self.client.pop_message() {
             let item = match item {
                 Ok((message, data)) => {
-                    self.client
-                        .messages_len
-                        .fetch_sub(data.len(), Ordering::Relaxed);
+                    if let Some(subscription) = self.subscription.as_mut() {
+                        subscription.consume(data.len());
+                    }
                     counter!(
                         metrics::GRPC_SUBSCRIBE_MESSAGES_COUNT_TOTAL,
                         "x_subscription_id" => Arc::clone(&self.client.x_subscription_id),
@@ -968,6 +1480,8 @@ impl MessagesCache {
             .map(|_| MessagesCacheItem {
                 pos: u64::MAX,
                 msg: None,
+                matches: None,
+                replay_index: u64::MAX,
             })
             .collect::<Vec<_>>();
 
@@ -988,7 +1502,9 @@ impl MessagesCache {
         receiver: &ReceiverSync,
         commitment: CommitmentLevel,
         head: u64,
-    ) -> Result<Option<Cow<'_, ParsedMessage>>, RecvError> {
+        filter_index: Option<&RwLock<FilterIndex<u64>>>,
+        key: u64,
+    ) -> Result<Option<MessagesCacheRecv<'_>>, RecvError> {
         if head > self.head {
             self.head = head;
         }
@@ -997,26 +1513,251 @@ impl MessagesCache {
         // return if item cached
         let idx = self.get_idx(head);
         if inrange && self.buffer[idx].pos == head {
-            return Ok(self.buffer[idx].msg.as_ref().map(Cow::Borrowed));
+            let item = &mut self.buffer[idx];
+            let Some(msg) = item.msg.as_ref() else {
+                return Ok(None);
+            };
+            let matches = filter_index.and_then(|index| {
+                MessagesCacheItem::get_matches(msg, &mut item.matches, index, key)
+            });
+            return Ok(Some((Cow::Borrowed(msg), matches, item.replay_index)));
         }
 
         // try to get from the channel
-        let Some(item) = receiver.try_recv(commitment, head)? else {
+        let Some((msg, replay_index)) = receiver.try_recv_indexed(commitment, head)? else {
             return Ok(None);
         };
 
         // save item if in range
+        let mut cached = None;
+        let matches = filter_index
+            .and_then(|index| MessagesCacheItem::get_matches(&msg, &mut cached, index, key));
         if inrange {
             self.buffer[idx] = MessagesCacheItem {
                 pos: head,
-                msg: Some(item.clone()),
+                msg: Some(msg.clone()),
+                matches: cached,
+                replay_index,
             };
         }
-        Ok(Some(Cow::Owned(item)))
+        Ok(Some((Cow::Owned(msg), matches, replay_index)))
     }
 }
 
+type MessagesCacheRecv<'a> = (
+    Cow<'a, ParsedMessage>,
+    Option<Arc<FilterIndexMatches<u64>>>,
+    u64,
+);
+
 struct MessagesCacheItem {
     pos: u64,
     msg: Option<ParsedMessage>,
+    matches: Option<Arc<FilterIndexMatches<u64>>>,
+    replay_index: u64,
+}
+
+impl MessagesCacheItem {
+    fn get_matches(
+        msg: &ParsedMessage,
+        cached: &mut Option<Arc<FilterIndexMatches<u64>>>,
+        index: &RwLock<FilterIndex<u64>>,
+        key: u64,
+    ) -> Option<Arc<FilterIndexMatches<u64>>> {
+        let message_ref: MessageRef = msg.into();
+        if !FilterIndex::<u64>::is_indexed(message_ref) {
+            return None;
+        }
+
+        let index = index.read().expect("filter index poisoned");
+        if let Some(matches) = cached {
+            if index.is_actual(matches, &key) {
+                return Some(Arc::clone(matches));
+            }
+        }
+
+        let matches = Arc::new(index.get_matches(message_ref));
+        *cached = Some(Arc::clone(&matches));
+        Some(matches)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{GrpcServer, SubscribeClient},
+        crate::{
+            admin::AdminClients,
+            channel::{IndexLocation, Messages},
+            config::ConfigChannelInner,
+            storage::tests::TestStorage,
+            tenants::Tenants,
+        },
+        prost::Message as _,
+        quanta::Instant,
+        richat_filter::{
+            config::{ConfigFilter, ConfigFilterSlots},
+            filter::Filter,
+            message::{Message, MessageParserEncoding},
+        },
+        richat_proto::geyser::{
+            SlotStatus, SubscribeUpdate, SubscribeUpdateSlot, subscribe_update::UpdateOneof,
+        },
+        solana_clock::Slot,
+        solana_commitment_config::CommitmentLevel,
+        std::{borrow::Cow, sync::atomic::Ordering, thread, time::Duration},
+        tokio_util::sync::CancellationToken,
+    };
+
+    fn slot(slot: Slot) -> Message {
+        let data = SubscribeUpdate {
+            filters: vec![],
+            update_oneof: Some(UpdateOneof::Slot(SubscribeUpdateSlot {
+                slot,
+                parent: slot.checked_sub(1),
+                status: SlotStatus::SlotProcessed as i32,
+                dead_error: None,
+            })),
+            created_at: Some(Default::default()),
+        }
+        .encode_to_vec();
+        Message::parse(Cow::Owned(data), MessageParserEncoding::Prost).expect("valid message")
+    }
+
+    fn recv_until(client: &SubscribeClient, slot_last: Slot, received: &mut Vec<Slot>) {
+        let ts = Instant::now();
+        while received.last() != Some(&slot_last) {
+            assert!(
+                ts.elapsed() < Duration::from_secs(10),
+                "messages are not received: {received:?}"
+            );
+            match client.pop_message() {
+                Some(Ok((_message, data))) => {
+                    match SubscribeUpdate::decode(data.as_slice())
+                        .unwrap()
+                        .update_oneof
+                    {
+                        Some(UpdateOneof::Slot(msg)) => received.push(msg.slot),
+                        update => panic!("unexpected update: {update:?}"),
+                    }
+                }
+                Some(Err(status)) => panic!("unexpected error: {status:?}"),
+                None => thread::sleep(Duration::from_millis(1)),
+            }
+        }
+    }
+
+    #[test]
+    fn test_lagged_replay() {
+        let config = TestStorage::config("grpc-lagged-replay", None);
+        let shutdown = CancellationToken::new();
+        let (mut messages, threads) = Messages::new(
+            MessageParserEncoding::Prost,
+            ConfigChannelInner {
+                max_messages: 16,
+                max_bytes: 16 * 1024 * 1024,
+                storage: Some(config),
+            },
+            false,
+            false,
+            false,
+            true,
+            shutdown.clone(),
+        )
+        .unwrap();
+        let (mut sender, _replay_from_slot) = messages.to_sender(1).unwrap();
+        let storage = messages.storage().expect("defined storage").clone();
+
+        let server = GrpcServer {
+            shutdown: shutdown.clone(),
+            messages: messages.clone(),
+            block_meta: None,
+            accounts_caches: None,
+            filter_limits: Default::default(),
+            tenants: Tenants::new(vec![]),
+            admin: AdminClients::default(),
+            filter_index: None,
+            ping_interval: Duration::from_secs(60),
+            subscribe_id: Default::default(),
+            subscribe_clients: Default::default(),
+            // worker stops after every message until the client reads it
+            subscribe_messages_len_max: 1,
+            subscribe_messages_replay_len_max: 1,
+            subscribe_lagged_replay: true,
+        };
+        let client = SubscribeClient::new(0, 1, 1, "test".into(), None);
+        let mut state = client.state_lock();
+        state.commitment = CommitmentLevel::Processed;
+        state.head =
+            IndexLocation::Memory(messages.get_current_tail(CommitmentLevel::Processed) + 1);
+        state.filter = Some(Filter::new(&ConfigFilter {
+            slots: [("".to_owned(), ConfigFilterSlots::default())]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        }));
+        drop(state);
+        server.push_client(client.clone());
+        let worker = thread::spawn({
+            let server = server.clone();
+            let shutdown = shutdown.clone();
+            move || server.worker_messages(0, 16, 1_000, 16, shutdown)
+        });
+
+        let mut received = vec![];
+        let mut push = |slots: std::ops::RangeInclusive<Slot>| {
+            let index_last = *slots.end();
+            for slot_value in slots {
+                sender.push(false, "test", slot(slot_value));
+            }
+            let ts = Instant::now();
+            while storage.next_index() < index_last {
+                assert!(
+                    ts.elapsed() < Duration::from_secs(10),
+                    "messages are not written"
+                );
+                thread::sleep(Duration::from_millis(1));
+            }
+        };
+
+        // the last message is not visible to receivers until the next one is pushed,
+        // the first message is in the client queue, the next ones are evicted from memory
+        push(1..=2);
+        let ts = Instant::now();
+        while client.messages_len.load(Ordering::Relaxed) == 0 {
+            assert!(
+                ts.elapsed() < Duration::from_secs(10),
+                "message is not sent"
+            );
+            thread::sleep(Duration::from_millis(1));
+        }
+        push(3..=64);
+        recv_until(&client, 64, &mut received);
+
+        // replay is switched back to memory
+        let ts = Instant::now();
+        while !matches!(client.state_lock().head, IndexLocation::Memory(_)) {
+            assert!(
+                ts.elapsed() < Duration::from_secs(10),
+                "replay is not switched to memory"
+            );
+            thread::sleep(Duration::from_millis(1));
+        }
+        push(65..=69);
+        recv_until(&client, 68, &mut received);
+        assert_eq!(received, (1..=68).collect::<Vec<_>>());
+        assert!(matches!(client.state_lock().head, IndexLocation::Memory(_)));
+
+        shutdown.cancel();
+        worker.join().unwrap().unwrap();
+        drop(server);
+        drop(sender);
+        drop(messages);
+        drop(storage);
+        for (_name, jh) in threads {
+            if let Some(jh) = jh {
+                jh.join().unwrap().unwrap();
+            }
+        }
+    }
 }
@@ -0,0 +1,45 @@
+use {
+    richat_shared::config::{
+        deserialize_affinity, deserialize_humansize_usize, deserialize_num_str,
+    },
+    serde::Deserialize,
+    std::{
+        io,
+        net::{IpAddr, Ipv4Addr, SocketAddr},
+    },
+    tokio::net::TcpStream,
+};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct ConfigAppsJsonrpc {
+    pub endpoint: SocketAddr,
+    pub tcp_nodelay: Option<bool>,
+    #[serde(deserialize_with = "deserialize_humansize_usize")]
+    pub request_body_max_size: usize,
+    #[serde(deserialize_with = "deserialize_affinity")]
+    pub block_meta_affinity: Option<Vec<usize>>,
+    #[serde(deserialize_with = "deserialize_num_str")]
+    pub requests_queue_size: usize,
+}
+
+impl Default for ConfigAppsJsonrpc {
+    fn default() -> Self {
+        Self {
+            endpoint: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8899),
+            tcp_nodelay: None,
+            request_body_max_size: 50 * 1024, // 50KiB, same as in Agave
+            block_meta_affinity: None,
+            requests_queue_size: 100,
+        }
+    }
+}
+
+impl ConfigAppsJsonrpc {
+    pub fn set_accepted_socket_options(&self, stream: &TcpStream) -> io::Result<()> {
+        if let Some(nodelay) = self.tcp_nodelay {
+            stream.set_nodelay(nodelay)?;
+        }
+        Ok(())
+    }
+}
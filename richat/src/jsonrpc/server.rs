@@ -0,0 +1,553 @@
+use {
+    crate::{
+        channel::Messages,
+        config::ConfigAppsWorkers,
+        grpc::block_meta::{BlockMeta, BlockMetaStorage},
+        jsonrpc::config::ConfigAppsJsonrpc,
+    },
+    futures::future::{BoxFuture, FutureExt, TryFutureExt, ready, try_join_all},
+    http_body_util::{BodyExt, Empty as BodyEmpty},
+    hyper::{
+        HeaderMap, Response as HttpResponse, StatusCode, body::Incoming as BodyIncoming,
+        service::service_fn,
+    },
+    hyper_util::{
+        rt::tokio::{TokioExecutor, TokioIo},
+        server::conn::auto::Builder as ServerBuilder,
+    },
+    jsonrpsee_types::{ErrorCode, ErrorObject, ErrorObjectOwned, Id, Params, Request},
+    richat_proto::geyser::CommitmentLevel as CommitmentLevelProto,
+    richat_shared::jsonrpc::{
+        helpers::{jsonrpc_error_custom, jsonrpc_response_error, jsonrpc_response_success},
+        requests::{RpcRequestResult, RpcRequestsProcessor},
+    },
+    serde::{Deserialize, Serialize, de::DeserializeOwned},
+    serde_json::value::RawValue,
+    solana_clock::{MAX_PROCESSING_AGE, Slot},
+    solana_commitment_config::CommitmentLevel,
+    solana_rpc_client_api::{
+        config::RpcContextConfig,
+        custom_error::RpcCustomError,
+        response::{Response as RpcResponse, RpcBlockhash, RpcResponseContext, RpcVersionInfo},
+    },
+    std::{borrow::Cow, future::Future, net::TcpListener as StdTcpListener, sync::Arc},
+    tokio::net::TcpListener,
+    tokio_util::sync::CancellationToken,
+    tonic::Status,
+    tracing::{error, info, warn},
+};
+
+#[derive(Debug)]
+pub struct JsonrpcServer;
+
+impl JsonrpcServer {
+    pub fn spawn(
+        mut config: ConfigAppsJsonrpc,
+        messages: Messages,
+        shutdown: CancellationToken,
+    ) -> anyhow::Result<impl Future<Output = anyhow::Result<()>>> {
+        let std_listener = StdTcpListener::bind(config.endpoint)?;
+        std_listener.set_nonblocking(true)?;
+
+        let listener = TcpListener::from_std(std_listener)?;
+        info!("start server at {}", config.endpoint);
+
+        // BlockMeta thread & task
+        let (block_meta, block_meta_task_jh) = BlockMetaStorage::new(config.requests_queue_size);
+        let block_meta_jh = ConfigAppsWorkers::run_once(
+            0,
+            "richatRpcWrkBM".to_owned(),
+            config.block_meta_affinity.take(),
+            {
+                let block_meta = block_meta.clone();
+                let shutdown = shutdown.clone();
+                move |_index| block_meta.run_worker(messages, shutdown)
+            },
+            shutdown.clone(),
+        )?
+        .boxed();
+
+        // Requests processor
+        let processor = Arc::new(Self::create_processor(
+            config.request_body_max_size,
+            block_meta,
+        ));
+
+        // Spawn server
+        let server_jh = tokio::spawn(Self::serve(listener, config, processor, shutdown))
+            .map_err(anyhow::Error::new)
+            .and_then(ready)
+            .boxed();
+
+        // Wait spawned features
+        Ok(try_join_all([block_meta_jh, block_meta_task_jh.boxed(), server_jh]).map_ok(|_| ()))
+    }
+
+    fn create_processor(
+        request_body_max_size: usize,
+        block_meta: BlockMetaStorage,
+    ) -> RpcRequestsProcessor<BlockMetaStorage> {
+        let mut processor =
+            RpcRequestsProcessor::new(request_body_max_size, block_meta, HeaderMap::new());
+        processor
+            .add_handler("getBlockHeight", Box::new(Self::get_block_height))
+            .add_handler("getLatestBlockhash", Box::new(Self::get_latest_blockhash))
+            .add_handler("getSlot", Box::new(Self::get_slot))
+            .add_handler("getVersion", Box::new(Self::get_version))
+            .add_handler("isBlockhashValid", Box::new(Self::is_blockhash_valid));
+        processor
+    }
+
+    async fn serve(
+        listener: TcpListener,
+        config: ConfigAppsJsonrpc,
+        processor: Arc<RpcRequestsProcessor<BlockMetaStorage>>,
+        shutdown: CancellationToken,
+    ) -> anyhow::Result<()> {
+        loop {
+            // accept connection
+            let stream = tokio::select! {
+                incoming = listener.accept() => match incoming {
+                    Ok((stream, _addr)) => {
+                        if let Err(error) = config.set_accepted_socket_options(&stream) {
+                            warn!("failed to set socket options {error:?}");
+                        }
+                        stream
+                    }
+                    Err(error) => {
+                        error!("failed to accept new connection: {error}");
+                        break;
+                    }
+                },
+                () = shutdown.cancelled() => break,
+            };
+
+            let service = service_fn({
+                let processor = Arc::clone(&processor);
+                move |req: hyper::Request<BodyIncoming>| {
+                    let processor = Arc::clone(&processor);
+                    async move {
+                        match req.uri().path() {
+                            "/" => processor.on_request(req).await,
+                            _ => HttpResponse::builder()
+                                .status(StatusCode::NOT_FOUND)
+                                .body(BodyEmpty::new().boxed()),
+                        }
+                    }
+                }
+            });
+
+            tokio::spawn(async move {
+                if let Err(error) = ServerBuilder::new(TokioExecutor::new())
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await
+                {
+                    error!("Error serving HTTP connection: {error:?}");
+                }
+            });
+        }
+        Ok(())
+    }
+
+    fn get_block_height(
+        storage: BlockMetaStorage,
+        _x_subscription_id: Arc<str>,
+        _upstream_disabled: bool,
+        request: Request<'_>,
+    ) -> BoxFuture<'_, RpcRequestResult> {
+        async move {
+            #[derive(Debug, Deserialize)]
+            struct ReqParams {
+                #[serde(default)]
+                config: Option<RpcContextConfig>,
+            }
+
+            let result = match parse_params(request.params) {
+                Ok(ReqParams { config }) => get_block(&storage, config)
+                    .await
+                    .map(|block| block.block_height),
+                Err(error) => Err(error),
+            };
+            Ok(response(request.id, result))
+        }
+        .boxed()
+    }
+
+    fn get_latest_blockhash(
+        storage: BlockMetaStorage,
+        _x_subscription_id: Arc<str>,
+        _upstream_disabled: bool,
+        request: Request<'_>,
+    ) -> BoxFuture<'_, RpcRequestResult> {
+        async move {
+            #[derive(Debug, Deserialize)]
+            struct ReqParams {
+                #[serde(default)]
+                config: Option<RpcContextConfig>,
+            }
+
+            let result = match parse_params(request.params) {
+                Ok(ReqParams { config }) => {
+                    get_block(&storage, config).await.map(|block| RpcResponse {
+                        context: RpcResponseContext::new(block.slot),
+                        value: RpcBlockhash {
+                            blockhash: block.blockhash.as_ref().clone(),
+                            last_valid_block_height: block.block_height + MAX_PROCESSING_AGE as u64,
+                        },
+                    })
+                }
+                Err(error) => Err(error),
+            };
+            Ok(response(request.id, result))
+        }
+        .boxed()
+    }
+
+    fn get_slot(
+        storage: BlockMetaStorage,
+        _x_subscription_id: Arc<str>,
+        _upstream_disabled: bool,
+        request: Request<'_>,
+    ) -> BoxFuture<'_, RpcRequestResult> {
+        async move {
+            #[derive(Debug, Deserialize)]
+            struct ReqParams {
+                #[serde(default)]
+                config: Option<RpcContextConfig>,
+            }
+
+            let result = match parse_params(request.params) {
+                Ok(ReqParams { config }) => {
+                    get_block(&storage, config).await.map(|block| block.slot)
+                }
+                Err(error) => Err(error),
+            };
+            Ok(response(request.id, result))
+        }
+        .boxed()
+    }
+
+    fn get_version(
+        _storage: BlockMetaStorage,
+        _x_subscription_id: Arc<str>,
+        _upstream_disabled: bool,
+        request: Request<'_>,
+    ) -> BoxFuture<'_, RpcRequestResult> {
+        async move {
+            let version = solana_version::Version::default();
+            Ok(jsonrpc_response_success(
+                request.id,
+                RpcVersionInfo {
+                    solana_core: version.to_string(),
+                    feature_set: Some(version.feature_set),
+                },
+            ))
+        }
+        .boxed()
+    }
+
+    fn is_blockhash_valid(
+        storage: BlockMetaStorage,
+        _x_subscription_id: Arc<str>,
+        _upstream_disabled: bool,
+        request: Request<'_>,
+    ) -> BoxFuture<'_, RpcRequestResult> {
+        async move {
+            #[derive(Debug, Deserialize)]
+            struct ReqParams {
+                blockhash: String,
+                #[serde(default)]
+                config: Option<RpcContextConfig>,
+            }
+
+            let result = match parse_params(request.params) {
+                Ok(ReqParams { blockhash, config }) => {
+                    let config = config.unwrap_or_default();
+                    match storage
+                        .get_blockhash_status(blockhash, get_commitment(config))
+                        .await
+                    {
+                        Ok((valid, slot)) => {
+                            check_min_context_slot(config, slot).map(|()| RpcResponse {
+                                context: RpcResponseContext::new(slot),
+                                value: valid.unwrap_or(false),
+                            })
+                        }
+                        Err(status) => Err(status_to_error(status)),
+                    }
+                }
+                Err(error) => Err(error),
+            };
+            Ok(response(request.id, result))
+        }
+        .boxed()
+    }
+}
+
+fn parse_params<T: DeserializeOwned>(
+    params: Option<Cow<'_, RawValue>>,
+) -> Result<T, ErrorObjectOwned> {
+    // missed params are the same as empty array
+    Params::new(Some(params.as_ref().map_or("[]", |params| params.get()))).parse()
+}
+
+fn response<T: Clone + Serialize>(id: Id<'_>, result: Result<T, ErrorObjectOwned>) -> Vec<u8> {
+    match result {
+        Ok(payload) => jsonrpc_response_success(id, payload),
+        Err(error) => jsonrpc_response_error(id, error),
+    }
+}
+
+fn get_commitment(config: RpcContextConfig) -> CommitmentLevelProto {
+    // finalized by default, same as in Agave
+    match config.commitment.unwrap_or_default().commitment {
+        CommitmentLevel::Processed => CommitmentLevelProto::Processed,
+        CommitmentLevel::Confirmed => CommitmentLevelProto::Confirmed,
+        CommitmentLevel::Finalized => CommitmentLevelProto::Finalized,
+    }
+}
+
+fn check_min_context_slot(config: RpcContextConfig, slot: Slot) -> Result<(), ErrorObjectOwned> {
+    match config.min_context_slot {
+        Some(min_context_slot) if slot < min_context_slot => Err(jsonrpc_error_custom(
+            RpcCustomError::MinContextSlotNotReached { context_slot: slot },
+        )),
+        _ => Ok(()),
+    }
+}
+
+async fn get_block(
+    storage: &BlockMetaStorage,
+    config: Option<RpcContextConfig>,
+) -> Result<BlockMeta, ErrorObjectOwned> {
+    let config = config.unwrap_or_default();
+    let block = storage
+        .get_block(get_commitment(config))
+        .await
+        .map_err(status_to_error)?;
+    check_min_context_slot(config, block.slot)?;
+    Ok(block)
+}
+
+fn status_to_error(status: Status) -> ErrorObjectOwned {
+    ErrorObject::owned::<()>(ErrorCode::InternalError.code(), status.message(), None)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::JsonrpcServer,
+        crate::{grpc::block_meta::BlockMetaStorage, jsonrpc::config::ConfigAppsJsonrpc},
+        prost::Message as _,
+        richat_filter::message::{Message, MessageParserEncoding},
+        richat_proto::{
+            geyser::{
+                SlotStatus, SubscribeUpdate, SubscribeUpdateBlockMeta, SubscribeUpdateSlot,
+                subscribe_update::UpdateOneof,
+            },
+            solana::storage::confirmed_block::BlockHeight,
+        },
+        serde_json::{Value, json},
+        solana_clock::MAX_PROCESSING_AGE,
+        std::{borrow::Cow, net::SocketAddr, sync::Arc},
+        tokio::net::TcpListener,
+        tokio_util::sync::CancellationToken,
+    };
+
+    fn parse(update_oneof: UpdateOneof) -> Message {
+        let data = SubscribeUpdate {
+            filters: vec![],
+            update_oneof: Some(update_oneof),
+            created_at: Some(Default::default()),
+        }
+        .encode_to_vec();
+        Message::parse(Cow::Owned(data), MessageParserEncoding::Prost).expect("valid message")
+    }
+
+    fn block_meta(slot: u64) -> Message {
+        parse(UpdateOneof::BlockMeta(SubscribeUpdateBlockMeta {
+            slot,
+            blockhash: format!("hash{slot}"),
+            block_height: Some(BlockHeight {
+                block_height: slot * 10,
+            }),
+            ..Default::default()
+        }))
+    }
+
+    fn slot(slot: u64, status: SlotStatus) -> Message {
+        parse(UpdateOneof::Slot(SubscribeUpdateSlot {
+            slot,
+            parent: None,
+            status: status as i32,
+            dead_error: None,
+        }))
+    }
+
+    /// Server with processed slot 12, confirmed slot 11 and finalized slot 10.
+    async fn spawn_server(shutdown: CancellationToken) -> SocketAddr {
+        let (storage, _storage_task_jh) = BlockMetaStorage::new(100);
+        for message in [
+            block_meta(10),
+            block_meta(11),
+            block_meta(12),
+            slot(11, SlotStatus::SlotConfirmed),
+            slot(10, SlotStatus::SlotFinalized),
+        ] {
+            storage.push(message.into());
+        }
+
+        let config = ConfigAppsJsonrpc::default();
+        let processor = Arc::new(JsonrpcServer::create_processor(
+            config.request_body_max_size,
+            storage,
+        ));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = listener.local_addr().unwrap();
+        tokio::spawn(JsonrpcServer::serve(listener, config, processor, shutdown));
+        endpoint
+    }
+
+    async fn request(endpoint: SocketAddr, body: Value) -> Value {
+        let response = reqwest::Client::new()
+            .post(format!("http://{endpoint}/"))
+            .header("content-type", "application/json")
+            .body(body.to_string())
+            .send()
+            .await
+            .unwrap();
+        assert!(response.status().is_success());
+        serde_json::from_slice(&response.bytes().await.unwrap()).unwrap()
+    }
+
+    fn call(method: &str, params: Value) -> Value {
+        json!({"jsonrpc": "2.0", "id": 1, "method": method, "params": params})
+    }
+
+    async fn result(endpoint: SocketAddr, method: &str, params: Value) -> Value {
+        let response = request(endpoint, call(method, params)).await;
+        assert_eq!(response["id"], 1, "{response}");
+        response
+            .get("result")
+            .unwrap_or_else(|| panic!("no result: {response}"))
+            .clone()
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_commitment() {
+        let shutdown = CancellationToken::new();
+        let endpoint = spawn_server(shutdown.clone()).await;
+
+        // finalized by default
+        for (config, slot) in [
+            (json!([]), 10),
+            (json!([{"commitment": "processed"}]), 12),
+            (json!([{"commitment": "confirmed"}]), 11),
+            (json!([{"commitment": "finalized"}]), 10),
+        ] {
+            assert_eq!(result(endpoint, "getSlot", config.clone()).await, slot);
+            assert_eq!(
+                result(endpoint, "getBlockHeight", config.clone()).await,
+                slot * 10
+            );
+            let value = result(endpoint, "getLatestBlockhash", config).await;
+            assert_eq!(value["context"]["slot"], slot);
+            assert_eq!(
+                value["value"],
+                json!({
+                    "blockhash": format!("hash{slot}"),
+                    "lastValidBlockHeight": slot * 10 + MAX_PROCESSING_AGE as u64,
+                })
+            );
+        }
+        // params could be omitted
+        assert_eq!(
+            request(
+                endpoint,
+                json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot"})
+            )
+            .await["result"],
+            10
+        );
+
+        for (params, slot, valid) in [
+            (json!(["hash10"]), 10, true),
+            (json!(["hash12", {"commitment": "processed"}]), 12, true),
+            (json!(["hash11", {"commitment": "confirmed"}]), 11, true),
+            (json!(["unknown", {"commitment": "processed"}]), 12, false),
+        ] {
+            let value = result(endpoint, "isBlockhashValid", params.clone()).await;
+            assert_eq!(value["context"]["slot"], slot, "{params}");
+            assert_eq!(value["value"], valid, "{params}");
+        }
+
+        shutdown.cancel();
+    }
+
+    #[tokio::test]
+    async fn test_batch() {
+        let shutdown = CancellationToken::new();
+        let endpoint = spawn_server(shutdown.clone()).await;
+
+        let response = request(
+            endpoint,
+            json!([
+                {"jsonrpc": "2.0", "id": 1, "method": "getSlot"},
+                {"jsonrpc": "2.0", "id": 2, "method": "getBalance", "params": []},
+                {"jsonrpc": "2.0", "id": 3, "method": "getSlot", "params": [{"commitment": "processed"}]},
+                {"jsonrpc": "2.0", "id": 4, "method": "isBlockhashValid", "params": []},
+            ]),
+        )
+        .await;
+        let responses = response.as_array().unwrap();
+        assert_eq!(responses.len(), 4);
+        assert_eq!(
+            responses[0],
+            json!({"jsonrpc": "2.0", "id": 1, "result": 10})
+        );
+        assert_eq!(responses[1]["id"], 2);
+        assert_eq!(responses[1]["error"]["code"], -32601);
+        assert_eq!(
+            responses[2],
+            json!({"jsonrpc": "2.0", "id": 3, "result": 12})
+        );
+        assert_eq!(responses[3]["id"], 4);
+        assert_eq!(responses[3]["error"]["code"], -32602);
+
+        assert_eq!(request(endpoint, json!([])).await, json!([]));
+
+        shutdown.cancel();
+    }
+
+    #[tokio::test]
+    async fn test_errors() {
+        let shutdown = CancellationToken::new();
+        let endpoint = spawn_server(shutdown.clone()).await;
+
+        assert_eq!(
+            request(endpoint, call("getBalance", json!([]))).await,
+            json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "error": {"code": -32601, "message": "Method not found"},
+            })
+        );
+
+        for (method, params) in [
+            ("getSlot", json!(["processed"])),
+            ("isBlockhashValid", json!([])),
+            ("isBlockhashValid", json!([42])),
+        ] {
+            let response = request(endpoint, call(method, params.clone())).await;
+            assert_eq!(response["jsonrpc"], "2.0", "{params}");
+            assert_eq!(response["id"], 1, "{params}");
+            assert!(response.get("result").is_none(), "{params}");
+            let error = response["error"].as_object().unwrap();
+            assert_eq!(error["code"], -32602, "{params}");
+            assert_eq!(error["message"], "Invalid params", "{params}");
+            assert!(error["data"].is_string(), "{params}");
+        }
+
+        shutdown.cancel();
+    }
+}
@@ -0,0 +1,80 @@
+use {
+    richat_filter::config::ConfigFilter,
+    richat_shared::config::deserialize_affinity,
+    serde::{Deserialize, de::Deserializer},
+    std::{collections::HashMap, time::Duration},
+};
+
+/// librdkafka delivery options set unless overridden in the config: failed sends are
+/// retried by librdkafka without duplicates or reordering, messages which are still
+/// not delivered are only logged and counted in `kafka_messages_total{status="failed"}`
+pub const KAFKA_DELIVERY_DEFAULTS: [(&str, &str); 2] = [
+    ("enable.idempotence", "true"),
+    ("message.send.max.retries", "2147483647"),
+];
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct ConfigAppsKafka {
+    /// librdkafka producer options, see [`KAFKA_DELIVERY_DEFAULTS`]
+    #[serde(deserialize_with = "ConfigAppsKafka::deserialize_config")]
+    pub config: HashMap<String, String>,
+    pub topics: Vec<ConfigAppsKafkaTopic>,
+    /// Max time to wait delivery of produced messages on shutdown
+    #[serde(with = "humantime_serde")]
+    pub flush_timeout: Duration,
+    #[serde(deserialize_with = "deserialize_affinity")]
+    pub affinity: Option<Vec<usize>>,
+}
+
+impl Default for ConfigAppsKafka {
+    fn default() -> Self {
+        Self {
+            config: [("bootstrap.servers".to_owned(), "localhost:9092".to_owned())]
+                .into_iter()
+                .collect(),
+            topics: vec![],
+            flush_timeout: Duration::from_secs(10),
+            affinity: None,
+        }
+    }
+}
+
+impl ConfigAppsKafka {
+    /// librdkafka options are strings, but numbers and booleans are more natural in the config
+    fn deserialize_config<'de, D>(deserializer: D) -> Result<HashMap<String, String>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Value {
+            Str(String),
+            Int(i64),
+            Float(f64),
+            Bool(bool),
+        }
+
+        Ok(HashMap::<String, Value>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(key, value)| {
+                let value = match value {
+                    Value::Str(value) => value,
+                    Value::Int(value) => value.to_string(),
+                    Value::Float(value) => value.to_string(),
+                    Value::Bool(value) => value.to_string(),
+                };
+                (key, value)
+            })
+            .collect())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigAppsKafkaTopic {
+    pub topic: String,
+    /// Same filter as in gRPC subscribe request, `commitment` selects the channel
+    #[serde(default)]
+    pub filter: ConfigFilter,
+}
@@ -0,0 +1,341 @@
+use {
+    crate::{
+        channel::{Messages, ParsedMessage},
+        config::ConfigAppsWorkers,
+        kafka::config::{ConfigAppsKafka, KAFKA_DELIVERY_DEFAULTS},
+        metrics,
+    },
+    ::metrics::{counter, gauge},
+    futures::future::{TryFutureExt, try_join_all},
+    rdkafka::{
+        ClientConfig, ClientContext, Message,
+        error::{KafkaError, RDKafkaErrorCode},
+        message::DeliveryResult,
+        producer::{BaseRecord, Producer, ProducerContext, ThreadedProducer},
+    },
+    richat_filter::filter::Filter,
+    richat_shared::transports::RecvError,
+    solana_commitment_config::CommitmentLevel,
+    std::{
+        future::Future,
+        sync::Arc,
+        thread::sleep,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    },
+    tokio_util::sync::CancellationToken,
+    tracing::{error, info},
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct KafkaContext;
+
+impl ClientContext for KafkaContext {}
+
+impl ProducerContext for KafkaContext {
+    /// Message creation time in milliseconds since UNIX epoch
+    type DeliveryOpaque = usize;
+
+    fn delivery(&self, delivery_result: &DeliveryResult<'_>, created_at: Self::DeliveryOpaque) {
+        let (topic, status) = match delivery_result {
+            Ok(message) => {
+                let lag = unix_time_ms().saturating_sub(created_at as u64);
+                gauge!(metrics::KAFKA_PRODUCE_LAG_SECONDS, "topic" => message.topic().to_owned())
+                    .set(lag as f64 / 1_000.0);
+                (message.topic(), "delivered")
+            }
+            Err((error, message)) => {
+                error!("failed to deliver message to {}: {error}", message.topic());
+                (message.topic(), "failed")
+            }
+        };
+        counter!(metrics::KAFKA_MESSAGES_TOTAL, "topic" => topic.to_owned(), "status" => status)
+            .increment(1);
+    }
+}
+
+fn unix_time_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// Produces filtered messages to the topic, same as gRPC `FilteredUpdate` payloads.
+#[derive(Clone)]
+pub struct KafkaTopic {
+    producer: Arc<ThreadedProducer<KafkaContext>>,
+    topic: String,
+    filter: Arc<Filter>,
+}
+
+impl KafkaTopic {
+    pub fn new(
+        producer: Arc<ThreadedProducer<KafkaContext>>,
+        topic: String,
+        filter: Filter,
+    ) -> Self {
+        Self {
+            producer,
+            topic,
+            filter: Arc::new(filter),
+        }
+    }
+
+    pub fn commitment(&self) -> CommitmentLevel {
+        self.filter.commitment().into()
+    }
+
+    /// Messages are keyed by pubkey for accounts, by signature for transactions and by slot
+    /// for everything else, so updates of the same key go to the same partition.
+    fn get_key(message: &ParsedMessage) -> String {
+        match message {
+            ParsedMessage::Account(msg) => msg.pubkey().to_string(),
+            ParsedMessage::Transaction(msg) => msg.signature().to_string(),
+            message => message.slot().to_string(),
+        }
+    }
+
+    /// Returns number of produced messages, waits if the producer queue is full.
+    pub fn send(
+        &self,
+        message: &ParsedMessage,
+        shutdown: &CancellationToken,
+    ) -> anyhow::Result<usize> {
+        let commitment = self.commitment();
+        let updates = self.filter.get_updates_ref(message.into(), commitment);
+        if updates.is_empty() {
+            return Ok(0);
+        }
+
+        let key = Self::get_key(message);
+        let created_at = message.created_at().as_millis() as usize;
+        for update in updates.iter() {
+            let payload = update.encode_to_vec();
+            let mut record = BaseRecord::with_opaque_to(&self.topic, created_at)
+                .key(&key)
+                .payload(&payload);
+            loop {
+                match self.producer.send(record) {
+                    Ok(()) => break,
+                    Err((KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull), value)) => {
+                        if shutdown.is_cancelled() {
+                            return Ok(0);
+                        }
+                        record = value;
+                        sleep(Duration::from_millis(1));
+                    }
+                    Err((error, _record)) => return Err(error.into()),
+                }
+            }
+        }
+        Ok(updates.len())
+    }
+
+    /// Blocking loop forwarding messages from the channel, should be executed in a dedicated thread.
+    fn run_worker(&self, messages: Messages, shutdown: CancellationToken) -> anyhow::Result<()> {
+        let commitment = self.commitment();
+        let receiver = messages.to_receiver();
+        let mut head = messages.get_current_tail(commitment);
+
+        const COUNTER_LIMIT: i32 = 10_000;
+        let mut counter = 0;
+        loop {
+            counter += 1;
+            if counter > COUNTER_LIMIT {
+                counter = 0;
+                gauge!(metrics::KAFKA_IN_FLIGHT_TOTAL).set(self.producer.in_flight_count() as f64);
+                if shutdown.is_cancelled() {
+                    info!("kafka topic {} thread shutdown", self.topic);
+                    return Ok(());
+                }
+            }
+
+            let message = match receiver.try_recv(commitment, head) {
+                Ok(Some(message)) => message,
+                Ok(None) => {
+                    counter = COUNTER_LIMIT;
+                    sleep(Duration::from_micros(100));
+                    continue;
+                }
+                Err(RecvError::Lagged) => {
+                    error!("kafka topic {} lagged, messages skipped", self.topic);
+                    counter!(metrics::KAFKA_LAGGED_TOTAL, "topic" => self.topic.clone())
+                        .increment(1);
+                    head = messages.get_current_tail(commitment);
+                    continue;
+                }
+                Err(error) => return Err(error.into()),
+            };
+            head += 1;
+
+            self.send(&message, &shutdown)?;
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct KafkaProducer;
+
+impl KafkaProducer {
+    pub fn create(config: &ConfigAppsKafka) -> anyhow::Result<ThreadedProducer<KafkaContext>> {
+        let mut client_config = ClientConfig::new();
+        for (key, value) in KAFKA_DELIVERY_DEFAULTS {
+            client_config.set(key, value);
+        }
+        for (key, value) in config.config.iter() {
+            client_config.set(key, value);
+        }
+        client_config
+            .create_with_context(KafkaContext)
+            .map_err(Into::into)
+    }
+
+    pub fn spawn(
+        mut config: ConfigAppsKafka,
+        messages: Messages,
+        shutdown: CancellationToken,
+    ) -> anyhow::Result<impl Future<Output = anyhow::Result<()>>> {
+        anyhow::ensure!(
+            !config.topics.is_empty(),
+            "kafka should have at least one topic"
+        );
+        let producer = Arc::new(Self::create(&config)?);
+        info!("start kafka producer with {} topics", config.topics.len());
+
+        let affinity = config.affinity.take();
+        let mut jhs = Vec::with_capacity(config.topics.len());
+        for (index, topic) in config.topics.into_iter().enumerate() {
+            let topic = KafkaTopic::new(
+                Arc::clone(&producer),
+                topic.topic,
+                Filter::new(&topic.filter),
+            );
+            jhs.push(ConfigAppsWorkers::run_once(
+                index,
+                format!("richatKafka{index:02}"),
+                affinity.clone(),
+                {
+                    let messages = messages.clone();
+                    let shutdown = shutdown.clone();
+                    move |_index| topic.run_worker(messages, shutdown)
+                },
+                shutdown.clone(),
+            )?);
+        }
+
+        let flush_timeout = config.flush_timeout;
+        Ok(try_join_all(jhs).and_then(move |_| async move {
+            tokio::task::spawn_blocking(move || producer.flush(flush_timeout))
+                .await?
+                .map_err(Into::into)
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{KafkaProducer, KafkaTopic},
+        crate::{channel::ParsedMessage, kafka::config::ConfigAppsKafka},
+        maplit::hashmap,
+        prost::Message as _,
+        rdkafka::{
+            ClientConfig, Message,
+            consumer::{BaseConsumer, Consumer},
+            mocking::MockCluster,
+            producer::Producer,
+        },
+        richat_filter::{
+            config::{ConfigFilter, ConfigFilterAccounts},
+            filter::Filter,
+            message::{Message as FilterMessage, MessageParserEncoding},
+        },
+        richat_proto::geyser::{
+            SubscribeUpdate, SubscribeUpdateAccount, SubscribeUpdateAccountInfo,
+            subscribe_update::UpdateOneof,
+        },
+        solana_pubkey::Pubkey,
+        std::{
+            borrow::Cow,
+            sync::Arc,
+            time::{Duration, Instant},
+        },
+        tokio_util::sync::CancellationToken,
+    };
+
+    fn account(pubkey: Pubkey) -> ParsedMessage {
+        let data = SubscribeUpdate {
+            filters: vec![],
+            update_oneof: Some(UpdateOneof::Account(SubscribeUpdateAccount {
+                account: Some(SubscribeUpdateAccountInfo {
+                    pubkey: pubkey.to_bytes().to_vec(),
+                    owner: Pubkey::new_unique().to_bytes().to_vec(),
+                    ..Default::default()
+                }),
+                slot: 1,
+                is_startup: false,
+            })),
+            created_at: Some(Default::default()),
+        }
+        .encode_to_vec();
+        FilterMessage::parse(Cow::Owned(data), MessageParserEncoding::Prost)
+            .expect("valid message")
+            .into()
+    }
+
+    #[test]
+    fn test_produce_filtered() {
+        let cluster = MockCluster::new(1).expect("mock cluster");
+        cluster.create_topic("accounts", 1, 1).expect("topic");
+        let bootstrap_servers = cluster.bootstrap_servers();
+
+        let config = ConfigAppsKafka {
+            config: hashmap! { "bootstrap.servers".to_owned() => bootstrap_servers.clone() },
+            ..Default::default()
+        };
+        let producer = Arc::new(KafkaProducer::create(&config).expect("producer"));
+        let pubkey = Pubkey::new_unique();
+        let filter = Filter::new(&ConfigFilter {
+            accounts: hashmap! {
+                "filter".to_owned() => ConfigFilterAccounts {
+                    account: vec![pubkey],
+                    ..Default::default()
+                }
+            },
+            ..Default::default()
+        });
+        let topic = KafkaTopic::new(Arc::clone(&producer), "accounts".to_owned(), filter);
+
+        let shutdown = CancellationToken::new();
+        assert_eq!(
+            topic
+                .send(&account(Pubkey::new_unique()), &shutdown)
+                .unwrap(),
+            0
+        );
+        assert_eq!(topic.send(&account(pubkey), &shutdown).unwrap(), 1);
+        producer.flush(Duration::from_secs(10)).expect("delivered");
+
+        let consumer: BaseConsumer = ClientConfig::new()
+            .set("bootstrap.servers", &bootstrap_servers)
+            .set("group.id", "test")
+            .set("auto.offset.reset", "earliest")
+            .create()
+            .expect("consumer");
+        consumer.subscribe(&["accounts"]).expect("subscribed");
+        let ts = Instant::now();
+        let message = loop {
+            if let Some(message) = consumer.poll(Duration::from_millis(100)) {
+                break message.expect("valid message");
+            }
+            assert!(
+                ts.elapsed() < Duration::from_secs(30),
+                "message is not consumed"
+            );
+        };
+        assert_eq!(message.key(), Some(pubkey.to_string().as_bytes()));
+        let update = SubscribeUpdate::decode(message.payload().expect("payload")).unwrap();
+        assert_eq!(update.filters, ["filter"]);
+        assert!(matches!(update.update_oneof, Some(UpdateOneof::Account(_))));
+    }
+}
@@ -1,10 +1,17 @@
+pub mod admin;
+pub mod archive;
 pub mod channel;
 pub mod config;
 pub mod grpc;
+pub mod jsonrpc;
+pub mod kafka;
 pub mod metrics;
 pub mod pubsub;
+pub mod reload;
 pub mod richat;
 pub mod source;
 pub mod storage;
+pub mod tenants;
 pub mod util;
 pub mod version;
+pub mod webhooks;
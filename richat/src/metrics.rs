@@ -3,7 +3,7 @@ use {
     ::metrics::{counter, describe_counter, describe_gauge},
     metrics_exporter_prometheus::{BuildError, PrometheusBuilder, PrometheusHandle},
     richat_filter::filter::FilteredUpdateType,
-    richat_metrics::ConfigMetrics,
+    richat_metrics::{AdminHandler, ConfigMetrics},
     solana_clock::Slot,
     std::{
         borrow::Cow,
@@ -20,8 +20,15 @@ use {
     tracing::error,
 };
 
+pub const ARCHIVE_SLOT: &str = "archive_slot";
+pub const ARCHIVE_ROWS_TOTAL: &str = "archive_rows_total"; // kind
+pub const ARCHIVE_FILES_TOTAL: &str = "archive_files_total"; // kind
 pub const BLOCK_MESSAGE_FAILED: &str = "block_message_failed"; // reason
 pub const CHANNEL_EVENTS_RECEIVED: &str = "channel_events_received"; // source, type
+pub const CHANNEL_SOURCE_ACTIVE: &str = "channel_source_active"; // source
+pub const CHANNEL_SOURCE_LAG_SECONDS: &str = "channel_source_lag_seconds"; // source
+pub const CHANNEL_SOURCE_GAPS_TOTAL: &str = "channel_source_gaps_total"; // source
+pub const CHANNEL_SOURCE_RECONNECTS_TOTAL: &str = "channel_source_reconnects_total"; // source
 pub const CHANNEL_SLOT: &str = "channel_slot"; // commitment
 pub const CHANNEL_MESSAGES_TOTAL: &str = "channel_messages_total";
 pub const CHANNEL_SLOTS_TOTAL: &str = "channel_slots_total";
@@ -36,18 +43,24 @@ pub const CHANNEL_STORAGE_FIRST_SLOT: &str = "channel_storage_first_slot";
 pub const CHANNEL_STORAGE_LAST_SLOT: &str = "channel_storage_last_slot";
 pub const STORAGE_SEGMENT_CHUNKS_WRITTEN_TOTAL: &str = "storage_segment_chunks_written_total";
 pub const STORAGE_WRITE_CHUNK_UNCOMPRESSED_BYTES_TOTAL: &str =
-    "storage_write_chunk_uncompressed_bytes_total";
+    "storage_write_chunk_uncompressed_bytes_total"; // codec
 pub const STORAGE_WRITE_CHUNK_COMPRESSED_BYTES_TOTAL: &str =
-    "storage_write_chunk_compressed_bytes_total";
+    "storage_write_chunk_compressed_bytes_total"; // codec
 pub const STORAGE_WRITE_SERIALIZE_SECONDS_TOTAL: &str = "storage_write_serialize_seconds_total";
-pub const STORAGE_WRITE_COMPRESS_SECONDS_TOTAL: &str = "storage_write_compress_seconds_total";
+pub const STORAGE_WRITE_COMPRESS_SECONDS_TOTAL: &str = "storage_write_compress_seconds_total"; // codec
 pub const STORAGE_WRITE_APPEND_SECONDS_TOTAL: &str = "storage_write_append_seconds_total";
 pub const STORAGE_WRITE_COMMIT_SECONDS_TOTAL: &str = "storage_write_commit_seconds_total";
 pub const STORAGE_WRITE_TRIM_SECONDS_TOTAL: &str = "storage_write_trim_seconds_total";
 pub const STORAGE_WRITE_ROTATE_SECONDS_TOTAL: &str = "storage_write_rotate_seconds_total";
-pub const STORAGE_REPLAY_COMPRESSED_BYTES_TOTAL: &str = "storage_replay_compressed_bytes_total";
-pub const STORAGE_REPLAY_DECOMPRESSED_BYTES_TOTAL: &str = "storage_replay_decompressed_bytes_total";
+pub const STORAGE_REPLAY_COMPRESSED_BYTES_TOTAL: &str = "storage_replay_compressed_bytes_total"; // codec
+pub const STORAGE_REPLAY_DECOMPRESSED_BYTES_TOTAL: &str = "storage_replay_decompressed_bytes_total"; // codec
 pub const STORAGE_DISK_SIZE_BYTES: &str = "storage_disk_size_bytes";
+pub const GRPC_ACCOUNTS_CACHE_SLOT: &str = "grpc_accounts_cache_slot"; // commitment
+pub const GRPC_ACCOUNTS_CACHE_LAGGED_TOTAL: &str = "grpc_accounts_cache_lagged_total"; // commitment
+pub const GRPC_ACCOUNTS_CACHE_READY: &str = "grpc_accounts_cache_ready"; // commitment
+pub const GRPC_ACCOUNTS_CACHE_SEED_ERRORS_TOTAL: &str = "grpc_accounts_cache_seed_errors_total"; // commitment
+pub const GRPC_ACCOUNTS_CACHE_SNAPSHOT_ACCOUNTS_TOTAL: &str =
+    "grpc_accounts_cache_snapshot_accounts_total"; // commitment
 pub const GRPC_BLOCK_META_SLOT: &str = "grpc_block_meta_slot"; // commitment
 pub const GRPC_BLOCK_META_QUEUE_SIZE: &str = "grpc_block_meta_queue_size";
 pub const GRPC_REQUESTS_TOTAL: &str = "grpc_requests_total"; // x_subscription_id, method
@@ -57,8 +70,19 @@ pub const GRPC_SUBSCRIBE_MESSAGES_BYTES_TOTAL: &str = "grpc_subscribe_messages_b
 pub const GRPC_SUBSCRIBE_CPU_SECONDS_TOTAL: &str = "grpc_subscribe_cpu_seconds_total"; // x_subscription_id
 pub const GRPC_SUBSCRIBE_REPLAY_DISK_SECONDS_TOTAL: &str =
     "grpc_subscribe_replay_disk_cpu_seconds_total"; // x_subscription_id
+pub const GRPC_SUBSCRIBE_LAGGED_REPLAY_TOTAL: &str = "grpc_subscribe_lagged_replay_total"; // x_subscription_id
+pub const KAFKA_MESSAGES_TOTAL: &str = "kafka_messages_total"; // topic, status
+pub const KAFKA_PRODUCE_LAG_SECONDS: &str = "kafka_produce_lag_seconds"; // topic
+pub const KAFKA_IN_FLIGHT_TOTAL: &str = "kafka_in_flight_total";
+pub const KAFKA_LAGGED_TOTAL: &str = "kafka_lagged_total"; // topic
+pub const WEBHOOKS_UPDATES_TOTAL: &str = "webhooks_updates_total"; // webhook, status
+pub const WEBHOOKS_REQUESTS_TOTAL: &str = "webhooks_requests_total"; // webhook, status
+pub const WEBHOOKS_DELIVERY_LAG_SECONDS: &str = "webhooks_delivery_lag_seconds"; // webhook
+pub const WEBHOOKS_LAGGED_TOTAL: &str = "webhooks_lagged_total"; // webhook
 pub const PUBSUB_SLOT: &str = "pubsub_slot"; // commitment
 pub const PUBSUB_CACHED_SIGNATURES_TOTAL: &str = "pubsub_cached_signatures_total";
+pub const PUBSUB_CACHED_MINTS_TOTAL: &str = "pubsub_cached_mints_total";
+pub const PUBSUB_CACHED_MINTS_EVICTED_TOTAL: &str = "pubsub_cached_mints_evicted_total";
 pub const PUBSUB_STORED_MESSAGES_COUNT_TOTAL: &str = "pubsub_stored_messages_count_total";
 pub const PUBSUB_STORED_MESSAGES_BYTES_TOTAL: &str = "pubsub_stored_messages_bytes_total";
 pub const PUBSUB_CONNECTIONS_TOTAL: &str = "pubsub_connections_total"; // x_subscription_id
@@ -66,6 +90,11 @@ pub const PUBSUB_SUBSCRIPTIONS_TOTAL: &str = "pubsub_subscriptions_total"; // x_
 pub const PUBSUB_MESSAGES_SENT_COUNT_TOTAL: &str = "pubsub_messages_sent_count_total"; // x_subscription_id, subscription
 pub const PUBSUB_MESSAGES_SENT_BYTES_TOTAL: &str = "pubsub_messages_sent_bytes_total"; // x_subscription_id, subscription
 pub const RICHAT_CONNECTIONS_TOTAL: &str = "richat_connections_total"; // transport
+pub const RICHAT_SUBSCRIBE_REPLAY_DISK_SECONDS_TOTAL: &str =
+    "richat_subscribe_replay_disk_cpu_seconds_total";
+pub const TENANT_SUBSCRIPTIONS_TOTAL: &str = "tenant_subscriptions_total"; // tenant, app
+pub const TENANT_REJECTED_TOTAL: &str = "tenant_rejected_total"; // tenant, app, reason
+pub const TENANT_THROTTLED_SECONDS_TOTAL: &str = "tenant_throttled_seconds_total"; // tenant
 
 #[rustfmt::skip]
 pub fn setup() -> Result<PrometheusHandle, BuildError> {
@@ -84,8 +113,15 @@ pub fn setup() -> Result<PrometheusHandle, BuildError> {
     )
     .absolute(1);
 
+    describe_gauge!(ARCHIVE_SLOT, "Latest finalized slot written to archive");
+    describe_counter!(ARCHIVE_ROWS_TOTAL, "Number of rows written to archive by kind");
+    describe_counter!(ARCHIVE_FILES_TOTAL, "Number of finished archive files by kind");
     describe_counter!(BLOCK_MESSAGE_FAILED, "Block message reconstruction errors");
     describe_counter!(CHANNEL_EVENTS_RECEIVED, "Total number of received messages by source");
+    describe_gauge!(CHANNEL_SOURCE_ACTIVE, "Source messages are pushed to the channel (1) or source is a backup (0)");
+    describe_gauge!(CHANNEL_SOURCE_LAG_SECONDS, "Average lag of source relative to the fastest source over latest slots");
+    describe_counter!(CHANNEL_SOURCE_GAPS_TOTAL, "Number of slots received from other sources but missed by source");
+    describe_counter!(CHANNEL_SOURCE_RECONNECTS_TOTAL, "Number of source reconnects");
     describe_gauge!(CHANNEL_SLOT, "Latest slot in channel by commitment");
     describe_gauge!(CHANNEL_MESSAGES_TOTAL, "Total number of messages in channel");
     describe_gauge!(CHANNEL_SLOTS_TOTAL, "Total number of slots in channel");
@@ -119,6 +155,11 @@ pub fn setup() -> Result<PrometheusHandle, BuildError> {
         "Decompressed bytes read from segmented replay storage"
     );
     describe_gauge!(STORAGE_DISK_SIZE_BYTES, "Total disk size of storage (metadata + segments) in bytes");
+    describe_gauge!(GRPC_ACCOUNTS_CACHE_SLOT, "Latest slot in gRPC accounts cache by commitment");
+    describe_counter!(GRPC_ACCOUNTS_CACHE_LAGGED_TOTAL, "Number of times gRPC accounts cache lagged behind the channel");
+    describe_gauge!(GRPC_ACCOUNTS_CACHE_READY, "gRPC accounts cache is loaded and serves snapshots");
+    describe_counter!(GRPC_ACCOUNTS_CACHE_SEED_ERRORS_TOTAL, "Number of failed attempts to load accounts into gRPC accounts cache");
+    describe_counter!(GRPC_ACCOUNTS_CACHE_SNAPSHOT_ACCOUNTS_TOTAL, "Number of accounts sent in gRPC accounts snapshots");
     describe_gauge!(GRPC_BLOCK_META_SLOT, "Latest slot in gRPC block meta");
     describe_gauge!(GRPC_BLOCK_META_QUEUE_SIZE, "Number of gRPC requests to block meta data");
     describe_counter!(GRPC_REQUESTS_TOTAL, "Number of gRPC requests per method");
@@ -127,8 +168,19 @@ pub fn setup() -> Result<PrometheusHandle, BuildError> {
     describe_counter!(GRPC_SUBSCRIBE_MESSAGES_BYTES_TOTAL, "Total size of gRPC messages in subscriptions by type");
     describe_gauge!(GRPC_SUBSCRIBE_CPU_SECONDS_TOTAL, "CPU consumption of gRPC filters in subscriptions");
     describe_gauge!(GRPC_SUBSCRIBE_REPLAY_DISK_SECONDS_TOTAL, "CPU consumption of gRPC filters in subscriptions on replay from disk");
+    describe_counter!(GRPC_SUBSCRIBE_LAGGED_REPLAY_TOTAL, "Number of lagged gRPC subscriptions moved to replay from disk");
+    describe_counter!(KAFKA_MESSAGES_TOTAL, "Number of Kafka messages by topic and delivery status");
+    describe_gauge!(KAFKA_PRODUCE_LAG_SECONDS, "Lag between message creation and Kafka delivery acknowledgement by topic");
+    describe_gauge!(KAFKA_IN_FLIGHT_TOTAL, "Number of Kafka messages waiting for delivery acknowledgement");
+    describe_counter!(KAFKA_LAGGED_TOTAL, "Number of times Kafka topic producer lagged behind the channel");
+    describe_counter!(WEBHOOKS_UPDATES_TOTAL, "Number of webhook updates by webhook and delivery status");
+    describe_counter!(WEBHOOKS_REQUESTS_TOTAL, "Number of webhook HTTP requests by webhook and status");
+    describe_gauge!(WEBHOOKS_DELIVERY_LAG_SECONDS, "Lag between message creation and webhook delivery by webhook");
+    describe_counter!(WEBHOOKS_LAGGED_TOTAL, "Number of times webhook lagged behind the channel");
     describe_gauge!(PUBSUB_SLOT, "Latest slot handled in PubSub by commitment");
     describe_gauge!(PUBSUB_CACHED_SIGNATURES_TOTAL, "Number of cached signatures");
+    describe_gauge!(PUBSUB_CACHED_MINTS_TOTAL, "Number of cached mints for jsonParsed token accounts");
+    describe_counter!(PUBSUB_CACHED_MINTS_EVICTED_TOTAL, "Number of mints evicted from full mints cache");
     describe_gauge!(PUBSUB_STORED_MESSAGES_COUNT_TOTAL, "Number of stored filtered messages in cache");
     describe_gauge!(PUBSUB_STORED_MESSAGES_BYTES_TOTAL, "Total size of stored filtered messages in cache");
     describe_gauge!(PUBSUB_CONNECTIONS_TOTAL, "Number of connections to PubSub");
@@ -136,6 +188,11 @@ pub fn setup() -> Result<PrometheusHandle, BuildError> {
     describe_counter!(PUBSUB_MESSAGES_SENT_COUNT_TOTAL, "Number of sent filtered messages by type");
     describe_counter!(PUBSUB_MESSAGES_SENT_BYTES_TOTAL, "Total size of sent filtered messages by type");
     describe_gauge!(RICHAT_CONNECTIONS_TOTAL, "Total number of connections to Richat");
+    describe_gauge!(RICHAT_SUBSCRIBE_REPLAY_DISK_SECONDS_TOTAL, "CPU consumption of Richat subscriptions on replay from disk");
+    describe_gauge!(TENANT_SUBSCRIPTIONS_TOTAL, "Number of subscriptions by tenant and app");
+    describe_counter!(TENANT_REJECTED_TOTAL, "Number of rejected tenant requests by reason");
+    describe_gauge!(TENANT_THROTTLED_SECONDS_TOTAL, "Total time of tenant subscriptions throttled by bandwidth budget");
+    richat_shared::jsonrpc::metrics::describe();
 
     Ok(handle)
 }
@@ -144,6 +201,7 @@ pub async fn spawn_server(
     config: ConfigMetrics,
     handle: PrometheusHandle,
     is_ready: Arc<AtomicBool>,
+    admin: Option<Arc<dyn AdminHandler>>,
     shutdown: impl Future<Output = ()> + Send + 'static,
 ) -> anyhow::Result<impl Future<Output = Result<(), JoinError>>> {
     let recorder_handle = handle.clone();
@@ -159,6 +217,7 @@ pub async fn spawn_server(
         move || handle.render().into_bytes(),     // metrics
         || true,                                  // health
         move || is_ready.load(Ordering::Relaxed), // ready
+        admin,
         shutdown,
     )
     .await
@@ -222,6 +281,8 @@ pub enum GrpcSubscribeMessage {
     Entry,
     BlockMeta,
     Block,
+    AccountsSnapshot,
+    Rollback,
     Ping,
     Pong,
 }
@@ -250,6 +311,8 @@ impl GrpcSubscribeMessage {
             GrpcSubscribeMessage::Entry => "entry",
             GrpcSubscribeMessage::BlockMeta => "blockmeta",
             GrpcSubscribeMessage::Block => "block",
+            GrpcSubscribeMessage::AccountsSnapshot => "accountssnapshot",
+            GrpcSubscribeMessage::Rollback => "rollback",
             GrpcSubscribeMessage::Ping => "ping",
             GrpcSubscribeMessage::Pong => "pong",
         }
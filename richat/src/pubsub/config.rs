@@ -7,6 +7,7 @@ use {
     std::{
         io,
         net::{IpAddr, Ipv4Addr, SocketAddr},
+        path::PathBuf,
     },
     tokio::net::TcpStream,
 };
@@ -21,6 +22,7 @@ pub struct ConfigAppsPubsub {
     #[serde(deserialize_with = "deserialize_humansize_usize")]
     pub recv_max_message_size: usize,
     pub enable_block_subscription: bool,
+    pub enable_vote_subscription: bool,
     pub enable_transaction_subscription: bool,
     #[serde(deserialize_with = "deserialize_num_str")]
     pub clients_requests_channel_size: usize,
@@ -42,6 +44,9 @@ pub struct ConfigAppsPubsub {
     pub signatures_cache_max: usize,
     #[serde(deserialize_with = "deserialize_num_str")]
     pub signatures_cache_slots_max: usize,
+    #[serde(deserialize_with = "deserialize_num_str")]
+    pub mints_cache_max: usize,
+    pub mints_cache_path: Option<PathBuf>,
 }
 
 impl Default for ConfigAppsPubsub {
@@ -52,6 +57,7 @@ impl Default for ConfigAppsPubsub {
             tls_config: None,
             recv_max_message_size: 4 * 1024, // 4KiB
             enable_block_subscription: false,
+            enable_vote_subscription: false,
             enable_transaction_subscription: false,
             clients_requests_channel_size: 8_192,
             subscriptions_worker_affinity: None,
@@ -63,6 +69,8 @@ impl Default for ConfigAppsPubsub {
             notifications_messages_max_bytes: 32 * 1024 * 1024 * 1024,
             signatures_cache_max: 150 * 8_192, // 8k more than enough per slot, should be about 300MiB
             signatures_cache_slots_max: 150,
+            mints_cache_max: 4_194_304,
+            mints_cache_path: None,
         }
     }
 }
@@ -0,0 +1,440 @@
+use {
+    crate::metrics,
+    ::metrics::{counter, gauge},
+    anyhow::Context,
+    foldhash::quality::RandomState,
+    richat_filter::message::MessageAccount,
+    solana_account::ReadableAccount,
+    solana_account_decoder::{
+        parse_account_data::{AccountAdditionalDataV3, SplTokenAdditionalDataV2},
+        parse_token::{get_token_account_mint, is_known_spl_token_id},
+    },
+    solana_clock::UnixTimestamp,
+    solana_pubkey::Pubkey,
+    spl_token_2022_interface::{
+        extension::{
+            BaseStateWithExtensions, StateWithExtensions,
+            interest_bearing_mint::InterestBearingConfig, scaled_ui_amount::ScaledUiAmountConfig,
+        },
+        native_mint,
+        state::Mint,
+    },
+    std::{
+        collections::{BTreeMap, HashMap},
+        fs,
+        path::Path,
+        str::FromStr,
+    },
+    tracing::{info, warn},
+};
+
+const SPL_TOKEN_NATIVE_MINT: Pubkey =
+    Pubkey::from_str_const("So11111111111111111111111111111111111111112");
+const SYSVAR_CLOCK: Pubkey = Pubkey::from_str_const("SysvarC1ock11111111111111111111111111111111");
+
+/// Mint decimals and Token-2022 extensions required to encode token accounts
+/// as `jsonParsed` in the same way as Agave does.
+#[derive(Debug)]
+pub struct MintsCache {
+    mints: HashMap<Pubkey, MintEntry, RandomState>,
+    /// Mints by the last update, the oldest are evicted once cache is full
+    updates: BTreeMap<u64, Pubkey>,
+    update_id: u64,
+    mints_max: usize,
+    full_reported: bool,
+    unix_timestamp: UnixTimestamp,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct MintEntry {
+    info: MintInfo,
+    /// `None` for native mints, they are never evicted
+    update_id: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct MintInfo {
+    decimals: u8,
+    interest_bearing_config: Option<InterestBearingConfig>,
+    scaled_ui_amount_config: Option<ScaledUiAmountConfig>,
+}
+
+impl MintInfo {
+    const fn with_decimals(decimals: u8) -> Self {
+        Self {
+            decimals,
+            interest_bearing_config: None,
+            scaled_ui_amount_config: None,
+        }
+    }
+}
+
+impl MintsCache {
+    pub fn new(mints_max: usize) -> Self {
+        let mut mints = HashMap::default();
+        for mint in [SPL_TOKEN_NATIVE_MINT, native_mint::id()] {
+            mints.insert(
+                mint,
+                MintEntry {
+                    info: MintInfo::with_decimals(native_mint::DECIMALS),
+                    update_id: None,
+                },
+            );
+        }
+        Self {
+            mints,
+            updates: BTreeMap::new(),
+            update_id: 0,
+            mints_max,
+            full_reported: false,
+            unix_timestamp: 0,
+        }
+    }
+
+    /// Seed decimals from JSON file with object `{"<mint>": <decimals>}`.
+    pub fn load_from_file(&mut self, path: &Path) -> anyhow::Result<()> {
+        let data = fs::read(path)
+            .with_context(|| format!("failed to read mints from {}", path.display()))?;
+        let mints: HashMap<String, u8> =
+            serde_json::from_slice(&data).context("failed to parse mints file")?;
+        for (mint, decimals) in mints {
+            let mint = Pubkey::from_str(&mint)
+                .with_context(|| format!("failed to parse mint pubkey: {mint}"))?;
+            self.insert(mint, MintInfo::with_decimals(decimals));
+        }
+        info!("loaded {} mints from {}", self.mints.len(), path.display());
+        Ok(())
+    }
+
+    fn insert(&mut self, mint: Pubkey, info: MintInfo) {
+        let update_id = if mint == SPL_TOKEN_NATIVE_MINT || mint == native_mint::id() {
+            None
+        } else {
+            self.update_id += 1;
+            self.updates.insert(self.update_id, mint);
+            Some(self.update_id)
+        };
+
+        match self.mints.insert(mint, MintEntry { info, update_id }) {
+            Some(entry) => {
+                if let Some(update_id) = entry.update_id {
+                    self.updates.remove(&update_id);
+                }
+            }
+            // least recently updated mint is evicted once cache is full
+            None if self.mints.len() > self.mints_max => {
+                if let Some((_update_id, mint)) = self.updates.pop_first() {
+                    self.mints.remove(&mint);
+                    counter!(metrics::PUBSUB_CACHED_MINTS_EVICTED_TOTAL).increment(1);
+                    if !self.full_reported {
+                        self.full_reported = true;
+                        warn!(
+                            "mints cache is full ({} mints), least recently updated mints are evicted",
+                            self.mints_max
+                        );
+                    }
+                }
+            }
+            None => {}
+        }
+        gauge!(metrics::PUBSUB_CACHED_MINTS_TOTAL).set(self.mints.len() as f64);
+    }
+
+    fn remove(&mut self, mint: &Pubkey) {
+        if let Some(entry) = self.mints.remove(mint) {
+            if let Some(update_id) = entry.update_id {
+                self.updates.remove(&update_id);
+            }
+            gauge!(metrics::PUBSUB_CACHED_MINTS_TOTAL).set(self.mints.len() as f64);
+        }
+    }
+
+    pub fn update(&mut self, message: &MessageAccount) {
+        let pubkey = message.pubkey();
+        if *pubkey == SYSVAR_CLOCK {
+            // `unix_timestamp` is the last field of bincode encoded `Clock`
+            if let Some(bytes) = message.data().get(32..40) {
+                self.unix_timestamp =
+                    UnixTimestamp::from_le_bytes(bytes.try_into().expect("valid slice"));
+            }
+            return;
+        }
+
+        // closed accounts are assigned to the system program, address could be reused
+        if message.lamports() == 0 {
+            self.remove(pubkey);
+            return;
+        }
+
+        if !is_known_spl_token_id(message.owner()) {
+            return;
+        }
+        if let Ok(mint) = StateWithExtensions::<Mint>::unpack(message.data()) {
+            self.insert(
+                *pubkey,
+                MintInfo {
+                    decimals: mint.base.decimals,
+                    interest_bearing_config: mint
+                        .get_extension::<InterestBearingConfig>()
+                        .ok()
+                        .copied(),
+                    scaled_ui_amount_config: mint
+                        .get_extension::<ScaledUiAmountConfig>()
+                        .ok()
+                        .copied(),
+                },
+            );
+        }
+    }
+
+    /// Additional data for token account, `None` if account is not a token account
+    /// or mint is unknown.
+    pub fn get_additional_data(&self, message: &MessageAccount) -> Option<AccountAdditionalDataV3> {
+        if !is_known_spl_token_id(message.owner()) {
+            return None;
+        }
+        let mint = get_token_account_mint(message.data())?;
+        let info = self.mints.get(&mint)?.info;
+        Some(AccountAdditionalDataV3 {
+            spl_token_additional_data: Some(SplTokenAdditionalDataV2 {
+                decimals: info.decimals,
+                interest_bearing_config: info
+                    .interest_bearing_config
+                    .map(|config| (config, self.unix_timestamp)),
+                scaled_ui_amount_config: info
+                    .scaled_ui_amount_config
+                    .map(|config| (config, self.unix_timestamp)),
+            }),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{MintsCache, SYSVAR_CLOCK},
+        prost::Message as _,
+        richat_filter::message::{Message, MessageAccount, MessageParserEncoding},
+        richat_proto::geyser::{
+            SubscribeUpdate, SubscribeUpdateAccount, SubscribeUpdateAccountInfo,
+            subscribe_update::UpdateOneof,
+        },
+        solana_account::Account,
+        solana_account_decoder::{
+            UiAccountEncoding, encode_ui_account,
+            parse_account_data::{AccountAdditionalDataV3, SplTokenAdditionalDataV2},
+        },
+        solana_pubkey::Pubkey,
+        spl_token_2022_interface::{
+            extension::{
+                BaseStateWithExtensions, BaseStateWithExtensionsMut, ExtensionType,
+                StateWithExtensions, StateWithExtensionsMut,
+                interest_bearing_mint::{self, InterestBearingConfig},
+            },
+            native_mint,
+            state::{Account as TokenAccount, AccountState, Mint},
+        },
+        std::borrow::Cow,
+    };
+
+    const UNIX_TIMESTAMP: i64 = 1_700_000_000;
+
+    fn message(pubkey: Pubkey, account: &Account) -> MessageAccount {
+        let data = SubscribeUpdate {
+            filters: vec![],
+            update_oneof: Some(UpdateOneof::Account(SubscribeUpdateAccount {
+                account: Some(SubscribeUpdateAccountInfo {
+                    pubkey: pubkey.to_bytes().to_vec(),
+                    lamports: account.lamports,
+                    owner: account.owner.to_bytes().to_vec(),
+                    executable: account.executable,
+                    rent_epoch: account.rent_epoch,
+                    data: account.data.clone(),
+                    write_version: 1,
+                    txn_signature: None,
+                }),
+                slot: 1,
+                is_startup: false,
+            })),
+            created_at: Some(Default::default()),
+        }
+        .encode_to_vec();
+        match Message::parse(Cow::Owned(data), MessageParserEncoding::Limited) {
+            Ok(Message::Account(message)) => message,
+            _ => panic!("expected account message"),
+        }
+    }
+
+    fn clock() -> Account {
+        let mut data = vec![0; 40];
+        data[32..].copy_from_slice(&UNIX_TIMESTAMP.to_le_bytes());
+        Account {
+            lamports: 1,
+            data,
+            owner: Pubkey::new_unique(),
+            executable: false,
+            rent_epoch: 0,
+        }
+    }
+
+    fn mint(program_id: Pubkey, decimals: u8, interest_rate: Option<i16>) -> Account {
+        let extensions = match interest_rate {
+            Some(_) => vec![ExtensionType::InterestBearingConfig],
+            None => vec![],
+        };
+        let mut data =
+            vec![0; ExtensionType::try_calculate_account_len::<Mint>(&extensions).unwrap()];
+        let mut state = StateWithExtensionsMut::<Mint>::unpack_uninitialized(&mut data).unwrap();
+        if let Some(rate) = interest_rate {
+            let config = state.init_extension::<InterestBearingConfig>(true).unwrap();
+            config.initialization_timestamp = (UNIX_TIMESTAMP - 365 * 24 * 3600).into();
+            config.last_update_timestamp = config.initialization_timestamp;
+            config.pre_update_average_rate = interest_bearing_mint::BasisPoints::from(rate);
+            config.current_rate = interest_bearing_mint::BasisPoints::from(rate);
+        }
+        state.base = Mint {
+            supply: 1_000_000,
+            decimals,
+            is_initialized: true,
+            ..Default::default()
+        };
+        state.pack_base();
+        state.init_account_type().unwrap();
+        Account {
+            lamports: 1_000_000,
+            data,
+            owner: program_id,
+            executable: false,
+            rent_epoch: u64::MAX,
+        }
+    }
+
+    fn token_account(program_id: Pubkey, mint: Pubkey) -> Account {
+        let mut data =
+            vec![0; ExtensionType::try_calculate_account_len::<TokenAccount>(&[]).unwrap()];
+        let mut state =
+            StateWithExtensionsMut::<TokenAccount>::unpack_uninitialized(&mut data).unwrap();
+        state.base = TokenAccount {
+            mint,
+            owner: Pubkey::new_unique(),
+            amount: 123_456_789,
+            state: AccountState::Initialized,
+            ..Default::default()
+        };
+        state.pack_base();
+        Account {
+            lamports: 2_039_280,
+            data,
+            owner: program_id,
+            executable: false,
+            rent_epoch: u64::MAX,
+        }
+    }
+
+    // the same as `get_mint_owner_and_additional_data` in Agave RPC
+    fn agave_additional_data(mint: &Pubkey, account: &Account) -> SplTokenAdditionalDataV2 {
+        if *mint == native_mint::id() {
+            return SplTokenAdditionalDataV2::with_decimals(native_mint::DECIMALS);
+        }
+        let mint = StateWithExtensions::<Mint>::unpack(&account.data).unwrap();
+        SplTokenAdditionalDataV2 {
+            decimals: mint.base.decimals,
+            interest_bearing_config: mint
+                .get_extension::<InterestBearingConfig>()
+                .map(|config| (*config, UNIX_TIMESTAMP))
+                .ok(),
+            scaled_ui_amount_config: None,
+        }
+    }
+
+    fn assert_json_parsed(mints: &MintsCache, mint: (Pubkey, &Account), program_id: Pubkey) {
+        let pubkey = Pubkey::new_unique();
+        let account = token_account(program_id, mint.0);
+        let expected = encode_ui_account(
+            &pubkey,
+            &account,
+            UiAccountEncoding::JsonParsed,
+            Some(AccountAdditionalDataV3 {
+                spl_token_additional_data: Some(agave_additional_data(&mint.0, mint.1)),
+            }),
+            None,
+        );
+
+        let message = message(pubkey, &account);
+        let actual = encode_ui_account(
+            &pubkey,
+            &message,
+            UiAccountEncoding::JsonParsed,
+            mints.get_additional_data(&message),
+            None,
+        );
+        let actual = serde_json::to_string(&actual).unwrap();
+        assert!(
+            actual.contains(r#""uiAmountString""#),
+            "not parsed: {actual}"
+        );
+        assert_eq!(actual, serde_json::to_string(&expected).unwrap());
+    }
+
+    #[test]
+    fn test_json_parsed_as_agave() {
+        let mut mints = MintsCache::new(16);
+        mints.update(&message(SYSVAR_CLOCK, &clock()));
+
+        let spl_token = spl_token_2022_interface::inline_spl_token::id();
+        let account = mint(spl_token, 6, None);
+        let pubkey = Pubkey::new_unique();
+        mints.update(&message(pubkey, &account));
+        assert_json_parsed(&mints, (pubkey, &account), spl_token);
+
+        let spl_token_2022 = spl_token_2022_interface::id();
+        let account = mint(spl_token_2022, 9, Some(500));
+        let pubkey = Pubkey::new_unique();
+        mints.update(&message(pubkey, &account));
+        assert_json_parsed(&mints, (pubkey, &account), spl_token_2022);
+
+        // native mint is known without updates
+        let account = mint(spl_token, native_mint::DECIMALS, None);
+        assert_json_parsed(&mints, (native_mint::id(), &account), spl_token);
+    }
+
+    #[test]
+    fn test_closed_mint_removed() {
+        let mut mints = MintsCache::new(16);
+        let spl_token = spl_token_2022_interface::inline_spl_token::id();
+        let mint_pubkey = Pubkey::new_unique();
+        mints.update(&message(mint_pubkey, &mint(spl_token, 6, None)));
+        assert!(mints.mints.contains_key(&mint_pubkey));
+
+        let closed = Account {
+            lamports: 0,
+            data: vec![],
+            owner: Pubkey::default(),
+            executable: false,
+            rent_epoch: 0,
+        };
+        mints.update(&message(mint_pubkey, &closed));
+        assert!(!mints.mints.contains_key(&mint_pubkey));
+
+        let account = message(Pubkey::new_unique(), &token_account(spl_token, mint_pubkey));
+        assert!(mints.get_additional_data(&account).is_none());
+    }
+
+    #[test]
+    fn test_full_cache_evicts_oldest() {
+        // native mints and two more
+        let mut mints = MintsCache::new(4);
+        let spl_token = spl_token_2022_interface::inline_spl_token::id();
+        let [mint1, mint2, mint3] = [(); 3].map(|()| Pubkey::new_unique());
+        for pubkey in [mint1, mint2, mint1, mint3] {
+            mints.update(&message(pubkey, &mint(spl_token, 6, None)));
+        }
+
+        assert!(mints.mints.contains_key(&mint1));
+        assert!(!mints.mints.contains_key(&mint2));
+        assert!(mints.mints.contains_key(&mint3));
+        assert!(mints.mints.contains_key(&native_mint::id()));
+        assert_eq!(mints.mints.len(), 4);
+        assert_eq!(mints.updates.len(), 2);
+    }
+}
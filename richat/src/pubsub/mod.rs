@@ -1,5 +1,6 @@
 pub mod config;
 pub mod filter;
+pub mod mints;
 pub mod notification;
 pub mod server;
 pub mod solana;
@@ -1,7 +1,11 @@
 use {
     crate::{
+        admin::{
+            AdminClientApp, AdminClientHandle, AdminClientInfo, AdminClientLag, AdminClientState,
+            AdminClients,
+        },
         channel::Messages,
-        config::ConfigAppsWorkers,
+        config::{ConfigAppsTenantApp, ConfigAppsWorkers},
         metrics,
         pubsub::{
             ClientId, SubscriptionId,
@@ -10,6 +14,7 @@ use {
             solana::{SubscribeConfig, SubscribeMessage, SubscribeMethod},
             tracker::{ClientRequest, subscriptions_worker},
         },
+        tenants::{TenantError, TenantSubscription, Tenants},
         version::VERSION,
     },
     ::metrics::{counter, gauge},
@@ -27,10 +32,22 @@ use {
     jsonrpsee_types::{
         ErrorCode, ErrorObject, ErrorObjectOwned, Extensions, ResponsePayload, TwoPointZero,
     },
-    richat_shared::jsonrpc::helpers::get_x_subscription_id,
+    richat_shared::{
+        jsonrpc::helpers::get_x_subscription_id,
+        mutex_lock,
+        transports::reload::{ReloadableCertResolver, ServerReloadError},
+    },
     solana_nohash_hasher::IntMap,
     solana_rpc_client_api::response::RpcVersionInfo,
-    std::{future::Future, net::TcpListener as StdTcpListener, sync::Arc},
+    std::{
+        collections::BTreeMap,
+        future::Future,
+        net::TcpListener as StdTcpListener,
+        sync::{
+            Arc, Mutex,
+            atomic::{AtomicU64, Ordering},
+        },
+    },
     tokio::{
         net::TcpListener,
         sync::{broadcast, oneshot},
@@ -40,6 +57,30 @@ use {
     tracing::{error, info, warn},
 };
 
+/// TLS certificate of the running PubSub app applied to new connections.
+#[derive(Debug, Clone)]
+pub struct PubSubServerReload {
+    tls: Option<Arc<ReloadableCertResolver>>,
+}
+
+impl PubSubServerReload {
+    pub const fn check(&self, config: &ConfigAppsPubsub) -> Result<(), ServerReloadError> {
+        if self.tls.is_some() == config.tls_config.is_some() {
+            Ok(())
+        } else {
+            Err(ServerReloadError::TlsChanged)
+        }
+    }
+
+    pub fn reload(&self, config: &ConfigAppsPubsub) -> Result<(), ServerReloadError> {
+        self.check(config)?;
+        if let (Some(resolver), Some(tls_config)) = (&self.tls, &config.tls_config) {
+            resolver.reload(tls_config);
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug)]
 pub struct PubSubServer;
 
@@ -47,8 +88,16 @@ impl PubSubServer {
     pub fn spawn(
         mut config: ConfigAppsPubsub,
         messages: Messages,
+        tenants: Tenants,
+        admin: AdminClients,
         shutdown: CancellationToken,
-    ) -> anyhow::Result<impl Future<Output = anyhow::Result<()>>> {
+    ) -> anyhow::Result<(PubSubServerReload, impl Future<Output = anyhow::Result<()>>)> {
+        let reload = PubSubServerReload {
+            tls: config
+                .tls_config
+                .as_mut()
+                .map(ReloadableCertResolver::install),
+        };
         let acceptor = config
             .tls_config
             .take()
@@ -72,6 +121,7 @@ impl PubSubServer {
             config.subscriptions_worker_affinity.take(),
             {
                 let subscriptions_workers_affinity = config.subscriptions_workers_affinity.take();
+                let mints_cache_path = config.mints_cache_path.take();
                 let notifications = notifications.clone();
                 move |_index| {
                     subscriptions_worker(
@@ -88,6 +138,8 @@ impl PubSubServer {
                         ),
                         config.signatures_cache_max,
                         config.signatures_cache_slots_max,
+                        config.mints_cache_max,
+                        mints_cache_path,
                     )
                 }
             },
@@ -100,14 +152,14 @@ impl PubSubServer {
             let mut client_id = 0;
             loop {
                 // accept connection
-                let stream = tokio::select! {
+                let (stream, addr) = tokio::select! {
                     incoming = listener.accept() => match incoming {
                         Ok((stream, addr)) => {
                             if let Err(error) = config.set_accepted_socket_options(&stream) {
                                 warn!("#{client_id}: failed to set socket options {error:?}");
                             }
                             info!("#{client_id}: new connection from {addr:?}");
-                            stream
+                            (stream, addr)
                         }
                         Err(error) => {
                             error!("failed to accept new connection: {error}");
@@ -120,17 +172,50 @@ impl PubSubServer {
                 // Create service
                 let recv_max_message_size = config.recv_max_message_size;
                 let enable_block_subscription = config.enable_block_subscription;
+                let enable_vote_subscription = config.enable_vote_subscription;
                 let enable_transaction_subscription = config.enable_transaction_subscription;
                 let service = service_fn({
                     let clients_tx = clients_tx.clone();
                     let notifications = notifications.clone();
+                    let tenants = tenants.clone();
+                    let admin = admin.clone();
                     let shutdown = shutdown.clone();
                     move |req: Request<BodyIncoming>| {
                         let clients_tx = clients_tx.clone();
                         let notifications = notifications.subscribe();
+                        let tenants = tenants.clone();
+                        let admin = admin.clone();
                         let shutdown = shutdown.clone();
                         async move {
-                            let x_subscription_id: Arc<str> = get_x_subscription_id(req.headers());
+                            let x_token =
+                                req.headers().get("x-token").map(|value| value.as_bytes());
+                            let x_token_owned = x_token.map(ToOwned::to_owned);
+                            let subscription = match tenants
+                                .subscribe(ConfigAppsTenantApp::Pubsub, x_token)
+                            {
+                                Ok(None) if tenants.has_app(ConfigAppsTenantApp::Pubsub) => {
+                                    return Response::builder()
+                                        .status(StatusCode::UNAUTHORIZED)
+                                        .body("No valid auth token".to_owned().boxed());
+                                }
+                                Ok(subscription) => subscription,
+                                Err(error) => {
+                                    let status = match error {
+                                        TenantError::AppNotAllowed { .. } => StatusCode::FORBIDDEN,
+                                        TenantError::SubscriptionsLimit { .. } => {
+                                            StatusCode::TOO_MANY_REQUESTS
+                                        }
+                                    };
+                                    return Response::builder()
+                                        .status(status)
+                                        .body(error.to_string().boxed());
+                                }
+                            };
+
+                            let x_subscription_id: Arc<str> = match &subscription {
+                                Some(subscription) => Arc::clone(subscription.name()),
+                                None => get_x_subscription_id(req.headers()),
+                            };
                             let connections_total = gauge!(
                                 metrics::PUBSUB_CONNECTIONS_TOTAL,
                                 "x_subscription_id" => Arc::clone(&x_subscription_id),
@@ -139,14 +224,28 @@ impl PubSubServer {
                             match (req.uri().path(), is_upgrade_request(&req)) {
                                 ("/", true) => match upgrade(req) {
                                     Ok((response, ws_fut)) => {
+                                        let admin_state = Arc::new(PubSubClientState::default());
+                                        let admin = admin.register(
+                                            AdminClientInfo {
+                                                app: AdminClientApp::Pubsub,
+                                                remote_addr: Some(addr),
+                                                x_token: x_token_owned,
+                                                x_subscription_id: Arc::clone(&x_subscription_id),
+                                            },
+                                            Arc::clone(&admin_state) as Arc<dyn AdminClientState>,
+                                        );
                                         tokio::spawn(async move {
                                             connections_total.increment(1);
                                             if let Err(error) = Self::handle_client(
                                                 client_id,
                                                 x_subscription_id,
                                                 ws_fut,
+                                                subscription,
+                                                admin,
+                                                admin_state,
                                                 recv_max_message_size,
                                                 enable_block_subscription,
+                                                enable_vote_subscription,
                                                 enable_transaction_subscription,
                                                 clients_tx,
                                                 notifications,
@@ -210,7 +309,10 @@ impl PubSubServer {
         .boxed();
 
         // Wait spawned features
-        Ok(try_join_all([subscriptions_jh, server_jh]).map_ok(|_| ()))
+        Ok((
+            reload,
+            try_join_all([subscriptions_jh, server_jh]).map_ok(|_| ()),
+        ))
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -218,8 +320,12 @@ impl PubSubServer {
         client_id: ClientId,
         x_subscription_id: Arc<str>,
         ws_fut: UpgradeFut,
+        mut subscription: Option<TenantSubscription>,
+        admin: AdminClientHandle,
+        admin_state: Arc<PubSubClientState>,
         recv_max_message_size: usize,
         enable_block_subscription: bool,
+        enable_vote_subscription: bool,
         enable_transaction_subscription: bool,
         clients_tx: kanal::AsyncSender<ClientRequest>,
         mut notifications: broadcast::Receiver<RpcNotification>,
@@ -232,66 +338,72 @@ impl PubSubServer {
         let (ws_rx, mut ws_tx) = ws.split(tokio::io::split);
         let mut ws_rx = FragmentCollectorRead::new(ws_rx);
 
+        let disconnect = admin.disconnect();
         let (read_tx, read_rx) = kanal::bounded_async::<WriteRequest>(1);
-        let read_fut = tokio::spawn(async move {
-            let mut send_frame = None;
-            let mut last_frame = false;
-            let mut send_fn = |_| async { Ok::<(), String>(()) };
-            loop {
-                if let Some(frame) = send_frame.take() {
-                    let (tx, rx) = oneshot::channel();
-                    let msg = WriteRequest::Frame { frame, tx };
-                    if read_tx.send(msg).await.is_err() || rx.await.is_err() {
-                        last_frame = true
-                    }
-                }
-                if last_frame {
-                    break;
-                }
-
-                // read msg
-                let frame = tokio::select! {
-                    frame = ws_rx.read_frame(&mut send_fn) => frame?,
-                    () = shutdown.cancelled() => break,
-                };
-                let payload = match frame.opcode {
-                    OpCode::Close => {
-                        send_frame = Some(create_frame_close(frame)?);
-                        last_frame = true;
-                        continue;
+        let read_fut = tokio::spawn({
+            let disconnect = disconnect.clone();
+            async move {
+                let mut send_frame = None;
+                let mut last_frame = false;
+                let mut send_fn = |_| async { Ok::<(), String>(()) };
+                loop {
+                    if let Some(frame) = send_frame.take() {
+                        let (tx, rx) = oneshot::channel();
+                        let msg = WriteRequest::Frame { frame, tx };
+                        if read_tx.send(msg).await.is_err() || rx.await.is_err() {
+                            last_frame = true
+                        }
                     }
-                    OpCode::Ping => {
-                        send_frame = Some(Frame::pong(frame.payload));
-                        continue;
+                    if last_frame {
+                        break;
                     }
-                    OpCode::Text | OpCode::Binary => frame.payload,
-                    OpCode::Continuation | OpCode::Pong => continue,
-                };
 
-                // parse msg
-                let message = match SubscribeMessage::parse(
-                    payload.as_ref(),
-                    enable_block_subscription,
-                    enable_transaction_subscription,
-                ) {
-                    Ok(Some(msg)) => msg,
-                    Ok(None) => continue,
-                    Err(error) => {
-                        let vec =
-                            serde_json::to_vec(&error).expect("json serialization never fail");
-                        send_frame = Some(Frame::text(Payload::Owned(vec)));
-                        continue;
-                    }
-                };
+                    // read msg
+                    let frame = tokio::select! {
+                        frame = ws_rx.read_frame(&mut send_fn) => frame?,
+                        () = shutdown.cancelled() => break,
+                        () = disconnect.cancelled() => break,
+                    };
+                    let payload = match frame.opcode {
+                        OpCode::Close => {
+                            send_frame = Some(create_frame_close(frame)?);
+                            last_frame = true;
+                            continue;
+                        }
+                        OpCode::Ping => {
+                            send_frame = Some(Frame::pong(frame.payload));
+                            continue;
+                        }
+                        OpCode::Text | OpCode::Binary => frame.payload,
+                        OpCode::Continuation | OpCode::Pong => continue,
+                    };
+
+                    // parse msg
+                    let message = match SubscribeMessage::parse(
+                        payload.as_ref(),
+                        enable_block_subscription,
+                        enable_vote_subscription,
+                        enable_transaction_subscription,
+                    ) {
+                        Ok(Some(msg)) => msg,
+                        Ok(None) => continue,
+                        Err(error) => {
+                            let vec =
+                                serde_json::to_vec(&error).expect("json serialization never fail");
+                            send_frame = Some(Frame::text(Payload::Owned(vec)));
+                            continue;
+                        }
+                    };
 
-                // send msg to write fut
-                let (tx, rx) = oneshot::channel();
-                let msg = WriteRequest::Message { message, tx };
-                if read_tx.send(msg).await.is_err() || rx.await.is_err() {
-                    last_frame = true
+                    // send msg to write fut
+                    let (tx, rx) = oneshot::channel();
+                    let msg = WriteRequest::Message { message, tx };
+                    if read_tx.send(msg).await.is_err() || rx.await.is_err() {
+                        last_frame = true
+                    }
                 }
+                Ok::<(), anyhow::Error>(())
             }
-            Ok::<(), anyhow::Error>(())
         })
         .map_err(anyhow::Error::new)
         .and_then(ready);
@@ -299,7 +411,16 @@ impl PubSubServer {
         let write_fut = tokio::spawn(async move {
             let mut subscriptions = IntMap::<SubscriptionId, SubscribeMethod>::default();
             let maybe_close_reason = loop {
+                // notifications are not received over the tenant bandwidth, requests are still
+                // processed, the client is disconnected as lagged if the queue is overflowed
+                let throttled = subscription.as_ref().is_some_and(TenantSubscription::is_throttled);
                 tokio::select! {
+                    () = disconnect.cancelled() => break Some("disconnected".as_bytes()),
+                    () = async {
+                        if let Some(subscription) = subscription.as_mut() {
+                            subscription.wait_budget().await;
+                        }
+                    }, if throttled => {},
                     message = read_rx.recv() => match message {
                         Ok(WriteRequest::Frame { frame, tx }) => {
                             ws_tx.write_frame(frame).await?;
@@ -328,6 +449,7 @@ impl PubSubServer {
                                     let removed = rx.await?;
                                     if removed {
                                         if let Some(method) = subscriptions.remove(&id) {
+                                            admin_state.update_subscriptions(method, false);
                                             gauge!(
                                                 metrics::PUBSUB_SUBSCRIPTIONS_TOTAL,
                                                 "x_subscription_id" => Arc::clone(&x_subscription_id),
@@ -355,6 +477,7 @@ impl PubSubServer {
                                     }
                                     let (id, method) = rx.await?;
                                     subscriptions.insert(id, method);
+                                    admin_state.update_subscriptions(method, true);
                                     gauge!(
                                         metrics::PUBSUB_SUBSCRIPTIONS_TOTAL,
                                         "x_subscription_id" => Arc::clone(&x_subscription_id),
@@ -381,10 +504,12 @@ impl PubSubServer {
                         },
                         Err(_) => break None, // means shutdown
                     },
-                    message = notifications.recv() => match message {
+                    message = notifications.recv(), if !throttled => match message {
                         Ok(notification) if subscriptions.contains_key(&notification.subscription_id) => {
+                            admin_state.lag.store(notifications.len() as u64, Ordering::Relaxed);
                             if notification.is_final {
                                 if let Some(method) = subscriptions.remove(&notification.subscription_id) {
+                                    admin_state.update_subscriptions(method, false);
                                     gauge!(
                                         metrics::PUBSUB_SUBSCRIPTIONS_TOTAL,
                                         "x_subscription_id" => Arc::clone(&x_subscription_id),
@@ -412,13 +537,17 @@ impl PubSubServer {
                                         "subscription" => notification.method.as_str(),
                                     )
                                     .increment(size as u64);
+
+                                    if let Some(subscription) = subscription.as_mut() {
+                                        subscription.consume(size);
+                                    }
                                 },
                                 None => {
                                     break Some("lagged: memory".as_bytes())
                                 }
                             }
                         },
-                        Ok(_) => {},
+                        Ok(_) => admin_state.lag.store(notifications.len() as u64, Ordering::Relaxed),
                         Err(broadcast::error::RecvError::Closed) => break Some("shutdown".as_bytes()),
                         Err(broadcast::error::RecvError::Lagged(_)) => break Some("lagged: len".as_bytes()),
                     }
@@ -445,6 +574,42 @@ impl PubSubServer {
     }
 }
 
+#[derive(Debug, Default)]
+struct PubSubClientState {
+    subscriptions: Mutex<BTreeMap<&'static str, usize>>,
+    // notifications in the queue, shared by all clients
+    lag: AtomicU64,
+}
+
+impl PubSubClientState {
+    fn update_subscriptions(&self, method: SubscribeMethod, added: bool) {
+        let mut subscriptions = mutex_lock(&self.subscriptions);
+        let count = subscriptions.entry(method.as_str()).or_default();
+        if added {
+            *count += 1;
+        } else {
+            *count = count.saturating_sub(1);
+            if *count == 0 {
+                subscriptions.remove(method.as_str());
+            }
+        }
+    }
+}
+
+impl AdminClientState for PubSubClientState {
+    fn filter(&self) -> serde_json::Value {
+        serde_json::to_value(&*mutex_lock(&self.subscriptions)).expect("valid subscriptions")
+    }
+
+    fn lag(&self, _messages: &Messages) -> AdminClientLag {
+        AdminClientLag {
+            messages: Some(self.lag.load(Ordering::Relaxed)),
+            slots: None,
+            queue_bytes: None,
+        }
+    }
+}
+
 #[allow(clippy::large_enum_variant)]
 enum WriteRequest<'a> {
     Frame {
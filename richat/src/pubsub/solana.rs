@@ -4,6 +4,7 @@ use {
         pubsub::{SubscriptionId, filter::TransactionFilter},
     },
     arrayvec::ArrayVec,
+    bincode::Options,
     jsonrpsee_types::{
         ErrorCode, ErrorObject, ErrorObjectOwned, Extensions, Id, Params, Request, Response,
         ResponsePayload, TwoPointZero,
@@ -26,10 +27,12 @@ use {
             RpcTransactionLogsFilter,
         },
         filter::RpcFilterType,
+        response::RpcVote,
     },
     solana_signature::Signature,
     solana_transaction_error::TransactionError,
     solana_transaction_status::{BlockEncodingOptions, TransactionDetails, UiTransactionEncoding},
+    solana_vote_interface::instruction::VoteInstruction,
     spl_token_2022_interface::{
         generic_token_account::GenericTokenAccount, state::Account as SplToken2022Account,
     },
@@ -51,6 +54,7 @@ impl SubscribeMessage {
     pub fn parse(
         message: &[u8],
         enable_block_subscription: bool,
+        enable_vote_subscription: bool,
         enable_transaction_subscription: bool,
     ) -> Result<Option<Self>, Response<'static, ()>> {
         let call: Request = serde_json::from_slice(message).map_err(|_error| Response {
@@ -64,6 +68,7 @@ impl SubscribeMessage {
             &call.method,
             call.params,
             enable_block_subscription,
+            enable_vote_subscription,
             enable_transaction_subscription,
         )
         .map_err(|error| Response {
@@ -89,6 +94,7 @@ pub enum SubscribeMethod {
     Slot,
     SlotsUpdates,
     Block,
+    Vote,
     Root,
     Transaction,
 }
@@ -98,7 +104,9 @@ impl SubscribeMethod {
         match message {
             ParsedMessage::Slot(_) => &[Self::Slot, Self::SlotsUpdates, Self::Root],
             ParsedMessage::Account(_) => &[Self::Account, Self::Program],
-            ParsedMessage::Transaction(_) => &[Self::Logs, Self::Signature, Self::Transaction],
+            ParsedMessage::Transaction(_) => {
+                &[Self::Logs, Self::Signature, Self::Vote, Self::Transaction]
+            }
             ParsedMessage::Entry(_) => &[],
             ParsedMessage::BlockMeta(_) => &[],
             ParsedMessage::Block(_) => &[Self::Block],
@@ -114,6 +122,7 @@ impl SubscribeMethod {
             Self::Slot => "slot",
             Self::SlotsUpdates => "slotsupdates",
             Self::Block => "block",
+            Self::Vote => "vote",
             Self::Root => "root",
             Self::Transaction => "transaction",
         }
@@ -168,6 +177,7 @@ pub enum SubscribeConfig {
         show_rewards: bool,
         max_supported_transaction_version: Option<u8>,
     },
+    Vote,
     Root,
     Transaction {
         filter: TransactionFilter,
@@ -189,6 +199,7 @@ impl SubscribeConfig {
         method: &str,
         params: Option<Cow<'_, RawValue>>,
         enable_block_subscription: bool,
+        enable_vote_subscription: bool,
         enable_transaction_subscription: bool,
     ) -> Result<Self, ErrorObjectOwned> {
         match method {
@@ -318,14 +329,14 @@ impl SubscribeConfig {
                     max_supported_transaction_version: config.max_supported_transaction_version,
                 })
             }
-            // "voteSubscribe" => {
-            //     if !enable_vote_subscription {
-            //         return Err(ErrorCode::MethodNotFound.into());
-            //     }
-
-            //     expect_no_params(params)?;
-            //     Ok(SubscribeConfig::Vote)
-            // }
+            "voteSubscribe" => {
+                if !enable_vote_subscription {
+                    return Err(ErrorCode::MethodNotFound.into());
+                }
+
+                expect_no_params(params)?;
+                Ok(SubscribeConfig::Vote)
+            }
             "rootSubscribe" => {
                 expect_no_params(params)?;
                 Ok(SubscribeConfig::Root)
@@ -398,10 +409,11 @@ impl SubscribeConfig {
             | "slotUnsubscribe"
             | "slotsUpdatesUnsubscribe"
             | "blockUnsubscribe"
-            // | "voteUnsubscribe"
+            | "voteUnsubscribe"
             | "rootUnsubscribe"
             | "transactionUnsubscribe" => {
                 if (method == "blockUnsubscribe" && !enable_block_subscription)
+                    || (method == "voteUnsubscribe" && !enable_vote_subscription)
                     || (method == "transactionUnsubscribe" && !enable_transaction_subscription)
                 {
                     return Err(ErrorCode::MethodNotFound.into());
@@ -436,6 +448,7 @@ impl SubscribeConfig {
             Self::Slot => CommitmentLevel::Processed,
             Self::SlotsUpdates => CommitmentLevel::Processed,
             Self::Block { commitment, .. } => commitment.commitment,
+            Self::Vote => CommitmentLevel::Processed,
             Self::Root => CommitmentLevel::Processed,
             Self::Transaction { commitment, .. } => commitment.commitment,
             Self::Unsubscribe { .. } => unreachable!(),
@@ -453,6 +466,7 @@ impl SubscribeConfig {
             Self::Slot => SubscribeMethod::Slot,
             Self::SlotsUpdates => SubscribeMethod::SlotsUpdates,
             Self::Block { .. } => SubscribeMethod::Block,
+            Self::Vote => SubscribeMethod::Vote,
             Self::Root => SubscribeMethod::Root,
             Self::Transaction { .. } => SubscribeMethod::Transaction,
             Self::Unsubscribe { .. } => unreachable!(),
@@ -572,6 +586,58 @@ impl SubscribeConfig {
         None
     }
 
+    // https://github.com/anza-xyz/agave/blob/v3.1.7/vote/src/vote_parser.rs
+    pub fn filter_vote(&self, message: &MessageTransaction) -> Option<RpcVote> {
+        if !matches!(self, Self::Vote) || !message.vote() {
+            return None;
+        }
+
+        // vote should be the first instruction
+        let tx_message = message
+            .transaction()
+            .ok()?
+            .transaction
+            .as_ref()?
+            .message
+            .as_ref()?;
+        let ix = tx_message.instructions.first()?;
+        let program_id = tx_message.account_keys.get(ix.program_id_index as usize)?;
+        if program_id.as_slice() != solana_vote_interface::program::ID.as_ref() {
+            return None;
+        }
+        let vote_pubkey = tx_message
+            .account_keys
+            .get(*ix.accounts.first()? as usize)?;
+        let vote_pubkey = Pubkey::try_from(vote_pubkey.as_slice()).ok()?;
+
+        let vote = bincode::options()
+            .with_limit(PACKET_DATA_SIZE)
+            .with_fixint_encoding()
+            .allow_trailing_bytes()
+            .deserialize::<VoteInstruction>(&ix.data)
+            .ok()?;
+        let slots = match &vote {
+            VoteInstruction::Vote(vote) | VoteInstruction::VoteSwitch(vote, _) => {
+                vote.slots.clone()
+            }
+            VoteInstruction::UpdateVoteState(update)
+            | VoteInstruction::UpdateVoteStateSwitch(update, _)
+            | VoteInstruction::CompactUpdateVoteState(update)
+            | VoteInstruction::CompactUpdateVoteStateSwitch(update, _) => update.slots(),
+            VoteInstruction::TowerSync(tower_sync)
+            | VoteInstruction::TowerSyncSwitch(tower_sync, _) => tower_sync.slots(),
+            _ => return None,
+        };
+
+        Some(RpcVote {
+            vote_pubkey: vote_pubkey.to_string(),
+            slots,
+            hash: vote.hash().to_string(),
+            timestamp: vote.timestamp(),
+            signature: message.signature().to_string(),
+        })
+    }
+
     pub fn filter_transaction(
         &self,
         message: &MessageTransaction,
@@ -595,6 +661,9 @@ impl SubscribeConfig {
     }
 }
 
+// same as `solana_packet::PACKET_DATA_SIZE`
+const PACKET_DATA_SIZE: u64 = 1280 - 40 - 8;
+
 fn check_is_at_least_confirmed(commitment: CommitmentConfig) -> Result<(), ErrorObjectOwned> {
     if !commitment.is_at_least_confirmed() {
         Err(invalid_params(
@@ -673,8 +742,18 @@ fn expect_no_params(params: Option<Cow<'_, RawValue>>) -> Result<(), ErrorObject
 #[cfg(test)]
 mod test {
     use {
-        super::parse_params, serde::Deserialize, serde_json::value::RawValue,
-        solana_rpc_client_api::config::RpcAccountInfoConfig, std::borrow::Cow,
+        super::{SubscribeConfig, parse_params},
+        richat_filter::message::MessageTransaction,
+        richat_proto::{
+            geyser::SubscribeUpdateTransactionInfo,
+            solana::storage::confirmed_block::{CompiledInstruction, Message, Transaction},
+        },
+        serde::Deserialize,
+        serde_json::value::RawValue,
+        solana_pubkey::Pubkey,
+        solana_rpc_client_api::{config::RpcAccountInfoConfig, response::RpcVote},
+        solana_vote_interface::{instruction::VoteInstruction, state::Vote},
+        std::{borrow::Cow, collections::HashSet},
     };
 
     #[test]
@@ -705,4 +784,59 @@ mod test {
             })
         );
     }
+
+    #[test]
+    fn test_filter_vote() {
+        let vote_pubkey = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let program_id = solana_vote_interface::program::ID;
+        let data = bincode::serialize(&VoteInstruction::Vote(Vote {
+            slots: vec![41, 42],
+            hash: Default::default(),
+            timestamp: Some(1_700_000_000),
+        }))
+        .unwrap();
+
+        let message = MessageTransaction::Prost {
+            error: None,
+            account_keys: HashSet::from([vote_pubkey, authority, program_id]),
+            transaction: SubscribeUpdateTransactionInfo {
+                signature: vec![1; 64],
+                is_vote: true,
+                transaction: Some(Transaction {
+                    signatures: vec![vec![1; 64]],
+                    message: Some(Message {
+                        account_keys: vec![
+                            authority.to_bytes().to_vec(),
+                            vote_pubkey.to_bytes().to_vec(),
+                            program_id.to_bytes().to_vec(),
+                        ],
+                        instructions: vec![CompiledInstruction {
+                            program_id_index: 2,
+                            accounts: vec![1, 0],
+                            data,
+                        }],
+                        ..Default::default()
+                    }),
+                }),
+                meta: None,
+                index: 0,
+            },
+            slot: 43,
+            created_at: Default::default(),
+            size: 0,
+        };
+
+        assert_eq!(
+            SubscribeConfig::Vote.filter_vote(&message),
+            Some(RpcVote {
+                vote_pubkey: vote_pubkey.to_string(),
+                slots: vec![41, 42],
+                hash: "11111111111111111111111111111111".to_owned(),
+                timestamp: Some(1_700_000_000),
+                signature: message.signature().to_string(),
+            })
+        );
+        assert_eq!(SubscribeConfig::Slot.filter_vote(&message), None);
+    }
 }
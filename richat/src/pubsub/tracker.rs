@@ -4,11 +4,13 @@ use {
         metrics,
         pubsub::{
             ClientId, SubscriptionId,
+            mints::MintsCache,
             notification::{
                 RpcBlockUpdate, RpcNotification, RpcNotifications, RpcTransactionUpdate,
             },
             solana::{SubscribeConfig, SubscribeConfigHashId, SubscribeMethod},
         },
+        storage::segments::SegmentReader,
     },
     ::metrics::gauge,
     foldhash::quality::RandomState,
@@ -17,10 +19,15 @@ use {
         ThreadPoolBuilder,
         iter::{IntoParallelIterator, ParallelIterator},
     },
-    richat_filter::message::{MessageSlot, MessageTransaction},
+    richat_filter::message::{MessageAccount, MessageSlot, MessageTransaction},
     richat_proto::{convert_from, geyser::SlotStatus},
-    richat_shared::five8::{pubkey_encode, signature_encode},
-    solana_account_decoder::encode_ui_account,
+    richat_shared::{
+        five8::{pubkey_encode, signature_encode},
+        transports::RecvError,
+    },
+    solana_account_decoder::{
+        UiAccountEncoding, encode_ui_account, parse_account_data::AccountAdditionalDataV3,
+    },
     solana_clock::Slot,
     solana_commitment_config::CommitmentLevel,
     solana_nohash_hasher::IntMap,
@@ -32,11 +39,13 @@ use {
     solana_transaction_error::TransactionError,
     std::{
         collections::{BTreeMap, HashMap, HashSet, hash_map::Entry as HashMapEntry},
+        path::PathBuf,
         sync::Arc,
         thread,
-        time::Duration,
+        time::{Duration, Instant},
     },
     tokio::sync::oneshot,
+    tracing::{info, warn},
 };
 
 #[allow(clippy::large_enum_variant)]
@@ -290,6 +299,8 @@ pub fn subscriptions_worker(
     mut notifications: RpcNotifications,
     signatures_cache_max: usize,
     signatures_cache_slots_max: usize,
+    mints_cache_max: usize,
+    mints_cache_path: Option<PathBuf>,
 ) -> anyhow::Result<()> {
     // Subscriptions storage
     let mut subscriptions = Subscriptions::default();
@@ -320,9 +331,31 @@ pub fn subscriptions_worker(
 
     // Signatures cache
     let mut signatures = CachedSignatures::new(signatures_cache_max, signatures_cache_slots_max);
+    signatures.load_from_storage(&messages)?;
+
+    // messages pushed while storage was loaded could be already removed from memory
+    for (commitment, head) in [
+        (CommitmentLevel::Processed, &mut head_processed),
+        (CommitmentLevel::Confirmed, &mut head_confirmed),
+        (CommitmentLevel::Finalized, &mut head_finalized),
+    ] {
+        if let Err(RecvError::Lagged) = receiver.try_recv(commitment, *head) {
+            *head = messages.get_current_tail(commitment);
+            warn!(
+                ?commitment,
+                "lagged on loading signatures from storage, messages skipped"
+            );
+        }
+    }
+
+    // Mints cache for `jsonParsed` token accounts
+    let mut mints = MintsCache::new(mints_cache_max);
+    if let Some(path) = mints_cache_path {
+        mints.load_from_file(&path)?;
+    }
 
     // main loop
-    let mut slot_finalized = 0;
+    let mut slot_finalized = signatures.slot_finalized;
     let mut slots_stats = BTreeMap::<Slot, SlotTransactionStatsItem>::new();
     let mut messages_extra = vec![];
     loop {
@@ -419,6 +452,10 @@ pub fn subscriptions_worker(
                             }
                             None
                         }
+                        ParsedMessage::Account(message) => {
+                            mints.update(message);
+                            None
+                        }
                         ParsedMessage::Transaction(message) => {
                             signatures.add_signature(message);
                             slots_stats
@@ -486,7 +523,7 @@ pub fn subscriptions_worker(
                                         message.pubkey(),
                                         message.as_ref(),
                                         encoding,
-                                        None,
+                                        get_additional_data(&mints, message, encoding),
                                         data_slice,
                                     ),
                                 );
@@ -507,7 +544,7 @@ pub fn subscriptions_worker(
                                             message.pubkey(),
                                             message.as_ref(),
                                             encoding,
-                                            None,
+                                            get_additional_data(&mints, message, encoding),
                                             data_slice,
                                         ),
                                     },
@@ -619,6 +656,16 @@ pub fn subscriptions_worker(
                                 return Some((subscription, false, json));
                             }
                         }
+                        (SubscribeMethod::Vote, ParsedMessage::Transaction(message)) => {
+                            if let Some(vote) = subscription.config.filter_vote(message) {
+                                let json = RpcNotification::serialize(
+                                    "voteNotification",
+                                    subscription.id,
+                                    vote,
+                                );
+                                return Some((subscription, false, json));
+                            }
+                        }
                         (SubscribeMethod::Root, ParsedMessage::Slot(message)) => {
                             if message.status() == SlotStatus::SlotFinalized {
                                 let json = RpcNotification::serialize(
@@ -768,6 +815,56 @@ impl CachedSignatures {
     const fn set_finalized(&mut self, slot: Slot) {
         self.slot_finalized = slot;
     }
+
+    // warm cache and commitment state from storage, so restart doesn't lose known signatures
+    fn load_from_storage(&mut self, messages: &Messages) -> anyhow::Result<()> {
+        match messages.read_storage_last_slots(self.slots_max) {
+            Some(reader) => self.load_from_reader(reader),
+            None => Ok(()),
+        }
+    }
+
+    fn load_from_reader(&mut self, reader: SegmentReader) -> anyhow::Result<()> {
+        let ts = Instant::now();
+        for chunk_result in reader {
+            let mut chunk = chunk_result?;
+            for result in &mut chunk {
+                match result?.1 {
+                    ParsedMessage::Slot(message) => match message.status() {
+                        SlotStatus::SlotDead => self.dead_slot(message.slot()),
+                        SlotStatus::SlotConfirmed => self.set_confirmed(message.slot()),
+                        SlotStatus::SlotFinalized => self.set_finalized(message.slot()),
+                        _ => {}
+                    },
+                    ParsedMessage::Transaction(message) => self.add_signature(&message),
+                    _ => {}
+                }
+            }
+        }
+        gauge!(metrics::PUBSUB_SLOT, "commitment" => "confirmed").set(self.slot_confirmed as f64);
+        gauge!(metrics::PUBSUB_SLOT, "commitment" => "finalized").set(self.slot_finalized as f64);
+        info!(
+            "loaded {} signatures from storage in {:?} (confirmed: {}, finalized: {})",
+            self.signatures.len(),
+            ts.elapsed(),
+            self.slot_confirmed,
+            self.slot_finalized
+        );
+
+        Ok(())
+    }
+}
+
+fn get_additional_data(
+    mints: &MintsCache,
+    message: &MessageAccount,
+    encoding: UiAccountEncoding,
+) -> Option<AccountAdditionalDataV3> {
+    if encoding == UiAccountEncoding::JsonParsed {
+        mints.get_additional_data(message)
+    } else {
+        None
+    }
 }
 
 type SlotTransactionStatsItemResult = Option<(ParsedMessage, SlotTransactionStats)>;
@@ -853,7 +950,21 @@ impl SlotTransactionStatsItem {
 
 #[cfg(test)]
 mod tests {
-    use {super::*, tokio::sync::broadcast};
+    use {
+        super::*,
+        crate::storage::tests::TestStorage,
+        prost::Message as _,
+        richat_filter::message::{Message, MessageParserEncoding},
+        richat_proto::{
+            geyser::{
+                SubscribeUpdate, SubscribeUpdateSlot, SubscribeUpdateTransaction,
+                SubscribeUpdateTransactionInfo, subscribe_update::UpdateOneof,
+            },
+            solana::storage::confirmed_block::Transaction,
+        },
+        std::borrow::Cow,
+        tokio::sync::broadcast,
+    };
 
     fn create_test_deps() -> (CachedSignatures, RpcNotifications) {
         let signatures = CachedSignatures::new(100, 100);
@@ -898,4 +1009,89 @@ mod tests {
         assert!(subscriptions.subscriptions_per_client.is_empty());
         assert!(subscriptions.subscriptions.is_empty());
     }
+
+    fn parse(update_oneof: UpdateOneof) -> ParsedMessage {
+        let data = SubscribeUpdate {
+            filters: vec![],
+            update_oneof: Some(update_oneof),
+            created_at: Some(Default::default()),
+        }
+        .encode_to_vec();
+        Message::parse(Cow::Owned(data), MessageParserEncoding::Prost)
+            .expect("valid message")
+            .into()
+    }
+
+    fn slot(slot: Slot, status: SlotStatus) -> (Slot, ParsedMessage) {
+        let message = parse(UpdateOneof::Slot(SubscribeUpdateSlot {
+            slot,
+            parent: None,
+            status: status as i32,
+            dead_error: None,
+        }));
+        (slot, message)
+    }
+
+    fn transaction(slot: Slot, signature: Signature) -> (Slot, ParsedMessage) {
+        let message = parse(UpdateOneof::Transaction(SubscribeUpdateTransaction {
+            transaction: Some(SubscribeUpdateTransactionInfo {
+                signature: signature.as_ref().to_vec(),
+                transaction: Some(Transaction {
+                    signatures: vec![signature.as_ref().to_vec()],
+                    message: Some(Default::default()),
+                }),
+                meta: Some(Default::default()),
+                ..Default::default()
+            }),
+            slot,
+        }));
+        (slot, message)
+    }
+
+    #[test]
+    fn test_signatures_load_from_storage() {
+        let storage = TestStorage::open(TestStorage::config("signatures", None)).unwrap();
+        let (sig1, sig2, sig3) = (
+            Signature::from([1; 64]),
+            Signature::from([2; 64]),
+            Signature::from([3; 64]),
+        );
+        storage.push([
+            slot(1, SlotStatus::SlotProcessed),
+            transaction(1, sig1),
+            slot(2, SlotStatus::SlotProcessed),
+            transaction(2, sig2),
+            slot(3, SlotStatus::SlotProcessed),
+            transaction(3, sig3),
+            slot(1, SlotStatus::SlotConfirmed),
+            slot(3, SlotStatus::SlotDead),
+            slot(1, SlotStatus::SlotFinalized),
+            slot(2, SlotStatus::SlotConfirmed),
+        ]);
+
+        let mut signatures = CachedSignatures::new(100, 100);
+        signatures
+            .load_from_reader(
+                storage
+                    .storage
+                    .read_messages_from_index(0, MessageParserEncoding::Prost),
+            )
+            .unwrap();
+        assert_eq!(signatures.slot_confirmed, 2);
+        assert_eq!(signatures.slot_finalized, 1);
+        assert_eq!(
+            signatures.get(&sig1, CommitmentLevel::Finalized),
+            Some((1, None))
+        );
+        assert_eq!(signatures.get(&sig2, CommitmentLevel::Finalized), None);
+        assert_eq!(
+            signatures.get(&sig2, CommitmentLevel::Confirmed),
+            Some((2, None))
+        );
+        // dead slot
+        assert_eq!(signatures.get(&sig3, CommitmentLevel::Processed), None);
+
+        let config = storage.close();
+        let _ = std::fs::remove_dir_all(config.path);
+    }
 }
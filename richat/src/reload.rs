@@ -0,0 +1,63 @@
+use {
+    crate::{
+        config::ConfigApps, grpc::server::GrpcServerReload, pubsub::server::PubSubServerReload,
+        richat::server::RichatServerReload, tenants::Tenants,
+    },
+    anyhow::Context,
+};
+
+/// Handles of running apps, new config is checked for every app before
+/// anything is applied, so reload is either fully applied or rejected.
+///
+/// Only x-tokens, tenants, gRPC filter limits and TLS certificates are reloaded,
+/// changes of other options require restart.
+#[derive(Debug, Clone)]
+pub struct AppsReload {
+    pub tenants: Tenants,
+    pub richat: Option<RichatServerReload>,
+    pub grpc: Option<GrpcServerReload>,
+    pub pubsub: Option<PubSubServerReload>,
+}
+
+impl AppsReload {
+    pub fn check(&self, config: &ConfigApps) -> anyhow::Result<()> {
+        Self::check_app("richat", &self.richat, &config.richat, |reload, config| {
+            reload.check(config)
+        })?;
+        Self::check_app("grpc", &self.grpc, &config.grpc, |reload, config| {
+            reload.check(config).map_err(Into::into)
+        })?;
+        Self::check_app("pubsub", &self.pubsub, &config.pubsub, |reload, config| {
+            reload.check(config).map_err(Into::into)
+        })
+    }
+
+    fn check_app<R, C>(
+        name: &str,
+        reload: &Option<R>,
+        config: &Option<C>,
+        check: impl FnOnce(&R, &C) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        match (reload, config) {
+            (Some(reload), Some(config)) => check(reload, config).context(format!("apps.{name}")),
+            (None, None) => Ok(()),
+            _ => anyhow::bail!("apps.{name} can't be enabled or disabled without restart"),
+        }
+    }
+
+    pub fn reload(&self, config: ConfigApps) -> anyhow::Result<()> {
+        self.check(&config)?;
+
+        self.tenants.reload(config.tenants);
+        if let (Some(reload), Some(config)) = (&self.richat, config.richat) {
+            reload.reload(config)?;
+        }
+        if let (Some(reload), Some(config)) = (&self.grpc, config.grpc) {
+            reload.reload(config)?;
+        }
+        if let (Some(reload), Some(config)) = (&self.pubsub, config.pubsub) {
+            reload.reload(&config)?;
+        }
+        Ok(())
+    }
+}
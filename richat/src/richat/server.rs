@@ -1,12 +1,90 @@
 use {
-    crate::{channel::Messages, metrics, richat::config::ConfigAppsRichat, version::VERSION},
+    crate::{
+        admin::{
+            AdminClientApp, AdminClientHandle, AdminClientInfo, AdminClientLag, AdminClientState,
+            AdminClients,
+        },
+        channel::{Messages, ReceiverAsync},
+        config::ConfigAppsTenantApp,
+        metrics,
+        richat::config::ConfigAppsRichat,
+        tenants::{TenantSubscription, Tenants},
+        version::VERSION,
+    },
     ::metrics::gauge,
-    futures::future::{FutureExt, TryFutureExt, try_join_all},
-    richat_shared::transports::{grpc::GrpcServer, quic::QuicServer},
-    std::future::Future,
-    tokio_util::sync::CancellationToken,
+    futures::{
+        future::{FutureExt, TryFutureExt, try_join_all},
+        stream::{Stream, StreamExt},
+    },
+    richat_proto::richat::RichatFilter,
+    richat_shared::transports::{
+        RecvError, RecvItem, RecvStream, Subscribe, SubscribeConnection, SubscribeError,
+        grpc::GrpcServer, quic::QuicServer, reload::ServerReload,
+    },
+    solana_clock::Slot,
+    solana_commitment_config::CommitmentLevel,
+    std::{
+        collections::HashSet,
+        future::Future,
+        pin::Pin,
+        sync::{
+            Arc,
+            atomic::{AtomicU64, Ordering},
+        },
+        task::{Context, Poll, ready},
+    },
+    tokio_util::sync::{CancellationToken, WaitForCancellationFutureOwned},
 };
 
+/// Settings of the running Richat transports applied to new connections.
+#[derive(Debug, Clone)]
+pub struct RichatServerReload {
+    quic: Option<ServerReload>,
+    grpc: Option<ServerReload>,
+    tenants: Tenants,
+}
+
+impl RichatServerReload {
+    pub fn check(&self, config: &ConfigAppsRichat) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            self.quic.is_some() == config.quic.is_some(),
+            "quic can't be enabled or disabled without restart"
+        );
+        anyhow::ensure!(
+            self.grpc.is_some() == config.grpc.is_some(),
+            "grpc can't be enabled or disabled without restart"
+        );
+        if let (Some(reload), Some(config)) = (&self.quic, &config.quic) {
+            reload.check(Some(&config.tls_config))?;
+        }
+        if let (Some(reload), Some(config)) = (&self.grpc, &config.grpc) {
+            reload.check(config.tls_config.as_ref())?;
+        }
+        Ok(())
+    }
+
+    /// Should be called after [`Tenants::reload`], tenants tokens are added to transports.
+    pub fn reload(&self, config: ConfigAppsRichat) -> anyhow::Result<()> {
+        self.check(&config)?;
+        if let (Some(reload), Some(mut config)) = (&self.quic, config.quic) {
+            add_tenants_tokens(&self.tenants, &mut config.x_tokens);
+            reload.reload(config.x_tokens, Some(&config.tls_config))?;
+        }
+        if let (Some(reload), Some(mut config)) = (&self.grpc, config.grpc) {
+            add_tenants_tokens(&self.tenants, &mut config.x_tokens);
+            reload.reload(config.x_tokens, config.tls_config.as_ref())?;
+        }
+        Ok(())
+    }
+}
+
+// tenants tokens are verified by transports, limits are checked on subscribe
+fn add_tenants_tokens(tenants: &Tenants, x_tokens: &mut HashSet<Vec<u8>>) {
+    if tenants.has_app(ConfigAppsTenantApp::Richat) {
+        x_tokens.extend(tenants.x_tokens());
+    }
+}
+
 #[derive(Debug)]
 pub struct RichatServer;
 
@@ -14,46 +92,173 @@ impl RichatServer {
     pub async fn spawn(
         config: ConfigAppsRichat,
         messages: Messages,
+        tenants: Tenants,
+        admin: AdminClients,
         shutdown: CancellationToken,
-    ) -> anyhow::Result<impl Future<Output = anyhow::Result<()>>> {
+    ) -> anyhow::Result<(RichatServerReload, impl Future<Output = anyhow::Result<()>>)> {
         let mut tasks = Vec::with_capacity(3);
+        let mut reload = RichatServerReload {
+            quic: None,
+            grpc: None,
+            tenants: tenants.clone(),
+        };
+
+        let messages = RichatMessages {
+            messages,
+            tenants: tenants.clone(),
+            admin,
+        };
 
         // Start Quic
-        if let Some(config) = config.quic {
+        if let Some(mut config) = config.quic {
+            add_tenants_tokens(&tenants, &mut config.x_tokens);
             let connections_inc = gauge!(metrics::RICHAT_CONNECTIONS_TOTAL, "transport" => "quic");
             let connections_dec = connections_inc.clone();
-            tasks.push(
-                QuicServer::spawn(
-                    config,
-                    messages.clone(),
-                    move || connections_inc.increment(1), // on_conn_new_cb
-                    move || connections_dec.decrement(1), // on_conn_drop_cb
-                    VERSION,
-                    shutdown.clone(),
-                )
-                .await?
-                .boxed(),
-            );
+            let (quic_reload, quic_fut) = QuicServer::spawn(
+                config,
+                messages.clone(),
+                move || connections_inc.increment(1), // on_conn_new_cb
+                move || connections_dec.decrement(1), // on_conn_drop_cb
+                VERSION,
+                shutdown.clone(),
+            )
+            .await?;
+            reload.quic = Some(quic_reload);
+            tasks.push(quic_fut.boxed());
         }
 
         // Start gRPC
-        if let Some(config) = config.grpc {
+        if let Some(mut config) = config.grpc {
+            add_tenants_tokens(&tenants, &mut config.x_tokens);
             let connections_inc = gauge!(metrics::RICHAT_CONNECTIONS_TOTAL, "transport" => "grpc");
             let connections_dec = connections_inc.clone();
-            tasks.push(
-                GrpcServer::spawn(
-                    config,
-                    messages.clone(),
-                    move || connections_inc.increment(1), // on_conn_new_cb
-                    move || connections_dec.decrement(1), // on_conn_drop_cb
-                    VERSION,
-                    shutdown.clone(),
-                )
-                .await?
-                .boxed(),
-            );
-        }
-
-        Ok(try_join_all(tasks).map_ok(|_| ()).map_err(Into::into))
+            let (grpc_reload, grpc_fut) = GrpcServer::spawn(
+                config,
+                messages.clone(),
+                move || connections_inc.increment(1), // on_conn_new_cb
+                move || connections_dec.decrement(1), // on_conn_drop_cb
+                VERSION,
+                shutdown.clone(),
+            )
+            .await?;
+            reload.grpc = Some(grpc_reload);
+            tasks.push(grpc_fut.boxed());
+        }
+
+        Ok((
+            reload,
+            try_join_all(tasks).map_ok(|_| ()).map_err(Into::into),
+        ))
+    }
+}
+
+#[derive(Debug, Clone)]
+struct RichatMessages {
+    messages: Messages,
+    tenants: Tenants,
+    admin: AdminClients,
+}
+
+impl Subscribe for RichatMessages {
+    fn subscribe(
+        &self,
+        connection: SubscribeConnection<'_>,
+        replay_from_slot: Option<Slot>,
+        replay_to_slot: Option<Slot>,
+        filter: Option<RichatFilter>,
+    ) -> Result<RecvStream, SubscribeError> {
+        let subscription = self
+            .tenants
+            .subscribe(ConfigAppsTenantApp::Richat, connection.x_token)?;
+        let stream =
+            self.messages
+                .subscribe_richat(replay_from_slot, replay_to_slot, filter.as_ref())?;
+        let admin = self.admin.register(
+            AdminClientInfo {
+                app: AdminClientApp::richat(connection.transport),
+                remote_addr: connection.remote_addr,
+                x_token: connection.x_token.map(ToOwned::to_owned),
+                x_subscription_id: subscription
+                    .as_ref()
+                    .map(|subscription| Arc::clone(subscription.name()))
+                    .unwrap_or_else(|| "".into()),
+            },
+            Arc::new(RichatClientState {
+                filter: filter.unwrap_or_default(),
+                position: stream.position(),
+            }),
+        );
+        Ok(RichatStream {
+            disconnect: Box::pin(admin.disconnect().cancelled_owned()),
+            stream,
+            subscription,
+            _admin: admin,
+            finished: false,
+        }
+        .boxed())
+    }
+}
+
+struct RichatClientState {
+    filter: RichatFilter,
+    position: Arc<AtomicU64>,
+}
+
+impl AdminClientState for RichatClientState {
+    fn filter(&self) -> serde_json::Value {
+        serde_json::json!({
+            "disable_accounts": self.filter.disable_accounts,
+            "disable_transactions": self.filter.disable_transactions,
+            "disable_entries": self.filter.disable_entries,
+            "accounts_pubkey": self.filter.accounts_pubkey.len(),
+            "accounts_owner": self.filter.accounts_owner.len(),
+            "transactions_account_include": self.filter.transactions_account_include.len(),
+            "transactions_exclude_vote": self.filter.transactions_exclude_vote,
+            "transactions_exclude_failed": self.filter.transactions_exclude_failed,
+        })
+    }
+
+    fn lag(&self, messages: &Messages) -> AdminClientLag {
+        let head = self.position.load(Ordering::Relaxed);
+        if head == u64::MAX {
+            return AdminClientLag::default();
+        }
+        let (lag_messages, lag_slots) = messages.get_lag(CommitmentLevel::Processed, head);
+        AdminClientLag {
+            messages: Some(lag_messages),
+            slots: lag_slots,
+            queue_bytes: None,
+        }
+    }
+}
+
+struct RichatStream {
+    stream: ReceiverAsync,
+    subscription: Option<TenantSubscription>,
+    disconnect: Pin<Box<WaitForCancellationFutureOwned>>,
+    _admin: AdminClientHandle,
+    finished: bool,
+}
+
+impl Stream for RichatStream {
+    type Item = Result<RecvItem, RecvError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.finished {
+            return Poll::Ready(None);
+        }
+        if self.disconnect.as_mut().poll(cx).is_ready() {
+            self.finished = true;
+            return Poll::Ready(Some(Err(RecvError::Closed)));
+        }
+
+        if let Some(subscription) = self.subscription.as_mut() {
+            ready!(subscription.poll_budget(cx));
+        }
+        let item = ready!(self.stream.poll_next_unpin(cx));
+        if let (Some(subscription), Some(Ok(data))) = (self.subscription.as_mut(), &item) {
+            subscription.consume(data.len());
+        }
+        Poll::Ready(item)
     }
 }
@@ -0,0 +1,347 @@
+use {
+    crate::{
+        config::{ConfigChannelSourcesPolicy, ConfigChannelSourcesPolicyMode},
+        metrics,
+    },
+    ::metrics::{counter, gauge},
+    quanta::Instant,
+    solana_clock::Slot,
+    std::collections::{BTreeMap, VecDeque},
+    tracing::info,
+};
+
+/// Tracks lag and gaps of every source relative to the fastest one and
+/// selects sources which messages are pushed to the channel.
+///
+/// Selected sources are switched on slot boundaries: demoted sources keep pushing slots
+/// started while they were active and promoted sources push slots in progress too, so
+/// the channel gets complete slots as long as the demoted source is alive.
+#[derive(Debug)]
+pub struct SourcesHealth {
+    policy: ConfigChannelSourcesPolicy,
+    sources: Vec<SourceHealth>,
+    slots: BTreeMap<Slot, SlotSources>,
+}
+
+#[derive(Debug)]
+struct SlotSources {
+    first: Instant,
+    // bit mask of sources (by index) which delivered the slot
+    delivered: u64,
+    // bit mask of sources (by index) which messages for the slot are pushed
+    forward: u64,
+}
+
+#[derive(Debug)]
+struct SourceHealth {
+    name: &'static str,
+    active: bool,
+    healthy: bool,
+    last_message: Instant,
+    last_slot: Slot,
+    // lag in microseconds for latest slots
+    lags: VecDeque<u64>,
+    lags_sum: u64,
+}
+
+impl SourceHealth {
+    const fn new(name: &'static str, now: Instant) -> Self {
+        Self {
+            name,
+            active: false,
+            healthy: true,
+            last_message: now,
+            last_slot: 0,
+            lags: VecDeque::new(),
+            lags_sum: 0,
+        }
+    }
+
+    fn lag_avg(&self) -> u64 {
+        self.lags_sum
+            .checked_div(self.lags.len() as u64)
+            .unwrap_or(0)
+    }
+
+    fn push_lag(&mut self, lag: u64, window: usize) {
+        self.lags.push_back(lag);
+        self.lags_sum += lag;
+        while self.lags.len() > window {
+            if let Some(lag) = self.lags.pop_front() {
+                self.lags_sum -= lag;
+            }
+        }
+        gauge!(metrics::CHANNEL_SOURCE_LAG_SECONDS, "source" => self.name)
+            .set(self.lag_avg() as f64 / 1_000_000.0);
+    }
+
+    fn set_active(&mut self, active: bool) {
+        if self.active != active {
+            self.active = active;
+            info!(name = self.name, active, "source state changed");
+            gauge!(metrics::CHANNEL_SOURCE_ACTIVE, "source" => self.name).set(active as u8 as f64);
+        }
+    }
+}
+
+impl SourcesHealth {
+    pub fn new(policy: ConfigChannelSourcesPolicy, names: &[&'static str]) -> Self {
+        let mut health = Self {
+            policy,
+            sources: vec![],
+            slots: BTreeMap::new(),
+        };
+        health.update_sources(names);
+        health
+    }
+
+    /// Set new list of sources (in config order), stats of existed sources are kept.
+    pub fn update_sources(&mut self, names: &[&'static str]) {
+        let now = Instant::now();
+        let mut sources = names
+            .iter()
+            .take(u64::BITS as usize)
+            .map(
+                |name| match self.sources.iter().position(|source| source.name == *name) {
+                    Some(index) => self.sources.swap_remove(index),
+                    None => SourceHealth::new(name, now),
+                },
+            )
+            .collect::<Vec<_>>();
+        for source in self.sources.drain(..) {
+            gauge!(metrics::CHANNEL_SOURCE_ACTIVE, "source" => source.name).set(0.0);
+        }
+        std::mem::swap(&mut self.sources, &mut sources);
+
+        // sources are re-indexed
+        self.slots.clear();
+        self.evaluate(now);
+    }
+
+    /// Update stats of the source, returns `true` if message should be pushed to the channel.
+    pub fn on_message(&mut self, name: &'static str, slot: Slot) -> bool {
+        self.on_message_at(name, slot, Instant::now())
+    }
+
+    fn on_message_at(&mut self, name: &'static str, slot: Slot, now: Instant) -> bool {
+        let Some(index) = self.sources.iter().position(|source| source.name == name) else {
+            return true;
+        };
+
+        self.sources[index].last_message = now;
+        if slot > self.sources[index].last_slot {
+            self.sources[index].last_slot = slot;
+
+            let slot_new = !self.slots.contains_key(&slot);
+            if slot_new {
+                // sources are switched before the new slot
+                self.evaluate(now);
+                self.slots.insert(
+                    slot,
+                    SlotSources {
+                        first: now,
+                        delivered: 0,
+                        forward: self.active_mask(),
+                    },
+                );
+                while self.slots.len() > self.policy.window_slots {
+                    let Some((slot, slot_sources)) = self.slots.pop_first() else {
+                        break;
+                    };
+                    for (index, source) in self.sources.iter().enumerate() {
+                        if slot_sources.delivered & (1 << index) == 0 && source.last_slot > slot {
+                            counter!(metrics::CHANNEL_SOURCE_GAPS_TOTAL, "source" => source.name)
+                                .increment(1);
+                        }
+                    }
+                }
+            }
+
+            if let Some(slot_sources) = self.slots.get_mut(&slot) {
+                slot_sources.delivered |= 1 << index;
+                let lag = now
+                    .saturating_duration_since(slot_sources.first)
+                    .as_micros() as u64;
+                self.sources[index].push_lag(lag, self.policy.window_slots);
+            }
+        }
+
+        match self.slots.get(&slot) {
+            Some(slot_sources) => slot_sources.forward & (1 << index) != 0,
+            None => self.sources[index].active,
+        }
+    }
+
+    fn active_mask(&self) -> u64 {
+        self.sources
+            .iter()
+            .enumerate()
+            .filter(|(_index, source)| source.active)
+            .fold(0, |mask, (index, _source)| mask | (1 << index))
+    }
+
+    fn evaluate(&mut self, now: Instant) {
+        let active_prev = self.active_mask();
+        self.select(now);
+
+        // promoted sources feed dedup for slots in progress
+        let promoted = self.active_mask() & !active_prev;
+        if promoted != 0 {
+            for slot_sources in self.slots.values_mut() {
+                slot_sources.forward |= promoted;
+            }
+        }
+    }
+
+    fn select(&mut self, now: Instant) {
+        for source in self.sources.iter_mut() {
+            let healthy = now.saturating_duration_since(source.last_message)
+                < self.policy.stall_timeout
+                && source.lag_avg() <= self.policy.lag_max.as_micros() as u64;
+            if source.healthy != healthy {
+                source.healthy = healthy;
+                info!(name = source.name, healthy, "source health changed");
+            }
+        }
+
+        let selected = match self.policy.mode {
+            ConfigChannelSourcesPolicyMode::All => {
+                for source in self.sources.iter_mut() {
+                    source.set_active(true);
+                }
+                return;
+            }
+            ConfigChannelSourcesPolicyMode::PrimaryWithFailover => self
+                .sources
+                .iter()
+                .position(|source| source.healthy)
+                .into_iter()
+                .collect::<Vec<_>>(),
+            ConfigChannelSourcesPolicyMode::FastestN => {
+                let mut healthy = self
+                    .sources
+                    .iter()
+                    .enumerate()
+                    .filter(|(_index, source)| source.healthy)
+                    .map(|(index, source)| (source.lag_avg(), index))
+                    .collect::<Vec<_>>();
+                healthy.sort_unstable();
+                healthy
+                    .into_iter()
+                    .take(self.policy.fastest_n.max(1))
+                    .map(|(_lag, index)| index)
+                    .collect()
+            }
+        };
+
+        // keep current sources if nothing is healthy
+        if selected.is_empty() {
+            if !self.sources.iter().any(|source| source.active) {
+                if let Some(source) = self.sources.first_mut() {
+                    source.set_active(true);
+                }
+            }
+            return;
+        }
+
+        for (index, source) in self.sources.iter_mut().enumerate() {
+            source.set_active(selected.contains(&index));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::SourcesHealth,
+        crate::config::{ConfigChannelSourcesPolicy, ConfigChannelSourcesPolicyMode},
+        quanta::Instant,
+        std::time::Duration,
+    };
+
+    fn policy(mode: ConfigChannelSourcesPolicyMode) -> ConfigChannelSourcesPolicy {
+        ConfigChannelSourcesPolicy {
+            mode,
+            fastest_n: 1,
+            window_slots: 4,
+            lag_max: Duration::from_millis(5),
+            stall_timeout: Duration::from_secs(60),
+        }
+    }
+
+    fn at(start: Instant, slot: u64, lag_ms: u64) -> Instant {
+        start + Duration::from_millis(slot * 400 + lag_ms)
+    }
+
+    #[test]
+    fn test_all() {
+        let mut health =
+            SourcesHealth::new(policy(ConfigChannelSourcesPolicyMode::All), &["a", "b"]);
+        assert!(health.on_message("a", 1));
+        assert!(health.on_message("b", 1));
+    }
+
+    #[test]
+    fn test_primary_with_failover_mid_slot() {
+        let start = Instant::now();
+        let mut health = SourcesHealth::new(
+            policy(ConfigChannelSourcesPolicyMode::PrimaryWithFailover),
+            &["a", "b"],
+        );
+        assert!(health.on_message_at("a", 1, at(start, 1, 0)));
+        assert!(!health.on_message_at("b", 1, at(start, 1, 0)));
+
+        // primary is behind the backup, lag is not above the limit yet
+        for slot in 2..=3 {
+            assert!(!health.on_message_at("b", slot, at(start, slot, 0)));
+            assert!(health.on_message_at("a", slot, at(start, slot, 10)));
+        }
+
+        // backup is promoted on the next slot
+        assert!(health.on_message_at("b", 4, at(start, 4, 0)));
+        // demoted primary completes started slot, promoted backup feeds it too
+        assert!(health.on_message_at("a", 3, at(start, 4, 1)));
+        assert!(health.on_message_at("b", 3, at(start, 4, 2)));
+        // but new slots are pushed only from the backup
+        assert!(!health.on_message_at("a", 4, at(start, 4, 10)));
+        assert!(health.on_message_at("b", 5, at(start, 5, 0)));
+        assert!(!health.on_message_at("a", 5, at(start, 5, 10)));
+    }
+
+    #[test]
+    fn test_primary_with_failover_stall() {
+        let start = Instant::now();
+        let mut policy = policy(ConfigChannelSourcesPolicyMode::PrimaryWithFailover);
+        policy.stall_timeout = Duration::from_secs(1);
+        let mut health = SourcesHealth::new(policy, &["a", "b"]);
+        assert!(health.on_message_at("a", 1, at(start, 1, 0)));
+        assert!(!health.on_message_at("b", 1, at(start, 1, 0)));
+
+        // primary stalled in the middle of slot 2
+        assert!(health.on_message_at("a", 2, at(start, 2, 0)));
+        assert!(!health.on_message_at("b", 2, at(start, 2, 0)));
+        for slot in 3..=5 {
+            health.on_message_at("b", slot, at(start, slot, 0));
+        }
+        // rest of slot 2 comes from the promoted backup
+        assert!(health.on_message_at("b", 2, at(start, 5, 1)));
+        assert!(health.on_message_at("b", 6, at(start, 6, 0)));
+    }
+
+    #[test]
+    fn test_fastest_n() {
+        let start = Instant::now();
+        let mut health = SourcesHealth::new(
+            policy(ConfigChannelSourcesPolicyMode::FastestN),
+            &["a", "b", "c"],
+        );
+        for slot in 1..=4 {
+            health.on_message_at("c", slot, at(start, slot, 0));
+            health.on_message_at("a", slot, at(start, slot, 10));
+            health.on_message_at("b", slot, at(start, slot, 20));
+        }
+        assert!(health.on_message_at("c", 5, at(start, 5, 0)));
+        assert!(!health.on_message_at("a", 5, at(start, 5, 10)));
+        assert!(!health.on_message_at("b", 5, at(start, 5, 20)));
+    }
+}
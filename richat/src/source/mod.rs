@@ -1,11 +1,16 @@
+pub mod health;
+
 use {
     crate::{
         channel::GlobalReplayFromSlot,
         config::{
             ConfigChannelSource, ConfigChannelSourceGeneral, ConfigChannelSourceReconnect,
-            ConfigGrpcClientSource,
+            ConfigChannelSourcesPolicy, ConfigGrpcClientSource,
         },
+        metrics,
+        source::health::SourcesHealth,
     },
+    ::metrics::counter,
     anyhow::Context as _,
     futures::{
         future::try_join_all,
@@ -13,10 +18,11 @@ use {
     },
     maplit::hashmap,
     richat_client::{
+        file::ConfigFileClient,
         grpc::{ConfigGrpcClient, GrpcClientBuilderError},
         quic::{ConfigQuicClient, QuicConnectError},
     },
-    richat_filter::message::{Message, MessageParseError, MessageParserEncoding},
+    richat_filter::message::{Message, MessageParseError},
     richat_proto::{
         geyser::{
             CommitmentLevel as CommitmentLevelProto, SubscribeRequest,
@@ -27,6 +33,7 @@ use {
         richat::{GrpcSubscribeRequest, RichatFilter},
     },
     solana_clock::Slot,
+    solana_pubkey::Pubkey,
     std::{
         collections::{HashMap, HashSet},
         fmt,
@@ -46,6 +53,8 @@ enum ConnectError {
     Quic(QuicConnectError),
     #[error(transparent)]
     Grpc(GrpcClientBuilderError),
+    #[error("failed to open file: {0}")]
+    File(std::io::Error),
 }
 
 #[derive(Debug, Error)]
@@ -77,6 +86,9 @@ enum SubscriptionConfig {
         source: ConfigGrpcClientSource,
         config: ConfigGrpcClient,
     },
+    File {
+        config: ConfigFileClient,
+    },
 }
 
 impl SubscriptionConfig {
@@ -88,6 +100,7 @@ impl SubscriptionConfig {
                 source,
                 config,
             } => (Self::Grpc { source, config }, general),
+            ConfigChannelSource::File { general, config } => (Self::File { config }, general),
         }
     }
 }
@@ -223,14 +236,14 @@ impl Subscription {
                                 }
                             }
                             state.4 = None;
+                            counter!(metrics::CHANNEL_SOURCE_RECONNECTS_TOTAL, "source" => name)
+                                .increment(1);
                             state.0.sleep().await;
                         } else {
                             match Subscription::subscribe(
                                 name,
                                 state.1.clone(),
-                                state.2.disable_accounts,
-                                state.2.parser,
-                                state.2.channel_size,
+                                &state.2,
                                 state.3.load(),
                             )
                             .await
@@ -260,9 +273,7 @@ impl Subscription {
             let rx = Self::subscribe(
                 name,
                 subscription_config,
-                config.disable_accounts,
-                config.parser,
-                config.channel_size,
+                &config,
                 global_replay_from_slot.load(),
             )
             .await?;
@@ -296,18 +307,17 @@ impl Subscription {
     async fn subscribe(
         name: &'static str,
         config: SubscriptionConfig,
-        disable_accounts: bool,
-        parser: MessageParserEncoding,
-        channel_size: usize,
+        general: &ConfigChannelSourceGeneral,
         replay_from_slot: Option<Slot>,
     ) -> Result<kanal::AsyncReceiver<SubscriptionMessage>, SubscribeError> {
-        let (tx, rx) = kanal::bounded_async(channel_size);
+        let (tx, rx) = kanal::bounded_async(general.channel_size);
+        let parser = general.parser;
 
         let mut stream = match config {
             SubscriptionConfig::Quic { config } => {
                 let connection = config.connect().await.map_err(ConnectError::Quic)?;
-                let filter = Self::create_richat_filter(disable_accounts);
-                match connection.subscribe(replay_from_slot, filter).await {
+                let filter = Self::create_richat_filter(general);
+                match connection.subscribe(replay_from_slot, None, filter).await {
                     Ok(stream) => {
                         info!(name, version = stream.get_version(), "connected");
                         stream.boxed()
@@ -330,7 +340,7 @@ impl Subscription {
                         info!(name, version = version.version, "connected");
                         connection
                             .subscribe_dragons_mouth_once(Self::create_dragons_mouth_filter(
-                                disable_accounts,
+                                general,
                                 replay_from_slot,
                             ))
                             .await?
@@ -339,12 +349,26 @@ impl Subscription {
                     ConfigGrpcClientSource::Richat => connection
                         .subscribe_richat(GrpcSubscribeRequest {
                             replay_from_slot,
-                            filter: Self::create_richat_filter(disable_accounts),
+                            replay_to_slot: None,
+                            filter: Self::create_richat_filter(general),
                         })
                         .await?
                         .boxed(),
                 }
             }
+            SubscriptionConfig::File { config } => {
+                // recorded stream is replayed as is, from the beginning of the file
+                if let Some(replay_from_slot) = replay_from_slot {
+                    warn!(
+                        name,
+                        replay_from_slot, "replay from slot is ignored by file source"
+                    );
+                }
+                let path = config.path.clone();
+                let stream = config.open().await.map_err(ConnectError::File)?;
+                info!(name, path = %path.display(), "opened");
+                stream
+            }
         };
         info!(name, "subscribed");
 
@@ -378,29 +402,50 @@ impl Subscription {
         Ok(rx)
     }
 
-    const fn create_richat_filter(disable_accounts: bool) -> Option<RichatFilter> {
+    fn create_richat_filter(config: &ConfigChannelSourceGeneral) -> Option<RichatFilter> {
+        let to_bytes = |pubkeys: &[Pubkey]| {
+            pubkeys
+                .iter()
+                .map(|pubkey| pubkey.to_bytes().to_vec())
+                .collect()
+        };
         Some(RichatFilter {
-            disable_accounts,
+            disable_accounts: config.disable_accounts,
             disable_transactions: false,
             disable_entries: false,
+            accounts_pubkey: to_bytes(&config.filter.accounts_pubkey),
+            accounts_owner: to_bytes(&config.filter.accounts_owner),
+            transactions_account_include: to_bytes(&config.filter.transactions_account_include),
+            transactions_exclude_vote: config.filter.transactions_exclude_vote,
+            transactions_exclude_failed: config.filter.transactions_exclude_failed,
         })
     }
 
     fn create_dragons_mouth_filter(
-        disable_accounts: bool,
+        config: &ConfigChannelSourceGeneral,
         from_slot: Option<Slot>,
     ) -> SubscribeRequest {
+        let to_strings = |pubkeys: &[Pubkey]| pubkeys.iter().map(ToString::to_string).collect();
         SubscribeRequest {
-            accounts: if disable_accounts {
+            accounts: if config.disable_accounts {
                 HashMap::new()
             } else {
-                hashmap! { "".to_owned() => SubscribeRequestFilterAccounts::default() }
+                hashmap! { "".to_owned() => SubscribeRequestFilterAccounts {
+                    account: to_strings(&config.filter.accounts_pubkey),
+                    owner: to_strings(&config.filter.accounts_owner),
+                    ..Default::default()
+                } }
             },
             slots: hashmap! { "".to_owned() => SubscribeRequestFilterSlots {
                 filter_by_commitment: Some(false),
                 interslot_updates: Some(true),
             } },
-            transactions: hashmap! { "".to_owned() => SubscribeRequestFilterTransactions::default() },
+            transactions: hashmap! { "".to_owned() => SubscribeRequestFilterTransactions {
+                vote: config.filter.transactions_exclude_vote.then_some(false),
+                failed: config.filter.transactions_exclude_failed.then_some(false),
+                account_include: to_strings(&config.filter.transactions_account_include),
+                ..Default::default()
+            } },
             transactions_status: HashMap::new(),
             blocks: HashMap::new(),
             blocks_meta: hashmap! { "".to_owned() => SubscribeRequestFilterBlocksMeta::default() },
@@ -416,6 +461,7 @@ impl Subscription {
 pub struct Subscriptions {
     global_replay_from_slot: GlobalReplayFromSlot,
     streams: Vec<Subscription>,
+    health: SourcesHealth,
     last_polled: usize,
 }
 
@@ -431,17 +477,24 @@ impl fmt::Debug for Subscriptions {
 impl Subscriptions {
     pub async fn new(
         sources: Vec<ConfigChannelSource>,
+        sources_policy: ConfigChannelSourcesPolicy,
         global_replay_from_slot: GlobalReplayFromSlot,
     ) -> anyhow::Result<Self> {
         let streams = Self::create_subscriptions(sources, &global_replay_from_slot).await?;
+        let health = SourcesHealth::new(sources_policy, &Self::get_names(&streams));
 
         Ok(Self {
             global_replay_from_slot,
             streams,
+            health,
             last_polled: 0,
         })
     }
 
+    fn get_names(streams: &[Subscription]) -> Vec<&'static str> {
+        streams.iter().map(|stream| stream.name).collect()
+    }
+
     async fn create_subscriptions(
         sources: impl IntoIterator<Item = ConfigChannelSource>,
         global_replay_from_slot: &GlobalReplayFromSlot,
@@ -515,6 +568,7 @@ impl Subscriptions {
             info!(name = stream.name, "adding subscription");
             self.streams.push(stream);
         }
+        self.health.update_sources(&Self::get_names(&self.streams));
 
         self.global_replay_from_slot
             .update_sources(self.streams.len());
@@ -531,13 +585,21 @@ impl Stream for Subscriptions {
             return Poll::Ready(None);
         }
 
-        let init_index = self.last_polled;
+        let mut init_index = self.last_polled;
         loop {
             self.last_polled = (self.last_polled + 1) % self.streams.len();
             let index = self.last_polled;
 
             match self.streams[index].stream.poll_next_unpin(cx) {
-                Poll::Ready(Some(value)) => return Poll::Ready(Some(value)),
+                Poll::Ready(Some(Ok((name, message)))) => {
+                    // messages from backup sources are only used for health stats
+                    if self.health.on_message(name, message.slot()) {
+                        return Poll::Ready(Some(Ok((name, message))));
+                    }
+                    // stream without `Pending` has no registered waker, start a new round
+                    init_index = index;
+                }
+                Poll::Ready(Some(Err(error))) => return Poll::Ready(Some(Err(error))),
                 Poll::Ready(None) => {
                     return if self.streams[index].config.exclude_on_finish() {
                         self.last_polled = 0;
@@ -545,6 +607,8 @@ impl Stream for Subscriptions {
                         warn!(name = removed.name, "source stream finished, removing");
                         self.global_replay_from_slot
                             .update_sources(self.streams.len());
+                        let names = Self::get_names(&self.streams);
+                        self.health.update_sources(&names);
                         self.poll_next(cx)
                     } else {
                         Poll::Ready(None)
@@ -559,3 +623,121 @@ impl Stream for Subscriptions {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{Subscription, Subscriptions},
+        crate::{
+            channel::GlobalReplayFromSlot,
+            config::{ConfigChannelSourcesPolicy, ConfigChannelSourcesPolicyMode},
+            source::health::SourcesHealth,
+        },
+        futures::stream::{self, StreamExt},
+        prost::Message as _,
+        richat_filter::message::{Message, MessageParserEncoding},
+        richat_proto::geyser::{
+            SlotStatus, SubscribeUpdate, SubscribeUpdateSlot, subscribe_update::UpdateOneof,
+        },
+        solana_clock::Slot,
+        std::{
+            borrow::Cow,
+            time::{Duration, Instant},
+        },
+        tokio::{sync::mpsc, time::timeout},
+        tokio_util::sync::CancellationToken,
+    };
+
+    fn slot(slot: Slot) -> Message {
+        let data = SubscribeUpdate {
+            filters: vec![],
+            update_oneof: Some(UpdateOneof::Slot(SubscribeUpdateSlot {
+                slot,
+                parent: slot.checked_sub(1),
+                status: SlotStatus::SlotProcessed as i32,
+                dead_error: None,
+            })),
+            created_at: Some(Default::default()),
+        }
+        .encode_to_vec();
+        Message::parse(Cow::Owned(data), MessageParserEncoding::Prost).expect("valid message")
+    }
+
+    fn subscription(
+        name: &'static str,
+        stream: impl futures::Stream<Item = Message> + Send + 'static,
+    ) -> Subscription {
+        let config = serde_json::from_value(serde_json::json!({
+            "transport": "file",
+            "name": name,
+            "parser": "prost",
+            "path": "/dev/null",
+        }))
+        .expect("valid config");
+        Subscription {
+            name,
+            config,
+            stream: stream.map(move |message| Ok((name, message))).boxed(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failover_stalled_primary() {
+        let policy = ConfigChannelSourcesPolicy {
+            mode: ConfigChannelSourcesPolicyMode::PrimaryWithFailover,
+            fastest_n: 1,
+            window_slots: 4,
+            lag_max: Duration::from_secs(60),
+            stall_timeout: Duration::from_millis(100),
+        };
+        let (backup_tx, backup_rx) = mpsc::unbounded_channel();
+        let streams = vec![
+            // primary sends first slot and stalls
+            subscription("primary", stream::iter([slot(1)]).chain(stream::pending())),
+            subscription(
+                "backup",
+                stream::unfold(backup_rx, |mut rx| async move {
+                    rx.recv().await.map(|message| (message, rx))
+                }),
+            ),
+        ];
+        let mut subscriptions = Subscriptions {
+            global_replay_from_slot: GlobalReplayFromSlot::new(None, streams.len()),
+            health: SourcesHealth::new(policy, &Subscriptions::get_names(&streams)),
+            streams,
+            last_polled: 0,
+        };
+
+        let (name, message) = subscriptions.next().await.unwrap().unwrap();
+        assert_eq!((name, message.slot()), ("primary", 1));
+
+        let shutdown = CancellationToken::new();
+        let producer = tokio::spawn({
+            let shutdown = shutdown.clone();
+            async move {
+                for slot_value in 1.. {
+                    if shutdown.is_cancelled() || backup_tx.send(slot(slot_value)).is_err() {
+                        break;
+                    }
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                }
+            }
+        });
+
+        // messages from the backup are dropped until the primary is stalled
+        let started = Instant::now();
+        let (name, message) = timeout(Duration::from_secs(5), subscriptions.next())
+            .await
+            .expect("backup should be promoted")
+            .unwrap()
+            .unwrap();
+        assert_eq!(name, "backup");
+        assert!(message.slot() > 1);
+        // `timeout` polls the stream on expiration, so without a wake up it still finishes
+        assert!(started.elapsed() < Duration::from_secs(1));
+
+        shutdown.cancel();
+        drop(subscriptions);
+        producer.await.unwrap();
+    }
+}
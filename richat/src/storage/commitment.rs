@@ -0,0 +1,336 @@
+use {
+    crate::{channel::ParsedMessage, storage::metadata::Metadata},
+    foldhash::quality::RandomState,
+    richat_proto::geyser::SlotStatus,
+    solana_clock::Slot,
+    solana_commitment_config::CommitmentLevel,
+    solana_pubkey::Pubkey,
+    std::collections::{BTreeMap, HashMap},
+};
+
+/// Re-orders messages replayed from storage in the same way as `Sender` fills
+/// confirmed / finalized channels: slot status messages are passed as is, other
+/// messages are held until the slot reaches the commitment.
+///
+/// Held messages are bounded by `buffered_max` bytes, replay fails once it's reached.
+#[derive(Debug)]
+pub struct CommitmentReplay {
+    commitment: CommitmentLevel,
+    status: SlotStatus,
+    from_slot: Slot,
+    slot_confirmed: Slot,
+    slots: BTreeMap<Slot, CommitmentReplaySlot>,
+    buffered: usize,
+    buffered_max: usize,
+}
+
+#[derive(Debug, Default)]
+struct CommitmentReplaySlot {
+    skip: bool,
+    status_seen: bool,
+    // messages are already sent, new messages are passed as is
+    sent: bool,
+    messages: Vec<Option<ParsedMessage>>,
+    messages_size: usize,
+    accounts_dedup: HashMap<Pubkey, (u64, usize), RandomState>,
+}
+
+impl CommitmentReplay {
+    pub const fn new(commitment: CommitmentLevel, from_slot: Slot, buffered_max: usize) -> Self {
+        Self {
+            commitment,
+            status: match commitment {
+                CommitmentLevel::Processed => SlotStatus::SlotProcessed,
+                CommitmentLevel::Confirmed => SlotStatus::SlotConfirmed,
+                CommitmentLevel::Finalized => SlotStatus::SlotFinalized,
+            },
+            from_slot,
+            slot_confirmed: 0,
+            slots: BTreeMap::new(),
+            buffered: 0,
+            buffered_max,
+        }
+    }
+
+    pub const fn commitment(&self) -> CommitmentLevel {
+        self.commitment
+    }
+
+    /// Push message in processed order, messages in commitment order are added to `output`.
+    /// Returns `true` for the first slot status message of the slot, at this point
+    /// replay can be switched to the memory channel.
+    pub fn push(
+        &mut self,
+        metadata: &Metadata,
+        message: ParsedMessage,
+        output: &mut Vec<ParsedMessage>,
+    ) -> anyhow::Result<bool> {
+        let slot = message.slot();
+        let from_slot = self.from_slot;
+        let status = self.status;
+        let entry = self
+            .slots
+            .entry(slot)
+            .or_insert_with(|| CommitmentReplaySlot {
+                // messages before `from_slot` are incomplete
+                skip: slot < from_slot
+                    || (status == SlotStatus::SlotFinalized
+                        && !metadata.catalog().is_finalized(slot)),
+                ..Default::default()
+            });
+
+        let ParsedMessage::Slot(msg) = &message else {
+            if !entry.skip {
+                if entry.sent {
+                    output.push(message);
+                } else {
+                    self.buffered -= entry.messages_size;
+                    entry.push(&message);
+                    self.buffered += entry.messages_size;
+                    anyhow::ensure!(
+                        self.buffered <= self.buffered_max,
+                        "replay buffer of not {:?} slots exceeded {} bytes",
+                        self.commitment,
+                        self.buffered_max
+                    );
+                    if self.status == SlotStatus::SlotConfirmed && slot <= self.slot_confirmed {
+                        output.push(message);
+                    }
+                }
+            }
+            return Ok(false);
+        };
+
+        let msg_status = msg.status();
+        let status_first = !entry.status_seen;
+        entry.status_seen = true;
+        output.push(message);
+        if msg_status == SlotStatus::SlotConfirmed {
+            self.slot_confirmed = slot;
+        }
+        if msg_status == self.status {
+            if let Some(entry) = self.slots.get_mut(&slot) {
+                if !entry.sent {
+                    entry.sent = true;
+                    self.buffered -= entry.messages_size;
+                    entry.messages_size = 0;
+                    entry.accounts_dedup = HashMap::default();
+                    output.extend(std::mem::take(&mut entry.messages).into_iter().flatten());
+                }
+            }
+        }
+        if msg_status == SlotStatus::SlotFinalized {
+            let slots = self.slots.split_off(&(slot + 1));
+            for entry in std::mem::replace(&mut self.slots, slots).into_values() {
+                self.buffered -= entry.messages_size;
+            }
+        }
+
+        Ok(status_first)
+    }
+}
+
+impl CommitmentReplaySlot {
+    fn push(&mut self, message: &ParsedMessage) {
+        let idx_new = self.messages.len();
+        self.messages.push(Some(message.clone()));
+        self.messages_size += message.size();
+
+        // drop previous update of the account, same as in the live channel
+        if let ParsedMessage::Account(message) = message {
+            let write_version = message.write_version();
+            if let Some(entry) = self.accounts_dedup.get_mut(message.pubkey()) {
+                if entry.0 < write_version {
+                    if let Some(message) = self.messages[entry.1].take() {
+                        self.messages_size -= message.size();
+                    }
+                    *entry = (write_version, idx_new);
+                }
+            } else {
+                self.accounts_dedup
+                    .insert(*message.pubkey(), (write_version, idx_new));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::CommitmentReplay,
+        crate::{
+            channel::{Messages, ParsedMessage},
+            config::ConfigChannelInner,
+            storage::tests::TestStorage,
+        },
+        prost::Message as _,
+        richat_filter::message::{Message, MessageParserEncoding},
+        richat_proto::geyser::{
+            SlotStatus, SubscribeUpdate, SubscribeUpdateAccount, SubscribeUpdateAccountInfo,
+            SubscribeUpdateSlot, subscribe_update::UpdateOneof,
+        },
+        solana_clock::Slot,
+        solana_commitment_config::CommitmentLevel,
+        solana_pubkey::Pubkey,
+        std::{
+            borrow::Cow,
+            thread,
+            time::{Duration, Instant},
+        },
+        tokio_util::sync::CancellationToken,
+    };
+
+    fn parse(update_oneof: UpdateOneof) -> Message {
+        let data = SubscribeUpdate {
+            filters: vec![],
+            update_oneof: Some(update_oneof),
+            created_at: Some(Default::default()),
+        }
+        .encode_to_vec();
+        Message::parse(Cow::Owned(data), MessageParserEncoding::Prost).expect("valid message")
+    }
+
+    fn slot(slot: Slot, parent: Option<Slot>, status: SlotStatus) -> Message {
+        parse(UpdateOneof::Slot(SubscribeUpdateSlot {
+            slot,
+            parent,
+            status: status as i32,
+            dead_error: None,
+        }))
+    }
+
+    fn account(slot: Slot, pubkey: u8, write_version: u64) -> Message {
+        parse(UpdateOneof::Account(SubscribeUpdateAccount {
+            account: Some(SubscribeUpdateAccountInfo {
+                pubkey: Pubkey::from([pubkey; 32]).to_bytes().to_vec(),
+                owner: Pubkey::default().to_bytes().to_vec(),
+                write_version,
+                ..Default::default()
+            }),
+            slot,
+            is_startup: false,
+        }))
+    }
+
+    fn key(message: &ParsedMessage) -> String {
+        match message {
+            ParsedMessage::Slot(msg) => format!("slot {} {:?}", msg.slot(), msg.status()),
+            ParsedMessage::Account(msg) => format!(
+                "account {} {} {}",
+                msg.slot(),
+                msg.pubkey(),
+                msg.write_version()
+            ),
+            message => format!("{} {}", message.as_str_type(), message.slot()),
+        }
+    }
+
+    #[test]
+    fn test_commitment_replay_order() {
+        let config = TestStorage::config("commitment", None);
+        let shutdown = CancellationToken::new();
+        let (mut messages, threads) = Messages::new(
+            MessageParserEncoding::Prost,
+            ConfigChannelInner {
+                max_messages: 1024,
+                max_bytes: 16 * 1024 * 1024,
+                storage: Some(config.clone()),
+            },
+            false,
+            true,
+            true,
+            true,
+            shutdown.clone(),
+        )
+        .unwrap();
+        let (mut sender, _replay_from_slot) = messages.to_sender(1).unwrap();
+
+        let stream = [
+            slot(1, Some(0), SlotStatus::SlotProcessed),
+            account(1, 1, 1),
+            account(1, 2, 1),
+            account(1, 1, 2),
+            slot(2, Some(1), SlotStatus::SlotProcessed),
+            account(2, 1, 1),
+            slot(1, Some(0), SlotStatus::SlotConfirmed),
+            account(1, 3, 1),
+            // fork
+            slot(3, Some(1), SlotStatus::SlotProcessed),
+            account(3, 1, 1),
+            slot(2, Some(1), SlotStatus::SlotConfirmed),
+            account(2, 2, 1),
+            slot(1, Some(0), SlotStatus::SlotFinalized),
+            slot(4, Some(2), SlotStatus::SlotProcessed),
+            account(4, 1, 1),
+            slot(2, Some(1), SlotStatus::SlotFinalized),
+            slot(4, Some(2), SlotStatus::SlotConfirmed),
+            slot(4, Some(2), SlotStatus::SlotFinalized),
+            // `ReceiverSync` returns messages before the channel tail
+            slot(5, Some(4), SlotStatus::SlotProcessed),
+        ];
+        let stream_len = stream.len() as u64;
+        for message in stream {
+            sender.push(false, "test", message);
+        }
+
+        let storage = messages.storage().expect("defined storage");
+        let ts = Instant::now();
+        while storage.next_index() < stream_len {
+            assert!(
+                ts.elapsed() < Duration::from_secs(10),
+                "messages are not written"
+            );
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        let receiver = messages.to_receiver();
+        for commitment in [CommitmentLevel::Confirmed, CommitmentLevel::Finalized] {
+            let mut live = vec![];
+            // positions in the channel start after `max_messages`
+            let mut head = 1024 + 1;
+            while let Some(message) = receiver.try_recv(commitment, head).unwrap() {
+                live.push(key(&message));
+                head += 1;
+            }
+
+            let mut replay = CommitmentReplay::new(commitment, 0, usize::MAX);
+            let mut replayed = vec![];
+            let mut output = vec![];
+            for result in storage
+                .read_messages_from_index(0, MessageParserEncoding::Prost)
+                .flat_map(|chunk| chunk.unwrap())
+                .take(stream_len as usize - 1)
+            {
+                let (_index, message) = result.unwrap();
+                replay
+                    .push(&storage.metadata, message, &mut output)
+                    .unwrap();
+                replayed.extend(output.drain(..).map(|message| key(&message)));
+            }
+            assert_eq!(replayed, live, "{commitment:?}");
+
+            // held messages are bounded
+            let mut replay = CommitmentReplay::new(commitment, 0, 1);
+            let result = storage
+                .read_messages_from_index(0, MessageParserEncoding::Prost)
+                .flat_map(|chunk| chunk.unwrap())
+                .try_for_each(|result| {
+                    let (_index, message) = result.unwrap();
+                    replay
+                        .push(&storage.metadata, message, &mut output)
+                        .map(|_| ())
+                });
+            assert!(result.is_err(), "{commitment:?}");
+        }
+
+        drop(sender);
+        drop(messages);
+        shutdown.cancel();
+        for (_name, jh) in threads {
+            if let Some(jh) = jh {
+                jh.join().unwrap().unwrap();
+            }
+        }
+        let _ = std::fs::remove_dir_all(config.path);
+    }
+}
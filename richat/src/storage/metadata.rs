@@ -6,7 +6,7 @@ use {
     },
     solana_clock::Slot,
     std::{
-        collections::BTreeMap,
+        collections::{BTreeMap, BTreeSet},
         path::{Path, PathBuf},
         sync::{Arc, RwLock, RwLockReadGuard},
     },
@@ -211,23 +211,50 @@ pub struct RotationCommit {
     pub state: MetadataState,
 }
 
+/// Metadata update produced by the startup recovery pass, brings the catalog
+/// in line with the segment files on disk.
+#[derive(Debug, Clone, Default)]
+pub struct MetadataRecoveryCommit {
+    pub deleted_slots: Vec<Slot>,
+    pub deleted_chunks: Vec<u64>,
+    pub deleted_segments: Vec<u64>,
+    pub updated_segments: Vec<SegmentMeta>,
+    pub state: MetadataState,
+}
+
 /// In-memory view of the metadata DB used for fast replay lookups and trim
 /// decisions.
 #[derive(Debug, Clone, Default)]
 pub struct MetadataMirror {
     pub slots: BTreeMap<Slot, SlotMeta>,
+    pub slots_finalized: BTreeSet<Slot>,
     pub segments: BTreeMap<u64, SegmentMeta>,
     pub chunks: Vec<ChunkMeta>,
     pub state: MetadataState,
 }
 
 impl MetadataMirror {
-    fn apply_chunk_commit(&mut self, commit: &MetadataChunkCommit) {
-        for meta in &commit.new_slots {
-            self.slots.insert(meta.slot, *meta);
+    /// Slots without `finalized` flag before the latest finalized slot are forks.
+    pub fn is_finalized(&self, slot: Slot) -> bool {
+        self.slots_finalized.contains(&slot)
+            || self.slots_finalized.range(slot + 1..).next().is_none()
+    }
+
+    fn insert_slot(&mut self, meta: SlotMeta) {
+        if meta.finalized {
+            self.slots_finalized.insert(meta.slot);
         }
-        for meta in &commit.updated_slots {
-            self.slots.insert(meta.slot, *meta);
+        self.slots.insert(meta.slot, meta);
+    }
+
+    fn remove_slot(&mut self, slot: Slot) {
+        self.slots_finalized.remove(&slot);
+        self.slots.remove(&slot);
+    }
+
+    fn apply_chunk_commit(&mut self, commit: &MetadataChunkCommit) {
+        for meta in commit.new_slots.iter().chain(commit.updated_slots.iter()) {
+            self.insert_slot(*meta);
         }
         self.segments
             .insert(commit.segment.segment_id, commit.segment);
@@ -236,9 +263,9 @@ impl MetadataMirror {
     }
 
     fn apply_trim_commit(&mut self, commit: &MetadataTrimCommit) {
-        self.slots.remove(&commit.removed_slot);
+        self.remove_slot(commit.removed_slot);
         for slot in &commit.deleted_slots {
-            self.slots.remove(slot);
+            self.remove_slot(*slot);
         }
         for segment_id in &commit.deleted_segments {
             self.segments.remove(segment_id);
@@ -335,6 +362,27 @@ impl Metadata {
         Ok(db)
     }
 
+    /// Open existing metadata without writes, safe to use while storage is used by Richat.
+    pub fn open_read_only(path: &Path, segments_path: PathBuf) -> anyhow::Result<Self> {
+        let db = Arc::new(
+            DB::open_cf_descriptors_read_only(
+                &Options::default(),
+                path,
+                Self::cf_descriptors(),
+                false,
+            )
+            .with_context(|| format!("failed to open metadata rocksdb at {path:?}"))?,
+        );
+        let db = Self {
+            db,
+            catalog: Arc::new(RwLock::new(MetadataMirror::default())),
+            segments_path,
+        };
+        *db.catalog.write().expect("poisoned") = db.load_catalog_from_db()?;
+
+        Ok(db)
+    }
+
     fn load_catalog_from_db(&self) -> anyhow::Result<MetadataMirror> {
         let state = self.read_state()?.unwrap_or_default();
 
@@ -375,6 +423,11 @@ impl Metadata {
         chunks.sort_by_key(|chunk| chunk.first_index);
 
         Ok(MetadataMirror {
+            slots_finalized: slots
+                .values()
+                .filter(|meta| meta.finalized)
+                .map(|meta| meta.slot)
+                .collect(),
             slots,
             segments,
             chunks,
@@ -512,6 +565,41 @@ impl Metadata {
         Ok(())
     }
 
+    pub fn apply_recovery_commit(&self, commit: &MetadataRecoveryCommit) -> anyhow::Result<()> {
+        let mut batch = WriteBatch::new();
+        for slot in &commit.deleted_slots {
+            batch.delete_cf(Self::cf_handle::<SlotsCf>(&self.db), encode_u64_key(*slot));
+        }
+        for first_index in &commit.deleted_chunks {
+            batch.delete_cf(
+                Self::cf_handle::<ChunksCf>(&self.db),
+                encode_u64_key(*first_index),
+            );
+        }
+        for segment_id in &commit.deleted_segments {
+            batch.delete_cf(
+                Self::cf_handle::<SegmentsCf>(&self.db),
+                encode_u64_key(*segment_id),
+            );
+        }
+        let mut buf = Vec::with_capacity(32);
+        for segment in &commit.updated_segments {
+            buf.clear();
+            segment.encode(&mut buf);
+            batch.put_cf(
+                Self::cf_handle::<SegmentsCf>(&self.db),
+                encode_u64_key(segment.segment_id),
+                &buf,
+            );
+        }
+        buf.clear();
+        commit.state.encode(&mut buf);
+        batch.put_cf(Self::cf_handle::<StateCf>(&self.db), b"state", &buf);
+        self.write_batch(batch)?;
+        *self.catalog.write().expect("poisoned") = self.load_catalog_from_db()?;
+        Ok(())
+    }
+
     fn write_batch(&self, batch: WriteBatch) -> anyhow::Result<()> {
         let mut write_options = WriteOptions::default();
         write_options.set_sync(true);
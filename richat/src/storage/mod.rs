@@ -1,13 +1,16 @@
+pub mod commitment;
 pub mod metadata;
+pub mod recovery;
 pub mod segments;
 
 use {
     crate::{
-        channel::{IndexLocation, ParsedMessage, SharedChannel},
+        channel::{IndexLocation, ParsedMessage, ReceiverSync, ReplayBound, encode_richat_message},
         config::ConfigStorage,
         grpc::server::SubscribeClient,
         metrics::GrpcSubscribeMessage,
         storage::{
+            commitment::CommitmentReplay,
             metadata::Metadata,
             segments::{SegmentReader, WriterCommand},
         },
@@ -17,7 +20,10 @@ use {
     anyhow::Context,
     futures::future::try_join_all,
     quanta::Instant,
-    richat_filter::message::{MessageParserEncoding, MessageRef},
+    richat_filter::{
+        filter::FilteredUpdateType,
+        message::{MessageParserEncoding, MessageRef},
+    },
     richat_metrics::duration_to_seconds,
     richat_shared::mutex_lock,
     smallvec::SmallVec,
@@ -50,6 +56,8 @@ pub struct Storage {
     metadata: Metadata,
     write_tx: kanal::Sender<WriterCommand>,
     replay_queue: Arc<Mutex<ReplayQueue>>,
+    replay_richat_messages_len_max: usize,
+    replay_commitment_buffer_max: usize,
     metric_disk_size_poll_interval: Duration,
 }
 
@@ -64,12 +72,15 @@ impl Storage {
             .with_context(|| format!("failed to create segments path: {segments_path:?}"))?;
 
         let metadata = Metadata::open(&config.metadata_path(), segments_path)?;
+        recovery::recover(&metadata, None).context("failed to recover storage")?;
         let (write_tx, mut threads) = segments::spawn_write_pipeline(&config, metadata.clone())?;
 
         let storage = Self {
             metadata,
             write_tx,
             replay_queue: Arc::new(Mutex::new(ReplayQueue::new(config.replay_inflight_max))),
+            replay_richat_messages_len_max: config.replay_richat_messages_len_max,
+            replay_commitment_buffer_max: config.replay_commitment_buffer_max,
             metric_disk_size_poll_interval: config.metric_disk_size_poll_interval,
         };
 
@@ -94,6 +105,12 @@ impl Storage {
         Ok((storage, threads))
     }
 
+    /// Verify every chunk and repair storage without starting the write pipeline.
+    pub fn repair(config: &ConfigStorage, parser: MessageParserEncoding) -> anyhow::Result<()> {
+        let metadata = Metadata::open(&config.metadata_path(), config.segments_path())?;
+        recovery::recover(&metadata, Some(parser))
+    }
+
     fn spawn_replay(
         storage: Self,
         parser: MessageParserEncoding,
@@ -138,54 +155,163 @@ impl Storage {
                 continue;
             };
 
+            let commitment = locked_state.commitment;
             let mut current_head = *req.state.head.get_or_insert(head);
-            if current_head != head {
+            if current_head != head
+                || req
+                    .state
+                    .commitment
+                    .as_ref()
+                    .is_some_and(|replay| replay.commitment() != commitment)
+            {
                 req.state.messages.clear();
+                req.state.commitment = None;
+                req.state.memory_head = None;
+                req.state.read_finished = false;
+            }
+
+            // confirmed / finalized messages are re-ordered from processed
+            if commitment != CommitmentLevel::Processed && req.state.commitment.is_none() {
+                req.state.commitment = Some(CommitmentReplay::new(
+                    commitment,
+                    locked_state.replay_from_slot.unwrap_or_default(),
+                    storage.replay_commitment_buffer_max,
+                ));
             }
 
             let ts = Instant::now();
             let mut pushed = false;
+            let mut replay_finished = false;
+            let mut memory_head = None;
+            let mut messages = Vec::new();
             let mut messages_len = req.client.messages_len.load(Ordering::Relaxed);
             while messages_len <= req.client.messages_replay_len_max {
                 let Some((index, message)) = req.state.messages.pop_front() else {
                     break;
                 };
+                current_head = index;
 
-                let filter = locked_state.filter.as_ref().expect("defined filter");
-                let message_ref: MessageRef = (&message).into();
-                let items = filter
-                    .get_updates_ref(message_ref, CommitmentLevel::Processed)
-                    .iter()
-                    .map(|msg| ((&msg.filtered_update).into(), msg.encode_to_vec()))
-                    .collect::<SmallVec<[(GrpcSubscribeMessage, Vec<u8>); 2]>>();
-
-                for (message, data) in items {
-                    messages_len += data.len();
-                    req.client.push_message(message, data);
-                    pushed = true;
+                messages.clear();
+                let mut switch_slot = None;
+                if let Some(replay) = req.state.commitment.as_mut() {
+                    if let ParsedMessage::Slot(msg) = &message {
+                        switch_slot = Some((msg.slot(), msg.status()));
+                    }
+                    match replay.push(&storage.metadata, message, &mut messages) {
+                        Ok(true) => {}
+                        Ok(false) => switch_slot = None,
+                        Err(error) => {
+                            req.state.read_error =
+                                Some(Status::resource_exhausted(error.to_string()));
+                            break;
+                        }
+                    }
+                } else {
+                    messages.push(message);
                 }
 
-                current_head = index;
+                for message in messages.drain(..) {
+                    let bound = ReplayBound::check(locked_state.replay_to_slot, &message);
+                    if bound == ReplayBound::Skip {
+                        continue;
+                    }
+                    if bound == ReplayBound::Finished {
+                        replay_finished = true;
+                        break;
+                    }
+
+                    let rollback_len = locked_state.push_rollback(&req.client, &message);
+                    if rollback_len > 0 {
+                        messages_len += rollback_len;
+                        pushed = true;
+                    }
+
+                    let items = if let Some(filter) = &locked_state.richat_filter {
+                        encode_richat_message(filter, &message)
+                            .map(|data| {
+                                let message_ref: MessageRef = (&message).into();
+                                ((&FilteredUpdateType::from(message_ref)).into(), data)
+                            })
+                            .into_iter()
+                            .collect::<SmallVec<[(GrpcSubscribeMessage, Vec<u8>); 2]>>()
+                    } else {
+                        let filter = locked_state.filter.as_ref().expect("defined filter");
+                        let message_ref: MessageRef = (&message).into();
+                        filter
+                            .get_updates_ref(message_ref, commitment)
+                            .iter()
+                            .map(|msg| ((&msg.filtered_update).into(), msg.encode_to_vec()))
+                            .collect::<SmallVec<[(GrpcSubscribeMessage, Vec<u8>); 2]>>()
+                    };
+
+                    for (message, data) in items {
+                        messages_len += data.len();
+                        req.client.push_message(message, data);
+                        pushed = true;
+                    }
+
+                    if bound == ReplayBound::Last {
+                        replay_finished = true;
+                        break;
+                    }
+                }
+                if replay_finished {
+                    break;
+                }
+
+                // first message of the slot in the commitment channel, switch if it's still in memory
+                if let Some((slot, status)) = switch_slot {
+                    memory_head = req
+                        .messages
+                        .get_head_after_slot_status(commitment, slot, status);
+                    if memory_head.is_some() {
+                        break;
+                    }
+                }
             }
 
             locked_state.head = IndexLocation::Storage(current_head);
             req.state.head = Some(current_head);
+            if req.state.commitment.is_none() {
+                locked_state.last_replay_index = Some(current_head);
+            }
 
-            if req.state.read_finished && req.state.messages.is_empty() {
-                if let Some(head) = req.messages.get_head_by_replay_index(current_head + 1) {
-                    locked_state.head = IndexLocation::Memory(head);
-                } else {
+            if replay_finished {
+                req.metric_cpu_usage
+                    .increment(duration_to_seconds(ts.elapsed()));
+                drop(locked_state);
+                req.client.push_finished();
+                ReplayQueue::drop_req(&storage.replay_queue);
+                continue;
+            }
+
+            if req.state.commitment.is_none()
+                && req.state.read_finished
+                && req.state.messages.is_empty()
+            {
+                // storage can be read up to the last message in memory, wait for the next one
+                memory_head = req
+                    .messages
+                    .get_head_by_replay_index(current_head + 1)
+                    .or_else(|| {
+                        req.messages
+                            .get_head_by_replay_index(current_head)
+                            .map(|head| head + 1)
+                    });
+                if memory_head.is_none() {
                     req.state.read_error = Some(Status::internal(
                         "failed to connect replay index to memory channel",
                     ));
                 }
+            }
 
+            if let Some(head) = memory_head {
+                locked_state.head = IndexLocation::Memory(head);
                 req.metric_cpu_usage
                     .increment(duration_to_seconds(ts.elapsed()));
                 drop(locked_state);
-                if pushed {
-                    req.client.wake();
-                }
+                // wake even without new messages, Richat subscriptions switch to memory in poll
+                req.client.wake();
                 ReplayQueue::drop_req(&storage.replay_queue);
                 continue;
             }
@@ -195,7 +321,10 @@ impl Storage {
                 req.client.wake();
             }
 
-            if !req.state.read_finished && req.state.messages.len() < messages_decode_per_tick {
+            if !req.state.read_finished
+                && req.state.read_error.is_none()
+                && req.state.messages.len() < messages_decode_per_tick
+            {
                 let mut messages_decoded = 0;
                 'outer: for chunk_result in
                     storage.read_messages_from_index(current_head + 1, parser)
@@ -231,6 +360,42 @@ impl Storage {
                 }
             }
 
+            // storage is behind the memory channel, keep re-ordering processed messages from
+            // memory until the slot to switch to the commitment channel
+            if req.state.commitment.is_some()
+                && req.state.read_finished
+                && req.state.messages.is_empty()
+                && req.state.read_error.is_none()
+            {
+                let head = match req.state.memory_head {
+                    Some(head) => Some(head),
+                    None => req.messages.get_head_by_replay_index(current_head + 1),
+                };
+                if let Some(mut head) = head {
+                    while req.state.messages.len() < messages_decode_per_tick {
+                        match req.messages.try_recv(CommitmentLevel::Processed, head) {
+                            Ok(Some(message)) => {
+                                head += 1;
+                                // blocks are not stored and not replayed
+                                if !matches!(message, ParsedMessage::Block(_)) {
+                                    req.state.messages.push_back((current_head, message));
+                                }
+                            }
+                            Ok(None) => break,
+                            Err(error) => {
+                                req.state.read_error = Some(Status::internal(error.to_string()));
+                                break;
+                            }
+                        }
+                    }
+                    req.state.memory_head = Some(head);
+                } else {
+                    req.state.read_error = Some(Status::internal(
+                        "failed to connect replay index to memory channel",
+                    ));
+                }
+            }
+
             req.metric_cpu_usage
                 .increment(duration_to_seconds(ts.elapsed()));
             prev_request = Some(req);
@@ -279,6 +444,15 @@ impl Storage {
             .collect()
     }
 
+    /// Index for the next message, right after the last stored chunk.
+    pub fn next_index(&self) -> u64 {
+        self.metadata
+            .catalog()
+            .chunks
+            .last()
+            .map_or(0, |chunk| chunk.last_index + 1)
+    }
+
     pub fn read_messages_from_index(
         &self,
         index: u64,
@@ -287,10 +461,14 @@ impl Storage {
         SegmentReader::new(&self.metadata, index, parser)
     }
 
+    pub const fn replay_richat_messages_len_max(&self) -> usize {
+        self.replay_richat_messages_len_max
+    }
+
     pub fn replay(
         &self,
         client: SubscribeClient,
-        messages: Arc<SharedChannel>,
+        messages: ReceiverSync,
         metric_cpu_usage: Gauge,
     ) -> Result<(), &'static str> {
         ReplayQueue::push_new(
@@ -374,7 +552,7 @@ async fn dir_size(path: PathBuf) -> std::io::Result<u64> {
 struct ReplayRequest {
     state: ReplayState,
     client: SubscribeClient,
-    messages: Arc<SharedChannel>,
+    messages: ReceiverSync,
     metric_cpu_usage: Gauge,
 }
 
@@ -384,6 +562,9 @@ struct ReplayState {
     messages: VecDeque<(u64, ParsedMessage)>,
     read_error: Option<Status>,
     read_finished: bool,
+    commitment: Option<CommitmentReplay>,
+    // position in the processed memory channel once storage is read
+    memory_head: Option<u64>,
 }
 
 #[derive(Debug)]
@@ -435,3 +616,95 @@ impl ReplayQueue {
         locked.requests.clear();
     }
 }
+
+#[cfg(test)]
+pub mod tests {
+    use {
+        super::Storage,
+        crate::{channel::ParsedMessage, config::ConfigStorage, util::SpawnedThreads},
+        richat_filter::message::MessageParserEncoding,
+        solana_clock::Slot,
+        std::{
+            thread,
+            time::{Duration, Instant},
+        },
+        tokio_util::sync::CancellationToken,
+    };
+
+    /// Storage in a temporary directory, every message is written as a separate chunk.
+    pub struct TestStorage {
+        pub config: ConfigStorage,
+        pub storage: Storage,
+        shutdown: CancellationToken,
+        threads: SpawnedThreads,
+    }
+
+    impl TestStorage {
+        pub fn config(name: &str, chunk_compression: Option<&str>) -> ConfigStorage {
+            let path =
+                std::env::temp_dir().join(format!("richat-storage-{name}-{}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&path);
+            let config = serde_json::json!({
+                "path": path,
+                "chunk_target_size": "1B",
+                "chunk_compression": chunk_compression,
+                "replay_threads": 1,
+            });
+            serde_json::from_str(&config.to_string()).expect("valid storage config")
+        }
+
+        pub fn open(config: ConfigStorage) -> anyhow::Result<Self> {
+            let shutdown = CancellationToken::new();
+            let (storage, threads) = Storage::open(
+                config.clone(),
+                MessageParserEncoding::Prost,
+                shutdown.clone(),
+            )?;
+            Ok(Self {
+                config,
+                storage,
+                shutdown,
+                threads,
+            })
+        }
+
+        /// Push messages with consecutive indexes and wait until they are written.
+        pub fn push(&self, messages: impl IntoIterator<Item = (Slot, ParsedMessage)>) {
+            let mut slots = self.storage.read_slots();
+            let mut index = self.storage.next_index();
+            for (slot, message) in messages {
+                let init = !slots.contains_key(&slot);
+                let head = slots.entry(slot).or_insert(super::SlotIndexValue {
+                    finalized: false,
+                    head: index,
+                });
+                self.storage
+                    .push_message(init, slot, head.head, index, message);
+                index += 1;
+            }
+
+            let ts = Instant::now();
+            while self.storage.next_index() < index {
+                assert!(
+                    ts.elapsed() < Duration::from_secs(10),
+                    "messages are not written"
+                );
+                thread::sleep(Duration::from_millis(1));
+            }
+        }
+
+        /// Stops write pipeline and replay threads, config can be used to re-open storage.
+        pub fn close(self) -> ConfigStorage {
+            self.shutdown.cancel();
+            drop(self.storage);
+            for (name, jh) in self.threads {
+                if let Some(jh) = jh {
+                    jh.join()
+                        .expect("failed to join storage thread")
+                        .unwrap_or_else(|error| panic!("storage thread {name} failed: {error}"));
+                }
+            }
+            self.config
+        }
+    }
+}
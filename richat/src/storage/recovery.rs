@@ -0,0 +1,413 @@
+use {
+    crate::storage::{
+        metadata::{ChunkMeta, Metadata, MetadataRecoveryCommit},
+        segments::{SegmentReader, segment_file_name},
+    },
+    anyhow::Context,
+    richat_filter::message::MessageParserEncoding,
+    std::{
+        collections::BTreeMap,
+        fs::{self, OpenOptions},
+        io::ErrorKind,
+    },
+    tracing::{info, warn},
+};
+
+/// Brings the metadata catalog in line with segment files after an unclean shutdown.
+///
+/// Without `verify_parser` (on every start) only leftovers of interrupted writes are
+/// fixed: uncommitted bytes at the end of the active segment are truncated and segment
+/// files created by rotation or left by trim are removed. Any other damage fails the
+/// start. With `verify_parser` (`--repair-storage`) every chunk is decoded as well and
+/// only the longest contiguous run of valid chunks at the tail is kept, catalog entries
+/// without bytes on disk are dropped and `MetadataState` is re-derived.
+pub fn recover(
+    metadata: &Metadata,
+    verify_parser: Option<MessageParserEncoding>,
+) -> anyhow::Result<()> {
+    let catalog = metadata.catalog().clone();
+    let segments_path = metadata.segments_path().to_path_buf();
+
+    // Segment files on disk with their sizes
+    let mut files = BTreeMap::new();
+    for entry in fs::read_dir(&segments_path)
+        .with_context(|| format!("failed to read segments dir: {segments_path:?}"))?
+    {
+        let entry = entry?;
+        let path = entry.path();
+        let Some(segment_id) = path
+            .extension()
+            .filter(|ext| *ext == "seg")
+            .and_then(|_| path.file_stem()?.to_str()?.parse::<u64>().ok())
+        else {
+            continue;
+        };
+        files.insert(segment_id, entry.metadata()?.len());
+    }
+
+    // Damage which is not expected after an unclean shutdown
+    let mut damaged = vec![];
+
+    // Keep the longest contiguous run of valid chunks at the tail
+    let valid = catalog
+        .chunks
+        .iter()
+        .map(|chunk| {
+            let Some(file_len) = files
+                .get(&chunk.segment_id)
+                .filter(|_| catalog.segments.contains_key(&chunk.segment_id))
+            else {
+                damaged.push(format!(
+                    "segment #{} is missed for chunk #{}",
+                    chunk.segment_id, chunk.first_index
+                ));
+                return false;
+            };
+            if chunk.offset + chunk.size > *file_len {
+                damaged.push(format!(
+                    "chunk #{} is out of segment #{} file",
+                    chunk.first_index, chunk.segment_id
+                ));
+                return false;
+            }
+            if let Some(parser) = verify_parser {
+                if let Err(error) = verify_chunk(metadata, chunk, parser) {
+                    damaged.push(format!("chunk #{} is broken: {error:#}", chunk.first_index));
+                    return false;
+                }
+            }
+            true
+        })
+        .collect::<Vec<bool>>();
+    let end = valid
+        .iter()
+        .rposition(|valid| *valid)
+        .map_or(0, |pos| pos + 1);
+    let start = valid[..end]
+        .iter()
+        .rposition(|valid| !*valid)
+        .map_or(0, |pos| pos + 1);
+    let chunks = &catalog.chunks[start..end];
+
+    let mut commit = MetadataRecoveryCommit {
+        deleted_chunks: catalog.chunks[..start]
+            .iter()
+            .chain(catalog.chunks[end..].iter())
+            .map(|chunk| chunk.first_index)
+            .collect(),
+        state: catalog.state,
+        ..Default::default()
+    };
+
+    // Re-build segments from the remaining chunks
+    let mut segments = BTreeMap::new();
+    let mut truncate = vec![];
+    for segment in catalog.segments.values() {
+        let Some(file_len) = files.get(&segment.segment_id).copied() else {
+            damaged.push(format!("segment #{} file is missed", segment.segment_id));
+            commit.deleted_segments.push(segment.segment_id);
+            continue;
+        };
+
+        let mut updated = *segment;
+        updated.chunk_count = 0;
+        updated.file_len = 0;
+        for chunk in chunks
+            .iter()
+            .filter(|chunk| chunk.segment_id == segment.segment_id)
+        {
+            updated.chunk_count += 1;
+            updated.file_len = updated.file_len.max(chunk.offset + chunk.size);
+            updated.last_index = chunk.last_index;
+        }
+        if updated.chunk_count == 0 && segment.sealed {
+            commit.deleted_segments.push(segment.segment_id);
+            continue;
+        }
+
+        if file_len > updated.file_len {
+            // only the active segment can have bytes of not committed chunk
+            if segment.segment_id != catalog.state.active_segment_id
+                || updated.file_len != segment.file_len
+            {
+                damaged.push(format!(
+                    "segment #{} has {file_len} bytes, {} expected",
+                    segment.segment_id, updated.file_len
+                ));
+            }
+            truncate.push((segment.segment_id, file_len, updated.file_len));
+        }
+        segments.insert(segment.segment_id, updated);
+    }
+
+    // Re-derive state, only one segment can stay active
+    let max_segment_id = files
+        .keys()
+        .chain(catalog.segments.keys())
+        .max()
+        .copied()
+        .unwrap_or_default();
+    commit.state.next_segment_id = commit.state.next_segment_id.max(max_segment_id + 1);
+    if segments
+        .get(&commit.state.active_segment_id)
+        .is_none_or(|segment| segment.sealed)
+    {
+        commit.state.active_segment_id = segments
+            .values()
+            .filter(|segment| !segment.sealed)
+            .map(|segment| segment.segment_id)
+            .max()
+            .unwrap_or_default();
+    }
+    for segment in segments.values_mut() {
+        if !segment.sealed && segment.segment_id != commit.state.active_segment_id {
+            segment.sealed = true;
+        }
+    }
+    commit.updated_segments = segments
+        .values()
+        .filter(|segment| catalog.segments.get(&segment.segment_id) != Some(*segment))
+        .copied()
+        .collect();
+
+    // Drop slots without data
+    let indexes = chunks
+        .first()
+        .zip(chunks.last())
+        .map(|(first, last)| first.first_index..=last.last_index);
+    commit.deleted_slots = catalog
+        .slots
+        .values()
+        .filter(|meta| {
+            !indexes
+                .as_ref()
+                .is_some_and(|indexes| indexes.contains(&meta.first_index))
+        })
+        .map(|meta| meta.slot)
+        .collect();
+
+    // Segment files unknown to the catalog, or dropped by recovery
+    let deleted_files = files
+        .keys()
+        .filter(|segment_id| !segments.contains_key(segment_id))
+        .copied()
+        .collect::<Vec<_>>();
+
+    // files created by rotation before commit or left by trim after commit
+    let segment_id_first = catalog.segments.keys().next().copied();
+    for segment_id in deleted_files.iter() {
+        if !catalog.segments.contains_key(segment_id)
+            && *segment_id < catalog.state.next_segment_id
+            && segment_id_first.is_some_and(|first| *segment_id > first)
+        {
+            damaged.push(format!("segment #{segment_id} file is unknown"));
+        }
+    }
+
+    if !damaged.is_empty() {
+        if verify_parser.is_none() {
+            anyhow::bail!(
+                "storage is damaged, use `richat --repair-storage` to keep only valid chunks at the tail: {}",
+                damaged.join("; ")
+            );
+        }
+        for damage in damaged {
+            warn!("storage recovery: {damage}");
+        }
+    }
+
+    if commit.deleted_slots.is_empty()
+        && commit.deleted_chunks.is_empty()
+        && commit.deleted_segments.is_empty()
+        && commit.updated_segments.is_empty()
+        && commit.state == catalog.state
+        && truncate.is_empty()
+        && deleted_files.is_empty()
+    {
+        return Ok(());
+    }
+
+    // Catalog first, so no entry points to removed bytes
+    metadata.apply_recovery_commit(&commit)?;
+    for (segment_id, file_len, committed_len) in truncate {
+        let path = segments_path.join(segment_file_name(segment_id));
+        let file = OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .with_context(|| format!("failed to open segment file: {path:?}"))?;
+        file.set_len(committed_len)?;
+        file.sync_all()?;
+        info!(
+            "storage recovery: segment #{segment_id} truncated from {file_len} to {committed_len} bytes"
+        );
+    }
+    for segment_id in deleted_files {
+        let path = segments_path.join(segment_file_name(segment_id));
+        match fs::remove_file(&path) {
+            Ok(()) => {}
+            Err(error) if error.kind() == ErrorKind::NotFound => {}
+            Err(error) => {
+                return Err(error).with_context(|| format!("failed to delete segment {path:?}"));
+            }
+        }
+        info!("storage recovery: segment #{segment_id} file removed");
+    }
+    info!(
+        "storage recovery: removed {} slots, {} chunks, {} segments; updated {} segments",
+        commit.deleted_slots.len(),
+        commit.deleted_chunks.len(),
+        commit.deleted_segments.len(),
+        commit.updated_segments.len()
+    );
+
+    Ok(())
+}
+
+/// Decodes every record of the chunk and checks message indexes.
+pub fn verify_chunk(
+    metadata: &Metadata,
+    chunk: &ChunkMeta,
+    parser: MessageParserEncoding,
+) -> anyhow::Result<()> {
+    let decompressed = SegmentReader::new(metadata, chunk.first_index, parser)
+        .next()
+        .context("chunk not found")??;
+
+    let mut count = 0;
+    for (expected_index, result) in (chunk.first_index..).zip(decompressed) {
+        let (index, _message) = result?;
+        anyhow::ensure!(
+            index == expected_index,
+            "unexpected message index: {index}, expected: {expected_index}"
+        );
+        count += 1;
+    }
+    let expected = chunk.last_index - chunk.first_index + 1;
+    anyhow::ensure!(
+        count == expected,
+        "invalid number of messages: {count}, expected: {expected}"
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{
+            channel::ParsedMessage,
+            storage::{Storage, segments::segment_file_name, tests::TestStorage},
+        },
+        prost::Message as _,
+        richat_filter::message::{Message, MessageParserEncoding},
+        richat_proto::geyser::{
+            SlotStatus, SubscribeUpdate, SubscribeUpdateSlot, subscribe_update::UpdateOneof,
+        },
+        solana_clock::Slot,
+        std::{borrow::Cow, fs, io::Write},
+    };
+
+    fn slot(slot: Slot) -> (Slot, ParsedMessage) {
+        let data = SubscribeUpdate {
+            filters: vec![],
+            update_oneof: Some(UpdateOneof::Slot(SubscribeUpdateSlot {
+                slot,
+                parent: None,
+                status: SlotStatus::SlotProcessed as i32,
+                dead_error: None,
+            })),
+            created_at: Some(Default::default()),
+        }
+        .encode_to_vec();
+        let message = Message::parse(Cow::Owned(data), MessageParserEncoding::Prost)
+            .expect("valid message")
+            .into();
+        (slot, message)
+    }
+
+    fn first_index(storage: &TestStorage) -> Option<u64> {
+        let catalog = storage.storage.metadata.catalog();
+        catalog.chunks.first().map(|chunk| chunk.first_index)
+    }
+
+    #[test]
+    fn test_recover_torn_tail() {
+        let storage = TestStorage::open(TestStorage::config("recover-torn", None)).unwrap();
+        storage.push((1..=4).map(slot));
+        let segment_id = storage.storage.metadata.catalog().state.active_segment_id;
+        let config = storage.close();
+
+        // bytes of not committed chunk
+        let path = config.segments_path().join(segment_file_name(segment_id));
+        let len = fs::metadata(&path).unwrap().len();
+        let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&[0xff; 32]).unwrap();
+        drop(file);
+
+        let storage = TestStorage::open(config).unwrap();
+        assert_eq!(storage.storage.next_index(), 4);
+        assert_eq!(first_index(&storage), Some(0));
+        assert_eq!(fs::metadata(&path).unwrap().len(), len);
+
+        let config = storage.close();
+        let _ = fs::remove_dir_all(config.path);
+    }
+
+    #[test]
+    fn test_recover_missed_segment() {
+        let mut config = TestStorage::config("recover-missed", None);
+        config.segment_target_size = 1;
+        let storage = TestStorage::open(config).unwrap();
+        storage.push((1..=4).map(slot));
+        let segments = storage
+            .storage
+            .metadata
+            .catalog()
+            .chunks
+            .iter()
+            .map(|chunk| chunk.segment_id)
+            .collect::<Vec<_>>();
+        let config = storage.close();
+        let first_segment = config.segments_path().join(segment_file_name(segments[0]));
+
+        // data before the damage is not dropped on start
+        fs::remove_file(config.segments_path().join(segment_file_name(segments[1]))).unwrap();
+        let error = TestStorage::open(config.clone())
+            .err()
+            .expect("damaged storage should not be opened");
+        assert!(format!("{error:#}").contains("--repair-storage"));
+        assert!(first_segment.exists());
+
+        Storage::repair(&config, MessageParserEncoding::Prost).unwrap();
+        assert!(!first_segment.exists());
+        let storage = TestStorage::open(config).unwrap();
+        assert_eq!(storage.storage.next_index(), 4);
+        assert_eq!(first_index(&storage), Some(2));
+
+        let config = storage.close();
+        let _ = fs::remove_dir_all(config.path);
+    }
+
+    #[test]
+    fn test_recover_orphan_segment() {
+        let mut config = TestStorage::config("recover-orphan", None);
+        config.segment_target_size = 1;
+        let storage = TestStorage::open(config).unwrap();
+        storage.push((1..=2).map(slot));
+        let next_segment_id = storage.storage.metadata.catalog().state.next_segment_id;
+        let config = storage.close();
+
+        // file created by rotation before metadata commit
+        let path = config
+            .segments_path()
+            .join(segment_file_name(next_segment_id));
+        fs::write(&path, []).unwrap();
+
+        let storage = TestStorage::open(config).unwrap();
+        assert!(!path.exists());
+        assert_eq!(storage.storage.next_index(), 2);
+        assert_eq!(first_index(&storage), Some(0));
+
+        let config = storage.close();
+        let _ = fs::remove_dir_all(config.path);
+    }
+}
@@ -29,14 +29,24 @@ use {
     solana_clock::Slot,
     std::{
         borrow::Cow,
-        collections::{BTreeMap, HashSet},
+        collections::{BTreeMap, HashMap, HashSet, hash_map::Entry as HashMapEntry},
         fs::{File, OpenOptions},
         io::{Read, Seek, SeekFrom, Write},
-        path::PathBuf,
+        path::{Path, PathBuf},
+        sync::{
+            Arc, Mutex, OnceLock,
+            atomic::{AtomicBool, Ordering},
+        },
         thread,
         time::Instant,
     },
-    zstd::{bulk::Compressor, stream::decode_all as zstd_decode_all},
+    tracing::{info, warn},
+    zstd::{
+        bulk::Compressor,
+        dict::from_continuous as zstd_dict_from_continuous,
+        stream::{decode_all as zstd_decode_all, read::Decoder as ZstdDecoder},
+        zstd_safe::{get_dict_id_from_dict, get_dict_id_from_frame},
+    },
 };
 
 /// Compression algorithm used for a chunk payload.
@@ -44,26 +54,45 @@ use {
 pub enum ChunkCompression {
     None,
     Zstd(i32),
+    Lz4,
+    /// Zstd with a dictionary trained from stored records, the dictionary id is
+    /// kept in the zstd frame header and the dictionary itself in the segments directory.
+    ZstdDict(i32),
 }
 
 impl ChunkCompression {
     const TAG_NONE: u8 = 0;
     const TAG_ZSTD: u8 = 1;
+    const TAG_LZ4: u8 = 2;
+    const TAG_ZSTD_DICT: u8 = 3;
 
     const fn tag(self) -> u8 {
         match self {
             Self::None => Self::TAG_NONE,
             Self::Zstd(_) => Self::TAG_ZSTD,
+            Self::Lz4 => Self::TAG_LZ4,
+            Self::ZstdDict(_) => Self::TAG_ZSTD_DICT,
         }
     }
 
-    fn from_tag(tag: u8) -> anyhow::Result<Self> {
+    pub fn from_tag(tag: u8) -> anyhow::Result<Self> {
         match tag {
             Self::TAG_NONE => Ok(Self::None),
             Self::TAG_ZSTD => Ok(Self::Zstd(0)),
+            Self::TAG_LZ4 => Ok(Self::Lz4),
+            Self::TAG_ZSTD_DICT => Ok(Self::ZstdDict(0)),
             other => anyhow::bail!("unsupported chunk compression tag: {other}"),
         }
     }
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Zstd(_) => "zstd",
+            Self::Lz4 => "lz4",
+            Self::ZstdDict(_) => "zstd_dict",
+        }
+    }
 }
 
 /// A single decompressed chunk that yields decoded records as an iterator.
@@ -126,6 +155,7 @@ pub struct SegmentReader {
     next_index: u64,
     current_segment_id: Option<u64>,
     current_file: Option<File>,
+    zstd_dicts: HashMap<u32, Vec<u8>>,
     failed: bool,
 }
 
@@ -137,6 +167,7 @@ impl SegmentReader {
             next_index: start_index,
             current_segment_id: None,
             current_file: None,
+            zstd_dicts: HashMap::new(),
             failed: false,
         }
     }
@@ -186,17 +217,44 @@ impl SegmentReader {
             vec
         };
         file.read_exact(&mut payload)?;
-        counter!(STORAGE_REPLAY_COMPRESSED_BYTES_TOTAL).increment(payload.len() as u64);
 
         let compression = ChunkCompression::from_tag(chunk.compression)
             .context("unsupported chunk compression")?;
+        counter!(STORAGE_REPLAY_COMPRESSED_BYTES_TOTAL, "codec" => compression.as_str())
+            .increment(payload.len() as u64);
         let uncompressed = match compression {
             ChunkCompression::None => payload,
             ChunkCompression::Zstd(_) => {
                 zstd_decode_all(payload.as_slice()).context("failed to decompress chunk")?
             }
+            ChunkCompression::Lz4 => lz4_decompress(&payload)?,
+            ChunkCompression::ZstdDict(_) => {
+                let dict_id = get_dict_id_from_frame(&payload)
+                    .context("missed dictionary id in zstd frame")?
+                    .get();
+                let dict = match self.zstd_dicts.entry(dict_id) {
+                    HashMapEntry::Occupied(entry) => entry.into_mut(),
+                    HashMapEntry::Vacant(entry) => {
+                        let path = self
+                            .metadata
+                            .segments_path()
+                            .join(zstd_dict_file_name(dict_id));
+                        let dict = std::fs::read(&path)
+                            .with_context(|| format!("failed to read zstd dictionary: {path:?}"))?;
+                        entry.insert(dict)
+                    }
+                };
+                let mut decoder = ZstdDecoder::with_dictionary(payload.as_slice(), dict)
+                    .context("failed to create zstd decoder")?;
+                let mut uncompressed = Vec::new();
+                decoder
+                    .read_to_end(&mut uncompressed)
+                    .context("failed to decompress chunk")?;
+                uncompressed
+            }
         };
-        counter!(STORAGE_REPLAY_DECOMPRESSED_BYTES_TOTAL).increment(uncompressed.len() as u64);
+        counter!(STORAGE_REPLAY_DECOMPRESSED_BYTES_TOTAL, "codec" => compression.as_str())
+            .increment(uncompressed.len() as u64);
 
         let skip: usize = self
             .next_index
@@ -440,18 +498,115 @@ struct CompressedChunk {
     pending_finalized: HashSet<Slot>,
 }
 
+/// Zstd dictionary shared by compressor threads, loaded from the segments directory
+/// or trained once from serialized records.
+#[derive(Debug)]
+struct ZstdDictTrainer {
+    segments_path: PathBuf,
+    dict_size: usize,
+    dict: OnceLock<Vec<u8>>,
+    training: AtomicBool,
+    samples: Mutex<(Vec<u8>, Vec<usize>)>,
+}
+
+impl ZstdDictTrainer {
+    // the same ratio as recommended by zstd: ~100x more samples than dictionary size
+    const SAMPLES_SIZE_MULTIPLIER: usize = 100;
+
+    fn open(segments_path: PathBuf, dict_size: usize) -> anyhow::Result<Self> {
+        let dict = OnceLock::new();
+        if let Some((dict_id, bytes)) = load_latest_zstd_dict(&segments_path)? {
+            info!("use zstd dictionary #{dict_id} for storage chunks");
+            let _ = dict.set(bytes);
+        }
+
+        Ok(Self {
+            segments_path,
+            dict_size,
+            dict,
+            training: AtomicBool::new(false),
+            samples: Mutex::default(),
+        })
+    }
+
+    fn get(&self) -> Option<&[u8]> {
+        self.dict.get().map(Vec::as_slice)
+    }
+
+    fn push_samples(&self, data: &[u8], sizes: &[usize]) -> anyhow::Result<()> {
+        if self.dict.get().is_some() || self.training.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        let (data, sizes) = {
+            let mut samples = self.samples.lock().expect("samples mutex poisoned");
+            samples.0.extend_from_slice(data);
+            samples.1.extend_from_slice(sizes);
+            if samples.0.len() < self.dict_size * Self::SAMPLES_SIZE_MULTIPLIER
+                || self.training.swap(true, Ordering::Relaxed)
+            {
+                return Ok(());
+            }
+            std::mem::take(&mut *samples)
+        };
+
+        let ts = Instant::now();
+        let dict = match zstd_dict_from_continuous(&data, &sizes, self.dict_size) {
+            Ok(dict) => dict,
+            Err(error) => {
+                warn!("failed to train zstd dictionary: {error}");
+                self.training.store(false, Ordering::Relaxed);
+                return Ok(());
+            }
+        };
+        let dict_id = get_dict_id_from_dict(&dict)
+            .context("missed id in trained zstd dictionary")?
+            .get();
+
+        // dictionary should be on disk before first chunk compressed with it
+        let path = self.segments_path.join(zstd_dict_file_name(dict_id));
+        let path_tmp = path.with_extension("tmp");
+        let mut file = File::create(&path_tmp)
+            .with_context(|| format!("failed to create zstd dictionary: {path_tmp:?}"))?;
+        file.write_all(&dict)?;
+        file.sync_all()?;
+        std::fs::rename(&path_tmp, &path)
+            .with_context(|| format!("failed to rename zstd dictionary: {path_tmp:?}"))?;
+        info!(
+            "trained zstd dictionary #{dict_id} ({} bytes) from {} samples in {:?}",
+            dict.len(),
+            sizes.len(),
+            ts.elapsed()
+        );
+
+        let _ = self.dict.set(dict);
+        Ok(())
+    }
+}
+
 fn spawn_compressor_pool(
     threads: usize,
     chunk_compression: Option<ChunkCompression>,
+    chunk_compression_dict_size: usize,
+    segments_path: PathBuf,
     affinity: Option<Vec<usize>>,
     rx: kanal::Receiver<CollectorOutput>,
     tx: kanal::Sender<CompressorOutput>,
 ) -> anyhow::Result<Vec<SpawnedThread>> {
+    let dict_trainer = match chunk_compression {
+        Some(ChunkCompression::ZstdDict(_)) => Some(Arc::new(ZstdDictTrainer::open(
+            segments_path,
+            chunk_compression_dict_size,
+        )?)),
+        _ => None,
+    };
+
     let mut handles = Vec::with_capacity(threads);
     for index in 0..threads {
         let th_name = format!("richatStrgCmp{index:02}");
         let rx = rx.clone();
         let tx = tx.clone();
+        let dict_trainer = dict_trainer.as_ref().map(Arc::clone);
         let cpus = affinity.as_ref().map(|aff| {
             if threads == aff.len() {
                 vec![aff[index]]
@@ -466,7 +621,7 @@ fn spawn_compressor_pool(
                     affinity_linux::set_thread_affinity(cpus.into_iter())
                         .expect("failed to set affinity");
                 }
-                run_compressor(index, chunk_compression, rx, tx)
+                run_compressor(index, chunk_compression, dict_trainer, rx, tx)
             })?;
         handles.push((th_name, Some(jh)));
     }
@@ -476,21 +631,25 @@ fn spawn_compressor_pool(
 fn run_compressor(
     thread_index: usize,
     chunk_compression: Option<ChunkCompression>,
+    dict_trainer: Option<Arc<ZstdDictTrainer>>,
     rx: kanal::Receiver<CollectorOutput>,
     tx: kanal::Sender<CompressorOutput>,
 ) -> anyhow::Result<()> {
+    let chunk_compression = chunk_compression.unwrap_or(ChunkCompression::None);
     let mut compressor = match chunk_compression {
-        Some(ChunkCompression::Zstd(level)) => {
+        ChunkCompression::Zstd(level) | ChunkCompression::ZstdDict(level) => {
             Some(Compressor::new(level).context("failed to create zstd compressor")?)
         }
         _ => None,
     };
-    let compression_tag = chunk_compression.unwrap_or(ChunkCompression::None).tag();
+    let mut compressor_with_dict = false;
     let thread_index_str: &'static str = thread_index.to_string().leak();
 
     // Per-thread serialization buffers
     let mut record_buf = Vec::with_capacity(11 * 1024 * 1024);
     let mut chunk_buf = Vec::new();
+    let mut samples_buf = Vec::new();
+    let mut samples_sizes = Vec::new();
 
     loop {
         match rx.recv() {
@@ -502,9 +661,25 @@ fn run_compressor(
                 pending_slots,
                 pending_finalized,
             }) => {
+                // Switch to the dictionary once it's available
+                if let (ChunkCompression::ZstdDict(level), false) =
+                    (chunk_compression, compressor_with_dict)
+                {
+                    if let Some(dict) = dict_trainer.as_deref().and_then(ZstdDictTrainer::get) {
+                        compressor = Some(
+                            Compressor::with_dictionary(level, dict)
+                                .context("failed to create zstd compressor with dictionary")?,
+                        );
+                        compressor_with_dict = true;
+                    }
+                }
+                let collect_samples = dict_trainer.is_some() && !compressor_with_dict;
+
                 // Serialize all records
                 let serialize_started_at = Instant::now();
                 chunk_buf.clear();
+                samples_buf.clear();
+                samples_sizes.clear();
                 for record in &records {
                     record_buf.clear();
                     let message_ref: MessageRef = (&record.message).into();
@@ -516,32 +691,49 @@ fn run_compressor(
                     filtered.encode(&mut record_buf);
                     encode_varint(record_buf.len() as u64, &mut chunk_buf);
                     chunk_buf.extend_from_slice(&record_buf);
+                    if collect_samples {
+                        samples_buf.extend_from_slice(&record_buf);
+                        samples_sizes.push(record_buf.len());
+                    }
                 }
                 gauge!(STORAGE_WRITE_SERIALIZE_SECONDS_TOTAL)
                     .increment(duration_to_seconds(serialize_started_at.elapsed()));
                 counter!(CHANNEL_STORAGE_WRITE_COMPRESSOR_INDEX, "thread" => thread_index_str)
                     .absolute(last_index);
+                let uncompressed_size = chunk_buf.len();
 
-                counter!(STORAGE_WRITE_CHUNK_UNCOMPRESSED_BYTES_TOTAL)
-                    .increment(chunk_buf.len() as u64);
+                if let Some(dict_trainer) = dict_trainer.as_deref().filter(|_| collect_samples) {
+                    dict_trainer.push_samples(&samples_buf, &samples_sizes)?;
+                }
 
-                // Compress
-                let payload = if let Some(compressor) = compressor.as_mut() {
-                    let compress_started_at = Instant::now();
-                    let compressed = compressor
+                // Compress, chunks are written with plain zstd until the dictionary is trained
+                let compression = match chunk_compression {
+                    ChunkCompression::ZstdDict(level) if !compressor_with_dict => {
+                        ChunkCompression::Zstd(level)
+                    }
+                    compression => compression,
+                };
+                let compress_started_at = Instant::now();
+                let payload = match compression {
+                    ChunkCompression::None => std::mem::take(&mut chunk_buf),
+                    ChunkCompression::Zstd(_) | ChunkCompression::ZstdDict(_) => compressor
+                        .as_mut()
+                        .context("zstd compressor should be created")?
                         .compress(&chunk_buf)
-                        .context("failed to compress chunk")?;
-                    gauge!(STORAGE_WRITE_COMPRESS_SECONDS_TOTAL)
-                        .increment(duration_to_seconds(compress_started_at.elapsed()));
-                    compressed
-                } else {
-                    std::mem::take(&mut chunk_buf)
+                        .context("failed to compress chunk")?,
+                    ChunkCompression::Lz4 => lz4_flex::block::compress_prepend_size(&chunk_buf),
                 };
-                counter!(STORAGE_WRITE_CHUNK_COMPRESSED_BYTES_TOTAL)
+                if compression != ChunkCompression::None {
+                    gauge!(STORAGE_WRITE_COMPRESS_SECONDS_TOTAL, "codec" => compression.as_str())
+                        .increment(duration_to_seconds(compress_started_at.elapsed()));
+                }
+                counter!(STORAGE_WRITE_CHUNK_UNCOMPRESSED_BYTES_TOTAL, "codec" => compression.as_str())
+                    .increment(uncompressed_size as u64);
+                counter!(STORAGE_WRITE_CHUNK_COMPRESSED_BYTES_TOTAL, "codec" => compression.as_str())
                     .increment(payload.len() as u64);
 
                 let compressed = CompressedChunk {
-                    compression: compression_tag,
+                    compression: compression.tag(),
                     first_index,
                     last_index,
                     payload,
@@ -581,12 +773,17 @@ struct SegmentWriter {
     metadata: Metadata,
     active_segment: SegmentMeta,
     active_file: File,
+    // zstd dictionary id to the last segment with chunks compressed by it
+    zstd_dicts: HashMap<u32, u64>,
+    // dictionary loaded by compressors on start, could be not used by chunks yet
+    zstd_dict_latest: Option<u32>,
 }
 
 impl SegmentWriter {
     fn open(
         segments_path: PathBuf,
         segment_target_size: u64,
+        keep_latest_zstd_dict: bool,
         metadata: Metadata,
     ) -> anyhow::Result<Self> {
         {
@@ -624,12 +821,25 @@ impl SegmentWriter {
             .with_context(|| format!("failed to open active segment: {active_path:?}"))?;
         active_file.seek(SeekFrom::Start(active_segment.file_len))?;
 
+        let zstd_dicts = load_zstd_dicts_usage(&segments_path, &metadata.catalog())?;
+        let zstd_dict_latest = find_latest_zstd_dict(&segments_path)?
+            .filter(|_| keep_latest_zstd_dict)
+            .map(|(dict_id, _path)| dict_id);
+        for (dict_id, path) in list_zstd_dicts(&segments_path)? {
+            if !zstd_dicts.contains_key(&dict_id) && zstd_dict_latest != Some(dict_id) {
+                info!("remove unused zstd dictionary #{dict_id}");
+                remove_file_if_exists(&path)?;
+            }
+        }
+
         Ok(Self {
             segments_path,
             segment_target_size,
             metadata,
             active_segment,
             active_file,
+            zstd_dicts,
+            zstd_dict_latest,
         })
     }
 
@@ -691,6 +901,13 @@ impl SegmentWriter {
         counter!(CHANNEL_STORAGE_WRITE_INDEX).absolute(chunk_meta.last_index);
         counter!(STORAGE_SEGMENT_CHUNKS_WRITTEN_TOTAL).increment(1);
 
+        if chunk.compression == ChunkCompression::TAG_ZSTD_DICT {
+            let dict_id = get_dict_id_from_frame(&chunk.payload)
+                .context("missed dictionary id in zstd frame")?
+                .get();
+            self.zstd_dicts.insert(dict_id, chunk_meta.segment_id);
+        }
+
         self.active_segment = commit.segment;
         if self.active_segment.file_len >= self.segment_target_size {
             self.rotate_segment()?;
@@ -747,14 +964,23 @@ impl SegmentWriter {
 
         for segment_id in &commit.deleted_segments {
             let path = self.segments_path.join(segment_file_name(*segment_id));
-            match std::fs::remove_file(&path) {
-                Ok(()) => {}
-                Err(error) if error.kind() == std::io::ErrorKind::NotFound => {}
-                Err(error) => {
-                    return Err(error)
-                        .with_context(|| format!("failed to delete segment {path:?}"));
-                }
-            }
+            remove_file_if_exists(&path)?;
+        }
+
+        // dictionaries are not needed once the last segment with them is deleted
+        let unused_dicts = self
+            .zstd_dicts
+            .iter()
+            .filter(|(dict_id, segment_id)| {
+                commit.deleted_segments.contains(segment_id)
+                    && self.zstd_dict_latest != Some(**dict_id)
+            })
+            .map(|(dict_id, _segment_id)| *dict_id)
+            .collect::<Vec<_>>();
+        for dict_id in unused_dicts {
+            self.zstd_dicts.remove(&dict_id);
+            info!("remove unused zstd dictionary #{dict_id}");
+            remove_file_if_exists(&self.segments_path.join(zstd_dict_file_name(dict_id)))?;
         }
         gauge!(STORAGE_WRITE_TRIM_SECONDS_TOTAL)
             .increment(duration_to_seconds(trim_started_at.elapsed()));
@@ -846,6 +1072,7 @@ impl SegmentWriter {
 fn spawn_writer(
     segments_path: PathBuf,
     segment_target_size: u64,
+    keep_latest_zstd_dict: bool,
     affinity: Option<Vec<usize>>,
     metadata: Metadata,
     rx: kanal::Receiver<CompressorOutput>,
@@ -858,7 +1085,13 @@ fn spawn_writer(
                 affinity_linux::set_thread_affinity(cpus.into_iter())
                     .expect("failed to set affinity");
             }
-            SegmentWriter::open(segments_path, segment_target_size, metadata)?.run(rx)
+            SegmentWriter::open(
+                segments_path,
+                segment_target_size,
+                keep_latest_zstd_dict,
+                metadata,
+            )?
+            .run(rx)
         })?;
     Ok((th_name, Some(jh)))
 }
@@ -882,6 +1115,8 @@ pub fn spawn_write_pipeline(
     threads.extend(spawn_compressor_pool(
         config.compressor_threads,
         config.chunk_compression,
+        config.chunk_compression_dict_size,
+        config.segments_path(),
         config.compressor_affinity.clone(),
         collector_rx,
         compressor_tx,
@@ -889,6 +1124,10 @@ pub fn spawn_write_pipeline(
     threads.push(spawn_writer(
         config.segments_path(),
         config.segment_target_size as u64,
+        matches!(
+            config.chunk_compression,
+            Some(ChunkCompression::ZstdDict(_))
+        ),
         config.write_affinity.clone(),
         metadata,
         compressor_rx,
@@ -897,6 +1136,280 @@ pub fn spawn_write_pipeline(
     Ok((write_tx, threads))
 }
 
-fn segment_file_name(segment_id: u64) -> String {
+pub fn segment_file_name(segment_id: u64) -> String {
     format!("{segment_id:012}.seg")
 }
+
+fn zstd_dict_file_name(dict_id: u32) -> String {
+    format!("{dict_id:010}.dict")
+}
+
+fn list_zstd_dicts(segments_path: &Path) -> anyhow::Result<Vec<(u32, PathBuf)>> {
+    let mut dicts = vec![];
+    for entry in std::fs::read_dir(segments_path)
+        .with_context(|| format!("failed to read segments dir: {segments_path:?}"))?
+    {
+        let path = entry?.path();
+        if let Some(dict_id) = path
+            .extension()
+            .filter(|ext| *ext == "dict")
+            .and_then(|_| path.file_stem()?.to_str()?.parse::<u32>().ok())
+        {
+            dicts.push((dict_id, path));
+        }
+    }
+    Ok(dicts)
+}
+
+fn find_latest_zstd_dict(segments_path: &Path) -> anyhow::Result<Option<(u32, PathBuf)>> {
+    let mut latest = None;
+    for (dict_id, path) in list_zstd_dicts(segments_path)? {
+        let modified = std::fs::metadata(&path)?.modified()?;
+        if latest
+            .as_ref()
+            .is_none_or(|(latest_modified, _, _)| *latest_modified < modified)
+        {
+            latest = Some((modified, dict_id, path));
+        }
+    }
+    Ok(latest.map(|(_modified, dict_id, path)| (dict_id, path)))
+}
+
+fn load_latest_zstd_dict(segments_path: &Path) -> anyhow::Result<Option<(u32, Vec<u8>)>> {
+    find_latest_zstd_dict(segments_path)?
+        .map(|(dict_id, path)| {
+            std::fs::read(&path)
+                .map(|dict| (dict_id, dict))
+                .with_context(|| format!("failed to read zstd dictionary: {path:?}"))
+        })
+        .transpose()
+}
+
+/// Reads dictionary ids from zstd frame headers of stored chunks,
+/// returns the last segment with chunks for every dictionary.
+fn load_zstd_dicts_usage(
+    segments_path: &Path,
+    catalog: &MetadataMirror,
+) -> anyhow::Result<HashMap<u32, u64>> {
+    // max size of zstd frame header
+    const ZSTD_FRAME_HEADER_SIZE_MAX: u64 = 18;
+
+    let mut dicts = HashMap::new();
+    let mut file: Option<(u64, File)> = None;
+    let mut header = Vec::with_capacity(ZSTD_FRAME_HEADER_SIZE_MAX as usize);
+    for chunk in catalog
+        .chunks
+        .iter()
+        .filter(|chunk| chunk.compression == ChunkCompression::TAG_ZSTD_DICT)
+    {
+        let file = match &mut file {
+            Some((segment_id, file)) if *segment_id == chunk.segment_id => file,
+            file => {
+                let path = segments_path.join(segment_file_name(chunk.segment_id));
+                let opened = File::open(&path)
+                    .with_context(|| format!("failed to open segment file: {path:?}"))?;
+                &mut file.insert((chunk.segment_id, opened)).1
+            }
+        };
+        file.seek(SeekFrom::Start(chunk.offset))?;
+        header.clear();
+        file.take(chunk.size.min(ZSTD_FRAME_HEADER_SIZE_MAX))
+            .read_to_end(&mut header)?;
+        let dict_id = get_dict_id_from_frame(&header)
+            .context("missed dictionary id in zstd frame")?
+            .get();
+        dicts.insert(dict_id, chunk.segment_id);
+    }
+    Ok(dicts)
+}
+
+fn remove_file_if_exists(path: &Path) -> anyhow::Result<()> {
+    match std::fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(error) => Err(error).with_context(|| format!("failed to delete {path:?}")),
+    }
+}
+
+/// Lz4 block with prepended size, lz4 can't compress better than 255:1
+/// so bigger size in the prefix means corrupted chunk.
+fn lz4_decompress(payload: &[u8]) -> anyhow::Result<Vec<u8>> {
+    const LZ4_RATIO_MAX: usize = 255;
+
+    let (size, block) =
+        lz4_flex::block::uncompressed_size(payload).context("failed to decompress chunk")?;
+    anyhow::ensure!(
+        size <= block.len().saturating_mul(LZ4_RATIO_MAX),
+        "invalid lz4 chunk: decompressed size {size} for {} compressed bytes",
+        block.len()
+    );
+    lz4_flex::block::decompress(block, size).context("failed to decompress chunk")
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{ChunkCompression, list_zstd_dicts, lz4_decompress},
+        crate::{channel::ParsedMessage, storage::tests::TestStorage},
+        prost::Message as _,
+        richat_filter::message::{Message, MessageAccount, MessageParserEncoding},
+        richat_proto::geyser::{
+            SubscribeUpdate, SubscribeUpdateAccount, SubscribeUpdateAccountInfo,
+            subscribe_update::UpdateOneof,
+        },
+        solana_clock::Slot,
+        std::{borrow::Cow, fs},
+    };
+
+    fn account(write_version: u64) -> (Slot, ParsedMessage) {
+        let slot = write_version / 10;
+        let data = SubscribeUpdate {
+            filters: vec![],
+            update_oneof: Some(UpdateOneof::Account(SubscribeUpdateAccount {
+                account: Some(SubscribeUpdateAccountInfo {
+                    pubkey: vec![(write_version % 7) as u8; 32],
+                    owner: vec![1; 32],
+                    lamports: write_version * 1_000,
+                    data: format!("account data #{write_version} ")
+                        .repeat(20)
+                        .into_bytes(),
+                    write_version,
+                    ..Default::default()
+                }),
+                slot,
+                is_startup: false,
+            })),
+            created_at: Some(Default::default()),
+        }
+        .encode_to_vec();
+        let message = Message::parse(Cow::Owned(data), MessageParserEncoding::Prost)
+            .expect("valid message")
+            .into();
+        (slot, message)
+    }
+
+    fn key(message: &ParsedMessage) -> (Slot, u64, Vec<u8>) {
+        let ParsedMessage::Account(msg) = message else {
+            panic!("expected account, got {}", message.as_str_type());
+        };
+        let MessageAccount::Prost { account, .. } = msg.as_ref() else {
+            panic!("expected prost message");
+        };
+        (msg.slot(), msg.write_version(), account.data.clone())
+    }
+
+    fn read_all(storage: &TestStorage) -> Vec<(Slot, u64, Vec<u8>)> {
+        let mut messages = vec![];
+        for chunk in storage
+            .storage
+            .read_messages_from_index(0, MessageParserEncoding::Prost)
+        {
+            for item in chunk.expect("valid chunk") {
+                let (_index, message) = item.expect("valid message");
+                messages.push(key(&message));
+            }
+        }
+        messages
+    }
+
+    fn expected(write_versions: impl Iterator<Item = u64>) -> Vec<(Slot, u64, Vec<u8>)> {
+        write_versions.map(|wv| key(&account(wv).1)).collect()
+    }
+
+    fn chunks_compression(storage: &TestStorage) -> Vec<u8> {
+        let catalog = storage.storage.metadata.catalog();
+        catalog
+            .chunks
+            .iter()
+            .map(|chunk| chunk.compression)
+            .collect()
+    }
+
+    fn dict_config(name: &str) -> crate::config::ConfigStorage {
+        let mut config = TestStorage::config(name, Some("zstd-dict-3"));
+        // 100KiB of samples is enough for training
+        config.chunk_compression_dict_size = 1024;
+        config
+    }
+
+    #[test]
+    fn test_lz4_round_trip() {
+        let storage = TestStorage::open(TestStorage::config("lz4", Some("lz4"))).unwrap();
+        storage.push((0..50).map(account));
+        assert!(
+            chunks_compression(&storage)
+                .iter()
+                .all(|tag| *tag == ChunkCompression::TAG_LZ4)
+        );
+        assert_eq!(read_all(&storage), expected(0..50));
+
+        let config = storage.close();
+        let _ = fs::remove_dir_all(config.path);
+    }
+
+    #[test]
+    fn test_lz4_decompress_size_limit() {
+        let mut payload = lz4_flex::block::compress_prepend_size(&[42; 1024]);
+        assert_eq!(lz4_decompress(&payload).unwrap(), vec![42; 1024]);
+
+        payload[..4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(lz4_decompress(&payload).is_err());
+        assert!(lz4_decompress(&payload[..2]).is_err());
+    }
+
+    #[test]
+    fn test_zstd_dict_round_trip() {
+        let storage = TestStorage::open(dict_config("zstd-dict")).unwrap();
+        storage.push((0..300).map(account));
+
+        // plain zstd before the dictionary is trained
+        let tags = chunks_compression(&storage);
+        assert_eq!(tags[0], ChunkCompression::TAG_ZSTD);
+        assert_eq!(tags[299], ChunkCompression::TAG_ZSTD_DICT);
+        assert_eq!(read_all(&storage), expected(0..300));
+
+        // the same dictionary is loaded after reopen
+        let dicts = list_zstd_dicts(&storage.config.segments_path()).unwrap();
+        assert_eq!(dicts.len(), 1);
+        let config = storage.close();
+        let storage = TestStorage::open(config).unwrap();
+        storage.push((300..310).map(account));
+        assert_eq!(
+            list_zstd_dicts(&storage.config.segments_path()).unwrap(),
+            dicts
+        );
+        let tags = chunks_compression(&storage);
+        assert_eq!(tags[309], ChunkCompression::TAG_ZSTD_DICT);
+        assert_eq!(read_all(&storage), expected(0..310));
+
+        let config = storage.close();
+        let _ = fs::remove_dir_all(config.path);
+    }
+
+    #[test]
+    fn test_zstd_dict_removed_with_segments() {
+        let mut config = dict_config("zstd-dict-remove");
+        config.segment_target_size = 1;
+        let storage = TestStorage::open(config).unwrap();
+        storage.push((0..300).map(account));
+        let segments_path = storage.config.segments_path();
+        assert_eq!(list_zstd_dicts(&segments_path).unwrap().len(), 1);
+        let mut config = storage.close();
+
+        // dictionary is used by stored chunks after compression change
+        config.chunk_compression = Some(ChunkCompression::Lz4);
+        let storage = TestStorage::open(config).unwrap();
+        assert_eq!(list_zstd_dicts(&segments_path).unwrap().len(), 1);
+        storage.push((300..310).map(account));
+
+        // and removed with the last segment with such chunks
+        let next_index = storage.storage.next_index();
+        storage.storage.trim_messages(0, Some(next_index - 1));
+        storage.push((310..311).map(account));
+        assert!(list_zstd_dicts(&segments_path).unwrap().is_empty());
+        assert_eq!(read_all(&storage), expected(309..311));
+
+        let config = storage.close();
+        let _ = fs::remove_dir_all(config.path);
+    }
+}
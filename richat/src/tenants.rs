@@ -0,0 +1,416 @@
+use {
+    crate::{
+        config::{ConfigAppsTenant, ConfigAppsTenantApp},
+        metrics,
+    },
+    ::metrics::{counter, gauge},
+    futures::future::poll_fn,
+    quanta::Instant,
+    richat_filter::config::ConfigLimits as ConfigFilterLimits,
+    richat_shared::{
+        mutex_lock,
+        transports::{SubscribeError, reload::Reloadable},
+    },
+    std::{
+        collections::{HashMap, HashSet},
+        future::Future,
+        pin::Pin,
+        sync::{
+            Arc, Mutex,
+            atomic::{AtomicUsize, Ordering},
+        },
+        task::{Context, Poll},
+        time::Duration,
+    },
+    thiserror::Error,
+    tokio::time::{Sleep, sleep},
+    tonic::Status,
+};
+
+#[derive(Debug, Clone, Error)]
+pub enum TenantError {
+    #[error("tenant {name} is not allowed to use {} app", app.as_str())]
+    AppNotAllowed {
+        name: Arc<str>,
+        app: ConfigAppsTenantApp,
+    },
+    #[error("tenant {name} reached max number of subscriptions: {max}")]
+    SubscriptionsLimit { name: Arc<str>, max: usize },
+}
+
+impl From<TenantError> for SubscribeError {
+    fn from(error: TenantError) -> Self {
+        match error {
+            TenantError::AppNotAllowed { .. } => Self::XTokenAppNotAllowed,
+            TenantError::SubscriptionsLimit { max, .. } => Self::XTokenSubscriptionsLimit { max },
+        }
+    }
+}
+
+impl From<TenantError> for Status {
+    fn from(error: TenantError) -> Self {
+        match error {
+            TenantError::AppNotAllowed { .. } => Self::permission_denied(error.to_string()),
+            TenantError::SubscriptionsLimit { .. } => Self::resource_exhausted(error.to_string()),
+        }
+    }
+}
+
+/// Table of tenants by x-token, shared by all apps, can be replaced with [`Tenants::reload`].
+#[derive(Debug, Default, Clone)]
+pub struct Tenants {
+    table: Reloadable<TenantsTable>,
+}
+
+#[derive(Debug, Default)]
+struct TenantsTable {
+    tokens: HashMap<Vec<u8>, Arc<Tenant>>,
+    apps: HashSet<ConfigAppsTenantApp>,
+}
+
+impl TenantsTable {
+    fn new(config: Vec<ConfigAppsTenant>, current: Option<&Self>) -> Self {
+        let mut tokens = HashMap::new();
+        let mut apps = HashSet::new();
+        for config in config {
+            // keep counters of active subscriptions and bandwidth budget on reload
+            let current = current.and_then(|table| {
+                table
+                    .tokens
+                    .values()
+                    .find(|tenant| *tenant.name == *config.name)
+            });
+            let subscriptions = current
+                .map(|tenant| Arc::clone(&tenant.subscriptions))
+                .unwrap_or_default();
+            let bandwidth = config.bytes_per_sec_max.map(|bytes_per_sec| {
+                current
+                    .and_then(|tenant| tenant.bandwidth.as_ref())
+                    .filter(|bandwidth| mutex_lock(bandwidth).bytes_per_sec == bytes_per_sec as f64)
+                    .map(Arc::clone)
+                    .unwrap_or_else(|| Arc::new(Mutex::new(Bandwidth::new(bytes_per_sec))))
+            });
+
+            let tenant = Arc::new(Tenant {
+                name: config.name.into(),
+                apps: config.apps,
+                filter_limits: config.filter_limits.map(Arc::new),
+                subscriptions_max: config.subscriptions_max,
+                subscriptions,
+                bandwidth,
+            });
+            apps.extend(tenant.apps.iter().copied());
+            for x_token in config.x_tokens {
+                tokens.insert(x_token, Arc::clone(&tenant));
+            }
+        }
+        Self { tokens, apps }
+    }
+}
+
+impl Tenants {
+    pub fn new(config: Vec<ConfigAppsTenant>) -> Self {
+        Self {
+            table: Reloadable::new(TenantsTable::new(config, None)),
+        }
+    }
+
+    /// Replace tenants for new requests, active subscriptions are counted by tenant name.
+    pub fn reload(&self, config: Vec<ConfigAppsTenant>) {
+        let table = TenantsTable::new(config, Some(&self.table.load()));
+        self.table.store(table);
+    }
+
+    pub fn x_tokens(&self) -> Vec<Vec<u8>> {
+        self.table.load().tokens.keys().cloned().collect()
+    }
+
+    /// `true` if at least one tenant is allowed to use the app, in that case
+    /// requests to the app should have a valid x-token.
+    pub fn has_app(&self, app: ConfigAppsTenantApp) -> bool {
+        self.table.load().apps.contains(&app)
+    }
+
+    /// Tenant of the x-token, `Ok(None)` if x-token is not listed in the table.
+    pub fn get(
+        &self,
+        app: ConfigAppsTenantApp,
+        x_token: Option<&[u8]>,
+    ) -> Result<Option<Arc<Tenant>>, TenantError> {
+        let table = self.table.load();
+        let Some(tenant) = x_token.and_then(|x_token| table.tokens.get(x_token)) else {
+            return Ok(None);
+        };
+        if !tenant.apps.contains(&app) {
+            tenant.inc_rejected(app, "app_not_allowed");
+            return Err(TenantError::AppNotAllowed {
+                name: Arc::clone(&tenant.name),
+                app,
+            });
+        }
+        Ok(Some(Arc::clone(tenant)))
+    }
+
+    /// Open subscription for the tenant of x-token, `Ok(None)` if x-token is not listed in the table.
+    pub fn subscribe(
+        &self,
+        app: ConfigAppsTenantApp,
+        x_token: Option<&[u8]>,
+    ) -> Result<Option<TenantSubscription>, TenantError> {
+        self.get(app, x_token)?
+            .map(|tenant| tenant.subscribe(app))
+            .transpose()
+    }
+}
+
+#[derive(Debug)]
+pub struct Tenant {
+    name: Arc<str>,
+    apps: HashSet<ConfigAppsTenantApp>,
+    filter_limits: Option<Arc<ConfigFilterLimits>>,
+    subscriptions_max: Option<usize>,
+    subscriptions: Arc<AtomicUsize>,
+    bandwidth: Option<Arc<Mutex<Bandwidth>>>,
+}
+
+impl Tenant {
+    pub const fn name(&self) -> &Arc<str> {
+        &self.name
+    }
+
+    fn subscribe(
+        self: &Arc<Self>,
+        app: ConfigAppsTenantApp,
+    ) -> Result<TenantSubscription, TenantError> {
+        let max = self.subscriptions_max.unwrap_or(usize::MAX);
+        if self
+            .subscriptions
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |count| {
+                (count < max).then_some(count + 1)
+            })
+            .is_err()
+        {
+            self.inc_rejected(app, "subscriptions_limit");
+            return Err(TenantError::SubscriptionsLimit {
+                name: Arc::clone(&self.name),
+                max,
+            });
+        }
+
+        gauge!(
+            metrics::TENANT_SUBSCRIPTIONS_TOTAL,
+            "tenant" => Arc::clone(&self.name),
+            "app" => app.as_str()
+        )
+        .increment(1);
+        Ok(TenantSubscription {
+            tenant: Arc::clone(self),
+            app,
+            throttle: None,
+        })
+    }
+
+    fn inc_rejected(&self, app: ConfigAppsTenantApp, reason: &'static str) {
+        counter!(
+            metrics::TENANT_REJECTED_TOTAL,
+            "tenant" => Arc::clone(&self.name),
+            "app" => app.as_str(),
+            "reason" => reason
+        )
+        .increment(1);
+    }
+}
+
+/// Active subscription of the tenant, released on drop.
+#[derive(Debug)]
+pub struct TenantSubscription {
+    tenant: Arc<Tenant>,
+    app: ConfigAppsTenantApp,
+    throttle: Option<Pin<Box<Sleep>>>,
+}
+
+impl Drop for TenantSubscription {
+    fn drop(&mut self) {
+        self.tenant.subscriptions.fetch_sub(1, Ordering::Relaxed);
+        gauge!(
+            metrics::TENANT_SUBSCRIPTIONS_TOTAL,
+            "tenant" => Arc::clone(&self.tenant.name),
+            "app" => self.app.as_str()
+        )
+        .decrement(1);
+    }
+}
+
+impl TenantSubscription {
+    #[allow(clippy::missing_const_for_fn)]
+    pub fn name(&self) -> &Arc<str> {
+        &self.tenant.name
+    }
+
+    pub fn filter_limits(&self) -> Option<Arc<ConfigFilterLimits>> {
+        self.tenant.filter_limits.as_ref().map(Arc::clone)
+    }
+
+    /// Consume bandwidth budget, next message should be sent only once
+    /// [`Self::poll_budget`] is ready.
+    pub fn consume(&mut self, bytes: usize) {
+        let Some(bandwidth) = &self.tenant.bandwidth else {
+            return;
+        };
+        if let Some(wait) = mutex_lock(bandwidth).consume(bytes) {
+            gauge!(
+                metrics::TENANT_THROTTLED_SECONDS_TOTAL,
+                "tenant" => Arc::clone(&self.tenant.name)
+            )
+            .increment(wait.as_secs_f64());
+            self.throttle = Some(Box::pin(sleep(wait)));
+        }
+    }
+
+    pub const fn is_throttled(&self) -> bool {
+        self.throttle.is_some()
+    }
+
+    pub fn poll_budget(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        if let Some(throttle) = self.throttle.as_mut() {
+            if throttle.as_mut().poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+            self.throttle = None;
+        }
+        Poll::Ready(())
+    }
+
+    pub async fn wait_budget(&mut self) {
+        poll_fn(|cx| self.poll_budget(cx)).await
+    }
+}
+
+// token bucket with capacity of one second
+#[derive(Debug)]
+struct Bandwidth {
+    bytes_per_sec: f64,
+    available: f64,
+    updated_at: Instant,
+}
+
+impl Bandwidth {
+    fn new(bytes_per_sec: usize) -> Self {
+        Self {
+            bytes_per_sec: bytes_per_sec as f64,
+            available: bytes_per_sec as f64,
+            updated_at: Instant::now(),
+        }
+    }
+
+    fn consume(&mut self, bytes: usize) -> Option<Duration> {
+        let now = Instant::now();
+        let elapsed = now.saturating_duration_since(self.updated_at);
+        self.updated_at = now;
+
+        self.available = (self.available + elapsed.as_secs_f64() * self.bytes_per_sec)
+            .min(self.bytes_per_sec)
+            - bytes as f64;
+        (self.available < 0.0)
+            .then(|| Duration::from_secs_f64(-self.available / self.bytes_per_sec))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{Bandwidth, TenantError, Tenants},
+        crate::config::{ConfigAppsTenant, ConfigAppsTenantApp},
+    };
+
+    fn tenant(x_token: &[u8], app: ConfigAppsTenantApp) -> ConfigAppsTenant {
+        ConfigAppsTenant {
+            name: "team".to_owned(),
+            x_tokens: [x_token.to_vec()].into_iter().collect(),
+            apps: [app].into_iter().collect(),
+            filter_limits: None,
+            subscriptions_max: Some(1),
+            bytes_per_sec_max: None,
+        }
+    }
+
+    fn tenants() -> Tenants {
+        Tenants::new(vec![tenant(b"token", ConfigAppsTenantApp::Grpc)])
+    }
+
+    #[test]
+    fn test_subscribe() {
+        let tenants = tenants();
+        assert!(matches!(
+            tenants.subscribe(ConfigAppsTenantApp::Grpc, None),
+            Ok(None)
+        ));
+        assert!(matches!(
+            tenants.subscribe(ConfigAppsTenantApp::Pubsub, Some(b"token")),
+            Err(TenantError::AppNotAllowed { .. })
+        ));
+
+        let subscription = tenants.subscribe(ConfigAppsTenantApp::Grpc, Some(b"token"));
+        assert!(matches!(subscription, Ok(Some(_))));
+        assert!(matches!(
+            tenants.subscribe(ConfigAppsTenantApp::Grpc, Some(b"token")),
+            Err(TenantError::SubscriptionsLimit { max: 1, .. })
+        ));
+        drop(subscription);
+        assert!(matches!(
+            tenants.subscribe(ConfigAppsTenantApp::Grpc, Some(b"token")),
+            Ok(Some(_))
+        ));
+    }
+
+    #[test]
+    fn test_reload() {
+        let tenants = tenants();
+        let subscription = tenants.subscribe(ConfigAppsTenantApp::Grpc, Some(b"token"));
+        assert!(matches!(subscription, Ok(Some(_))));
+
+        tenants.reload(vec![tenant(b"token2", ConfigAppsTenantApp::Pubsub)]);
+        assert!(tenants.has_app(ConfigAppsTenantApp::Pubsub));
+        assert!(!tenants.has_app(ConfigAppsTenantApp::Grpc));
+        assert!(matches!(
+            tenants.subscribe(ConfigAppsTenantApp::Grpc, Some(b"token")),
+            Ok(None)
+        ));
+        // subscription opened before reload is still counted
+        assert!(matches!(
+            tenants.subscribe(ConfigAppsTenantApp::Pubsub, Some(b"token2")),
+            Err(TenantError::SubscriptionsLimit { max: 1, .. })
+        ));
+        drop(subscription);
+        assert!(matches!(
+            tenants.subscribe(ConfigAppsTenantApp::Pubsub, Some(b"token2")),
+            Ok(Some(_))
+        ));
+    }
+
+    #[test]
+    fn test_bandwidth() {
+        let mut bandwidth = Bandwidth::new(1_000);
+        assert!(bandwidth.consume(600).is_none());
+        let wait = bandwidth.consume(600).expect("budget exhausted");
+        assert!(wait.as_millis() > 150 && wait.as_millis() <= 200);
+    }
+
+    #[tokio::test]
+    async fn test_throttle() {
+        let mut config = tenant(b"token", ConfigAppsTenantApp::Pubsub);
+        config.bytes_per_sec_max = Some(10_000);
+        let tenants = Tenants::new(vec![config]);
+        let mut subscription = tenants
+            .subscribe(ConfigAppsTenantApp::Pubsub, Some(b"token"))
+            .unwrap()
+            .expect("tenant subscription");
+
+        subscription.consume(5_000);
+        assert!(!subscription.is_throttled());
+        subscription.consume(5_100);
+        assert!(subscription.is_throttled());
+        subscription.wait_budget().await;
+        assert!(!subscription.is_throttled());
+    }
+}
@@ -0,0 +1,144 @@
+use {
+    richat_filter::config::ConfigFilter,
+    richat_shared::config::{
+        deserialize_affinity, deserialize_humansize_usize, deserialize_num_str,
+    },
+    serde::Deserialize,
+    solana_account_decoder::UiAccountEncoding,
+    solana_transaction_status::{TransactionDetails, UiTransactionEncoding},
+    std::{path::PathBuf, time::Duration},
+};
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct ConfigAppsWebhooks {
+    pub webhooks: Vec<ConfigAppsWebhook>,
+    #[serde(deserialize_with = "deserialize_affinity")]
+    pub affinity: Option<Vec<usize>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigAppsWebhook {
+    /// Used in logs and metrics, `url` by default
+    pub name: Option<String>,
+    pub url: String,
+    /// Same filter as in gRPC subscribe request, `commitment` selects the channel
+    #[serde(default)]
+    pub filter: ConfigFilter,
+    #[serde(default)]
+    pub encoding: ConfigAppsWebhookEncoding,
+    /// Key for HMAC-SHA256 signature of the payload, requests are not signed if not set
+    pub secret: Option<String>,
+    #[serde(default)]
+    pub batch: ConfigAppsWebhookBatch,
+    #[serde(default)]
+    pub retry: ConfigAppsWebhookRetry,
+    #[serde(
+        default = "ConfigAppsWebhook::default_request_timeout",
+        with = "humantime_serde"
+    )]
+    pub request_timeout: Duration,
+    /// Max number of batches waiting for delivery
+    #[serde(
+        default = "ConfigAppsWebhook::default_queue_size",
+        deserialize_with = "deserialize_num_str"
+    )]
+    pub queue_size: usize,
+    /// Undelivered batches are appended to this file (one JSON per line), dropped if not set
+    pub dead_letter: Option<PathBuf>,
+}
+
+impl ConfigAppsWebhook {
+    const fn default_request_timeout() -> Duration {
+        Duration::from_secs(10)
+    }
+
+    const fn default_queue_size() -> usize {
+        16
+    }
+
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.url)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct ConfigAppsWebhookEncoding {
+    pub accounts: UiAccountEncoding,
+    pub transactions: UiTransactionEncoding,
+    /// Used for transactions and blocks
+    pub transaction_details: TransactionDetails,
+    pub show_rewards: bool,
+    pub max_supported_transaction_version: Option<u8>,
+}
+
+impl Default for ConfigAppsWebhookEncoding {
+    fn default() -> Self {
+        Self {
+            accounts: UiAccountEncoding::Base64,
+            transactions: UiTransactionEncoding::Base64,
+            transaction_details: TransactionDetails::Full,
+            show_rewards: false,
+            max_supported_transaction_version: Some(0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct ConfigAppsWebhookBatch {
+    /// Max number of updates in one request
+    #[serde(deserialize_with = "deserialize_num_str")]
+    pub max_updates: usize,
+    /// Max size of the request body, single update can exceed it
+    #[serde(deserialize_with = "deserialize_humansize_usize")]
+    pub max_bytes: usize,
+    /// Max time to wait for more updates before sending non-empty batch
+    #[serde(with = "humantime_serde")]
+    pub interval: Duration,
+}
+
+impl Default for ConfigAppsWebhookBatch {
+    fn default() -> Self {
+        Self {
+            max_updates: 100,
+            max_bytes: 1024 * 1024, // 1MiB
+            interval: Duration::from_millis(100),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct ConfigAppsWebhookRetry {
+    /// Max number of requests for one batch, including the first one
+    #[serde(deserialize_with = "deserialize_num_str")]
+    pub max_attempts: usize,
+    /// Delay before the first retry, doubled for every next retry
+    #[serde(with = "humantime_serde")]
+    pub initial_interval: Duration,
+    #[serde(with = "humantime_serde")]
+    pub max_interval: Duration,
+}
+
+impl Default for ConfigAppsWebhookRetry {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_interval: Duration::from_millis(100),
+            max_interval: Duration::from_secs(10),
+        }
+    }
+}
+
+impl ConfigAppsWebhookRetry {
+    /// Delay after failed attempt, `attempt` starts from 1
+    pub fn backoff(&self, attempt: usize) -> Duration {
+        let exp = attempt.saturating_sub(1).min(31) as u32;
+        self.initial_interval
+            .saturating_mul(2u32.saturating_pow(exp))
+            .min(self.max_interval)
+    }
+}
@@ -0,0 +1,753 @@
+use {
+    crate::{
+        channel::{Messages, ParsedMessage},
+        config::ConfigAppsWorkers,
+        metrics,
+        pubsub::notification::{RpcBlockUpdate, RpcTransactionUpdate},
+        webhooks::config::{
+            ConfigAppsWebhook, ConfigAppsWebhookBatch, ConfigAppsWebhookEncoding,
+            ConfigAppsWebhookRetry, ConfigAppsWebhooks,
+        },
+    },
+    ::metrics::{counter, gauge},
+    anyhow::Context,
+    futures::future::{FutureExt, TryFutureExt, try_join_all},
+    hmac::{Hmac, Mac},
+    reqwest::{Client, StatusCode, Url, header::CONTENT_TYPE},
+    richat_filter::filter::{Filter, FilteredUpdate, FilteredUpdateType},
+    richat_proto::convert_from,
+    richat_shared::{
+        five8::{pubkey_encode, signature_encode},
+        transports::RecvError,
+    },
+    serde::Serialize,
+    serde_json::value::RawValue,
+    sha2::Sha256,
+    solana_account_decoder::{UiAccount, encode_ui_account},
+    solana_clock::Slot,
+    solana_commitment_config::CommitmentLevel,
+    solana_transaction_error::TransactionError,
+    solana_transaction_status::BlockEncodingOptions,
+    std::{
+        future::Future,
+        path::PathBuf,
+        sync::Arc,
+        thread::sleep,
+        time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+    },
+    tokio::{fs::OpenOptions, io::AsyncWriteExt, sync::mpsc},
+    tokio_util::sync::CancellationToken,
+    tracing::{error, info, warn},
+};
+
+pub const HEADER_TIMESTAMP: &str = "x-richat-timestamp";
+pub const HEADER_SIGNATURE: &str = "x-richat-signature";
+
+/// JSON object for every filtered update, request body is an array of them.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookUpdate<'a> {
+    pub filters: &'a [&'a str],
+    /// Milliseconds since UNIX epoch
+    pub created_at: u64,
+    #[serde(flatten)]
+    pub value: WebhookUpdateValue<'a>,
+}
+
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", content = "value", rename_all = "camelCase")]
+pub enum WebhookUpdateValue<'a> {
+    #[serde(rename_all = "camelCase")]
+    Slot {
+        slot: Slot,
+        parent: Option<Slot>,
+        status: &'static str,
+        dead_error: Option<&'a str>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Account {
+        slot: Slot,
+        pubkey: String,
+        account: UiAccount,
+    },
+    Transaction(RpcTransactionUpdate),
+    #[serde(rename_all = "camelCase")]
+    TransactionStatus {
+        slot: Slot,
+        signature: String,
+        is_vote: bool,
+        index: u64,
+        err: Option<TransactionError>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Entry {
+        slot: Slot,
+        index: u64,
+        executed_transaction_count: u64,
+    },
+    #[serde(rename_all = "camelCase")]
+    BlockMeta {
+        slot: Slot,
+        blockhash: &'a str,
+        parent_slot: Slot,
+        block_height: Slot,
+        block_time: Option<i64>,
+        executed_transaction_count: u64,
+        entries_count: u64,
+    },
+    Block(RpcBlockUpdate),
+}
+
+impl<'a> WebhookUpdateValue<'a> {
+    pub fn new(update: &FilteredUpdateType<'a>, encoding: &ConfigAppsWebhookEncoding) -> Self {
+        match update {
+            FilteredUpdateType::Slot { message } => Self::Slot {
+                slot: message.slot(),
+                parent: message.parent(),
+                status: message.status().as_str_name(),
+                dead_error: message.dead_error(),
+            },
+            FilteredUpdateType::Account { message, .. } => Self::Account {
+                slot: message.slot(),
+                pubkey: pubkey_encode(&message.pubkey().to_bytes()),
+                account: encode_ui_account(
+                    message.pubkey(),
+                    *message,
+                    encoding.accounts,
+                    None,
+                    None,
+                ),
+            },
+            FilteredUpdateType::Transaction { message } => {
+                Self::Transaction(RpcTransactionUpdate::new(
+                    message,
+                    encoding.transactions,
+                    encoding.transaction_details,
+                    encoding.show_rewards,
+                    encoding.max_supported_transaction_version,
+                ))
+            }
+            FilteredUpdateType::TransactionStatus { message } => Self::TransactionStatus {
+                slot: message.slot(),
+                signature: signature_encode(message.signature().as_array()),
+                is_vote: message.vote(),
+                index: message.index(),
+                err: convert_from::create_tx_error(message.error().as_ref())
+                    .ok()
+                    .flatten(),
+            },
+            FilteredUpdateType::Entry { message } => Self::Entry {
+                slot: message.slot(),
+                index: message.index(),
+                executed_transaction_count: message.executed_transaction_count(),
+            },
+            FilteredUpdateType::BlockMeta { message } => Self::BlockMeta {
+                slot: message.slot(),
+                blockhash: message.blockhash(),
+                parent_slot: message.parent_slot(),
+                block_height: message.block_height(),
+                block_time: message.block_time(),
+                executed_transaction_count: message.executed_transaction_count(),
+                entries_count: message.entries_count(),
+            },
+            FilteredUpdateType::Block { message, .. } => Self::Block(RpcBlockUpdate::new(
+                message,
+                encoding.transactions,
+                BlockEncodingOptions {
+                    transaction_details: encoding.transaction_details,
+                    show_rewards: encoding.show_rewards,
+                    max_supported_transaction_version: encoding.max_supported_transaction_version,
+                },
+            )),
+        }
+    }
+}
+
+/// Updates collected for one request.
+#[derive(Debug, Default)]
+pub struct WebhookBatch {
+    updates: Vec<String>,
+    bytes: usize,
+    started_at: Option<Instant>,
+    /// Creation time of the first message in the batch, milliseconds since UNIX epoch
+    created_at: u64,
+}
+
+impl WebhookBatch {
+    pub fn push(&mut self, update: String, created_at: u64) {
+        if self.updates.is_empty() {
+            self.started_at = Some(Instant::now());
+            self.created_at = created_at;
+        }
+        self.bytes += update.len() + 1;
+        self.updates.push(update);
+    }
+
+    pub fn len(&self) -> usize {
+        self.updates.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.updates.is_empty()
+    }
+
+    pub fn is_full(&self, config: &ConfigAppsWebhookBatch) -> bool {
+        self.updates.len() >= config.max_updates || self.bytes >= config.max_bytes
+    }
+
+    pub fn is_expired(&self, config: &ConfigAppsWebhookBatch) -> bool {
+        self.started_at
+            .is_some_and(|started_at| started_at.elapsed() >= config.interval)
+    }
+
+    /// JSON array of updates
+    pub fn into_body(self) -> String {
+        let mut body = String::with_capacity(self.bytes + 2);
+        body.push('[');
+        for (index, update) in self.updates.iter().enumerate() {
+            if index > 0 {
+                body.push(',');
+            }
+            body.push_str(update);
+        }
+        body.push(']');
+        body
+    }
+}
+
+#[derive(Debug)]
+struct WebhookRequest {
+    count: usize,
+    created_at: u64,
+    body: String,
+}
+
+impl From<WebhookBatch> for WebhookRequest {
+    fn from(batch: WebhookBatch) -> Self {
+        Self {
+            count: batch.len(),
+            created_at: batch.created_at,
+            body: batch.into_body(),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum WebhookSendError {
+    #[error(transparent)]
+    Request(#[from] reqwest::Error),
+    #[error("unexpected response status: {0}")]
+    Status(StatusCode),
+}
+
+impl WebhookSendError {
+    /// Client errors (except rate limit) would not go away with retries
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Request(_) => true,
+            Self::Status(status) => {
+                status.is_server_error() || *status == StatusCode::TOO_MANY_REQUESTS
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct WebhookDeadLetter<'a> {
+    url: &'a str,
+    failed_at: u64,
+    attempts: usize,
+    error: String,
+    updates: &'a RawValue,
+}
+
+/// Hex encoded HMAC-SHA256 of `{timestamp}.{body}`
+pub fn sign(secret: &[u8], timestamp: u64, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC can take key of any size");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    format!("sha256={}", const_hex::encode(mac.finalize().into_bytes()))
+}
+
+fn unix_time_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// Filters and encodes messages from the channel, should be used in a dedicated thread.
+#[derive(Debug)]
+pub struct WebhookEncoder {
+    name: String,
+    filter: Filter,
+    encoding: ConfigAppsWebhookEncoding,
+    batch: ConfigAppsWebhookBatch,
+}
+
+impl WebhookEncoder {
+    pub fn new(config: &ConfigAppsWebhook) -> Self {
+        Self {
+            name: config.name().to_owned(),
+            filter: Filter::new(&config.filter),
+            encoding: config.encoding,
+            batch: config.batch,
+        }
+    }
+
+    pub fn commitment(&self) -> CommitmentLevel {
+        self.filter.commitment().into()
+    }
+
+    pub fn encode(&self, message: &ParsedMessage) -> Vec<String> {
+        let created_at = message.created_at().as_millis();
+        self.filter
+            .get_updates_ref(message.into(), self.commitment())
+            .iter()
+            .map(
+                |FilteredUpdate {
+                     filters,
+                     filtered_update,
+                 }| {
+                    serde_json::to_string(&WebhookUpdate {
+                        filters,
+                        created_at,
+                        value: WebhookUpdateValue::new(filtered_update, &self.encoding),
+                    })
+                    .expect("json serialization never fail")
+                },
+            )
+            .collect()
+    }
+
+    fn send(
+        &self,
+        tx: &mpsc::Sender<WebhookRequest>,
+        batch: &mut WebhookBatch,
+    ) -> anyhow::Result<()> {
+        if !batch.is_empty() {
+            tx.blocking_send(std::mem::take(batch).into())
+                .map_err(|_| anyhow::anyhow!("webhook {} sender closed", self.name))?;
+        }
+        Ok(())
+    }
+
+    /// Blocking loop collecting batches from the channel, should be executed in a dedicated thread.
+    fn run_worker(
+        &self,
+        messages: Messages,
+        tx: mpsc::Sender<WebhookRequest>,
+        shutdown: CancellationToken,
+    ) -> anyhow::Result<()> {
+        let commitment = self.commitment();
+        let receiver = messages.to_receiver();
+        let mut head = messages.get_current_tail(commitment);
+        let mut batch = WebhookBatch::default();
+
+        const COUNTER_LIMIT: i32 = 10_000;
+        let mut counter = 0;
+        loop {
+            counter += 1;
+            if counter > COUNTER_LIMIT {
+                counter = 0;
+                if shutdown.is_cancelled() {
+                    info!("webhook {} thread shutdown", self.name);
+                    return self.send(&tx, &mut batch);
+                }
+                if batch.is_expired(&self.batch) {
+                    self.send(&tx, &mut batch)?;
+                }
+            }
+
+            let message = match receiver.try_recv(commitment, head) {
+                Ok(Some(message)) => message,
+                Ok(None) => {
+                    counter = COUNTER_LIMIT;
+                    sleep(Duration::from_micros(100));
+                    continue;
+                }
+                Err(RecvError::Lagged) => {
+                    error!("webhook {} lagged, messages skipped", self.name);
+                    counter!(metrics::WEBHOOKS_LAGGED_TOTAL, "webhook" => self.name.clone())
+                        .increment(1);
+                    head = messages.get_current_tail(commitment);
+                    continue;
+                }
+                Err(error) => return Err(error.into()),
+            };
+            head += 1;
+
+            let created_at = message.created_at().as_millis();
+            for update in self.encode(&message) {
+                batch.push(update, created_at);
+                if batch.is_full(&self.batch) {
+                    self.send(&tx, &mut batch)?;
+                }
+            }
+        }
+    }
+}
+
+/// Delivers batches with retries, undelivered batches go to the dead-letter file.
+#[derive(Debug, Clone)]
+pub struct WebhookClient {
+    name: String,
+    url: Url,
+    client: Client,
+    secret: Option<Arc<[u8]>>,
+    retry: ConfigAppsWebhookRetry,
+    dead_letter: Option<PathBuf>,
+}
+
+impl WebhookClient {
+    pub fn new(config: &ConfigAppsWebhook) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            config.retry.max_attempts > 0,
+            "webhook {} should have at least one attempt",
+            config.name()
+        );
+        Ok(Self {
+            name: config.name().to_owned(),
+            url: config
+                .url
+                .parse()
+                .with_context(|| format!("webhook {}: invalid url", config.name()))?,
+            client: Client::builder().timeout(config.request_timeout).build()?,
+            secret: config
+                .secret
+                .as_ref()
+                .map(|secret| Arc::from(secret.as_bytes())),
+            retry: config.retry,
+            dead_letter: config.dead_letter.clone(),
+        })
+    }
+
+    pub async fn send_once(&self, body: &str) -> Result<(), WebhookSendError> {
+        let mut request = self
+            .client
+            .post(self.url.clone())
+            .header(CONTENT_TYPE, "application/json");
+        if let Some(secret) = &self.secret {
+            let timestamp = unix_time_ms();
+            request = request
+                .header(HEADER_TIMESTAMP, timestamp)
+                .header(HEADER_SIGNATURE, sign(secret, timestamp, body.as_bytes()));
+        }
+
+        let response = request.body(body.to_owned()).send().await?;
+        let status = response.status();
+        if status.is_success() {
+            Ok(())
+        } else {
+            Err(WebhookSendError::Status(status))
+        }
+    }
+
+    /// Returns number of made attempts and last error, stops retries on shutdown.
+    pub async fn send(
+        &self,
+        body: &str,
+        shutdown: &CancellationToken,
+    ) -> (usize, Result<(), WebhookSendError>) {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let error = match self.send_once(body).await {
+                Ok(()) => {
+                    counter!(metrics::WEBHOOKS_REQUESTS_TOTAL, "webhook" => self.name.clone(), "status" => "success").increment(1);
+                    return (attempt, Ok(()));
+                }
+                Err(error) => error,
+            };
+            counter!(metrics::WEBHOOKS_REQUESTS_TOTAL, "webhook" => self.name.clone(), "status" => "error").increment(1);
+
+            if !error.is_retryable() || attempt >= self.retry.max_attempts {
+                return (attempt, Err(error));
+            }
+            let backoff = self.retry.backoff(attempt);
+            warn!(
+                "webhook {} attempt #{attempt} failed, retry in {backoff:?}: {error}",
+                self.name
+            );
+            tokio::select! {
+                () = tokio::time::sleep(backoff) => {},
+                () = shutdown.cancelled() => return (attempt, Err(error)),
+            }
+        }
+    }
+
+    async fn write_dead_letter(&self, body: &str, attempts: usize, error: String) {
+        let Some(path) = &self.dead_letter else {
+            return;
+        };
+
+        let result = async {
+            let mut line = serde_json::to_vec(&WebhookDeadLetter {
+                url: self.url.as_str(),
+                failed_at: unix_time_ms(),
+                attempts,
+                error,
+                updates: serde_json::from_str(body)?,
+            })?;
+            line.push(b'\n');
+
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .await?;
+            file.write_all(&line).await?;
+            file.flush().await?;
+            Ok::<(), anyhow::Error>(())
+        }
+        .await;
+        if let Err(error) = result {
+            error!(
+                "webhook {} failed to write dead letter to {path:?}: {error}",
+                self.name
+            );
+        }
+    }
+
+    /// Queued batches are not sent after shutdown, but still saved to the dead-letter file.
+    async fn run(
+        self,
+        mut rx: mpsc::Receiver<WebhookRequest>,
+        shutdown: CancellationToken,
+    ) -> anyhow::Result<()> {
+        while let Some(request) = rx.recv().await {
+            let (attempts, result) = if shutdown.is_cancelled() {
+                (0, Err("shutdown".to_owned()))
+            } else {
+                let (attempts, result) = self.send(&request.body, &shutdown).await;
+                (attempts, result.map_err(|error| error.to_string()))
+            };
+
+            let status = match result {
+                Ok(()) => {
+                    let lag = unix_time_ms().saturating_sub(request.created_at);
+                    gauge!(metrics::WEBHOOKS_DELIVERY_LAG_SECONDS, "webhook" => self.name.clone())
+                        .set(lag as f64 / 1_000.0);
+                    "delivered"
+                }
+                Err(error) => {
+                    error!(
+                        "webhook {} failed to deliver {} updates after {attempts} attempts: {error}",
+                        self.name, request.count
+                    );
+                    self.write_dead_letter(&request.body, attempts, error).await;
+                    "failed"
+                }
+            };
+            counter!(metrics::WEBHOOKS_UPDATES_TOTAL, "webhook" => self.name.clone(), "status" => status)
+                .increment(request.count as u64);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct Webhooks;
+
+impl Webhooks {
+    pub fn spawn(
+        config: ConfigAppsWebhooks,
+        messages: Messages,
+        shutdown: CancellationToken,
+    ) -> anyhow::Result<impl Future<Output = anyhow::Result<()>>> {
+        anyhow::ensure!(
+            !config.webhooks.is_empty(),
+            "webhooks should have at least one webhook"
+        );
+        info!("start {} webhooks", config.webhooks.len());
+
+        let mut futs = Vec::with_capacity(config.webhooks.len() * 2);
+        for (index, webhook) in config.webhooks.iter().enumerate() {
+            anyhow::ensure!(
+                webhook.filter.accounts_data_slice.is_empty(),
+                "webhook {}: accounts_data_slice is not supported",
+                webhook.name()
+            );
+            let encoder = WebhookEncoder::new(webhook);
+            let client = WebhookClient::new(webhook)?;
+            let (tx, rx) = mpsc::channel(webhook.queue_size);
+
+            futs.push(
+                ConfigAppsWorkers::run_once(
+                    index,
+                    format!("richatWebhook{index:02}"),
+                    config.affinity.clone(),
+                    {
+                        let messages = messages.clone();
+                        let shutdown = shutdown.clone();
+                        move |_index| encoder.run_worker(messages, tx, shutdown)
+                    },
+                    shutdown.clone(),
+                )?
+                .boxed(),
+            );
+            futs.push(
+                tokio::spawn(client.run(rx, shutdown.clone()))
+                    .map_err(anyhow::Error::from)
+                    .and_then(|result| async move { result })
+                    .boxed(),
+            );
+        }
+
+        Ok(try_join_all(futs).map_ok(|_| ()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{
+            HEADER_SIGNATURE, HEADER_TIMESTAMP, WebhookBatch, WebhookClient, WebhookEncoder, sign,
+        },
+        crate::{channel::ParsedMessage, webhooks::config::ConfigAppsWebhook},
+        http_body_util::{BodyExt, Full as BodyFull},
+        hyper::{Request, Response, StatusCode, body::Incoming, service::service_fn},
+        hyper_util::{
+            rt::tokio::{TokioExecutor, TokioIo},
+            server::conn::auto::Builder as ServerBuilder,
+        },
+        prost::Message as _,
+        richat_filter::message::{Message as FilterMessage, MessageParserEncoding},
+        richat_proto::geyser::{
+            SubscribeUpdate, SubscribeUpdateAccount, SubscribeUpdateAccountInfo,
+            subscribe_update::UpdateOneof,
+        },
+        solana_pubkey::Pubkey,
+        std::{
+            borrow::Cow,
+            convert::Infallible,
+            sync::{
+                Arc, Mutex,
+                atomic::{AtomicUsize, Ordering},
+            },
+            time::Duration,
+        },
+        tokio::net::TcpListener,
+        tokio_util::sync::CancellationToken,
+    };
+
+    fn account(pubkey: Pubkey) -> ParsedMessage {
+        let data = SubscribeUpdate {
+            filters: vec![],
+            update_oneof: Some(UpdateOneof::Account(SubscribeUpdateAccount {
+                account: Some(SubscribeUpdateAccountInfo {
+                    pubkey: pubkey.to_bytes().to_vec(),
+                    owner: Pubkey::new_unique().to_bytes().to_vec(),
+                    lamports: 42,
+                    ..Default::default()
+                }),
+                slot: 1,
+                is_startup: false,
+            })),
+            created_at: Some(Default::default()),
+        }
+        .encode_to_vec();
+        FilterMessage::parse(Cow::Owned(data), MessageParserEncoding::Prost)
+            .expect("valid message")
+            .into()
+    }
+
+    fn config(url: String, pubkey: Pubkey) -> ConfigAppsWebhook {
+        let config = serde_json::json!({
+            "url": url,
+            "secret": "secret",
+            "retry": { "max_attempts": 3, "initial_interval": "1ms", "max_interval": "1ms" },
+            "filter": {
+                "accounts": { "filter": { "account": [pubkey.to_string()] } }
+            }
+        });
+        serde_json::from_str(&config.to_string()).expect("valid config")
+    }
+
+    #[test]
+    fn test_encode() {
+        let pubkey = Pubkey::new_unique();
+        let encoder = WebhookEncoder::new(&config("http://localhost".to_owned(), pubkey));
+        assert!(encoder.encode(&account(Pubkey::new_unique())).is_empty());
+
+        let updates = encoder.encode(&account(pubkey));
+        assert_eq!(updates.len(), 1);
+        let mut batch = WebhookBatch::default();
+        batch.push(updates[0].clone(), 0);
+        batch.push(updates[0].clone(), 0);
+        let body: serde_json::Value = serde_json::from_str(&batch.into_body()).unwrap();
+        assert_eq!(body.as_array().map(|updates| updates.len()), Some(2));
+        assert_eq!(body[0]["filters"], serde_json::json!(["filter"]));
+        assert_eq!(body[0]["type"], "account");
+        assert_eq!(body[0]["value"]["pubkey"], pubkey.to_string());
+        assert_eq!(body[0]["value"]["account"]["lamports"], 42);
+    }
+
+    #[tokio::test]
+    async fn test_send_retry_signed() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let received = Arc::new(Mutex::new(vec![]));
+
+        tokio::spawn({
+            let attempts = Arc::clone(&attempts);
+            let received = Arc::clone(&received);
+            async move {
+                loop {
+                    let (stream, _) = listener.accept().await.unwrap();
+                    let service = service_fn({
+                        let attempts = Arc::clone(&attempts);
+                        let received = Arc::clone(&received);
+                        move |req: Request<Incoming>| {
+                            let attempts = Arc::clone(&attempts);
+                            let received = Arc::clone(&received);
+                            async move {
+                                let headers = req.headers().clone();
+                                let body = req.into_body().collect().await.unwrap().to_bytes();
+                                received.lock().unwrap().push((headers, body));
+                                let status = if attempts.fetch_add(1, Ordering::SeqCst) == 0 {
+                                    StatusCode::SERVICE_UNAVAILABLE
+                                } else {
+                                    StatusCode::OK
+                                };
+                                Ok::<_, Infallible>(
+                                    Response::builder()
+                                        .status(status)
+                                        .body(BodyFull::new(hyper::body::Bytes::new()))
+                                        .unwrap(),
+                                )
+                            }
+                        }
+                    });
+                    tokio::spawn(async move {
+                        ServerBuilder::new(TokioExecutor::new())
+                            .serve_connection(TokioIo::new(stream), service)
+                            .await
+                    });
+                }
+            }
+        });
+
+        let client = WebhookClient::new(&config(url, Pubkey::new_unique())).unwrap();
+        let shutdown = CancellationToken::new();
+        let (count, result) =
+            tokio::time::timeout(Duration::from_secs(10), client.send("[]", &shutdown))
+                .await
+                .unwrap();
+        assert!(result.is_ok());
+        assert_eq!(count, 2);
+
+        let received = received.lock().unwrap();
+        let (headers, body) = received.last().unwrap();
+        assert_eq!(body.as_ref(), b"[]");
+        let timestamp = headers[HEADER_TIMESTAMP].to_str().unwrap().parse().unwrap();
+        assert_eq!(
+            headers[HEADER_SIGNATURE].to_str().unwrap(),
+            sign(b"secret", timestamp, body)
+        );
+    }
+}
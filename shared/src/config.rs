@@ -164,6 +164,16 @@ where
         .map_err(|_| de::Error::custom("size value exceeds usize maximum"))
 }
 
+pub fn deserialize_maybe_humansize_usize<'de, D>(deserializer: D) -> Result<Option<usize>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    struct Size(#[serde(deserialize_with = "deserialize_humansize_usize")] usize);
+
+    Ok(Option::<Size>::deserialize(deserializer)?.map(|size| size.0))
+}
+
 #[derive(Debug, Error)]
 enum DecodeXTokenError {
     #[error(transparent)]
@@ -67,11 +67,12 @@ pub fn jsonrpc_response_error(id: Id<'_>, error: ErrorObjectOwned) -> Vec<u8> {
 }
 
 pub fn jsonrpc_response_error_custom(id: Id<'_>, error: RpcCustomError) -> Vec<u8> {
+    jsonrpc_response_error(id, jsonrpc_error_custom(error))
+}
+
+pub fn jsonrpc_error_custom(error: RpcCustomError) -> ErrorObjectOwned {
     let error = jsonrpc_core::Error::from(error);
-    jsonrpc_response_error(
-        id,
-        ErrorObject::owned(error.code.code() as i32, error.message, error.data),
-    )
+    ErrorObject::owned(error.code.code() as i32, error.message, error.data)
 }
 
 pub fn jsonrpc_error_invalid_params<S: Serialize>(
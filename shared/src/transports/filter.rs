@@ -0,0 +1,95 @@
+use {
+    richat_proto::richat::RichatFilter, solana_pubkey::Pubkey, std::collections::HashSet,
+    thiserror::Error,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum RichatFilterError {
+    #[error("invalid pubkey in `{0}`")]
+    InvalidPubkey(&'static str),
+}
+
+/// [`RichatFilter`] with decoded pubkeys, evaluated for every message of the subscription.
+///
+/// Accounts are sent if match both `accounts_pubkey` and `accounts_owner` (empty list match
+/// everything), transactions are sent if use any key from `transactions_account_include`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RichatFilterMatcher {
+    disable_accounts: bool,
+    disable_transactions: bool,
+    disable_entries: bool,
+    accounts_pubkey: HashSet<Pubkey>,
+    accounts_owner: HashSet<Pubkey>,
+    transactions_account_include: HashSet<Pubkey>,
+    transactions_exclude_vote: bool,
+    transactions_exclude_failed: bool,
+}
+
+impl TryFrom<&RichatFilter> for RichatFilterMatcher {
+    type Error = RichatFilterError;
+
+    fn try_from(filter: &RichatFilter) -> Result<Self, Self::Error> {
+        Ok(Self {
+            disable_accounts: filter.disable_accounts,
+            disable_transactions: filter.disable_transactions,
+            disable_entries: filter.disable_entries,
+            accounts_pubkey: Self::decode_pubkeys(&filter.accounts_pubkey, "accounts_pubkey")?,
+            accounts_owner: Self::decode_pubkeys(&filter.accounts_owner, "accounts_owner")?,
+            transactions_account_include: Self::decode_pubkeys(
+                &filter.transactions_account_include,
+                "transactions_account_include",
+            )?,
+            transactions_exclude_vote: filter.transactions_exclude_vote,
+            transactions_exclude_failed: filter.transactions_exclude_failed,
+        })
+    }
+}
+
+impl RichatFilterMatcher {
+    pub fn new(filter: Option<&RichatFilter>) -> Result<Self, RichatFilterError> {
+        filter.map_or_else(|| Ok(Self::default()), Self::try_from)
+    }
+
+    fn decode_pubkeys(
+        pubkeys: &[Vec<u8>],
+        field: &'static str,
+    ) -> Result<HashSet<Pubkey>, RichatFilterError> {
+        pubkeys
+            .iter()
+            .map(|pubkey| {
+                Pubkey::try_from(pubkey.as_slice())
+                    .map_err(|_| RichatFilterError::InvalidPubkey(field))
+            })
+            .collect()
+    }
+
+    /// Returns `true` if account or transaction pubkeys are required to evaluate the filter.
+    pub fn is_pubkeys_required(&self) -> bool {
+        !self.accounts_pubkey.is_empty()
+            || !self.accounts_owner.is_empty()
+            || !self.transactions_account_include.is_empty()
+    }
+
+    pub fn match_account(&self, pubkey: &Pubkey, owner: &Pubkey) -> bool {
+        !self.disable_accounts
+            && (self.accounts_pubkey.is_empty() || self.accounts_pubkey.contains(pubkey))
+            && (self.accounts_owner.is_empty() || self.accounts_owner.contains(owner))
+    }
+
+    pub fn match_transaction<'a>(
+        &self,
+        is_vote: bool,
+        is_failed: bool,
+        mut account_keys: impl Iterator<Item = &'a Pubkey>,
+    ) -> bool {
+        !self.disable_transactions
+            && (!self.transactions_exclude_vote || !is_vote)
+            && (!self.transactions_exclude_failed || !is_failed)
+            && (self.transactions_account_include.is_empty()
+                || account_keys.any(|pubkey| self.transactions_account_include.contains(pubkey)))
+    }
+
+    pub const fn match_entry(&self) -> bool {
+        !self.disable_entries
+    }
+}
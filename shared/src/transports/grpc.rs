@@ -1,10 +1,16 @@
 use {
     crate::{
-        config::{deserialize_humansize_usize, deserialize_x_tokens_set},
-        transports::{RecvError, RecvStream, Subscribe, SubscribeError},
+        config::{
+            deserialize_humansize_usize, deserialize_maybe_rustls_server_config,
+            deserialize_x_tokens_set,
+        },
+        transports::{
+            RecvError, RecvStream, Subscribe, SubscribeConnection, SubscribeError,
+            reload::ServerReload,
+        },
         version::Version,
     },
-    futures::stream::{Stream, StreamExt},
+    futures::stream::{BoxStream, Stream, StreamExt},
     prost::{Message, bytes::BufMut},
     richat_proto::{
         geyser::{GetVersionRequest, GetVersionResponse},
@@ -17,8 +23,9 @@ use {
     std::{
         borrow::Cow,
         collections::HashSet,
-        fmt, fs,
+        fmt,
         future::Future,
+        io,
         marker::PhantomData,
         net::{IpAddr, Ipv4Addr, SocketAddr},
         pin::Pin,
@@ -30,20 +37,27 @@ use {
         time::Duration,
     },
     thiserror::Error,
-    tokio::task::JoinError,
+    tokio::{
+        io::{AsyncRead, AsyncWrite, ReadBuf},
+        net::TcpStream,
+        task::{JoinError, JoinSet},
+        time::timeout,
+    },
+    tokio_rustls::{TlsAcceptor, server::TlsStream},
     tokio_util::sync::CancellationToken,
     tonic::{
         Request, Response, Status, Streaming,
         codec::{Codec, CompressionEncoding, DecodeBuf, Decoder, EncodeBuf, Encoder},
         service::interceptor::InterceptorLayer,
-        transport::{
-            Identity, ServerTlsConfig,
-            server::{Server, TcpIncoming},
-        },
+        transport::server::{Connected, Server, TcpConnectInfo, TcpIncoming},
     },
     tracing::{error, info},
 };
 
+/// Trailer metadata key set with `OK` status once `replay_to_slot` is reached,
+/// value is the number of messages sent in the stream.
+pub const REPLAY_FINISHED_METADATA_KEY: &str = "x-richat-replay-finished";
+
 pub mod geyser_gen {
     #![allow(clippy::clone_on_ref_ptr)]
     #![allow(clippy::missing_const_for_fn)]
@@ -85,7 +99,7 @@ impl ConfigGrpcCompression {
 pub struct ConfigGrpcServer {
     pub endpoint: SocketAddr,
     #[serde(deserialize_with = "ConfigGrpcServer::deserialize_tls_config")]
-    pub tls_config: Option<ServerTlsConfig>,
+    pub tls_config: Option<rustls::ServerConfig>,
     pub compression: ConfigGrpcCompression,
     /// Limits the maximum size of a decoded message, default is 4MiB
     #[serde(deserialize_with = "deserialize_humansize_usize")]
@@ -126,32 +140,20 @@ impl Default for ConfigGrpcServer {
 impl ConfigGrpcServer {
     pub fn deserialize_tls_config<'de, D>(
         deserializer: D,
-    ) -> Result<Option<ServerTlsConfig>, D::Error>
+    ) -> Result<Option<rustls::ServerConfig>, D::Error>
     where
         D: Deserializer<'de>,
     {
-        #[derive(Debug, Deserialize)]
-        #[serde(deny_unknown_fields)]
-        struct ConfigTls<'a> {
-            cert: &'a str,
-            key: &'a str,
+        let mut tls_config = deserialize_maybe_rustls_server_config(deserializer)?;
+        if let Some(tls_config) = tls_config.as_mut() {
+            tls_config.alpn_protocols = vec![b"h2".to_vec()];
         }
-
-        Option::<ConfigTls>::deserialize(deserializer)?
-            .map(|config| {
-                let cert = fs::read(config.cert).map_err(|error| {
-                    de::Error::custom(format!("failed to read cert {}: {error:?}", config.cert))
-                })?;
-                let key = fs::read(config.key).map_err(|error| {
-                    de::Error::custom(format!("failed to read key {}: {error:?}", config.key))
-                })?;
-
-                Ok(ServerTlsConfig::new().identity(Identity::from_pem(cert, key)))
-            })
-            .transpose()
+        Ok(tls_config)
     }
 
-    pub fn create_server_builder(&self) -> Result<(TcpIncoming, Server), CreateServerError> {
+    /// TLS handshake is done by returned incoming stream, so certificate can be
+    /// replaced with [`ServerReload`] created before the call.
+    pub fn create_server_builder(&self) -> Result<(GrpcIncoming, Server), CreateServerError> {
         // Bind service address
         let incoming = TcpIncoming::bind(self.endpoint)
             .map_err(|error| CreateServerError::Bind {
@@ -160,12 +162,18 @@ impl ConfigGrpcServer {
             })?
             .with_nodelay(Some(self.server_tcp_nodelay))
             .with_keepalive(self.server_tcp_keepalive);
+        let incoming = match self.tls_config.clone() {
+            Some(tls_config) => TlsIncoming {
+                incoming: Some(incoming),
+                acceptor: TlsAcceptor::from(Arc::new(tls_config)),
+                handshakes: JoinSet::new(),
+            }
+            .boxed(),
+            None => incoming.map(|stream| stream.map(GrpcStream::Tcp)).boxed(),
+        };
 
         // Create service
         let mut server_builder = Server::builder();
-        if let Some(tls_config) = self.tls_config.clone() {
-            server_builder = server_builder.tls_config(tls_config)?;
-        }
         if let Some(enabled) = self.server_http2_adaptive_window {
             server_builder = server_builder.http2_adaptive_window(Some(enabled));
         }
@@ -187,6 +195,126 @@ impl ConfigGrpcServer {
     }
 }
 
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub type GrpcIncoming = BoxStream<'static, io::Result<GrpcStream>>;
+
+/// Every TLS handshake runs in own task, so slow clients do not block accepting
+/// of new connections.
+struct TlsIncoming {
+    incoming: Option<TcpIncoming>,
+    acceptor: TlsAcceptor,
+    handshakes: JoinSet<io::Result<TlsStream<TcpStream>>>,
+}
+
+impl Stream for TlsIncoming {
+    type Item = io::Result<GrpcStream>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let me = self.get_mut();
+
+        while let Some(incoming) = me.incoming.as_mut() {
+            match incoming.poll_next_unpin(cx) {
+                Poll::Ready(Some(Ok(stream))) => {
+                    let acceptor = me.acceptor.clone();
+                    me.handshakes.spawn(async move {
+                        timeout(TLS_HANDSHAKE_TIMEOUT, acceptor.accept(stream))
+                            .await
+                            .map_err(io::Error::from)?
+                    });
+                }
+                Poll::Ready(Some(Err(error))) => return Poll::Ready(Some(Err(error))),
+                Poll::Ready(None) => me.incoming = None,
+                Poll::Pending => break,
+            }
+        }
+
+        match me.handshakes.poll_join_next(cx) {
+            Poll::Ready(Some(Ok(result))) => {
+                Poll::Ready(Some(result.map(|stream| GrpcStream::Tls(Box::new(stream)))))
+            }
+            Poll::Ready(Some(Err(error))) => Poll::Ready(Some(Err(io::Error::other(error)))),
+            Poll::Ready(None) if me.incoming.is_none() => Poll::Ready(None),
+            Poll::Ready(None) | Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum GrpcStream {
+    Tcp(TcpStream),
+    Tls(Box<TlsStream<TcpStream>>),
+}
+
+impl Connected for GrpcStream {
+    type ConnectInfo = TcpConnectInfo;
+
+    fn connect_info(&self) -> Self::ConnectInfo {
+        match self {
+            Self::Tcp(stream) => stream.connect_info(),
+            Self::Tls(stream) => stream.get_ref().0.connect_info(),
+        }
+    }
+}
+
+impl AsyncRead for GrpcStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Tcp(stream) => Pin::new(stream).poll_read(cx, buf),
+            Self::Tls(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for GrpcStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Self::Tcp(stream) => Pin::new(stream).poll_write(cx, buf),
+            Self::Tls(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Self::Tcp(stream) => Pin::new(stream).poll_write_vectored(cx, bufs),
+            Self::Tls(stream) => Pin::new(stream).poll_write_vectored(cx, bufs),
+        }
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        match self {
+            Self::Tcp(stream) => stream.is_write_vectored(),
+            Self::Tls(stream) => stream.is_write_vectored(),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Tcp(stream) => Pin::new(stream).poll_flush(cx),
+            Self::Tls(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Tcp(stream) => Pin::new(stream).poll_shutdown(cx),
+            Self::Tls(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum CreateServerError {
     #[error("failed to bind {endpoint}: {error}")]
@@ -194,8 +322,6 @@ pub enum CreateServerError {
         error: std::io::Error,
         endpoint: SocketAddr,
     },
-    #[error("failed to apply tls_config: {0}")]
-    Tls(#[from] tonic::transport::Error),
 }
 
 pub struct GrpcServer<S, F1, F2> {
@@ -221,14 +347,20 @@ where
     F1: Fn() + Clone + Unpin + Send + Sync + 'static,
     F2: Fn() + Clone + Unpin + Send + Sync + 'static,
 {
+    /// Returned [`ServerReload`] updates `x_tokens` and `tls_config` for new connections.
     pub async fn spawn(
-        config: ConfigGrpcServer,
+        mut config: ConfigGrpcServer,
         messages: S,
         on_conn_new_cb: F1,
         on_conn_drop_cb: F2,
         version: Version<'static>,
         shutdown: CancellationToken,
-    ) -> Result<impl Future<Output = Result<(), JoinError>>, CreateServerError> {
+    ) -> Result<(ServerReload, impl Future<Output = Result<(), JoinError>>), CreateServerError>
+    {
+        let reload = ServerReload::new(
+            std::mem::take(&mut config.x_tokens),
+            config.tls_config.as_mut(),
+        );
         let (incoming, server_builder) = config.create_server_builder()?;
         info!("start server at {}", config.endpoint);
 
@@ -248,16 +380,16 @@ where
         }
 
         // Spawn server
-        Ok(tokio::spawn(async move {
+        let interceptor_reload = reload.clone();
+        let server = tokio::spawn(async move {
             if let Err(error) = server_builder
                 .layer(InterceptorLayer::new(move |request: Request<()>| {
-                    if config.x_tokens.is_empty() {
+                    let x_tokens = interceptor_reload.x_tokens();
+                    if x_tokens.is_empty() {
                         Ok(request)
                     } else {
                         match request.metadata().get("x-token") {
-                            Some(token) if config.x_tokens.contains(token.as_bytes()) => {
-                                Ok(request)
-                            }
+                            Some(token) if x_tokens.contains(token.as_bytes()) => Ok(request),
                             _ => Err(Status::unauthenticated("No valid auth token")),
                         }
                     }
@@ -270,7 +402,8 @@ where
             } else {
                 info!("shutdown")
             }
-        }))
+        });
+        Ok((reload, server))
     }
 }
 
@@ -289,12 +422,18 @@ where
     ) -> Result<Response<Self::SubscribeStream>, Status> {
         let id = self.subscribe_id.fetch_add(1, Ordering::Relaxed);
         info!("#{id}: new connection from {:?}", request.remote_addr());
+        let remote_addr = request.remote_addr();
+        let x_token = request
+            .metadata()
+            .get("x-token")
+            .map(|token| token.as_bytes().to_vec());
 
-        let (replay_from_slot, filter) = match request.get_mut().message().await {
+        let (replay_from_slot, replay_to_slot, filter) = match request.get_mut().message().await {
             Ok(Some(GrpcSubscribeRequest {
                 replay_from_slot,
+                replay_to_slot,
                 filter,
-            })) => (replay_from_slot, filter),
+            })) => (replay_from_slot, replay_to_slot, filter),
             Ok(None) => {
                 info!("#{id}: connection closed before receiving request");
                 return Err(Status::aborted("stream closed before request received"));
@@ -305,12 +444,32 @@ where
             }
         };
 
-        match self.messages.subscribe(replay_from_slot, filter) {
+        if let (Some(from_slot), Some(to_slot)) = (replay_from_slot, replay_to_slot) {
+            if to_slot < from_slot {
+                return Err(Status::invalid_argument(
+                    "replay_to_slot should be greater than or equal to replay_from_slot",
+                ));
+            }
+        }
+
+        match self.messages.subscribe(
+            SubscribeConnection {
+                transport: "grpc",
+                remote_addr,
+                x_token: x_token.as_deref(),
+            },
+            replay_from_slot,
+            replay_to_slot,
+            filter,
+        ) {
             Ok(rx) => {
                 let pos = replay_from_slot
                     .map(|slot| format!("slot {slot}").into())
                     .unwrap_or(Cow::Borrowed("latest"));
-                info!("#{id}: subscribed from {pos}");
+                match replay_to_slot {
+                    Some(to_slot) => info!("#{id}: subscribed from {pos} to slot {to_slot}"),
+                    None => info!("#{id}: subscribed from {pos}"),
+                }
                 Ok(Response::new(ReceiverStream::new(
                     rx.boxed(),
                     id,
@@ -322,6 +481,18 @@ where
             Err(SubscribeError::SlotNotAvailable { first_available }) => Err(
                 Status::invalid_argument(format!("first available slot: {first_available}")),
             ),
+            Err(SubscribeError::ReplayQueueFull) => Err(Status::resource_exhausted(
+                "replay queue is full; try again later",
+            )),
+            Err(error @ SubscribeError::XTokenAppNotAllowed) => {
+                Err(Status::permission_denied(error.to_string()))
+            }
+            Err(error @ SubscribeError::XTokenSubscriptionsLimit { .. }) => {
+                Err(Status::resource_exhausted(error.to_string()))
+            }
+            Err(error @ SubscribeError::InvalidFilter(_)) => {
+                Err(Status::invalid_argument(error.to_string()))
+            }
         }
     }
 
@@ -338,6 +509,7 @@ where
 pub struct ReceiverStream<F2: Fn()> {
     rx: RecvStream,
     id: u64,
+    messages: u64,
     on_conn_drop_cb: F2,
 }
 
@@ -353,6 +525,7 @@ impl<F2: Fn()> ReceiverStream<F2> {
         Self {
             rx,
             id,
+            messages: 0,
             on_conn_drop_cb,
         }
     }
@@ -370,12 +543,25 @@ impl<F2: Fn() + Unpin> Stream for ReceiverStream<F2> {
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         match ready!(self.rx.poll_next_unpin(cx)) {
-            Some(Ok(value)) => Poll::Ready(Some(Ok(value))),
+            Some(Ok(value)) => {
+                self.messages += 1;
+                Poll::Ready(Some(Ok(value)))
+            }
+            Some(Err(RecvError::ReplayFinished)) => {
+                info!("#{}: replay finished", self.id);
+                // `OK` status is sent in trailers, clients see it as the end of the stream
+                let mut status = Status::ok("replay finished");
+                status
+                    .metadata_mut()
+                    .insert(REPLAY_FINISHED_METADATA_KEY, self.messages.into());
+                Poll::Ready(Some(Err(status)))
+            }
             Some(Err(error)) => {
                 error!("#{}: failed to get message: {error}", self.id);
                 match error {
                     RecvError::Lagged => Poll::Ready(Some(Err(Status::out_of_range("lagged")))),
                     RecvError::Closed => Poll::Ready(Some(Err(Status::out_of_range("closed")))),
+                    RecvError::ReplayFinished => unreachable!(),
                 }
             }
             None => Poll::Ready(None),
@@ -476,3 +662,145 @@ fn from_decode_error(error: prost::DecodeError) -> Status {
     // https://github.com/grpc/grpc/blob/master/doc/statuscodes.md
     Status::new(tonic::Code::Internal, error.to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::ConfigGrpcServer,
+        crate::transports::reload::ServerReload,
+        futures::stream::StreamExt,
+        rustls::{
+            ClientConfig, DigitallySignedStruct, ServerConfig, SignatureScheme,
+            client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+            crypto::{CryptoProvider, aws_lc_rs, verify_tls12_signature, verify_tls13_signature},
+            pki_types::{CertificateDer, PrivatePkcs8KeyDer, ServerName, UnixTime},
+        },
+        std::{
+            collections::HashSet,
+            net::{SocketAddr, TcpListener},
+            sync::Arc,
+            time::Duration,
+        },
+        tokio::{net::TcpStream, time::timeout},
+        tokio_rustls::TlsConnector,
+    };
+
+    #[derive(Debug)]
+    struct AcceptAnyCert(Arc<CryptoProvider>);
+
+    impl ServerCertVerifier for AcceptAnyCert {
+        fn verify_server_cert(
+            &self,
+            _end_entity: &CertificateDer<'_>,
+            _intermediates: &[CertificateDer<'_>],
+            _server_name: &ServerName<'_>,
+            _ocsp_response: &[u8],
+            _now: UnixTime,
+        ) -> Result<ServerCertVerified, rustls::Error> {
+            Ok(ServerCertVerified::assertion())
+        }
+
+        fn verify_tls12_signature(
+            &self,
+            message: &[u8],
+            cert: &CertificateDer<'_>,
+            dss: &DigitallySignedStruct,
+        ) -> Result<HandshakeSignatureValid, rustls::Error> {
+            verify_tls12_signature(
+                message,
+                cert,
+                dss,
+                &self.0.signature_verification_algorithms,
+            )
+        }
+
+        fn verify_tls13_signature(
+            &self,
+            message: &[u8],
+            cert: &CertificateDer<'_>,
+            dss: &DigitallySignedStruct,
+        ) -> Result<HandshakeSignatureValid, rustls::Error> {
+            verify_tls13_signature(
+                message,
+                cert,
+                dss,
+                &self.0.signature_verification_algorithms,
+            )
+        }
+
+        fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+            self.0.signature_verification_algorithms.supported_schemes()
+        }
+    }
+
+    fn provider() -> Arc<CryptoProvider> {
+        Arc::new(aws_lc_rs::default_provider())
+    }
+
+    fn server_config() -> (CertificateDer<'static>, ServerConfig) {
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_owned()]).unwrap();
+        let cert_der = CertificateDer::from(cert.cert);
+        let key = PrivatePkcs8KeyDer::from(cert.signing_key.serialize_der());
+        let mut config = ServerConfig::builder_with_provider(provider())
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(vec![cert_der.clone()], key.into())
+            .unwrap();
+        config.alpn_protocols = vec![b"h2".to_vec()];
+        (cert_der, config)
+    }
+
+    async fn peer_cert(endpoint: SocketAddr) -> CertificateDer<'static> {
+        let mut config = ClientConfig::builder_with_provider(provider())
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(AcceptAnyCert(provider())))
+            .with_no_client_auth();
+        config.alpn_protocols = vec![b"h2".to_vec()];
+
+        let stream = TcpStream::connect(endpoint).await.unwrap();
+        let server_name = ServerName::try_from("localhost").unwrap();
+        let stream = timeout(
+            Duration::from_secs(5),
+            TlsConnector::from(Arc::new(config)).connect(server_name, stream),
+        )
+        .await
+        .expect("handshake in time")
+        .unwrap();
+        stream.get_ref().1.peer_certificates().unwrap()[0].clone()
+    }
+
+    #[tokio::test]
+    async fn test_tls_reload() {
+        let (cert1, tls_config1) = server_config();
+        let (cert2, tls_config2) = server_config();
+
+        let endpoint = TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.local_addr())
+            .unwrap();
+        let mut config = ConfigGrpcServer {
+            endpoint,
+            tls_config: Some(tls_config1),
+            ..Default::default()
+        };
+        let reload = ServerReload::new(HashSet::new(), config.tls_config.as_mut());
+        let (mut incoming, _server_builder) = config.create_server_builder().unwrap();
+        let server = tokio::spawn(async move {
+            let mut streams = vec![];
+            while let Some(stream) = incoming.next().await {
+                streams.push(stream);
+            }
+        });
+
+        // connection without handshake does not block new connections
+        let _idle = TcpStream::connect(endpoint).await.unwrap();
+
+        assert_eq!(peer_cert(endpoint).await, cert1);
+        reload.reload(HashSet::new(), Some(&tls_config2)).unwrap();
+        assert_eq!(peer_cert(endpoint).await, cert2);
+
+        server.abort();
+    }
+}
@@ -1,13 +1,17 @@
+pub mod filter;
 pub mod grpc;
 pub mod quic;
+pub mod reload;
 
 use {
+    crate::transports::filter::RichatFilterError,
     futures::stream::BoxStream,
     richat_proto::richat::RichatFilter,
     solana_clock::Slot,
     std::{
         future::Future,
         io::{self, IoSlice},
+        net::SocketAddr,
         pin::Pin,
         sync::Arc,
         task::{Context, Poll, ready},
@@ -26,6 +30,8 @@ pub enum RecvError {
     Lagged,
     #[error("channel closed")]
     Closed,
+    #[error("replay finished")]
+    ReplayFinished,
 }
 
 #[derive(Debug, Error)]
@@ -34,12 +40,33 @@ pub enum SubscribeError {
     NotInitialized,
     #[error("only available from slot {first_available}")]
     SlotNotAvailable { first_available: Slot },
+    #[error("replay queue is full")]
+    ReplayQueueFull,
+    #[error("x-token is not allowed to use this app")]
+    XTokenAppNotAllowed,
+    #[error("x-token reached max number of subscriptions: {max}")]
+    XTokenSubscriptionsLimit { max: usize },
+    #[error("invalid filter: {0}")]
+    InvalidFilter(#[from] RichatFilterError),
+}
+
+/// Connection of the subscriber as seen by transport.
+#[derive(Debug, Clone, Copy)]
+pub struct SubscribeConnection<'a> {
+    pub transport: &'static str,
+    pub remote_addr: Option<SocketAddr>,
+    /// Already verified by transport, can be used for per-token limits
+    pub x_token: Option<&'a [u8]>,
 }
 
 pub trait Subscribe {
+    /// With `replay_to_slot` stream is finished with [`RecvError::ReplayFinished`]
+    /// once that slot (or any later slot) is finalized.
     fn subscribe(
         &self,
+        connection: SubscribeConnection<'_>,
         replay_from_slot: Option<Slot>,
+        replay_to_slot: Option<Slot>,
         filter: Option<RichatFilter>,
     ) -> Result<RecvStream, SubscribeError>;
 }
@@ -1,7 +1,10 @@
 use {
     crate::{
         config::{deserialize_num_str, deserialize_rustls_server_config, deserialize_x_tokens_set},
-        transports::{RecvError, RecvItem, RecvStream, Subscribe, SubscribeError, WriteVectored},
+        transports::{
+            RecvError, RecvItem, RecvStream, Subscribe, SubscribeConnection, SubscribeError,
+            WriteVectored, reload::ServerReload,
+        },
         version::Version,
     },
     futures::stream::StreamExt,
@@ -151,62 +154,71 @@ enum ConnectionError {
 pub struct QuicServer;
 
 impl QuicServer {
+    /// Returned [`ServerReload`] updates `x_tokens` and `tls_config` for new connections.
     pub async fn spawn(
-        config: ConfigQuicServer,
+        mut config: ConfigQuicServer,
         messages: impl Subscribe + Clone + Send + 'static,
         on_conn_new_cb: impl Fn() + Clone + Send + 'static,
         on_conn_drop_cb: impl Fn() + Clone + Send + 'static,
         version: Version<'static>,
         shutdown: CancellationToken,
-    ) -> Result<impl Future<Output = Result<(), JoinError>>, CreateEndpointError> {
+    ) -> Result<(ServerReload, impl Future<Output = Result<(), JoinError>>), CreateEndpointError>
+    {
+        let reload = ServerReload::new(
+            std::mem::take(&mut config.x_tokens),
+            Some(&mut config.tls_config),
+        );
         let endpoint = config.create_endpoint()?;
         info!("start server at {}", config.endpoint);
 
-        Ok(tokio::spawn(async move {
-            let max_recv_streams = config.max_recv_streams;
-            let max_request_size = config.max_request_size as u64;
-            let x_tokens = Arc::new(config.x_tokens);
-
-            let mut id = 0;
-            loop {
-                tokio::select! {
-                    incoming = endpoint.accept() => {
-                        let Some(incoming) = incoming else {
-                            error!("quic connection closed");
-                            break;
-                        };
-
-                        let messages = messages.clone();
-                        let on_conn_new_cb = on_conn_new_cb.clone();
-                        let on_conn_drop_cb = on_conn_drop_cb.clone();
-                        let x_tokens = Arc::clone(&x_tokens);
-                        tokio::spawn(async move {
-                            on_conn_new_cb();
-                            if let Err(error) = Self::handle_incoming(
-                                id,
-                                incoming,
-                                messages,
-                                max_recv_streams,
-                                max_request_size,
-                                x_tokens,
-                                version.create_grpc_version_info().json(),
-                            ).await {
-                                error!("#{id}: connection failed: {error}");
-                            } else {
-                                info!("#{id}: connection closed");
-                            }
-                            on_conn_drop_cb();
-                        });
-                        id += 1;
-                    }
-                    () = shutdown.cancelled() => {
-                        endpoint.close(0u32.into(), b"shutdown");
-                        info!("shutdown");
-                        break
-                    },
-                };
+        let task = tokio::spawn({
+            let reload = reload.clone();
+            async move {
+                let max_recv_streams = config.max_recv_streams;
+                let max_request_size = config.max_request_size as u64;
+
+                let mut id = 0;
+                loop {
+                    tokio::select! {
+                        incoming = endpoint.accept() => {
+                            let Some(incoming) = incoming else {
+                                error!("quic connection closed");
+                                break;
+                            };
+
+                            let messages = messages.clone();
+                            let on_conn_new_cb = on_conn_new_cb.clone();
+                            let on_conn_drop_cb = on_conn_drop_cb.clone();
+                            let x_tokens = reload.x_tokens();
+                            tokio::spawn(async move {
+                                on_conn_new_cb();
+                                if let Err(error) = Self::handle_incoming(
+                                    id,
+                                    incoming,
+                                    messages,
+                                    max_recv_streams,
+                                    max_request_size,
+                                    x_tokens,
+                                    version.create_grpc_version_info().json(),
+                                ).await {
+                                    error!("#{id}: connection failed: {error}");
+                                } else {
+                                    info!("#{id}: connection closed");
+                                }
+                                on_conn_drop_cb();
+                            });
+                            id += 1;
+                        }
+                        () = shutdown.cancelled() => {
+                            endpoint.close(0u32.into(), b"shutdown");
+                            info!("shutdown");
+                            break
+                        },
+                    };
+                }
             }
-        }))
+        });
+        Ok((reload, task))
     }
 
     async fn handle_incoming(
@@ -283,7 +295,11 @@ impl QuicServer {
                     match message {
                         Some(Ok(message)) => next_message = Some(message),
                         Some(Err(error)) => {
-                            error!("#{id}: failed to get message: {error}");
+                            if error == RecvError::ReplayFinished {
+                                info!("#{id}: replay finished");
+                            } else {
+                                error!("#{id}: failed to get message: {error}");
+                            }
                             if streams.is_empty() {
                                 let (msg_id, stream) = set.join_next().await.expect("already verified")??;
                                 msg_ids.remove(&msg_id);
@@ -297,7 +313,9 @@ impl QuicServer {
                                 error: match error {
                                     RecvError::Lagged => QuicSubscribeCloseError::Lagged,
                                     RecvError::Closed => QuicSubscribeCloseError::Closed,
-                                } as i32
+                                    RecvError::ReplayFinished => QuicSubscribeCloseError::Finished,
+                                } as i32,
+                                messages: (error == RecvError::ReplayFinished).then_some(msg_id),
                             };
                             let message = msg.encode_to_vec();
 
@@ -368,12 +386,13 @@ impl QuicServer {
             max_backlog,
             replay_from_slot,
             filter,
+            replay_to_slot,
         } = Message::decode(buf.as_slice())?;
 
         // verify access token
         if !x_tokens.is_empty() {
-            if let Some(error) = match x_token {
-                Some(x_token) if !x_tokens.contains(&x_token) => {
+            if let Some(error) = match &x_token {
+                Some(x_token) if !x_tokens.contains(x_token) => {
                     Some(QuicSubscribeResponseError::XTokenInvalid as i32)
                 }
                 None => Some(QuicSubscribeResponseError::XTokenRequired as i32),
@@ -404,42 +423,100 @@ impl QuicServer {
             return Ok((send, msg, None));
         }
 
-        Ok(match messages.subscribe(replay_from_slot, filter) {
-            Ok(rx) => {
-                let pos = replay_from_slot
-                    .map(|slot| format!("slot {slot}").into())
-                    .unwrap_or(Cow::Borrowed("latest"));
-                info!("#{id}: subscribed from {pos}");
-                (
-                    send,
-                    QuicSubscribeResponse {
-                        version,
-                        ..Default::default()
-                    },
-                    Some((
-                        recv_streams,
-                        max_backlog.map(|x| x as u64).unwrap_or(u64::MAX),
-                        rx,
-                    )),
-                )
-            }
-            Err(SubscribeError::NotInitialized) => {
-                let msg = QuicSubscribeResponse {
-                    error: Some(QuicSubscribeResponseError::NotInitialized as i32),
-                    version,
-                    ..Default::default()
-                };
-                (send, msg, None)
-            }
-            Err(SubscribeError::SlotNotAvailable { first_available }) => {
+        // validate replay range
+        if let (Some(from_slot), Some(to_slot)) = (replay_from_slot, replay_to_slot) {
+            if to_slot < from_slot {
                 let msg = QuicSubscribeResponse {
-                    error: Some(QuicSubscribeResponseError::SlotNotAvailable as i32),
-                    first_available_slot: Some(first_available),
+                    error: Some(QuicSubscribeResponseError::InvalidReplayRange as i32),
                     version,
                     ..Default::default()
                 };
-                (send, msg, None)
+                return Ok((send, msg, None));
             }
-        })
+        }
+
+        Ok(
+            match messages.subscribe(
+                SubscribeConnection {
+                    transport: "quic",
+                    remote_addr: Some(conn.remote_address()),
+                    x_token: x_token.as_deref(),
+                },
+                replay_from_slot,
+                replay_to_slot,
+                filter,
+            ) {
+                Ok(rx) => {
+                    let pos = replay_from_slot
+                        .map(|slot| format!("slot {slot}").into())
+                        .unwrap_or(Cow::Borrowed("latest"));
+                    match replay_to_slot {
+                        Some(to_slot) => info!("#{id}: subscribed from {pos} to slot {to_slot}"),
+                        None => info!("#{id}: subscribed from {pos}"),
+                    }
+                    (
+                        send,
+                        QuicSubscribeResponse {
+                            version,
+                            ..Default::default()
+                        },
+                        Some((
+                            recv_streams,
+                            max_backlog.map(|x| x as u64).unwrap_or(u64::MAX),
+                            rx,
+                        )),
+                    )
+                }
+                Err(SubscribeError::NotInitialized) => {
+                    let msg = QuicSubscribeResponse {
+                        error: Some(QuicSubscribeResponseError::NotInitialized as i32),
+                        version,
+                        ..Default::default()
+                    };
+                    (send, msg, None)
+                }
+                Err(SubscribeError::SlotNotAvailable { first_available }) => {
+                    let msg = QuicSubscribeResponse {
+                        error: Some(QuicSubscribeResponseError::SlotNotAvailable as i32),
+                        first_available_slot: Some(first_available),
+                        version,
+                        ..Default::default()
+                    };
+                    (send, msg, None)
+                }
+                Err(SubscribeError::ReplayQueueFull) => {
+                    let msg = QuicSubscribeResponse {
+                        error: Some(QuicSubscribeResponseError::ReplayQueueFull as i32),
+                        version,
+                        ..Default::default()
+                    };
+                    (send, msg, None)
+                }
+                Err(SubscribeError::XTokenAppNotAllowed) => {
+                    let msg = QuicSubscribeResponse {
+                        error: Some(QuicSubscribeResponseError::XTokenAppNotAllowed as i32),
+                        version,
+                        ..Default::default()
+                    };
+                    (send, msg, None)
+                }
+                Err(SubscribeError::XTokenSubscriptionsLimit { .. }) => {
+                    let msg = QuicSubscribeResponse {
+                        error: Some(QuicSubscribeResponseError::XTokenSubscriptionsLimit as i32),
+                        version,
+                        ..Default::default()
+                    };
+                    (send, msg, None)
+                }
+                Err(SubscribeError::InvalidFilter(_)) => {
+                    let msg = QuicSubscribeResponse {
+                        error: Some(QuicSubscribeResponseError::InvalidFilter as i32),
+                        version,
+                        ..Default::default()
+                    };
+                    (send, msg, None)
+                }
+            },
+        )
     }
 }
@@ -0,0 +1,134 @@
+use {
+    rustls::server::{ClientHello, ResolvesServerCert},
+    std::{
+        collections::HashSet,
+        fmt,
+        sync::{Arc, RwLock},
+    },
+    thiserror::Error,
+};
+
+/// Value which can be replaced while servers are running, readers get a snapshot.
+pub struct Reloadable<T> {
+    value: Arc<RwLock<Arc<T>>>,
+}
+
+impl<T> Clone for Reloadable<T> {
+    fn clone(&self) -> Self {
+        Self {
+            value: Arc::clone(&self.value),
+        }
+    }
+}
+
+impl<T: Default> Default for Reloadable<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Reloadable<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Reloadable").field(&self.load()).finish()
+    }
+}
+
+impl<T> Reloadable<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            value: Arc::new(RwLock::new(Arc::new(value))),
+        }
+    }
+
+    pub fn load(&self) -> Arc<T> {
+        match self.value.read() {
+            Ok(value) => Arc::clone(&value),
+            Err(p_err) => Arc::clone(&p_err.into_inner()),
+        }
+    }
+
+    pub fn store(&self, value: T) {
+        let value = Arc::new(value);
+        match self.value.write() {
+            Ok(mut lock) => *lock = value,
+            Err(p_err) => *p_err.into_inner() = value,
+        }
+    }
+}
+
+/// Certificate resolver which can be replaced for new TLS connections.
+#[derive(Debug)]
+pub struct ReloadableCertResolver {
+    inner: Reloadable<Arc<dyn ResolvesServerCert>>,
+}
+
+impl ResolvesServerCert for ReloadableCertResolver {
+    fn resolve(&self, client_hello: ClientHello<'_>) -> Option<Arc<rustls::sign::CertifiedKey>> {
+        self.inner.load().resolve(client_hello)
+    }
+}
+
+impl ReloadableCertResolver {
+    /// Replace resolver of the config with reloadable one.
+    pub fn install(tls_config: &mut rustls::ServerConfig) -> Arc<Self> {
+        let resolver = Arc::new(Self {
+            inner: Reloadable::new(Arc::clone(&tls_config.cert_resolver)),
+        });
+        tls_config.cert_resolver = Arc::clone(&resolver) as Arc<dyn ResolvesServerCert>;
+        resolver
+    }
+
+    pub fn reload(&self, tls_config: &rustls::ServerConfig) {
+        self.inner.store(Arc::clone(&tls_config.cert_resolver));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum ServerReloadError {
+    #[error("tls_config can't be enabled or disabled without restart")]
+    TlsChanged,
+}
+
+/// Settings of the running server applied to new connections.
+#[derive(Debug, Clone)]
+pub struct ServerReload {
+    x_tokens: Reloadable<HashSet<Vec<u8>>>,
+    tls: Option<Arc<ReloadableCertResolver>>,
+}
+
+impl ServerReload {
+    pub fn new(x_tokens: HashSet<Vec<u8>>, tls_config: Option<&mut rustls::ServerConfig>) -> Self {
+        Self {
+            x_tokens: Reloadable::new(x_tokens),
+            tls: tls_config.map(ReloadableCertResolver::install),
+        }
+    }
+
+    pub fn x_tokens(&self) -> Arc<HashSet<Vec<u8>>> {
+        self.x_tokens.load()
+    }
+
+    pub const fn check(
+        &self,
+        tls_config: Option<&rustls::ServerConfig>,
+    ) -> Result<(), ServerReloadError> {
+        if self.tls.is_some() == tls_config.is_some() {
+            Ok(())
+        } else {
+            Err(ServerReloadError::TlsChanged)
+        }
+    }
+
+    pub fn reload(
+        &self,
+        x_tokens: HashSet<Vec<u8>>,
+        tls_config: Option<&rustls::ServerConfig>,
+    ) -> Result<(), ServerReloadError> {
+        self.check(tls_config)?;
+        self.x_tokens.store(x_tokens);
+        if let (Some(resolver), Some(tls_config)) = (&self.tls, tls_config) {
+            resolver.reload(tls_config);
+        }
+        Ok(())
+    }
+}